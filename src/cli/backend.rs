@@ -0,0 +1,182 @@
+use std::{fs, io, net::SocketAddr, path::PathBuf};
+
+use anyhow::{anyhow, Context};
+use serde_json::json;
+
+use crate::{
+    model::{amount::Amount, block::Block, blockchain::Blockchain, chain_spec::ChainSpec},
+    rpc::{
+        client::RpcClient,
+        message::{BalanceResult, BlockResult, TipResult},
+    },
+};
+
+// everything a node and the cli keep between runs
+#[derive(Debug, Clone)]
+pub struct DataDir {
+    pub path: PathBuf,
+}
+
+impl DataDir {
+    pub fn new(path: PathBuf) -> Self {
+        DataDir { path }
+    }
+
+    pub fn create(&self) -> io::Result<()> {
+        fs::create_dir_all(&self.path)
+    }
+
+    pub fn chain_path(&self) -> PathBuf {
+        self.path.join("chain.bin")
+    }
+
+    pub fn keystore_path(&self) -> PathBuf {
+        self.path.join("keystore.json")
+    }
+
+    pub fn watch_list_path(&self) -> PathBuf {
+        self.path.join("watching.json")
+    }
+
+    pub fn address_book_path(&self) -> PathBuf {
+        self.path.join("peers.bin")
+    }
+
+    pub fn ban_list_path(&self) -> PathBuf {
+        self.path.join("bans.bin")
+    }
+
+    // a fresh chain when nothing has been saved yet
+    pub fn load_chain(&self, spec: &ChainSpec) -> anyhow::Result<Blockchain> {
+        let path = self.chain_path();
+        if !path.exists() {
+            return Ok(Blockchain::from_spec(spec));
+        }
+        Blockchain::load(&path, spec).with_context(|| format!("couldn't load {}", path.display()))
+    }
+
+    pub fn save_chain(&self, blockchain: &Blockchain) -> anyhow::Result<()> {
+        self.create()?;
+        let path = self.chain_path();
+        blockchain
+            .save(&path)
+            .with_context(|| format!("couldn't save {}", path.display()))
+    }
+}
+
+// where the answers come from, a running node or the chain in the data directory
+pub enum Backend {
+    Rpc(RpcClient),
+    Local(Box<Blockchain>),
+}
+
+impl Backend {
+    pub fn open(
+        rpc: Option<SocketAddr>,
+        data_dir: &DataDir,
+        spec: &ChainSpec,
+    ) -> anyhow::Result<Self> {
+        match rpc {
+            Some(addr) => Ok(Backend::Rpc(RpcClient::new(addr))),
+            None => Ok(Backend::Local(Box::new(data_dir.load_chain(spec)?))),
+        }
+    }
+
+    pub async fn tip(&self) -> anyhow::Result<TipResult> {
+        match self {
+            Backend::Rpc(client) => Ok(client.call("get_best_tip", json!(null)).await?),
+            Backend::Local(blockchain) => {
+                let tip = blockchain.tip();
+                Ok(TipResult {
+                    height: tip.index,
+                    hash: tip.hash(),
+                })
+            }
+        }
+    }
+
+    pub async fn balance(&self, address: &str) -> anyhow::Result<Amount> {
+        match self {
+            Backend::Rpc(client) => {
+                let result: BalanceResult = client
+                    .call("get_balance", json!({ "address": address }))
+                    .await?;
+                Ok(result.balance)
+            }
+            Backend::Local(blockchain) => Ok(blockchain.balance_of(address)),
+        }
+    }
+
+    // counts transactions still waiting in the node's mempool
+    pub async fn next_nonce(&self, address: &str) -> anyhow::Result<u64> {
+        match self {
+            Backend::Rpc(client) => Ok(client
+                .call("get_next_nonce", json!({ "address": address }))
+                .await?),
+            Backend::Local(blockchain) => Ok(blockchain.next_nonce(address)),
+        }
+    }
+
+    pub async fn block_by_height(&self, height: u64) -> anyhow::Result<BlockResult> {
+        match self {
+            Backend::Rpc(client) => Ok(client
+                .call("get_block_by_height", json!({ "height": height }))
+                .await?),
+            Backend::Local(blockchain) => {
+                let block = blockchain
+                    .chain
+                    .get(height as usize)
+                    .ok_or_else(|| anyhow!("there's no block at height {}", height))?;
+                Ok(local_block_result(blockchain, block))
+            }
+        }
+    }
+
+    pub async fn block_by_hash(&self, hash: &str) -> anyhow::Result<BlockResult> {
+        match self {
+            Backend::Rpc(client) => Ok(client
+                .call("get_block_by_hash", json!({ "hash": hash }))
+                .await?),
+            Backend::Local(blockchain) => {
+                let block = blockchain
+                    .get_block(hash)
+                    .ok_or_else(|| anyhow!("there's no block {}", hash))?;
+                Ok(local_block_result(blockchain, block))
+            }
+        }
+    }
+
+    // genesis first
+    pub async fn blocks(&self) -> anyhow::Result<Vec<Block>> {
+        match self {
+            Backend::Rpc(_) => {
+                let tip = self.tip().await?;
+                let mut blocks = Vec::new();
+                for height in 0..=tip.height {
+                    blocks.push(self.block_by_height(height).await?.block);
+                }
+                Ok(blocks)
+            }
+            Backend::Local(blockchain) => Ok(blockchain.chain.clone()),
+        }
+    }
+}
+
+impl Backend {
+    // the whole chain, a node's blocks get checked over again on the way in
+    pub async fn blockchain(&self, spec: &ChainSpec) -> anyhow::Result<Blockchain> {
+        match self {
+            Backend::Rpc(_) => Blockchain::from_blocks(spec, self.blocks().await?)
+                .map_err(|e| anyhow!("the chain is invalid: {:?}", e)),
+            Backend::Local(blockchain) => Ok(blockchain.as_ref().clone()),
+        }
+    }
+}
+
+fn local_block_result(blockchain: &Blockchain, block: &Block) -> BlockResult {
+    BlockResult {
+        hash: block.hash(),
+        confirmations: blockchain.tip().index - block.index + 1,
+        block: block.clone(),
+    }
+}
@@ -0,0 +1,94 @@
+use std::{fs, net::SocketAddr};
+
+use anyhow::anyhow;
+use serde::Serialize;
+
+use super::{
+    backend::{Backend, DataDir},
+    spec, ChainCommand, Output,
+};
+use crate::{
+    model::blockchain::Blockchain,
+    rpc::message::{BlockResult, TipResult},
+};
+
+#[derive(Debug, Serialize)]
+struct Validated {
+    valid: bool,
+    blocks: usize,
+    tip: TipResult,
+}
+
+pub async fn run(
+    command: ChainCommand,
+    rpc: Option<SocketAddr>,
+    data_dir: &DataDir,
+    output: Output,
+) -> anyhow::Result<()> {
+    let spec = spec();
+    let backend = Backend::open(rpc, data_dir, &spec)?;
+
+    match command {
+        ChainCommand::Show { height, hash } => {
+            let block = match (height, hash) {
+                (Some(height), _) => backend.block_by_height(height).await?,
+                (_, Some(hash)) => backend.block_by_hash(&hash).await?,
+                (None, None) => {
+                    let tip = backend.tip().await?;
+                    return output.show(&tip, |tip| format!("tip {} {}", tip.height, tip.hash));
+                }
+            };
+            output.show(&block, describe)
+        }
+        ChainCommand::Validate => {
+            // every block gets checked again on the way in
+            let blocks = backend.blocks().await?;
+            let count = blocks.len();
+            let blockchain = Blockchain::from_blocks(&spec, blocks)
+                .map_err(|e| anyhow!("the chain is invalid: {:?}", e))?;
+            if !blockchain.is_valid() {
+                return Err(anyhow!("the chain is invalid"));
+            }
+
+            let tip = blockchain.tip();
+            let validated = Validated {
+                valid: true,
+                blocks: count,
+                tip: TipResult {
+                    height: tip.index,
+                    hash: tip.hash(),
+                },
+            };
+            output.show(&validated, |validated| {
+                format!(
+                    "all {} blocks are valid, the tip is {} {}",
+                    validated.blocks, validated.tip.height, validated.tip.hash
+                )
+            })
+        }
+        ChainCommand::Export { output: path } => {
+            let json = serde_json::to_string_pretty(&backend.blocks().await?)?;
+            match path {
+                Some(path) => fs::write(path, json)?,
+                None => println!("{}", json),
+            }
+            Ok(())
+        }
+    }
+}
+
+fn describe(result: &BlockResult) -> String {
+    let block = &result.block;
+    let mut lines = vec![
+        format!("hash           {}", result.hash),
+        format!("height         {}", block.index),
+        format!("confirmations  {}", result.confirmations),
+        format!("previous       {}", block.previous_hash),
+        format!("timestamp      {}", block.timestamp),
+        format!("transactions   {}", block.transactions.len()),
+    ];
+    for transaction in block.transactions.iter() {
+        lines.push(format!("  {}", transaction.txid()));
+    }
+    lines.join("\n")
+}
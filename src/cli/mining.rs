@@ -0,0 +1,70 @@
+use std::net::SocketAddr;
+
+use anyhow::bail;
+use serde::Serialize;
+use serde_json::json;
+
+use super::{backend::DataDir, spec, MineArgs, Output};
+use crate::{
+    mine::{fill_block, proof_of_work},
+    rpc::{client::RpcClient, message::TipResult},
+};
+
+#[derive(Debug, Serialize)]
+struct Mining {
+    mining: bool,
+}
+
+// with a node it starts or stops the node's miner, otherwise it mines
+// coinbase only blocks straight into the data directory
+pub async fn run(
+    args: MineArgs,
+    rpc: Option<SocketAddr>,
+    data_dir: &DataDir,
+    output: Output,
+) -> anyhow::Result<()> {
+    if let Some(addr) = rpc {
+        let client = RpcClient::new(addr);
+        if args.stop {
+            let _: bool = client.call("stop_mining", json!(null)).await?;
+            return output.show(&Mining { mining: false }, |_| "stopped mining".to_string());
+        }
+
+        let _: bool = client
+            .call("start_mining", json!({ "address": args.address }))
+            .await?;
+        return output.show(&Mining { mining: true }, |_| {
+            format!(
+                "mining, paying {}",
+                args.address.as_deref().unwrap_or_default()
+            )
+        });
+    }
+
+    let Some(address) = args.address else {
+        bail!("only a running node can be stopped, pass --rpc");
+    };
+    let spec = spec();
+    let mut blockchain = data_dir.load_chain(&spec)?;
+    let mut mined = Vec::new();
+    for _ in 0..args.blocks {
+        let mut block = fill_block(&blockchain, Vec::new(), Some(&address));
+        proof_of_work(&mut block, &blockchain.target_hash_prefix);
+        mined.push(TipResult {
+            height: block.index,
+            hash: block.hash(),
+        });
+        blockchain
+            .add_new_block(block)
+            .map_err(|e| anyhow::anyhow!("mined an invalid block: {:?}", e))?;
+    }
+    data_dir.save_chain(&blockchain)?;
+
+    output.show(&mined, |mined| {
+        mined
+            .iter()
+            .map(|block| format!("mined block {} {}", block.height, block.hash))
+            .collect::<Vec<_>>()
+            .join("\n")
+    })
+}
@@ -0,0 +1,538 @@
+use std::{env, net::SocketAddr, path::PathBuf};
+
+use clap::{Args, Parser, Subcommand};
+use serde::Serialize;
+
+use crate::{
+    model::{
+        chain_spec::ChainSpec,
+        script::{Script, Value},
+        transaction::LockTime,
+    },
+    wallet::{builder::DEFAULT_FEE_RATE, hd::DEFAULT_GAP_LIMIT, watch::DEFAULT_SETTLED_DEPTH},
+};
+
+pub mod backend;
+pub mod chain;
+pub mod mining;
+pub mod multisig;
+pub mod node;
+pub mod psbt;
+pub mod script;
+pub mod tx;
+pub mod wallet;
+pub mod watch;
+
+use backend::DataDir;
+
+// what `rustbucks --help` shows, every command either talks to a running node
+// over rpc or works straight on the data directory
+#[derive(Debug, Parser)]
+#[command(
+    name = "rustbucks",
+    version,
+    about = "Run and talk to a rustbucks node"
+)]
+pub struct Cli {
+    #[arg(
+        long,
+        global = true,
+        value_name = "ADDR",
+        help = "Talk to the node whose rpc server listens here instead of using the data directory"
+    )]
+    pub rpc: Option<SocketAddr>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        help = "Where the chain and wallet are kept [default: ~/.rustbucks]"
+    )]
+    pub data_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Print machine readable json instead of text"
+    )]
+    pub json: bool,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    #[command(subcommand, about = "Run a node")]
+    Node(NodeCommand),
+    #[command(subcommand, about = "Manage addresses and send coins")]
+    Wallet(WalletCommand),
+    #[command(subcommand, about = "Look at the chain")]
+    Chain(ChainCommand),
+    #[command(
+        subcommand,
+        about = "Pass partially signed transactions between their signers"
+    )]
+    Psbt(PsbtCommand),
+    #[command(about = "Mine blocks paying the given address")]
+    Mine(MineArgs),
+    #[command(subcommand, about = "Work with encoded transactions")]
+    Tx(TxCommand),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum NodeCommand {
+    #[command(about = "Run a node until ctrl-c, keeping its chain in the data directory")]
+    Run(NodeArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct NodeArgs {
+    #[arg(
+        long,
+        value_name = "ADDR",
+        help = "Where to listen for peers [default: 0.0.0.0:7878]"
+    )]
+    pub listen: Option<SocketAddr>,
+    #[arg(
+        long,
+        value_name = "ADDR",
+        default_value = "127.0.0.1:7879",
+        help = "Where to serve rpc"
+    )]
+    pub rpc_listen: SocketAddr,
+    #[arg(
+        long,
+        value_name = "ADDR",
+        default_value = "127.0.0.1:7880",
+        help = "Where to serve websocket subscriptions"
+    )]
+    pub ws_listen: SocketAddr,
+    #[arg(
+        long = "peer",
+        value_name = "ADDR",
+        help = "A peer to connect to, can be given more than once"
+    )]
+    pub peers: Vec<SocketAddr>,
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        help = "Start mining right away, paying this address"
+    )]
+    pub mine: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum WalletCommand {
+    #[command(about = "Make a new key and keep it in the keystore")]
+    New {
+        #[arg(long, help = "What to call it [default: account-N]")]
+        name: Option<String>,
+        #[command(flatten)]
+        keystore: KeystoreArgs,
+    },
+    #[command(about = "Show the accounts in the keystore")]
+    List,
+    #[command(about = "Show what an address has to spend, or every account in the keystore")]
+    Balance { address: Option<String> },
+    #[command(about = "Sign and send coins from one of the keystore's accounts")]
+    Send(SendArgs),
+    #[command(about = "Add a hex encoded secret key to the keystore")]
+    Import {
+        #[arg(long)]
+        name: String,
+        #[arg(long, env = "RUSTBUCKS_SECRET_KEY", hide_env_values = true)]
+        key: String,
+        #[command(flatten)]
+        keystore: KeystoreArgs,
+    },
+    #[command(about = "Give the keystore a mnemonic that new accounts are derived from")]
+    Mnemonic {
+        #[arg(long, default_value_t = 12, help = "12, 15, 18, 21 or 24")]
+        words: usize,
+        #[arg(
+            long,
+            default_value = "",
+            help = "Extra words that aren't written down with the mnemonic"
+        )]
+        passphrase: String,
+        #[command(flatten)]
+        keystore: KeystoreArgs,
+    },
+    #[command(
+        about = "Find the accounts a mnemonic has used on the chain and add them to the keystore"
+    )]
+    Restore {
+        #[arg(long, env = "RUSTBUCKS_MNEMONIC", hide_env_values = true)]
+        mnemonic: String,
+        #[arg(long, default_value = "")]
+        passphrase: String,
+        #[arg(long, default_value_t = DEFAULT_GAP_LIMIT, help = "How many unused addresses in a row end the scan")]
+        gap_limit: u32,
+        #[command(flatten)]
+        keystore: KeystoreArgs,
+    },
+    #[command(about = "Print an account's hex encoded secret key")]
+    Export {
+        #[arg(help = "The account's name or address")]
+        account: String,
+        #[command(flatten)]
+        keystore: KeystoreArgs,
+    },
+    #[command(
+        subcommand,
+        about = "Keep an eye on addresses without holding their keys"
+    )]
+    Watch(WatchCommand),
+    #[command(subcommand, about = "Addresses that need M of N keys to spend from")]
+    Multisig(MultisigCommand),
+    #[command(
+        subcommand,
+        about = "Addresses whose coins are spent by satisfying a script"
+    )]
+    Script(ScriptCommand),
+}
+
+#[derive(Debug, Args)]
+pub struct PolicyArgs {
+    #[arg(long, help = "How many of the keys have to sign")]
+    pub threshold: u32,
+    #[arg(
+        long = "public-key",
+        required = true,
+        help = "Hex encoded, once for each key"
+    )]
+    pub public_keys: Vec<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum MultisigCommand {
+    #[command(about = "Show the address the keys share")]
+    Address(PolicyArgs),
+    #[command(about = "Make an unsigned transaction from the shared address for its keys to sign")]
+    Spend {
+        #[command(flatten)]
+        policy: PolicyArgs,
+        #[arg(long)]
+        to: String,
+        #[arg(long, help = "In coins, e.g. 1.5")]
+        amount: String,
+        #[arg(
+            long,
+            default_value_t = DEFAULT_FEE_RATE,
+            help = "In base units per 1000 bytes, paid on top of the amount"
+        )]
+        fee_rate: u64,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ScriptCommand {
+    #[command(about = "Show the address coins locked by the script get sent to")]
+    Address {
+        #[arg(help = "Ops and values separated by spaces, e.g. \"SHA256 0x9f86.. EQUAL\"")]
+        script: Script,
+    },
+    #[command(about = "Make a transaction from a script's address for the keys it checks to sign")]
+    Spend {
+        #[arg(long)]
+        script: Script,
+        #[arg(
+            long = "witness",
+            allow_hyphen_values = true,
+            help = "A value to put on the stack before the script runs, once for each in order"
+        )]
+        witness: Vec<Value>,
+        #[arg(long)]
+        to: String,
+        #[arg(long, help = "In coins, e.g. 1.5")]
+        amount: String,
+        #[arg(
+            long,
+            default_value_t = DEFAULT_FEE_RATE,
+            help = "In base units per 1000 bytes, paid on top of the amount"
+        )]
+        fee_rate: u64,
+        #[command(flatten)]
+        lock: LockArgs,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum WatchCommand {
+    #[command(about = "Start watching an address, or the address a public key signs for")]
+    Add {
+        #[arg(long)]
+        label: String,
+        #[arg(
+            long,
+            required_unless_present = "public_key",
+            conflicts_with = "public_key"
+        )]
+        address: Option<String>,
+        #[arg(long, help = "Hex encoded")]
+        public_key: Option<String>,
+    },
+    #[command(about = "Stop watching an address")]
+    Remove {
+        #[arg(help = "The label or address")]
+        label: String,
+    },
+    #[command(about = "Show the watched addresses and their balances")]
+    List,
+    #[command(about = "Report payments to watched addresses as they come in and get confirmed")]
+    Follow {
+        #[arg(
+            long,
+            default_value_t = DEFAULT_SETTLED_DEPTH,
+            help = "How many confirmations settle a payment"
+        )]
+        confirmations: u64,
+        #[arg(
+            long,
+            default_value_t = 1000,
+            help = "How often to ask for news, in milliseconds"
+        )]
+        interval: u64,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct KeystoreArgs {
+    #[arg(
+        long,
+        env = "RUSTBUCKS_PASSWORD",
+        hide_env_values = true,
+        help = "What the keystore is encrypted with"
+    )]
+    pub password: String,
+}
+
+#[derive(Debug, Args)]
+pub struct SendArgs {
+    #[arg(long, help = "The sending account's name or address")]
+    pub from: String,
+    #[arg(long)]
+    pub to: String,
+    #[arg(long, help = "In coins, e.g. 1.5")]
+    pub amount: String,
+    #[arg(
+        long,
+        default_value_t = DEFAULT_FEE_RATE,
+        help = "In base units per 1000 bytes, paid on top of the amount"
+    )]
+    pub fee_rate: u64,
+    #[command(flatten)]
+    pub lock: LockArgs,
+    #[arg(long, help = "Print the encoded transaction instead of submitting it")]
+    pub dry_run: bool,
+    #[command(flatten)]
+    pub keystore: KeystoreArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct LockArgs {
+    #[arg(
+        long,
+        conflicts_with = "lock_time",
+        help = "The first block height it can be mined at"
+    )]
+    pub lock_height: Option<u64>,
+    #[arg(
+        long,
+        help = "The first block timestamp it can be mined at, in seconds since the epoch"
+    )]
+    pub lock_time: Option<i64>,
+}
+
+impl LockArgs {
+    pub fn lock_time(&self) -> Option<LockTime> {
+        match (self.lock_height, self.lock_time) {
+            (Some(height), _) => Some(LockTime::Height(height)),
+            (None, timestamp) => timestamp.map(LockTime::Timestamp),
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ChainCommand {
+    #[command(about = "Show the tip, or a block by height or hash")]
+    Show {
+        #[arg(long, conflicts_with = "hash")]
+        height: Option<u64>,
+        #[arg(long)]
+        hash: Option<String>,
+    },
+    #[command(about = "Check every block from genesis to the tip")]
+    Validate,
+    #[command(about = "Write every block out as json")]
+    Export {
+        #[arg(
+            long,
+            short,
+            value_name = "FILE",
+            help = "Where to write it [default: stdout]"
+        )]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct MineArgs {
+    #[arg(
+        long,
+        required_unless_present = "stop",
+        help = "Who gets the block rewards"
+    )]
+    pub address: Option<String>,
+    #[arg(
+        long,
+        default_value_t = 1,
+        help = "How many blocks to mine into the data directory, a node mines until stopped"
+    )]
+    pub blocks: u64,
+    #[arg(
+        long,
+        conflicts_with = "address",
+        help = "Stop the node mining, needs --rpc"
+    )]
+    pub stop: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TxCommand {
+    #[command(about = "Show what's in a hex encoded transaction")]
+    Decode { encoded: String },
+    #[command(about = "Add a keystore account's signature to a hex encoded transaction")]
+    Sign {
+        encoded: String,
+        #[arg(long, help = "The signing account's name or address")]
+        account: String,
+        #[command(flatten)]
+        keystore: KeystoreArgs,
+    },
+    #[command(about = "Put the signatures on copies of the same transaction together")]
+    Combine {
+        #[arg(required = true, num_args = 2..)]
+        encoded: Vec<String>,
+    },
+    #[command(about = "Send a signed transaction to the node, needs --rpc")]
+    Submit { encoded: String },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PsbtCommand {
+    #[command(about = "Wrap a hex encoded transaction up for its signers")]
+    Create {
+        encoded: String,
+        #[arg(
+            long,
+            help = "The sender's hex encoded public key, when it's a key address"
+        )]
+        public_key: Option<String>,
+        #[arg(
+            long = "meta",
+            value_name = "KEY=VALUE",
+            value_parser = parse_metadata,
+            help = "A note for the signers, can be repeated"
+        )]
+        metadata: Vec<(String, String)>,
+        #[command(flatten)]
+        output: PsbtOutputArgs,
+    },
+    #[command(about = "Add a keystore account's signature")]
+    Sign {
+        #[arg(help = PSBT_HELP)]
+        psbt: String,
+        #[arg(long, help = "The signing account's name or address")]
+        account: String,
+        #[command(flatten)]
+        keystore: KeystoreArgs,
+        #[command(flatten)]
+        output: PsbtOutputArgs,
+    },
+    #[command(about = "Put the signatures from copies of the same one together")]
+    Combine {
+        #[arg(required = true, num_args = 2.., help = PSBT_HELP)]
+        psbts: Vec<String>,
+        #[command(flatten)]
+        output: PsbtOutputArgs,
+    },
+    #[command(about = "Show what's in one and whether it holds up")]
+    Inspect {
+        #[arg(help = PSBT_HELP)]
+        psbt: String,
+    },
+    #[command(about = "Turn one with enough signatures into a hex encoded transaction")]
+    Finalize {
+        #[arg(help = PSBT_HELP)]
+        psbt: String,
+    },
+}
+
+const PSBT_HELP: &str = "Base64, or a file holding one in binary or base64";
+
+#[derive(Debug, Args)]
+pub struct PsbtOutputArgs {
+    #[arg(
+        long,
+        short,
+        value_name = "FILE",
+        help = "Write it to a file in binary instead of printing base64"
+    )]
+    pub output: Option<PathBuf>,
+}
+
+fn parse_metadata(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| "expected KEY=VALUE".to_string())
+}
+
+// prints results either for people or for scripts
+#[derive(Debug, Clone, Copy)]
+pub struct Output {
+    pub json: bool,
+}
+
+impl Output {
+    pub fn show<T: Serialize>(
+        &self,
+        value: &T,
+        human: impl FnOnce(&T) -> String,
+    ) -> anyhow::Result<()> {
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(value)?);
+        } else {
+            println!("{}", human(value));
+        }
+        Ok(())
+    }
+}
+
+// the chain every command works with
+pub fn spec() -> ChainSpec {
+    ChainSpec::default()
+}
+
+pub fn default_data_dir() -> PathBuf {
+    match env::var_os("HOME") {
+        Some(home) => PathBuf::from(home).join(".rustbucks"),
+        None => PathBuf::from(".rustbucks"),
+    }
+}
+
+pub async fn run(cli: Cli) -> anyhow::Result<()> {
+    let data_dir = DataDir::new(cli.data_dir.unwrap_or_else(default_data_dir));
+    let output = Output { json: cli.json };
+
+    match cli.command {
+        Command::Node(NodeCommand::Run(args)) => node::run(args, &data_dir, output).await,
+        Command::Wallet(command) => wallet::run(command, cli.rpc, &data_dir, output).await,
+        Command::Chain(command) => chain::run(command, cli.rpc, &data_dir, output).await,
+        Command::Psbt(command) => psbt::run(command, &data_dir, output),
+        Command::Mine(args) => mining::run(args, cli.rpc, &data_dir, output).await,
+        Command::Tx(command) => tx::run(command, cli.rpc, &data_dir, output).await,
+    }
+}
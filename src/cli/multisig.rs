@@ -0,0 +1,65 @@
+use std::net::SocketAddr;
+
+use anyhow::anyhow;
+
+use super::{
+    backend::{Backend, DataDir},
+    spec,
+    tx::{self, SignedTransaction},
+    MultisigCommand, Output, PolicyArgs,
+};
+use crate::{
+    model::{amount::Amount, multisig::MultisigPolicy},
+    wallet::builder::TransactionBuilder,
+};
+
+fn policy(args: &PolicyArgs) -> anyhow::Result<MultisigPolicy> {
+    MultisigPolicy::new(args.threshold, &args.public_keys).map_err(|_| {
+        anyhow!(
+            "can't need {} of {} keys, the threshold has to be between 1 and the number of \
+             distinct keys and every key has to be a hex encoded public key",
+            args.threshold,
+            args.public_keys.len()
+        )
+    })
+}
+
+pub async fn run(
+    command: MultisigCommand,
+    rpc: Option<SocketAddr>,
+    data_dir: &DataDir,
+    output: Output,
+) -> anyhow::Result<()> {
+    let spec = spec();
+    match command {
+        MultisigCommand::Address(args) => {
+            let policy = policy(&args)?;
+            let mut shown = serde_json::to_value(&policy)?;
+            shown["address"] = policy.address().into();
+            output.show(&shown, |_| policy.address())
+        }
+        MultisigCommand::Spend {
+            policy: args,
+            to,
+            amount,
+            fee_rate,
+        } => {
+            let policy = policy(&args)?;
+            let amount = Amount::parse(&amount, spec.decimals)
+                .map_err(|e| anyhow!("bad amount {}: {:?}", amount, e))?;
+            let backend = Backend::open(rpc, data_dir, &spec)?;
+            let blockchain = backend.blockchain(&spec).await?;
+            let mut transaction = TransactionBuilder::multisig(&blockchain, &policy)
+                .pay(&to, amount)
+                .with_fee_rate(fee_rate)
+                .build()?;
+            // the node knows about transactions we don't
+            if !transaction.is_utxo() {
+                transaction.nonce = backend.next_nonce(&transaction.sender).await?;
+            }
+
+            let unsigned = SignedTransaction::new(&transaction)?;
+            output.show(&unsigned, tx::describe_signed)
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::{net::SocketAddr, time::Duration};
+
+use serde::Serialize;
+
+use super::{backend::DataDir, spec, NodeArgs, Output};
+use crate::{
+    model::node::Node,
+    net::tcp::{TcpConfig, TcpNetwork},
+    rpc::{server::RpcServer, subscriptions::SubscriptionServer},
+};
+
+// how often a running node writes its chain out when the tip has moved
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Serialize)]
+struct Listening {
+    p2p: SocketAddr,
+    rpc: SocketAddr,
+    websocket: SocketAddr,
+    height: u64,
+}
+
+pub async fn run(args: NodeArgs, data_dir: &DataDir, output: Output) -> anyhow::Result<()> {
+    let spec = spec();
+    data_dir.create()?;
+    let mut node = Node::with_spec(&spec);
+    node.blockchain = data_dir.load_chain(&spec)?;
+    let height = node.blockchain.tip().index;
+
+    let listen_addr = args
+        .listen
+        .unwrap_or_else(|| SocketAddr::from(([0, 0, 0, 0], spec.default_port)));
+    let mut config = TcpConfig::new(listen_addr);
+    config.address_book_path = Some(data_dir.address_book_path());
+    config.ban_list_path = Some(data_dir.ban_list_path());
+
+    let network = TcpNetwork::start(node, config).await?;
+    for peer in args.peers {
+        if let Err(e) = network.connect(peer).await {
+            tracing::warn!("failed to connect to {}: {}", peer, e);
+        }
+    }
+    let server = RpcServer::start(network.clone(), args.rpc_listen).await?;
+    let subscriptions = SubscriptionServer::start(network.clone(), args.ws_listen).await?;
+    if let Some(address) = args.mine {
+        server.start_mining(Some(address));
+    }
+
+    output.show(
+        &Listening {
+            p2p: network.local_addr,
+            rpc: server.local_addr,
+            websocket: subscriptions.local_addr,
+            height,
+        },
+        |listening| {
+            format!(
+                "at height {}, listening for peers on {}, rpc on {} and websockets on {}",
+                listening.height, listening.p2p, listening.rpc, listening.websocket
+            )
+        },
+    )?;
+
+    let mut saved_tip = network.node.lock().await.blockchain.tip().hash();
+    let mut interval = tokio::time::interval(SAVE_INTERVAL);
+    loop {
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => {
+                result?;
+                break;
+            }
+            _ = interval.tick() => {
+                let node = network.node.lock().await;
+                let tip = node.blockchain.tip().hash();
+                if tip != saved_tip {
+                    data_dir.save_chain(&node.blockchain)?;
+                    saved_tip = tip;
+                }
+            }
+        }
+    }
+
+    server.stop_mining();
+    subscriptions.shutdown();
+    server.shutdown();
+    network.shutdown();
+    let node = network.node.lock().await;
+    data_dir.save_chain(&node.blockchain)
+}
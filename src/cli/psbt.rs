@@ -0,0 +1,150 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Context};
+use serde::Serialize;
+
+use super::{
+    backend::DataDir,
+    tx::{self, describe_status, SignedTransaction},
+    wallet::open_keystore,
+    Output, PsbtCommand, PsbtOutputArgs,
+};
+use crate::wallet::{
+    multisig::SigningStatus,
+    psbt::{PartiallySignedTransaction, PSBT_MAGIC},
+};
+
+#[derive(Debug, Serialize)]
+struct Written {
+    // when it wasn't written to a file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    base64: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
+    status: SigningStatus,
+}
+
+#[derive(Debug, Serialize)]
+struct Inspected {
+    txid: String,
+    psbt: PartiallySignedTransaction,
+    status: SigningStatus,
+    // why it doesn't hold up, when it doesn't
+    #[serde(skip_serializing_if = "Option::is_none")]
+    problem: Option<String>,
+}
+
+// a file if there's one at that path, base64 otherwise
+fn read(psbt: &str) -> anyhow::Result<PartiallySignedTransaction> {
+    let path = Path::new(psbt);
+    if !path.is_file() {
+        return Ok(PartiallySignedTransaction::from_base64(psbt)?);
+    }
+
+    let bytes = fs::read(path).with_context(|| format!("couldn't read {}", path.display()))?;
+    if bytes.starts_with(PSBT_MAGIC) {
+        Ok(PartiallySignedTransaction::decode(&bytes)?)
+    } else {
+        let text = String::from_utf8(bytes).context("neither binary nor base64")?;
+        Ok(PartiallySignedTransaction::from_base64(&text)?)
+    }
+}
+
+fn write(
+    psbt: &PartiallySignedTransaction,
+    args: &PsbtOutputArgs,
+    output: Output,
+) -> anyhow::Result<()> {
+    let written = match &args.output {
+        Some(path) => {
+            fs::write(path, psbt.encode())
+                .with_context(|| format!("couldn't write {}", path.display()))?;
+            Written {
+                base64: None,
+                file: Some(path.display().to_string()),
+                status: psbt.status(),
+            }
+        }
+        None => Written {
+            base64: Some(psbt.to_base64()),
+            file: None,
+            status: psbt.status(),
+        },
+    };
+    output.show(&written, |written| {
+        let first = match (&written.base64, &written.file) {
+            (Some(base64), _) => base64.clone(),
+            (None, file) => format!("written to {}", file.as_deref().unwrap_or_default()),
+        };
+        format!("{}\n{}", first, describe_status(&written.status))
+    })
+}
+
+pub fn run(command: PsbtCommand, data_dir: &DataDir, output: Output) -> anyhow::Result<()> {
+    match command {
+        PsbtCommand::Create {
+            encoded,
+            public_key,
+            metadata,
+            output: args,
+        } => {
+            let transaction = tx::decode(&encoded)?;
+            let mut psbt = PartiallySignedTransaction::new(transaction, public_key.as_deref())?;
+            for (key, value) in metadata {
+                psbt = psbt.with_metadata(&key, &value);
+            }
+            write(&psbt, &args, output)
+        }
+        PsbtCommand::Sign {
+            psbt,
+            account,
+            keystore,
+            output: args,
+        } => {
+            let mut psbt = read(&psbt)?;
+            let keystore = open_keystore(data_dir, &keystore)?;
+            let account = keystore
+                .account(&account)
+                .ok_or_else(|| anyhow!("there's no account {} in the keystore", account))?;
+            psbt.sign(account.signing_key())?;
+            write(&psbt, &args, output)
+        }
+        PsbtCommand::Combine {
+            psbts,
+            output: args,
+        } => {
+            let mut combined = read(&psbts[0])?;
+            for psbt in psbts[1..].iter() {
+                combined.combine(&read(psbt)?)?;
+            }
+            write(&combined, &args, output)
+        }
+        PsbtCommand::Inspect { psbt } => {
+            let psbt = read(&psbt)?;
+            let inspected = Inspected {
+                txid: psbt.transaction.txid(),
+                status: psbt.status(),
+                problem: psbt.validate().err().map(|e| e.to_string()),
+                psbt,
+            };
+            output.show(&inspected, describe)
+        }
+        PsbtCommand::Finalize { psbt } => {
+            let transaction = read(&psbt)?.finalize()?;
+            output.show(&SignedTransaction::new(&transaction)?, tx::describe_signed)
+        }
+    }
+}
+
+fn describe(inspected: &Inspected) -> String {
+    let mut lines = vec![format!("unsigned  {}", inspected.txid)];
+    lines.push(format!("sender    {}", inspected.psbt.transaction.sender));
+    for (key, value) in inspected.psbt.metadata.iter() {
+        lines.push(format!("{:<9} {}", key, value));
+    }
+    lines.push(describe_status(&inspected.status));
+    if let Some(problem) = &inspected.problem {
+        lines.push(format!("invalid: {}", problem));
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,67 @@
+use std::net::SocketAddr;
+
+use anyhow::anyhow;
+use serde::Serialize;
+
+use super::{
+    backend::{Backend, DataDir},
+    spec,
+    tx::{self, SignedTransaction},
+    Output, ScriptCommand,
+};
+use crate::{
+    model::{amount::Amount, script::ScriptSpend},
+    wallet::builder::TransactionBuilder,
+};
+
+#[derive(Debug, Serialize)]
+struct ScriptAddress {
+    address: String,
+    script: String,
+}
+
+pub async fn run(
+    command: ScriptCommand,
+    rpc: Option<SocketAddr>,
+    data_dir: &DataDir,
+    output: Output,
+) -> anyhow::Result<()> {
+    let spec = spec();
+    match command {
+        ScriptCommand::Address { script } => {
+            let shown = ScriptAddress {
+                address: script.address(),
+                script: script.to_string(),
+            };
+            output.show(&shown, |shown| shown.address.clone())
+        }
+        ScriptCommand::Spend {
+            script,
+            witness,
+            to,
+            amount,
+            fee_rate,
+            lock,
+        } => {
+            let amount = Amount::parse(&amount, spec.decimals)
+                .map_err(|e| anyhow!("bad amount {}: {:?}", amount, e))?;
+            let backend = Backend::open(rpc, data_dir, &spec)?;
+            let blockchain = backend.blockchain(&spec).await?;
+            let mut builder =
+                TransactionBuilder::script(&blockchain, &ScriptSpend { script, witness })
+                    .pay(&to, amount)
+                    .with_fee_rate(fee_rate);
+            if let Some(lock_time) = lock.lock_time() {
+                builder = builder.with_lock_time(lock_time);
+            }
+            let mut transaction = builder.build()?;
+            // the node knows about transactions we don't
+            if !transaction.is_utxo() {
+                transaction.nonce = backend.next_nonce(&transaction.sender).await?;
+            }
+
+            let unsigned = SignedTransaction::new(&transaction)?;
+            output.show(&unsigned, tx::describe_signed)
+        }
+    }
+}
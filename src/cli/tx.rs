@@ -0,0 +1,158 @@
+use std::net::SocketAddr;
+
+use anyhow::{bail, Context};
+use serde::Serialize;
+use serde_json::json;
+
+use super::{backend::DataDir, spec, wallet::open_keystore, Output, TxCommand};
+use crate::{
+    model::transaction::{LockTime, Transaction},
+    rpc::client::RpcClient,
+    wallet::multisig::{combine, SigningStatus},
+};
+
+#[derive(Debug, Serialize)]
+pub struct DecodedTransaction {
+    pub txid: String,
+    pub size: u64,
+    pub transaction: Transaction,
+}
+
+// a transaction on its way round its signers
+#[derive(Debug, Serialize)]
+pub struct SignedTransaction {
+    pub txid: String,
+    pub encoded: String,
+    // only for multisig transactions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signing: Option<SigningStatus>,
+}
+
+impl SignedTransaction {
+    pub fn new(transaction: &Transaction) -> anyhow::Result<Self> {
+        Ok(SignedTransaction {
+            txid: transaction.txid(),
+            encoded: encode(transaction)?,
+            signing: SigningStatus::of(transaction),
+        })
+    }
+}
+
+// what goes over the wire, hex encoded so it can be pasted around
+pub fn encode(transaction: &Transaction) -> anyhow::Result<String> {
+    Ok(hex::encode(bincode::serialize(transaction)?))
+}
+
+pub fn decode(encoded: &str) -> anyhow::Result<Transaction> {
+    let bytes = hex::decode(encoded.trim()).context("not hex")?;
+    bincode::deserialize(&bytes).context("not a transaction")
+}
+
+pub async fn run(
+    command: TxCommand,
+    rpc: Option<SocketAddr>,
+    data_dir: &DataDir,
+    output: Output,
+) -> anyhow::Result<()> {
+    match command {
+        TxCommand::Decode { encoded } => {
+            let transaction = decode(&encoded)?;
+            let decoded = DecodedTransaction {
+                txid: transaction.txid(),
+                size: transaction.size(),
+                transaction,
+            };
+            output.show(&decoded, describe)
+        }
+        TxCommand::Sign {
+            encoded,
+            account,
+            keystore,
+        } => {
+            let keystore = open_keystore(data_dir, &keystore)?;
+            let mut transaction = decode(&encoded)?;
+            keystore.sign(&account, &mut transaction)?;
+            output.show(&SignedTransaction::new(&transaction)?, describe_signed)
+        }
+        TxCommand::Combine { encoded } => {
+            let mut transactions = encoded
+                .iter()
+                .map(|encoded| decode(encoded))
+                .collect::<anyhow::Result<Vec<Transaction>>>()?;
+            let first = transactions.remove(0);
+            let combined = combine(first, &transactions)?;
+            output.show(&SignedTransaction::new(&combined)?, describe_signed)
+        }
+        TxCommand::Submit { encoded } => {
+            let transaction = decode(&encoded)?;
+            if let Some(status) =
+                SigningStatus::of(&transaction).filter(|status| !status.is_complete())
+            {
+                bail!("it still needs {} more signatures", status.missing());
+            }
+            let Some(addr) = rpc else {
+                bail!("submitting needs a running node, pass --rpc");
+            };
+            let txid: String = RpcClient::new(addr)
+                .call("submit_transaction", json!({ "transaction": transaction }))
+                .await?;
+            output.show(&json!({ "txid": txid }), |_| format!("sent {}", txid))
+        }
+    }
+}
+
+// the encoding first so it's easy to copy to the next signer
+pub fn describe_signed(signed: &SignedTransaction) -> String {
+    match &signed.signing {
+        Some(status) => format!("{}\n{}", signed.encoded, describe_status(status)),
+        None => signed.encoded.clone(),
+    }
+}
+
+pub fn describe_status(status: &SigningStatus) -> String {
+    if status.is_complete() {
+        format!(
+            "signed by {} of {} needed, ready to submit",
+            status.signed.len(),
+            status.threshold
+        )
+    } else {
+        format!(
+            "signed by {} of {} needed, waiting on {} more from {}",
+            status.signed.len(),
+            status.threshold,
+            status.missing(),
+            status.unsigned.join(", ")
+        )
+    }
+}
+
+fn describe(decoded: &DecodedTransaction) -> String {
+    let decimals = spec().decimals;
+    let transaction = &decoded.transaction;
+    let mut lines = vec![
+        format!("txid      {}", decoded.txid),
+        format!("size      {} bytes", decoded.size),
+        format!("sender    {}", transaction.sender),
+        format!("nonce     {}", transaction.nonce),
+        format!("fee       {}", transaction.fee.format(decimals)),
+    ];
+    match transaction.lock_time {
+        Some(LockTime::Height(height)) => lines.push(format!("locked    until height {}", height)),
+        Some(LockTime::Timestamp(timestamp)) => {
+            lines.push(format!("locked    until timestamp {}", timestamp))
+        }
+        None => {}
+    }
+    for input in transaction.inputs.iter() {
+        lines.push(format!("spends    {}:{}", input.txid, input.index));
+    }
+    for (receiver, amount) in transaction.credits() {
+        lines.push(format!(
+            "pays      {} to {}",
+            amount.format(decimals),
+            receiver
+        ));
+    }
+    lines.join("\n")
+}
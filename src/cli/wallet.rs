@@ -0,0 +1,262 @@
+use std::net::SocketAddr;
+
+use anyhow::{anyhow, bail};
+use serde::Serialize;
+use serde_json::json;
+
+use super::{
+    backend::{Backend, DataDir},
+    multisig, script, spec, tx, watch, KeystoreArgs, Output, SendArgs, WalletCommand,
+};
+use crate::{
+    model::amount::Amount,
+    wallet::{
+        builder::TransactionBuilder,
+        hd::HdWallet,
+        keystore::{AccountInfo, KdfParams, Keystore},
+    },
+};
+
+#[derive(Debug, Serialize)]
+struct Balance {
+    address: String,
+    balance: Amount,
+    formatted: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Sent {
+    txid: String,
+    // only when it wasn't submitted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    encoded: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Restored {
+    accounts: Vec<RestoredAccount>,
+    balance: Amount,
+    formatted: String,
+}
+
+#[derive(Debug, Serialize)]
+struct RestoredAccount {
+    index: u32,
+    address: String,
+    balance: Amount,
+    transactions: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct SecretKey {
+    address: String,
+    secret_key: String,
+}
+
+// makes the keystore the first time it's needed
+pub fn open_keystore(data_dir: &DataDir, keystore: &KeystoreArgs) -> anyhow::Result<Keystore> {
+    data_dir.create()?;
+    Ok(Keystore::open_or_create(
+        &data_dir.keystore_path(),
+        &keystore.password,
+        KdfParams::new(),
+    )?)
+}
+
+fn list_accounts(data_dir: &DataDir) -> anyhow::Result<Vec<AccountInfo>> {
+    let path = data_dir.keystore_path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(Keystore::list(&path)?)
+}
+
+fn describe_account(account: &AccountInfo) -> String {
+    format!("{} {}", account.name, account.address)
+}
+
+pub async fn run(
+    command: WalletCommand,
+    rpc: Option<SocketAddr>,
+    data_dir: &DataDir,
+    output: Output,
+) -> anyhow::Result<()> {
+    let spec = spec();
+    match command {
+        WalletCommand::New { name, keystore } => {
+            let mut keystore = open_keystore(data_dir, &keystore)?;
+            let name = name.unwrap_or_else(|| format!("account-{}", keystore.accounts().len()));
+            let account = keystore.new_account(&name)?.info();
+            output.show(&account, describe_account)
+        }
+        WalletCommand::List => output.show(&list_accounts(data_dir)?, |accounts| {
+            accounts
+                .iter()
+                .map(describe_account)
+                .collect::<Vec<_>>()
+                .join("\n")
+        }),
+        WalletCommand::Balance { address } => {
+            let addresses = match address {
+                Some(address) => vec![address],
+                None => list_accounts(data_dir)?
+                    .into_iter()
+                    .map(|account| account.address)
+                    .collect(),
+            };
+
+            let backend = Backend::open(rpc, data_dir, &spec)?;
+            let mut balances = Vec::new();
+            for address in addresses {
+                let balance = backend.balance(&address).await?;
+                balances.push(Balance {
+                    address,
+                    balance,
+                    formatted: balance.format(spec.decimals),
+                });
+            }
+            output.show(&balances, |balances| {
+                balances
+                    .iter()
+                    .map(|balance| format!("{} {}", balance.address, balance.formatted))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+        }
+        WalletCommand::Send(args) => send(args, rpc, data_dir, output).await,
+        WalletCommand::Import {
+            name,
+            key,
+            keystore,
+        } => {
+            let mut keystore = open_keystore(data_dir, &keystore)?;
+            let account = keystore.import_key(&name, &key)?.info();
+            output.show(&account, describe_account)
+        }
+        WalletCommand::Mnemonic {
+            words,
+            passphrase,
+            keystore,
+        } => {
+            let mut keystore = open_keystore(data_dir, &keystore)?;
+            let wallet = HdWallet::generate(words, &passphrase)?;
+            keystore.set_mnemonic(&wallet)?;
+            output.show(&json!({ "mnemonic": wallet.phrase() }), |_| {
+                format!(
+                    "write these words down somewhere safe:\n{}",
+                    wallet.phrase()
+                )
+            })
+        }
+        WalletCommand::Restore {
+            mnemonic,
+            passphrase,
+            gap_limit,
+            keystore,
+        } => {
+            let wallet = HdWallet::from_mnemonic(&mnemonic, &passphrase)?;
+            let mut keystore = open_keystore(data_dir, &keystore)?;
+            let backend = Backend::open(rpc, data_dir, &spec)?;
+            let blockchain = backend.blockchain(&spec).await?;
+            let scan = wallet.scan(&blockchain, gap_limit);
+            keystore.restore(&wallet, &scan)?;
+
+            let restored = Restored {
+                accounts: scan
+                    .used
+                    .iter()
+                    .map(|used| RestoredAccount {
+                        index: used.index,
+                        address: used.address.clone(),
+                        balance: used.balance,
+                        transactions: used.history.len(),
+                    })
+                    .collect(),
+                balance: scan.balance,
+                formatted: scan.balance.format(spec.decimals),
+            };
+            output.show(&restored, |restored| {
+                let mut lines: Vec<String> = restored
+                    .accounts
+                    .iter()
+                    .map(|account| {
+                        format!(
+                            "hd-{} {} {} in {} transactions",
+                            account.index,
+                            account.address,
+                            account.balance.format(spec.decimals),
+                            account.transactions
+                        )
+                    })
+                    .collect();
+                lines.push(format!("{} all told", restored.formatted));
+                lines.join("\n")
+            })
+        }
+        WalletCommand::Export { account, keystore } => {
+            let keystore = open_keystore(data_dir, &keystore)?;
+            let exported = SecretKey {
+                secret_key: keystore.export_key(&account)?,
+                address: keystore
+                    .account(&account)
+                    .expect("just exported it")
+                    .address(),
+            };
+            output.show(&exported, |exported| exported.secret_key.clone())
+        }
+        WalletCommand::Watch(command) => watch::run(command, rpc, data_dir, output).await,
+        WalletCommand::Multisig(command) => multisig::run(command, rpc, data_dir, output).await,
+        WalletCommand::Script(command) => script::run(command, rpc, data_dir, output).await,
+    }
+}
+
+async fn send(
+    args: SendArgs,
+    rpc: Option<SocketAddr>,
+    data_dir: &DataDir,
+    output: Output,
+) -> anyhow::Result<()> {
+    let spec = spec();
+    let amount = Amount::parse(&args.amount, spec.decimals)
+        .map_err(|e| anyhow!("bad amount {}: {:?}", args.amount, e))?;
+    if rpc.is_none() && !args.dry_run {
+        bail!("sending needs a running node, pass --rpc or use --dry-run");
+    }
+
+    let keystore = open_keystore(data_dir, &args.keystore)?;
+    let sender = keystore
+        .account(&args.from)
+        .ok_or_else(|| anyhow!("there's no account {} in the keystore", args.from))?
+        .address();
+    let backend = Backend::open(rpc, data_dir, &spec)?;
+    let blockchain = backend.blockchain(&spec).await?;
+    let mut builder = TransactionBuilder::new(&blockchain, &sender)
+        .pay(&args.to, amount)
+        .with_fee_rate(args.fee_rate);
+    if let Some(lock_time) = args.lock.lock_time() {
+        builder = builder.with_lock_time(lock_time);
+    }
+    let mut transaction = builder.build()?;
+    // the node knows about transactions we don't
+    if !transaction.is_utxo() {
+        transaction.nonce = backend.next_nonce(&sender).await?;
+    }
+    keystore.sign(&args.from, &mut transaction)?;
+
+    let sent = match backend {
+        Backend::Rpc(client) if !args.dry_run => Sent {
+            txid: client
+                .call("submit_transaction", json!({ "transaction": transaction }))
+                .await?,
+            encoded: None,
+        },
+        _ => Sent {
+            txid: transaction.txid(),
+            encoded: Some(tx::encode(&transaction)?),
+        },
+    };
+    output.show(&sent, |sent| match &sent.encoded {
+        Some(encoded) => encoded.clone(),
+        None => format!("sent {}", sent.txid),
+    })
+}
@@ -0,0 +1,262 @@
+use std::{collections::HashMap, net::SocketAddr, time::Duration};
+
+use anyhow::bail;
+use serde::Serialize;
+use serde_json::json;
+
+use super::{
+    backend::{Backend, DataDir},
+    spec, Output, WatchCommand,
+};
+use crate::{
+    model::{
+        amount::Amount,
+        block::Block,
+        events::{EvictionReason, NodeEvent},
+        transaction::Transaction,
+    },
+    rpc::message::PendingTransaction,
+    wallet::watch::{Payment, WatchEvent, WatchList, WatchMonitor, WatchedAddress},
+};
+
+#[derive(Debug, Serialize)]
+struct WatchedBalance {
+    label: String,
+    address: String,
+    balance: Amount,
+    formatted: String,
+}
+
+fn open_watch_list(data_dir: &DataDir) -> anyhow::Result<WatchList> {
+    data_dir.create()?;
+    Ok(WatchList::open(&data_dir.watch_list_path())?)
+}
+
+fn describe_watched(watched: &WatchedAddress) -> String {
+    format!("{} {}", watched.label, watched.address)
+}
+
+pub async fn run(
+    command: WatchCommand,
+    rpc: Option<SocketAddr>,
+    data_dir: &DataDir,
+    output: Output,
+) -> anyhow::Result<()> {
+    let spec = spec();
+    match command {
+        WatchCommand::Add {
+            label,
+            address,
+            public_key,
+        } => {
+            let mut watch_list = open_watch_list(data_dir)?;
+            let watched = match (address, public_key) {
+                (_, Some(public_key)) => watch_list.watch_public_key(&label, &public_key)?,
+                (Some(address), None) => watch_list.watch_address(&label, &address)?,
+                (None, None) => bail!("pass --address or --public-key"),
+            };
+            output.show(watched, describe_watched)
+        }
+        WatchCommand::Remove { label } => {
+            let removed = open_watch_list(data_dir)?.remove(&label)?;
+            output.show(&removed, describe_watched)
+        }
+        WatchCommand::List => {
+            let watch_list = open_watch_list(data_dir)?;
+            let backend = Backend::open(rpc, data_dir, &spec)?;
+            let mut balances = Vec::new();
+            for watched in watch_list.addresses() {
+                let balance = backend.balance(&watched.address).await?;
+                balances.push(WatchedBalance {
+                    label: watched.label.clone(),
+                    address: watched.address.clone(),
+                    balance,
+                    formatted: balance.format(spec.decimals),
+                });
+            }
+            output.show(&balances, |balances| {
+                balances
+                    .iter()
+                    .map(|balance| {
+                        format!(
+                            "{} {} {}",
+                            balance.label, balance.address, balance.formatted
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+        }
+        WatchCommand::Follow {
+            confirmations,
+            interval,
+        } => follow(rpc, data_dir, output, confirmations, interval).await,
+    }
+}
+
+// polls the node, or the data directory's chain, and turns whatever changed into
+// the events a node would have sent so the monitor can make sense of them
+async fn follow(
+    rpc: Option<SocketAddr>,
+    data_dir: &DataDir,
+    output: Output,
+    confirmations: u64,
+    interval: u64,
+) -> anyhow::Result<()> {
+    let spec = spec();
+    let watch_list = open_watch_list(data_dir)?;
+    if watch_list.addresses().is_empty() {
+        bail!("nothing to watch, add addresses with `wallet watch add`");
+    }
+
+    let mut backend = Backend::open(rpc, data_dir, &spec)?;
+    let blockchain = backend.blockchain(&spec).await?;
+    let mut monitor = WatchMonitor::new(watch_list.addresses(), &blockchain, confirmations);
+    let mut known = blockchain.chain;
+    let mut pending = HashMap::new();
+    let mut interval = tokio::time::interval(Duration::from_millis(interval));
+
+    loop {
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => return Ok(result?),
+            _ = interval.tick() => {}
+        }
+        // the data directory's chain only changes on disk
+        if rpc.is_none() {
+            backend = Backend::open(rpc, data_dir, &spec)?;
+        }
+
+        let mut events = chain_events(&backend, &mut known).await?;
+        if let Backend::Rpc(client) = &backend {
+            let mempool: Vec<PendingTransaction> = client.call("get_mempool", json!(null)).await?;
+            events.extend(mempool_events(mempool, &mut pending));
+        }
+
+        for event in events.iter() {
+            for watched in monitor.handle(event) {
+                if output.json {
+                    println!("{}", serde_json::to_string(&watched)?);
+                } else {
+                    println!("{}", describe_event(&watched, spec.decimals));
+                }
+            }
+        }
+    }
+}
+
+// disconnects back to where the node's chain and ours meet, then connects the node's
+async fn chain_events(backend: &Backend, known: &mut Vec<Block>) -> anyhow::Result<Vec<NodeEvent>> {
+    let tip = backend.tip().await?;
+    if known.last().map(Block::hash) == Some(tip.hash) {
+        return Ok(Vec::new());
+    }
+
+    let mut connected = Vec::new();
+    let mut height = tip.height;
+    loop {
+        let block = backend.block_by_height(height).await?.block;
+        if known
+            .get(height as usize)
+            .is_some_and(|known| known.hash() == block.hash())
+        {
+            break;
+        }
+        if height == 0 {
+            bail!("the node is on a chain with a different genesis block");
+        }
+        connected.push(block);
+        height -= 1;
+    }
+
+    let mut events = Vec::new();
+    let mut depth = 0;
+    while known.len() as u64 > height + 1 {
+        events.push(NodeEvent::BlockDisconnected(
+            known.pop().expect("longer than height"),
+        ));
+        depth += 1;
+    }
+    for block in connected.into_iter().rev() {
+        known.push(block.clone());
+        events.push(NodeEvent::BlockConnected(block));
+    }
+    if depth > 0 {
+        events.push(NodeEvent::ChainReorganized { depth });
+    }
+    Ok(events)
+}
+
+// anything new in the mempool was accepted, anything gone from it was evicted or
+// confirmed. polling can't tell why something was evicted, but the monitor doesn't
+// care and ignores evictions of what it's already seen confirmed
+fn mempool_events(
+    mempool: Vec<PendingTransaction>,
+    pending: &mut HashMap<String, Transaction>,
+) -> Vec<NodeEvent> {
+    let mut events = Vec::new();
+    let mut still_pending = HashMap::new();
+    for entry in mempool {
+        if !pending.contains_key(&entry.txid) {
+            events.push(NodeEvent::TransactionAccepted(entry.transaction.clone()));
+        }
+        still_pending.insert(entry.txid, entry.transaction);
+    }
+
+    for (txid, transaction) in pending.drain() {
+        if !still_pending.contains_key(&txid) {
+            events.push(NodeEvent::TransactionEvicted {
+                transaction,
+                reason: EvictionReason::Conflicted,
+            });
+        }
+    }
+    *pending = still_pending;
+    events
+}
+
+fn describe_payment(payment: &Payment, decimals: u32) -> String {
+    format!(
+        "{} to {} ({}) in {}",
+        payment.amount.format(decimals),
+        payment.label,
+        payment.address,
+        payment.txid
+    )
+}
+
+fn describe_event(event: &WatchEvent, decimals: u32) -> String {
+    match event {
+        WatchEvent::PaymentPending(payment) => {
+            format!("pending {}", describe_payment(payment, decimals))
+        }
+        WatchEvent::PaymentConfirmed(payment) => format!(
+            "confirmed at height {} {}",
+            payment.height.unwrap_or_default(),
+            describe_payment(payment, decimals)
+        ),
+        WatchEvent::PaymentSettled {
+            payment,
+            confirmations,
+        } => format!(
+            "settled after {} confirmations {}",
+            confirmations,
+            describe_payment(payment, decimals)
+        ),
+        WatchEvent::PaymentReverted(payment) => {
+            format!("reverted {}", describe_payment(payment, decimals))
+        }
+        WatchEvent::PaymentDropped(payment) => {
+            format!("dropped {}", describe_payment(payment, decimals))
+        }
+        WatchEvent::BalanceChanged {
+            label,
+            address,
+            balance,
+        } => format!(
+            "{} ({}) now has {}",
+            label,
+            address,
+            balance.format(decimals)
+        ),
+    }
+}
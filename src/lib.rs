@@ -1,2 +1,6 @@
-pub mod model;
+pub mod cli;
 pub mod mine;
+pub mod model;
+pub mod net;
+pub mod rpc;
+pub mod wallet;
@@ -1,44 +1,88 @@
 use chrono::Utc;
-use sha2::Sha256;
-use sha2::Digest;
 use tracing::instrument;
 
-use crate::model::{blockchain::Blockchain, transaction::Transaction, block::Block};
+use crate::model::{
+    amount::Amount,
+    block::Block,
+    blockchain::{Blockchain, MAX_BLOCK_SIZE},
+    chain_spec::LedgerModel,
+    transaction::Transaction,
+};
 
 #[instrument]
-pub fn mine_pending_transactions(blockchain: &Blockchain, pending_transactions: Vec<Transaction>) -> Block {
+pub fn mine_pending_transactions(
+    blockchain: &Blockchain,
+    pending_transactions: Vec<Transaction>,
+) -> Block {
     // for now try to include all current transactions into the next block,
     // theoretically we could cherry pick a subset of the transactions
-    let last_block = blockchain
-        .chain
-        .last()
-        .expect("couldnt get last block. this shouldn't happen.");
-
-    let mut hasher = Sha256::new();
-
-    //compute hash of the previous block
-    hasher.update(format!("{:?}", last_block));
-    let previous_hash = format!("{:x}", hasher.finalize());
-    let timestamp = Utc::now().timestamp();
+    let mut new_block = next_block(blockchain, pending_transactions);
+    proof_of_work(&mut new_block, &blockchain.target_hash_prefix);
+    new_block
+}
 
-    let mut new_block = Block {
-        index: last_block.index + 1,
-        transactions: pending_transactions.clone().into_iter().collect(),
-        previous_hash,
-        timestamp,
-        nonce: 0,
+// a block on top of the tip holding as many of the candidates as fit, in the order
+// they're given. stops at the first one that doesn't fit, a sender's later
+// nonces can't go in without it. with a reward address the block starts with
+// a coinbase paying it the block reward and every fee
+pub fn fill_block(
+    blockchain: &Blockchain,
+    candidates: Vec<Transaction>,
+    reward_address: Option<&str>,
+) -> Block {
+    let mut new_block = next_block(blockchain, Vec::new());
+    let height = new_block.index;
+    let utxo = blockchain.ledger == LedgerModel::Utxo;
+    let coinbase = |fees: Amount, address: &str| {
+        let reward = blockchain.block_reward.saturating_add(fees);
+        Transaction::coinbase(height, address, reward, utxo)
     };
 
-    loop {
-        hasher = Sha256::new();
-        hasher.update(format!("{:?}", new_block));
-        let hash = format!("{:x}", hasher.finalize());
-        if hash.starts_with(&blockchain.target_hash_prefix) {
+    // the coinbase only grows by a few bytes once the fees are added in
+    let mut size = new_block.size();
+    if let Some(address) = reward_address {
+        size += coinbase(Amount::MAX, address).size();
+    }
+
+    let mut fees = Amount::ZERO;
+    for transaction in candidates {
+        let transaction_size = transaction.size();
+        if size + transaction_size > MAX_BLOCK_SIZE {
             break;
         }
-
-        new_block.nonce = new_block.nonce + 1;
+        size += transaction_size;
+        fees = fees.saturating_add(transaction.fee);
+        new_block.transactions.push(transaction);
     }
 
+    if let Some(address) = reward_address {
+        new_block.transactions.insert(0, coinbase(fees, address));
+    }
     new_block
 }
+
+fn next_block(blockchain: &Blockchain, transactions: Vec<Transaction>) -> Block {
+    let last_block = blockchain.tip();
+    Block {
+        index: last_block.index + 1,
+        transactions,
+        previous_hash: last_block.hash(),
+        // blocks mined within the same second still have to move past the median
+        timestamp: Utc::now()
+            .timestamp()
+            .max(blockchain.median_time_past() + 1),
+        nonce: 0,
+    }
+}
+
+// finds a nonce that gives the block a hash starting with the target prefix
+pub fn proof_of_work(block: &mut Block, target_hash_prefix: &str) {
+    // only the nonce changes from here on out,
+    // no need to recompute the merkle root every time
+    let mut header = block.header();
+    while !header.hash().starts_with(target_hash_prefix) {
+        header.nonce += 1;
+    }
+
+    block.nonce = header.nonce;
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+
+use super::{block::Block, transaction::Transaction};
+
+// one transaction in an address's history
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub txid: String,
+    pub height: u64,
+    // the address paid for it
+    pub sent: bool,
+    // the address got paid by it, change included
+    pub received: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum HistoryFilter {
+    #[default]
+    All,
+    Sent,
+    Received,
+}
+
+impl HistoryFilter {
+    fn matches(&self, entry: &HistoryEntry) -> bool {
+        match self {
+            HistoryFilter::All => true,
+            HistoryFilter::Sent => entry.sent,
+            HistoryFilter::Received => entry.received,
+        }
+    }
+}
+
+// part of an address's history, along with how many entries there are all told
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryPage {
+    pub entries: Vec<HistoryEntry>,
+    pub total: usize,
+}
+
+// every transaction touching each address, oldest first
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AddressIndex {
+    entries: HashMap<String, Vec<HistoryEntry>>,
+}
+
+impl AddressIndex {
+    pub fn new() -> Self {
+        AddressIndex {
+            entries: HashMap::new(),
+        }
+    }
+
+    // blocks have to be added in chain order
+    pub fn add_block(&mut self, block: &Block) {
+        for transaction in block.transactions.iter() {
+            let txid = transaction.txid();
+            for (address, sent, received) in touched_addresses(transaction) {
+                self.entries.entry(address).or_default().push(HistoryEntry {
+                    txid: txid.clone(),
+                    height: block.index,
+                    sent,
+                    received,
+                });
+            }
+        }
+    }
+
+    // only the tip can be removed, so its entries are always at the end
+    pub fn remove_block(&mut self, block: &Block) {
+        for transaction in block.transactions.iter() {
+            for (address, _, _) in touched_addresses(transaction) {
+                if let Some(history) = self.entries.get_mut(&address) {
+                    while history
+                        .last()
+                        .is_some_and(|entry| entry.height >= block.index)
+                    {
+                        history.pop();
+                    }
+                    if history.is_empty() {
+                        self.entries.remove(&address);
+                    }
+                }
+            }
+        }
+    }
+
+    // skips offset matching entries and returns at most limit of the ones after
+    pub fn history(
+        &self,
+        address: &str,
+        filter: HistoryFilter,
+        offset: usize,
+        limit: usize,
+    ) -> HistoryPage {
+        let matching: Vec<&HistoryEntry> = self
+            .entries
+            .get(address)
+            .map(|history| {
+                history
+                    .iter()
+                    .filter(|entry| filter.matches(entry))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        HistoryPage {
+            total: matching.len(),
+            entries: matching
+                .into_iter()
+                .skip(offset)
+                .take(limit)
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+// each address the transaction touches once, with whether it sent and whether it received.
+// the genesis allocations come from nobody so there's no sender to record
+pub fn touched_addresses(transaction: &Transaction) -> Vec<(String, bool, bool)> {
+    let mut touched: Vec<(String, bool, bool)> = Vec::new();
+    if !transaction.sender.is_empty() {
+        touched.push((transaction.sender.clone(), true, false));
+    }
+
+    for (receiver, _) in transaction.credits() {
+        if receiver.is_empty() {
+            continue;
+        }
+        match touched
+            .iter_mut()
+            .find(|(address, _, _)| *address == receiver)
+        {
+            Some((_, _, received)) => *received = true,
+            None => touched.push((receiver, false, true)),
+        }
+    }
+
+    touched
+}
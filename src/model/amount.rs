@@ -0,0 +1,198 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+// how many decimal places a coin splits into unless the chain spec says otherwise
+pub const DEFAULT_DECIMALS: u32 = 8;
+
+// 10^19 is the biggest power of ten a u64 holds, past that a whole coin couldn't exist
+pub const MAX_DECIMALS: u32 = 19;
+
+// a number of base units, the smallest piece a coin can be split into.
+// amounts can't go negative and all the math is checked so they can't wrap around either
+#[derive(
+    Clone, Copy, Debug, Default, Eq, PartialEq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+pub struct Amount(u64);
+
+// why a string couldn't be read as an amount
+#[derive(Debug, Clone, PartialEq)]
+pub enum AmountError {
+    Empty,
+    Negative,
+    Invalid,
+    // more digits after the point than the chain splits a coin into
+    TooManyDecimals,
+    Overflow,
+}
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+    pub const MAX: Amount = Amount(u64::MAX);
+
+    pub const fn new(base_units: u64) -> Self {
+        Amount(base_units)
+    }
+
+    pub const fn base_units(self) -> u64 {
+        self.0
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        self.0.checked_add(other.0).map(Amount)
+    }
+
+    pub fn checked_sub(self, other: Amount) -> Option<Amount> {
+        self.0.checked_sub(other.0).map(Amount)
+    }
+
+    pub fn saturating_add(self, other: Amount) -> Amount {
+        Amount(self.0.saturating_add(other.0))
+    }
+
+    pub fn saturating_sub(self, other: Amount) -> Amount {
+        Amount(self.0.saturating_sub(other.0))
+    }
+
+    // None if the total doesn't fit
+    pub fn checked_sum<I: IntoIterator<Item = Amount>>(amounts: I) -> Option<Amount> {
+        amounts
+            .into_iter()
+            .try_fold(Amount::ZERO, |total, amount| total.checked_add(amount))
+    }
+
+    // whole coins and the fraction after the point, e.g. 150000000 is 1.50000000 with 8 decimals
+    pub fn format(self, decimals: u32) -> String {
+        if decimals == 0 {
+            return self.0.to_string();
+        }
+
+        // padded so there's always a digit before the point
+        let decimals = decimals as usize;
+        let digits = format!("{:0>width$}", self.0, width = decimals + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - decimals);
+        format!("{}.{}", whole, fraction)
+    }
+
+    // the reverse of format, the fraction can be shorter than decimals but not longer
+    pub fn parse(s: &str, decimals: u32) -> Result<Amount, AmountError> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(AmountError::Empty);
+        }
+        if s.starts_with('-') {
+            return Err(AmountError::Negative);
+        }
+
+        let (whole, fraction) = match s.split_once('.') {
+            Some((whole, fraction)) => (whole, fraction),
+            None => (s, ""),
+        };
+        let is_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if whole.is_empty() || !is_digits(whole) || !is_digits(fraction) || s.ends_with('.') {
+            return Err(AmountError::Invalid);
+        }
+        if fraction.len() > decimals as usize {
+            return Err(AmountError::TooManyDecimals);
+        }
+
+        // the digits with the fraction padded out to decimals, as a count of base units
+        let significant = format!("{}{}", whole, fraction);
+        let significant = significant.trim_start_matches('0');
+        if significant.is_empty() {
+            return Ok(Amount::ZERO);
+        }
+        let padding = decimals as usize - fraction.len();
+        if significant.len() + padding > u64::MAX.to_string().len() {
+            return Err(AmountError::Overflow);
+        }
+        format!("{}{}", significant, "0".repeat(padding))
+            .parse()
+            .map(Amount)
+            .map_err(|_| AmountError::Overflow)
+    }
+}
+
+// always at DEFAULT_DECIMALS, a chain that splits coins differently has to go
+// through format and parse with its spec's decimals
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format(DEFAULT_DECIMALS))
+    }
+}
+
+impl FromStr for Amount {
+    type Err = AmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Amount::parse(s, DEFAULT_DECIMALS)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Amount, AmountError, MAX_DECIMALS};
+    use crate::model::chain_spec::ChainSpec;
+
+    #[test]
+    pub fn amounts_should_round_trip_through_strings() {
+        assert_eq!(Amount::new(150_000_000).to_string(), "1.50000000");
+        assert_eq!(Amount::new(7).format(2), "0.07");
+        assert_eq!(Amount::new(7).format(0), "7");
+        assert_eq!("1.5".parse(), Ok(Amount::new(150_000_000)));
+        assert_eq!(Amount::parse("12", 2), Ok(Amount::new(1200)));
+        assert_eq!(Amount::parse("0.07", 2), Ok(Amount::new(7)));
+        assert_eq!(Amount::parse(&Amount::MAX.format(8), 8), Ok(Amount::MAX));
+    }
+
+    #[test]
+    pub fn any_number_of_decimals_should_format_and_parse() {
+        assert_eq!(Amount::new(7).format(19), "0.0000000000000000007");
+        assert_eq!(Amount::MAX.format(19), "1.8446744073709551615");
+        assert_eq!(Amount::new(7).format(40).len(), 42);
+        assert_eq!(Amount::parse(&Amount::MAX.format(19), 19), Ok(Amount::MAX));
+        assert_eq!(
+            Amount::parse(&Amount::new(7).format(40), 40),
+            Ok(Amount::new(7))
+        );
+        assert_eq!(Amount::parse("1", 40), Err(AmountError::Overflow));
+        assert_eq!(Amount::parse("0.000", 40), Ok(Amount::ZERO));
+
+        // but a chain can't split its coins that far
+        assert!(ChainSpec::regtest().with_decimals(MAX_DECIMALS).is_ok());
+        assert_eq!(
+            ChainSpec::regtest().with_decimals(MAX_DECIMALS + 1),
+            Err(AmountError::TooManyDecimals)
+        );
+    }
+
+    #[test]
+    pub fn bad_amounts_should_not_parse() {
+        assert_eq!(Amount::parse("", 8), Err(AmountError::Empty));
+        assert_eq!(Amount::parse("-1", 8), Err(AmountError::Negative));
+        assert_eq!(Amount::parse("1.2.3", 8), Err(AmountError::Invalid));
+        assert_eq!(Amount::parse("1.", 8), Err(AmountError::Invalid));
+        assert_eq!(Amount::parse(".5", 8), Err(AmountError::Invalid));
+        assert_eq!(Amount::parse("lots", 8), Err(AmountError::Invalid));
+        assert_eq!(Amount::parse("0.001", 2), Err(AmountError::TooManyDecimals));
+        assert_eq!(
+            Amount::parse("184467440737.09551616", 8),
+            Err(AmountError::Overflow)
+        );
+    }
+
+    #[test]
+    pub fn math_should_not_wrap_around() {
+        assert_eq!(Amount::MAX.checked_add(Amount::new(1)), None);
+        assert_eq!(Amount::ZERO.checked_sub(Amount::new(1)), None);
+        assert_eq!(Amount::checked_sum([Amount::MAX, Amount::new(1)]), None);
+        assert_eq!(
+            Amount::checked_sum([Amount::new(1), Amount::new(2)]),
+            Some(Amount::new(3))
+        );
+    }
+}
@@ -1,8 +1,9 @@
 use super::transaction::Transaction;
-use sha2::Sha256;
+use serde::{Deserialize, Serialize};
 use sha2::Digest;
+use sha2::Sha256;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Block {
     pub index: u64,
     pub transactions: Vec<Transaction>,
@@ -11,10 +12,70 @@ pub struct Block {
     pub timestamp: i64,
 }
 
-impl Block {
+// everything needed to check a block's proof of work without its transactions,
+// the transactions are committed to through their merkle root
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub index: u64,
+    pub previous_hash: String,
+    pub transactions_root: String,
+    pub nonce: u64,
+    pub timestamp: i64,
+}
+
+impl BlockHeader {
     pub fn hash(&self) -> String {
         let mut hasher = Sha256::new();
         hasher.update(format!("{:?}", self));
         format!("{:x}", hasher.finalize())
     }
 }
+
+impl Block {
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            index: self.index,
+            previous_hash: self.previous_hash.clone(),
+            transactions_root: merkle_root(&self.transactions),
+            nonce: self.nonce,
+            timestamp: self.timestamp,
+        }
+    }
+
+    pub fn hash(&self) -> String {
+        self.header().hash()
+    }
+
+    // how many bytes this block takes up on the wire
+    pub fn size(&self) -> u64 {
+        bincode::serialized_size(self).unwrap_or(u64::MAX)
+    }
+}
+
+// pair up hashes and hash them together until there is only one left,
+// an odd one out moves up a level as it is. pairing it with itself would give
+// [.., tx] and [.., tx, tx] the same root
+pub fn merkle_root(transactions: &[Transaction]) -> String {
+    let mut level: Vec<String> = transactions.iter().map(|t| t.txid()).collect();
+    if level.is_empty() {
+        return format!("{:x}", Sha256::new().finalize());
+    }
+
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => {
+                    let mut hasher = Sha256::new();
+                    hasher.update(left);
+                    hasher.update(right);
+                    format!("{:x}", hasher.finalize())
+                }
+                [odd] => odd.clone(),
+                _ => unreachable!("chunks of two"),
+            })
+            .collect();
+    }
+
+    level.remove(0)
+}
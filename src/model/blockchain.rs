@@ -1,10 +1,21 @@
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::Path,
+};
 
-use fixed::types::I32F32;
+use chrono::Utc;
 use sha2::Digest;
 use sha2::Sha256;
 
-use super::{block::Block, transaction::Transaction};
+use super::{
+    address_index::{AddressIndex, HistoryFilter, HistoryPage},
+    amount::Amount,
+    block::{Block, BlockHeader},
+    chain_spec::{ChainSpec, LedgerModel},
+    script::ScriptError,
+    transaction::{OutPoint, Transaction, TxOutput},
+};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Blockchain {
@@ -13,20 +24,125 @@ pub struct Blockchain {
     //this is for adjusting the difficulty
     pub target_hash_prefix: String,
 
-    pub confirmed_transactions: HashSet<Transaction>,
+    // where to find each confirmed transaction by its txid
+    pub confirmed_transactions: HashMap<String, TransactionLocation>,
+
+    // the height of every block in the chain by its hash
+    pub block_index: HashMap<String, u64>,
+
+    // what everybody has to spend as of the tip
+    pub balances: HashMap<String, Amount>,
+
+    // the nonce each sender's next transaction has to use
+    pub nonces: HashMap<String, u64>,
+
+    pub ledger: LedgerModel,
+
+    // outputs nobody has spent yet, only used by the utxo ledger
+    pub utxos: HashMap<OutPoint, TxOutput>,
+
+    // the outputs each block spent, in the same order as the chain,
+    // so they can be put back if the block gets disconnected
+    pub spent_outputs: Vec<Vec<(OutPoint, TxOutput)>>,
+
+    // every transaction touching each address, only kept up when somebody asks for it
+    pub address_index: Option<AddressIndex>,
+
+    // what each block's coinbase can pay out on top of the fees
+    pub block_reward: Amount,
+}
+
+// a confirmed transaction is the position'th one in the block at height
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransactionLocation {
+    pub height: u64,
+    pub position: usize,
 }
 
-#[derive(Debug, PartialEq)]
+// everything there is to know about a confirmed transaction
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfirmedTransaction {
+    pub transaction: Transaction,
+    pub block_hash: String,
+    pub height: u64,
+    // 1 when it's in the tip, and one more for every block on top of that
+    pub confirmations: u64,
+}
+
+// the biggest a serialized block is allowed to be
+pub const MAX_BLOCK_SIZE: u64 = 1_000_000;
+
+// a block's timestamp has to be later than the median of this many blocks before it
+pub const MEDIAN_TIME_SPAN: usize = 11;
+
+// and can't be more than this far ahead of our own clock
+pub const MAX_FUTURE_DRIFT_SECS: i64 = 2 * 60 * 60;
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum BlockchainError {
     UnknownTransaction,
     IncorrectProof,
     InvalidIndex,
     PreviousHashDoesNotMatch,
     EmptyTransactions,
+    BlockTooLarge,
+    InsufficientFunds,
+    DuplicateTransaction,
+    // a sender's transactions have to count up from their last one without gaps
+    InvalidNonce,
+    // the transaction doesn't fit the chain's ledger model
+    WrongLedgerModel,
+    // the output being spent doesn't exist or has already been spent
+    UnknownOutput,
+    // the same output is spent twice in one block
+    DoubleSpend,
+    // somebody tried to spend an output that belongs to somebody else
+    InputNotOwned,
+    // the inputs don't add up to the outputs plus the fee
+    UnbalancedTransaction,
+    // transfers of nothing aren't allowed
+    InvalidAmount,
+    // the amounts add up to more than an amount can hold
+    AmountOverflow,
+    // a coinbase somewhere other than first, at the wrong height or paying out too much
+    InvalidCoinbase,
+    // a signature that doesn't match the transaction or the key it claims to be from
+    InvalidSignature,
+    // the sender's key never signed, or not enough of a multisig sender's keys did
+    MissingSignature,
+    // signatures in anything but the one way a transaction can carry them: lower case
+    // hex, ordered by public key and only the ones the sender needs. otherwise anybody
+    // relaying it could change its txid
+    NonCanonicalSignatures,
+    // a multisig policy that's malformed, doesn't match the sender or has no business being there
+    InvalidMultisig,
+    // its lock time hasn't come yet as of the block it's in
+    NonFinalTransaction,
+    // a block timestamped no later than the median time past
+    TimestampTooEarly,
+    // a block timestamped too far ahead of our clock
+    TimestampTooFarAhead,
+    // a script that doesn't match the sender or has no business being there
+    InvalidScript,
+    // the sender's script didn't pass
+    ScriptFailed(ScriptError),
+}
+
+// how much each address's balance goes up or down, in base units
+pub type BalanceChanges = HashMap<String, i128>;
+
+impl Default for Blockchain {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Blockchain {
     pub fn new() -> Self {
+        Self::from_spec(&ChainSpec::default())
+    }
+
+    pub fn from_spec(spec: &ChainSpec) -> Self {
         let mut hasher = Sha256::new();
         hasher.update("let there be light");
         let timestamp = 0;
@@ -34,24 +150,94 @@ impl Blockchain {
         let genesis_transaction = Transaction {
             sender: "".to_string(),
             receiver: "".to_string(),
-            amount: I32F32::from_num(0.0),
+            amount: Amount::ZERO,
+            fee: Amount::ZERO,
+            nonce: 0,
             timestamp,
+            ..Transaction::default()
         };
 
-        let confirmed_transactions = vec![genesis_transaction.clone()].into_iter().collect();
+        // coins have to come from somewhere
+        let mut transactions = vec![genesis_transaction];
+        let mut balances = HashMap::new();
+        for (receiver, amount) in spec.genesis_allocations.iter() {
+            let allocation = Transaction {
+                sender: "".to_string(),
+                receiver: receiver.clone(),
+                amount: *amount,
+                fee: Amount::ZERO,
+                nonce: 0,
+                timestamp,
+                ..Transaction::default()
+            };
+            transactions.push(match spec.ledger {
+                LedgerModel::Account => allocation,
+                // paid out as an output so it can be spent later
+                LedgerModel::Utxo => Transaction {
+                    receiver: "".to_string(),
+                    amount: Amount::ZERO,
+                    outputs: vec![TxOutput {
+                        receiver: receiver.clone(),
+                        amount: *amount,
+                    }],
+                    ..allocation
+                },
+            });
+            let balance: &mut Amount = balances.entry(receiver.clone()).or_default();
+            *balance = balance
+                .checked_add(*amount)
+                .expect("genesis allocations add up to more than an amount can hold");
+        }
+
+        let confirmed_transactions = transactions
+            .iter()
+            .enumerate()
+            .map(|(position, transaction)| {
+                (
+                    transaction.txid(),
+                    TransactionLocation {
+                        height: 0,
+                        position,
+                    },
+                )
+            })
+            .collect();
+        let mut utxos = HashMap::new();
+        for transaction in transactions.iter() {
+            utxos.extend(
+                transaction
+                    .outpoints()
+                    .into_iter()
+                    .zip(transaction.outputs.clone()),
+            );
+        }
 
-        let chain = vec![Block {
+        let mut genesis = Block {
             index: 0,
-            transactions: vec![genesis_transaction],
-            nonce: 41,
+            transactions,
+            nonce: 334,
             previous_hash: format!("{:x}", hasher.finalize()),
             timestamp,
-        }];
+        };
+
+        // 334 is good enough for the default difficulty,
+        // other difficulties need to find their own
+        while !genesis.hash().starts_with(&spec.target_hash_prefix) {
+            genesis.nonce += 1;
+        }
 
         Blockchain {
-            chain,
-            target_hash_prefix: "00".to_string(), // pretty low difficulty
+            block_index: HashMap::from([(genesis.hash(), 0)]),
+            chain: vec![genesis],
+            target_hash_prefix: spec.target_hash_prefix.clone(),
             confirmed_transactions,
+            balances,
+            nonces: HashMap::new(),
+            ledger: spec.ledger,
+            utxos,
+            spent_outputs: vec![Vec::new()],
+            address_index: None,
+            block_reward: spec.block_reward,
         }
     }
 
@@ -67,6 +253,11 @@ impl Blockchain {
         if new_block.transactions.is_empty() {
             return Err(BlockchainError::EmptyTransactions);
         }
+
+        // cheap to check so do it before any hashing
+        if new_block.size() > MAX_BLOCK_SIZE {
+            return Err(BlockchainError::BlockTooLarge);
+        }
         //verify the last block hash is correct
         let last_hash = last.hash();
         if last_hash != new_block.previous_hash {
@@ -83,15 +274,512 @@ impl Blockchain {
             return Err(BlockchainError::InvalidIndex);
         }
 
-        self.chain.push(new_block.clone());
+        // the timestamp can't be pulled back past the blocks before it or be pushed
+        // far ahead of everybody's clocks
+        let median_time_past = self.median_time_past();
+        if new_block.timestamp <= median_time_past {
+            return Err(BlockchainError::TimestampTooEarly);
+        }
+        if new_block.timestamp > Utc::now().timestamp() + MAX_FUTURE_DRIFT_SECS {
+            return Err(BlockchainError::TimestampTooFarAhead);
+        }
+
+        // locked transactions have to wait for a later block. time locks go by the
+        // median time past so no miner can unlock them early with their own timestamp
+        if !new_block
+            .transactions
+            .iter()
+            .all(|transaction| transaction.is_final(new_block.index, median_time_past))
+        {
+            return Err(BlockchainError::NonFinalTransaction);
+        }
+
+        // the miner's pay is checked on its own, everything else is spending
+        let (coinbase, transactions) = self.split_coinbase(&new_block)?;
+        for transaction in transactions {
+            transaction.check_signatures()?;
+        }
+
+        // make sure nothing is sent out of order or twice
+        let spent = match self.ledger {
+            LedgerModel::Account => {
+                self.check_nonces(transactions)?;
+                Vec::new()
+            }
+            LedgerModel::Utxo => self.check_utxos(transactions)?,
+        };
+        // or spends what they don't have
+        let mut changes = self.balance_changes(transactions)?;
+        if let Some(coinbase) = coinbase {
+            for (receiver, amount) in coinbase.credits() {
+                let change = changes.entry(receiver.clone()).or_insert(0);
+                *change += amount.base_units() as i128;
+                if self.balance_of(&receiver).base_units() as i128 + *change > u64::MAX as i128 {
+                    return Err(BlockchainError::AmountOverflow);
+                }
+            }
+        }
+
+        self.apply_balance_changes(changes, false);
+        for transaction in new_block.transactions.iter() {
+            match self.ledger {
+                LedgerModel::Account if transaction.is_coinbase() => {}
+                LedgerModel::Account => {
+                    self.nonces
+                        .insert(transaction.sender.clone(), transaction.nonce + 1);
+                }
+                LedgerModel::Utxo => {
+                    for input in transaction.inputs.iter() {
+                        self.utxos.remove(input);
+                    }
+                    self.utxos.extend(
+                        transaction
+                            .outpoints()
+                            .into_iter()
+                            .zip(transaction.outputs.clone()),
+                    );
+                }
+            }
+        }
+        self.spent_outputs.push(spent);
         // so we can easily look them up later
-        for transaction in new_block.transactions {
-            self.confirmed_transactions.insert(transaction);
+        for (position, transaction) in new_block.transactions.iter().enumerate() {
+            self.confirmed_transactions.insert(
+                transaction.txid(),
+                TransactionLocation {
+                    height: new_block.index,
+                    position,
+                },
+            );
+        }
+        self.block_index.insert(new_block.hash(), new_block.index);
+        if let Some(address_index) = self.address_index.as_mut() {
+            address_index.add_block(&new_block);
+        }
+        self.chain.push(new_block);
+
+        Ok(())
+    }
+
+    // a block's coinbase has to come first, count the block's height as its nonce and
+    // pay out no more than the block reward plus the fees of everything else in the block.
+    // returns the coinbase if there is one and the rest of the transactions
+    pub fn split_coinbase<'a>(
+        &self,
+        block: &'a Block,
+    ) -> Result<(Option<&'a Transaction>, &'a [Transaction]), BlockchainError> {
+        let (coinbase, rest) = match block.transactions.split_first() {
+            Some((first, rest)) if first.is_coinbase() => (Some(first), rest),
+            _ => (None, &block.transactions[..]),
+        };
+        if rest.iter().any(|transaction| transaction.is_coinbase()) {
+            return Err(BlockchainError::InvalidCoinbase);
+        }
+
+        if let Some(coinbase) = coinbase {
+            let pays_nobody = coinbase
+                .credits()
+                .iter()
+                .any(|(receiver, _)| receiver.is_empty());
+            if coinbase.nonce != block.index || !coinbase.fee.is_zero() || pays_nobody {
+                return Err(BlockchainError::InvalidCoinbase);
+            }
+            if coinbase.is_utxo() != (self.ledger == LedgerModel::Utxo) {
+                return Err(BlockchainError::WrongLedgerModel);
+            }
+
+            let fees = Amount::checked_sum(rest.iter().map(|transaction| transaction.fee))
+                .ok_or(BlockchainError::AmountOverflow)?;
+            let allowed = self
+                .block_reward
+                .checked_add(fees)
+                .ok_or(BlockchainError::AmountOverflow)?;
+            if coinbase.check_amounts()? > allowed {
+                return Err(BlockchainError::InvalidCoinbase);
+            }
+        }
+
+        Ok((coinbase, rest))
+    }
+
+    pub fn balance_of(&self, address: &str) -> Amount {
+        self.balances.get(address).copied().unwrap_or_default()
+    }
+
+    pub fn next_nonce(&self, sender: &str) -> u64 {
+        self.nonces.get(sender).copied().unwrap_or(0)
+    }
+
+    // each sender's transactions have to pick up right where their confirmed ones left off
+    // and go up by one each time, so none of them can be confirmed twice
+    pub fn check_nonces(&self, transactions: &[Transaction]) -> Result<(), BlockchainError> {
+        let mut next: HashMap<&str, u64> = HashMap::new();
+        for transaction in transactions {
+            if transaction.is_utxo() {
+                return Err(BlockchainError::WrongLedgerModel);
+            }
+
+            let sender = transaction.sender.as_str();
+            let expected = next
+                .entry(sender)
+                .or_insert_with(|| self.next_nonce(sender));
+            if transaction.nonce != *expected {
+                return Err(BlockchainError::InvalidNonce);
+            }
+            *expected += 1;
+        }
+
+        Ok(())
+    }
+
+    // every input has to be an unspent output belonging to the sender, either from an
+    // earlier block or an earlier transaction in this one, and has to be spent only once.
+    // returns the outputs that get spent along with what they were worth
+    pub fn check_utxos(
+        &self,
+        transactions: &[Transaction],
+    ) -> Result<Vec<(OutPoint, TxOutput)>, BlockchainError> {
+        let mut created: HashMap<OutPoint, TxOutput> = HashMap::new();
+        let mut spent: Vec<(OutPoint, TxOutput)> = Vec::new();
+        let mut seen: HashSet<&OutPoint> = HashSet::new();
+        for transaction in transactions {
+            if transaction.inputs.is_empty()
+                || transaction.outputs.is_empty()
+                || !transaction.receiver.is_empty()
+                || !transaction.amount.is_zero()
+            {
+                return Err(BlockchainError::WrongLedgerModel);
+            }
+
+            let total_cost = transaction.check_amounts()?;
+            let mut total = Amount::ZERO;
+            for input in transaction.inputs.iter() {
+                if !seen.insert(input) {
+                    return Err(BlockchainError::DoubleSpend);
+                }
+
+                let output = self
+                    .utxos
+                    .get(input)
+                    .or_else(|| created.get(input))
+                    .ok_or(BlockchainError::UnknownOutput)?;
+                if output.receiver != transaction.sender {
+                    return Err(BlockchainError::InputNotOwned);
+                }
+
+                total = total
+                    .checked_add(output.amount)
+                    .ok_or(BlockchainError::AmountOverflow)?;
+                spent.push((input.clone(), output.clone()));
+            }
+
+            if total != total_cost {
+                return Err(BlockchainError::UnbalancedTransaction);
+            }
+
+            created.extend(
+                transaction
+                    .outpoints()
+                    .into_iter()
+                    .zip(transaction.outputs.clone()),
+            );
+        }
+
+        Ok(spent)
+    }
+
+    // what the address can spend on a utxo chain, always in the same order
+    pub fn unspent_outputs(&self, address: &str) -> Vec<(OutPoint, TxOutput)> {
+        let mut unspent: Vec<(OutPoint, TxOutput)> = self
+            .utxos
+            .iter()
+            .filter(|(_, output)| output.receiver == address)
+            .map(|(outpoint, output)| (outpoint.clone(), output.clone()))
+            .collect();
+        unspent.sort_by(|(a, _), (b, _)| a.cmp(b));
+        unspent
+    }
+
+    // how the transactions would change everybody's balances if they were
+    // confirmed in order, fails if somebody would end up spending more than they have
+    // or with more than an amount can hold.
+    // fees aren't credited to anybody here, the block's coinbase can claim them
+    pub fn balance_changes(
+        &self,
+        transactions: &[Transaction],
+    ) -> Result<BalanceChanges, BlockchainError> {
+        let mut changes: BalanceChanges = HashMap::new();
+        let mut seen = HashSet::new();
+        for transaction in transactions {
+            if self.is_confirmed(transaction) || !seen.insert(transaction) {
+                return Err(BlockchainError::DuplicateTransaction);
+            }
+
+            let total_cost = transaction.check_amounts()?.base_units() as i128;
+            let sender = transaction.sender.as_str();
+            let available = self.balance_of(sender).base_units() as i128
+                + changes.get(sender).copied().unwrap_or(0);
+            if available < total_cost {
+                return Err(BlockchainError::InsufficientFunds);
+            }
+
+            *changes.entry(transaction.sender.clone()).or_insert(0) -= total_cost;
+            for (receiver, amount) in transaction.credits() {
+                let change = changes.entry(receiver.clone()).or_insert(0);
+                *change += amount.base_units() as i128;
+                if self.balance_of(&receiver).base_units() as i128 + *change > u64::MAX as i128 {
+                    return Err(BlockchainError::AmountOverflow);
+                }
+            }
+        }
+
+        Ok(changes)
+    }
+
+    fn apply_balance_changes(&mut self, changes: BalanceChanges, undo: bool) {
+        for (address, change) in changes {
+            let change = if undo { -change } else { change };
+            let balance = self.balance_of(&address).base_units() as i128 + change;
+            let balance =
+                u64::try_from(balance).expect("balance changes are checked before they're applied");
+
+            // no point remembering empty accounts
+            if balance == 0 {
+                self.balances.remove(&address);
+            } else {
+                self.balances.insert(address, Amount::new(balance));
+            }
+        }
+    }
+
+    pub fn tip(&self) -> &Block {
+        self.chain
+            .last()
+            .expect("could not get last block in chain, this should never happen")
+    }
+
+    // the median timestamp of the last MEDIAN_TIME_SPAN blocks. the next block has to be
+    // timestamped later than this and time locks are measured against it
+    pub fn median_time_past(&self) -> i64 {
+        let start = self.chain.len().saturating_sub(MEDIAN_TIME_SPAN);
+        let mut timestamps: Vec<i64> = self.chain[start..]
+            .iter()
+            .map(|block| block.timestamp)
+            .collect();
+        timestamps.sort_unstable();
+        timestamps[timestamps.len() / 2]
+    }
+
+    // the height of a block is the same as its index
+    pub fn height_of(&self, hash: &str) -> Option<u64> {
+        self.block_index.get(hash).copied()
+    }
+
+    pub fn is_confirmed(&self, transaction: &Transaction) -> bool {
+        self.confirmed_transactions
+            .contains_key(&transaction.txid())
+    }
+
+    // builds the address index from the whole chain, from here on it's kept up to date
+    // as blocks are added and disconnected
+    pub fn enable_address_index(&mut self) {
+        let mut address_index = AddressIndex::new();
+        for block in self.chain.iter() {
+            address_index.add_block(block);
+        }
+        self.address_index = Some(address_index);
+    }
+
+    // None unless the address index is enabled
+    pub fn address_history(
+        &self,
+        address: &str,
+        filter: HistoryFilter,
+        offset: usize,
+        limit: usize,
+    ) -> Option<HistoryPage> {
+        self.address_index
+            .as_ref()
+            .map(|address_index| address_index.history(address, filter, offset, limit))
+    }
+
+    pub fn get_transaction(&self, txid: &str) -> Option<ConfirmedTransaction> {
+        let location = self.confirmed_transactions.get(txid)?;
+        let block = self.chain.get(location.height as usize)?;
+        Some(ConfirmedTransaction {
+            transaction: block.transactions.get(location.position)?.clone(),
+            block_hash: block.hash(),
+            height: location.height,
+            confirmations: self.tip().index - location.height + 1,
+        })
+    }
+
+    pub fn get_block(&self, hash: &str) -> Option<&Block> {
+        self.height_of(hash)
+            .and_then(|height| self.chain.get(height as usize))
+    }
+
+    // every block needs the same proof of work, each hex digit
+    // in the target prefix makes finding a block 16 times harder
+    pub fn block_work(&self) -> u128 {
+        16u128.pow(self.target_hash_prefix.len() as u32)
+    }
+
+    pub fn total_work(&self) -> u128 {
+        self.block_work() * self.chain.len() as u128
+    }
+
+    // hashes from our tip back to genesis, dense near the tip and sparse after,
+    // so a peer can find the point where our chains diverge
+    pub fn locator(&self) -> Vec<String> {
+        let mut locator = Vec::new();
+        let mut height = self.tip().index;
+        let mut step = 1;
+        loop {
+            locator.push(self.chain[height as usize].hash());
+            if height == 0 {
+                break;
+            }
+
+            if locator.len() >= 10 {
+                step *= 2;
+            }
+            height = height.saturating_sub(step);
+        }
+
+        locator
+    }
+
+    // the headers following the first locator hash we recognize,
+    // if we don't recognize any of them start right after genesis
+    pub fn headers_after(&self, locator: &[String], max: usize) -> Vec<BlockHeader> {
+        let start = locator
+            .iter()
+            .find_map(|hash| self.height_of(hash))
+            .unwrap_or(0);
+
+        self.chain
+            .iter()
+            .skip(start as usize + 1)
+            .take(max)
+            .map(|block| block.header())
+            .collect()
+    }
+
+    // make sure a run of headers links up, one after another, starting from
+    // the block with previous_hash at previous_index and that each has a valid proof
+    pub fn validate_headers(
+        &self,
+        previous_hash: &str,
+        previous_index: u64,
+        headers: &[BlockHeader],
+    ) -> Result<(), BlockchainError> {
+        let mut previous_hash = previous_hash.to_string();
+        let mut previous_index = previous_index;
+        for header in headers {
+            if header.previous_hash != previous_hash {
+                return Err(BlockchainError::PreviousHashDoesNotMatch);
+            }
+
+            if header.index != previous_index + 1 {
+                return Err(BlockchainError::InvalidIndex);
+            }
+
+            let hash = header.hash();
+            if !hash.starts_with(&self.target_hash_prefix) {
+                return Err(BlockchainError::IncorrectProof);
+            }
+
+            if header.timestamp > Utc::now().timestamp() + MAX_FUTURE_DRIFT_SECS {
+                return Err(BlockchainError::TimestampTooFarAhead);
+            }
+
+            previous_hash = hash;
+            previous_index = header.index;
         }
 
         Ok(())
     }
 
+    // take the most recent block off of the chain,
+    // the genesis block can never be disconnected
+    pub fn disconnect_tip(&mut self) -> Option<Block> {
+        if self.chain.len() <= 1 {
+            return None;
+        }
+
+        let block = self.chain.pop()?;
+        self.block_index.remove(&block.hash());
+        if let Some(address_index) = self.address_index.as_mut() {
+            address_index.remove_block(&block);
+        }
+        let mut spent = self.spent_outputs.pop().unwrap_or_default();
+        for transaction in block.transactions.iter().rev() {
+            self.confirmed_transactions.remove(&transaction.txid());
+            match self.ledger {
+                LedgerModel::Account if transaction.is_coinbase() => {}
+                LedgerModel::Account => {
+                    if transaction.nonce == 0 {
+                        self.nonces.remove(&transaction.sender);
+                    } else {
+                        self.nonces
+                            .insert(transaction.sender.clone(), transaction.nonce);
+                    }
+                }
+                LedgerModel::Utxo => {
+                    for outpoint in transaction.outpoints() {
+                        self.utxos.remove(&outpoint);
+                    }
+                    // spent was filled in transaction order so this one's inputs are at the end
+                    let inputs = spent.split_off(spent.len() - transaction.inputs.len());
+                    self.utxos.extend(inputs);
+                }
+            }
+        }
+
+        self.apply_balance_changes(net_changes(&block.transactions), true);
+
+        Some(block)
+    }
+
+    // switch over to a branch that forks off of our chain at fork_height,
+    // returns the blocks that were disconnected to make room for the branch.
+    // if any block in the branch is invalid the chain is left untouched
+    pub fn reorganize(
+        &mut self,
+        fork_height: u64,
+        branch: &[Block],
+    ) -> Result<Vec<Block>, BlockchainError> {
+        if fork_height > self.tip().index {
+            return Err(BlockchainError::InvalidIndex);
+        }
+
+        let mut disconnected = Vec::new();
+        while self.tip().index > fork_height {
+            if let Some(block) = self.disconnect_tip() {
+                disconnected.push(block);
+            }
+        }
+        disconnected.reverse();
+
+        for (connected, block) in branch.iter().enumerate() {
+            if let Err(e) = self.add_new_block(block.clone()) {
+                // put everything back the way we found it
+                for _ in 0..connected {
+                    self.disconnect_tip();
+                }
+                for block in disconnected {
+                    self.add_new_block(block)
+                        .expect("previously connected block should still be valid");
+                }
+
+                return Err(e);
+            }
+        }
+
+        Ok(disconnected)
+    }
+
     // things to validate
     // 1. Previous hash matches actual hash of previous block
     // 2. Hashes all have the target prefix
@@ -103,7 +791,8 @@ impl Blockchain {
         };
 
         for block in &self.chain[1..] {
-            if !prev_hash.starts_with(&self.target_hash_prefix) || prev_hash != prev_hash {
+            if !prev_hash.starts_with(&self.target_hash_prefix) || block.previous_hash != prev_hash
+            {
                 return false;
             }
 
@@ -116,25 +805,143 @@ impl Blockchain {
 
         true
     }
+
+    // builds the chain back up from its blocks, checking every one of them on the way.
+    // the first block has to be the spec's genesis
+    pub fn from_blocks(spec: &ChainSpec, blocks: Vec<Block>) -> Result<Self, BlockchainError> {
+        let mut blockchain = Self::from_spec(spec);
+        let mut blocks = blocks.into_iter();
+        if blocks.next().as_ref() != blockchain.chain.first() {
+            return Err(BlockchainError::PreviousHashDoesNotMatch);
+        }
+
+        for block in blocks {
+            blockchain.add_new_block(block)?;
+        }
+        Ok(blockchain)
+    }
+
+    // only the blocks are kept, everything else gets rebuilt from them on load
+    pub fn load(path: &Path, spec: &ChainSpec) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let blocks: Vec<Block> = bincode::deserialize(&bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Self::from_blocks(spec, blocks)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", e)))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let bytes = bincode::serialize(&self.chain)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bytes)?;
+        fs::rename(tmp, path)
+    }
+}
+
+// what the transactions do to everybody's balances, without checking any of it
+fn net_changes(transactions: &[Transaction]) -> BalanceChanges {
+    let mut changes: BalanceChanges = HashMap::new();
+    for transaction in transactions {
+        let credits = transaction.credits();
+        // new coins, nobody paid for them
+        if !transaction.is_coinbase() {
+            let total_cost = credits
+                .iter()
+                .map(|(_, amount)| amount.base_units() as i128)
+                .sum::<i128>()
+                + transaction.fee.base_units() as i128;
+            *changes.entry(transaction.sender.clone()).or_insert(0) -= total_cost;
+        }
+        for (receiver, amount) in credits {
+            *changes.entry(receiver).or_insert(0) += amount.base_units() as i128;
+        }
+    }
+
+    changes
 }
 
 #[cfg(test)]
 mod test {
-    use fixed::types::I32F32;
-
-    use crate::model::{block::Block, blockchain::BlockchainError, transaction::Transaction};
+    use crate::model::{
+        address_index::HistoryFilter,
+        amount::Amount,
+        block::Block,
+        blockchain::BlockchainError,
+        chain_spec::{ChainSpec, LedgerModel},
+        signature::test::{named_address, signed_by},
+        transaction::{OutPoint, Transaction, TxOutput},
+    };
 
     use super::Blockchain;
 
+    fn funded_chain() -> Blockchain {
+        let allocations = ["Billy", "Jill", "Jane", "me"]
+            .iter()
+            .map(|name| (named_address(name), Amount::new(1000)))
+            .collect();
+        Blockchain::from_spec(&ChainSpec::default().with_genesis_allocations(allocations))
+    }
+
+    fn utxo_chain() -> Blockchain {
+        Blockchain::from_spec(
+            &ChainSpec::default()
+                .with_ledger(LedgerModel::Utxo)
+                .with_genesis_allocations(vec![
+                    (named_address("Billy"), Amount::new(1000)),
+                    (named_address("Jill"), Amount::new(1000)),
+                ]),
+        )
+    }
+
+    // spends everything in inputs, paying amount to Timmy and the rest back to the sender
+    fn spend(
+        sender: &str,
+        inputs: Vec<(OutPoint, TxOutput)>,
+        amount: u64,
+        fee: u64,
+    ) -> Transaction {
+        let total = Amount::checked_sum(inputs.iter().map(|(_, output)| output.amount))
+            .expect("nobody has that much");
+        let change = total
+            .checked_sub(Amount::new(amount + fee))
+            .expect("sender can afford it");
+        let transaction = Transaction {
+            sender: named_address(sender),
+            receiver: "".to_string(),
+            amount: Amount::new(0),
+            fee: Amount::new(fee),
+            nonce: 0,
+            timestamp: 0,
+            inputs: inputs.into_iter().map(|(outpoint, _)| outpoint).collect(),
+            outputs: vec![
+                TxOutput {
+                    receiver: "Timmy".to_string(),
+                    amount: Amount::new(amount),
+                },
+                TxOutput {
+                    receiver: named_address(sender),
+                    amount: change,
+                },
+            ],
+            ..Transaction::default()
+        };
+        signed_by(sender, transaction)
+    }
+
     // this is really just to discover the nonce of the first block
     // should we ever need to update the contents of the block
     #[test]
     pub fn discover_nonce_for_first_block() {
         let chain = Blockchain::new();
-        let mut first_block = chain.chain.first().expect("should have genesis block").clone();
+        let mut first_block = chain
+            .chain
+            .first()
+            .expect("should have genesis block")
+            .clone();
 
         while !first_block.hash().starts_with(&chain.target_hash_prefix) {
-            first_block.nonce = first_block.nonce + 1;
+            first_block.nonce += 1;
         }
 
         println!("nonce discovered:");
@@ -158,7 +965,10 @@ mod test {
                 sender: "Billy".to_string(),
                 receiver: "Timmy".to_string(),
                 timestamp: 0,
-                amount: I32F32::from_num(1),
+                amount: Amount::new(1),
+                fee: Amount::new(0),
+                nonce: 0,
+                ..Transaction::default()
             }],
             timestamp: 0,
         };
@@ -167,7 +977,7 @@ mod test {
 
         chain.chain.push(block_with_hash_without_target_prefix);
 
-        assert_eq!(chain.is_valid(), false);
+        assert!(!chain.is_valid());
     }
 
     #[test]
@@ -180,7 +990,10 @@ mod test {
                 sender: "Billy".to_string(),
                 receiver: "Timmy".to_string(),
                 timestamp: 0,
-                amount: I32F32::from_num(1),
+                amount: Amount::new(1),
+                fee: Amount::new(0),
+                nonce: 0,
+                ..Transaction::default()
             }],
             timestamp: 0,
         };
@@ -188,7 +1001,7 @@ mod test {
         let mut chain = Blockchain::new();
         chain.chain.push(invalid_block);
 
-        assert_eq!(chain.is_valid(), false);
+        assert!(!chain.is_valid());
     }
 
     #[test]
@@ -207,7 +1020,10 @@ mod test {
                 sender: "Billy".to_string(),
                 receiver: "Timmy".to_string(),
                 timestamp: 0,
-                amount: I32F32::from_num(1),
+                amount: Amount::new(1),
+                fee: Amount::new(0),
+                nonce: 0,
+                ..Transaction::default()
             }],
             timestamp: 0,
         };
@@ -228,7 +1044,10 @@ mod test {
                 sender: "me".to_string(),
                 receiver: "you".to_string(),
                 timestamp: 0,
-                amount: I32F32::from_num(50),
+                amount: Amount::new(50),
+                fee: Amount::new(0),
+                nonce: 0,
+                ..Transaction::default()
             }],
             timestamp: 1719876768,
         };
@@ -239,26 +1058,490 @@ mod test {
         assert_eq!(res, Err(BlockchainError::PreviousHashDoesNotMatch));
     }
 
+    // timestamped no earlier than the chain allows
+    fn mine_block_on(chain: &Blockchain, transactions: Vec<Transaction>, timestamp: i64) -> Block {
+        let mut block = Block {
+            index: chain.tip().index + 1,
+            nonce: 0,
+            previous_hash: chain.tip().hash(),
+            transactions,
+            timestamp: timestamp.max(chain.median_time_past() + 1),
+        };
+
+        while !block.hash().starts_with(&chain.target_hash_prefix) {
+            block.nonce += 1;
+        }
+
+        block
+    }
+
     #[test]
-    pub fn should_add_valid_block() {
+    pub fn reorganize_should_switch_to_branch_and_return_disconnected_blocks() {
+        let transaction = |sender: &str| {
+            signed_by(
+                sender,
+                Transaction {
+                    sender: named_address(sender),
+                    receiver: "Timmy".to_string(),
+                    timestamp: 0,
+                    amount: Amount::new(1),
+                    fee: Amount::new(0),
+                    nonce: 0,
+                    ..Transaction::default()
+                },
+            )
+        };
+
+        let mut chain = funded_chain();
+        let mut fork = chain.clone();
+
+        let original = mine_block_on(&chain, vec![transaction("Billy")], 1);
+        chain.add_new_block(original.clone()).expect("valid block");
+
+        let first = mine_block_on(&fork, vec![transaction("Jill")], 2);
+        fork.add_new_block(first.clone()).expect("valid block");
+        let second = mine_block_on(&fork, vec![transaction("Jane")], 3);
+
+        let res = chain.reorganize(0, &[first.clone(), second.clone()]);
+
+        assert_eq!(res, Ok(vec![original]));
+        assert_eq!(chain.tip(), &second);
+        assert!(!chain.is_confirmed(&transaction("Billy")));
+        assert!(chain.is_confirmed(&transaction("Jill")));
+    }
+
+    #[test]
+    pub fn reorganize_should_leave_chain_untouched_on_invalid_branch() {
+        let mut chain = funded_chain();
+        let original = mine_block_on(
+            &chain,
+            vec![signed_by(
+                "Billy",
+                Transaction {
+                    sender: named_address("Billy"),
+                    receiver: "Timmy".to_string(),
+                    timestamp: 0,
+                    amount: Amount::new(1),
+                    fee: Amount::new(0),
+                    nonce: 0,
+                    ..Transaction::default()
+                },
+            )],
+            1,
+        );
+        chain.add_new_block(original).expect("valid block");
+        let before = chain.clone();
+
+        // blocks without any transactions are never valid
+        let bad_block = mine_block_on(&funded_chain(), vec![], 2);
+
+        let res = chain.reorganize(0, &[bad_block]);
+
+        assert_eq!(res, Err(BlockchainError::EmptyTransactions));
+        assert_eq!(chain, before);
+    }
+
+    #[test]
+    pub fn should_not_add_block_that_is_too_large() {
         let mut chain = Blockchain::new();
+        let transactions = (0..30_000)
+            .map(|i| Transaction {
+                sender: "Billy".to_string(),
+                receiver: "Timmy".to_string(),
+                timestamp: i,
+                amount: Amount::new(1),
+                fee: Amount::new(0),
+                nonce: i as u64,
+                ..Transaction::default()
+            })
+            .collect();
+        let huge_block = Block {
+            index: 1,
+            nonce: 0,
+            previous_hash: chain.tip().hash(),
+            transactions,
+            timestamp: 0,
+        };
+
+        let res = chain.add_new_block(huge_block);
+
+        assert_eq!(res, Err(BlockchainError::BlockTooLarge));
+    }
+
+    #[test]
+    pub fn should_not_add_block_spending_more_than_sender_has() {
+        let mut chain = funded_chain();
+        let spend = |timestamp| {
+            signed_by(
+                "Billy",
+                Transaction {
+                    sender: named_address("Billy"),
+                    receiver: "Timmy".to_string(),
+                    timestamp,
+                    amount: Amount::new(600),
+                    fee: Amount::new(0),
+                    nonce: timestamp as u64,
+                    ..Transaction::default()
+                },
+            )
+        };
+        let overspending_block = mine_block_on(&chain, vec![spend(0), spend(1)], 0);
+
+        let res = chain.add_new_block(overspending_block);
+
+        assert_eq!(res, Err(BlockchainError::InsufficientFunds));
+        assert_eq!(chain.balance_of(&named_address("Billy")), Amount::new(1000));
+    }
+
+    #[test]
+    pub fn should_not_add_block_with_empty_or_overflowing_transfers() {
+        let mut chain = funded_chain();
+        let transaction = |amount, fee| {
+            signed_by(
+                "Billy",
+                Transaction {
+                    sender: named_address("Billy"),
+                    receiver: "Timmy".to_string(),
+                    timestamp: 0,
+                    amount: Amount::new(amount),
+                    fee: Amount::new(fee),
+                    nonce: 0,
+                    ..Transaction::default()
+                },
+            )
+        };
+
+        let empty = mine_block_on(&chain, vec![transaction(0, 1)], 0);
+        assert_eq!(
+            chain.add_new_block(empty),
+            Err(BlockchainError::InvalidAmount)
+        );
+
+        let overflowing = mine_block_on(&chain, vec![transaction(u64::MAX, 1)], 0);
+        assert_eq!(
+            chain.add_new_block(overflowing),
+            Err(BlockchainError::AmountOverflow)
+        );
+    }
+
+    #[test]
+    pub fn should_not_add_block_with_transactions_out_of_order() {
+        let mut chain = funded_chain();
+        let transaction = |nonce| {
+            signed_by(
+                "Billy",
+                Transaction {
+                    sender: named_address("Billy"),
+                    receiver: "Timmy".to_string(),
+                    timestamp: 0,
+                    amount: Amount::new(1),
+                    fee: Amount::new(0),
+                    nonce,
+                    ..Transaction::default()
+                },
+            )
+        };
+
+        let skipping_block = mine_block_on(&chain, vec![transaction(1)], 0);
+        assert_eq!(
+            chain.add_new_block(skipping_block),
+            Err(BlockchainError::InvalidNonce)
+        );
+
+        let first = mine_block_on(&chain, vec![transaction(0), transaction(1)], 0);
+        chain.add_new_block(first).expect("valid block");
+        assert_eq!(chain.next_nonce(&named_address("Billy")), 2);
+
+        // identical to the one before except that it was sent again
+        let replayed = signed_by(
+            "Billy",
+            Transaction {
+                timestamp: 1,
+                ..transaction(1)
+            },
+        );
+        let replaying_block = mine_block_on(&chain, vec![replayed], 1);
+        assert_eq!(
+            chain.add_new_block(replaying_block),
+            Err(BlockchainError::InvalidNonce)
+        );
+
+        chain.disconnect_tip();
+        assert_eq!(chain.next_nonce(&named_address("Billy")), 0);
+    }
+
+    #[test]
+    pub fn utxo_chain_should_spend_outputs_and_pay_change() {
+        let mut chain = utxo_chain();
+        let before = chain.clone();
+        let billy = named_address("Billy");
+        let payment = spend("Billy", chain.unspent_outputs(&billy), 300, 10);
+        let new_block = mine_block_on(&chain, vec![payment.clone()], 0);
+
+        chain.add_new_block(new_block).expect("valid block");
+
+        assert_eq!(chain.balance_of(&billy), Amount::new(690));
+        assert_eq!(chain.balance_of("Timmy"), Amount::new(300));
+        let outpoints = payment.outpoints();
+        assert_eq!(
+            chain.unspent_outputs(&billy),
+            vec![(outpoints[1].clone(), payment.outputs[1].clone())]
+        );
+
+        // the change can be spent again in the next block
+        let next = spend("Billy", chain.unspent_outputs(&billy), 90, 0);
+        assert_eq!(chain.check_utxos(&[next]).map(|spent| spent.len()), Ok(1));
+
+        chain.disconnect_tip();
+        assert_eq!(chain, before);
+    }
+
+    #[test]
+    pub fn utxo_chain_should_reject_invalid_spends() {
+        let mut chain = utxo_chain();
+        let billys = chain.unspent_outputs(&named_address("Billy"));
+        let jills = chain.unspent_outputs(&named_address("Jill"));
+
+        let double_spend = vec![
+            spend("Billy", billys.clone(), 1, 0),
+            spend("Billy", billys.clone(), 2, 0),
+        ];
+        assert_eq!(
+            chain.check_utxos(&double_spend),
+            Err(BlockchainError::DoubleSpend)
+        );
+
+        let stealing = spend("Billy", jills, 1, 0);
+        assert_eq!(
+            chain.check_utxos(&[stealing]),
+            Err(BlockchainError::InputNotOwned)
+        );
+
+        let mut unbalanced = spend("Billy", billys.clone(), 1, 0);
+        unbalanced.outputs[0].amount = Amount::new(2);
+        assert_eq!(
+            chain.check_utxos(&[unbalanced]),
+            Err(BlockchainError::UnbalancedTransaction)
+        );
+
+        let account_style = signed_by(
+            "Billy",
+            Transaction {
+                sender: named_address("Billy"),
+                receiver: "Timmy".to_string(),
+                amount: Amount::new(1),
+                fee: Amount::new(0),
+                nonce: 0,
+                timestamp: 0,
+                ..Transaction::default()
+            },
+        );
+        let block = mine_block_on(&chain, vec![account_style], 0);
+        assert_eq!(
+            chain.add_new_block(block),
+            Err(BlockchainError::WrongLedgerModel)
+        );
+
+        // spent in one block, gone for the next
+        let first = mine_block_on(&chain, vec![spend("Billy", billys.clone(), 1, 0)], 0);
+        chain.add_new_block(first).expect("valid block");
+        let again = mine_block_on(&chain, vec![spend("Billy", billys, 2, 0)], 1);
+        assert_eq!(
+            chain.add_new_block(again),
+            Err(BlockchainError::UnknownOutput)
+        );
+    }
+
+    #[test]
+    pub fn address_history_should_follow_the_chain() {
+        let mut chain = funded_chain();
+        let billy = named_address("Billy");
+        assert_eq!(
+            chain.address_history(&billy, HistoryFilter::All, 0, 10),
+            None
+        );
+        chain.enable_address_index();
+
+        let payment = |sender: &str, receiver: &str, nonce| {
+            signed_by(
+                sender,
+                Transaction {
+                    sender: named_address(sender),
+                    receiver: named_address(receiver),
+                    timestamp: 0,
+                    amount: Amount::new(1),
+                    fee: Amount::new(0),
+                    nonce,
+                    ..Transaction::default()
+                },
+            )
+        };
+        let first = mine_block_on(
+            &chain,
+            vec![payment("Billy", "Jill", 0), payment("Jill", "Billy", 0)],
+            1,
+        );
+        chain.add_new_block(first).expect("valid block");
+        let before = chain.clone();
+        let second = mine_block_on(&chain, vec![payment("Billy", "Jane", 1)], 2);
+        chain.add_new_block(second).expect("valid block");
+
+        // the genesis allocation, two payments out and one in
+        let all = chain
+            .address_history(&billy, HistoryFilter::All, 0, 10)
+            .expect("index is enabled");
+        assert_eq!(all.total, 4);
+        assert_eq!(
+            all.entries
+                .iter()
+                .map(|entry| entry.height)
+                .collect::<Vec<u64>>(),
+            vec![0, 1, 1, 2]
+        );
+
+        let sent = chain
+            .address_history(&billy, HistoryFilter::Sent, 1, 10)
+            .expect("index is enabled");
+        assert_eq!(sent.total, 2);
+        assert_eq!(sent.entries.len(), 1);
+        assert_eq!(sent.entries[0].txid, payment("Billy", "Jane", 1).txid());
+
+        let received = chain
+            .address_history(&billy, HistoryFilter::Received, 0, 1)
+            .expect("index is enabled");
+        assert_eq!(received.total, 2);
+        assert_eq!(received.entries.len(), 1);
+        assert_eq!(received.entries[0].height, 0);
+
+        chain.disconnect_tip();
+        assert_eq!(chain, before);
+    }
+
+    #[test]
+    pub fn coinbase_should_pay_the_miner_the_reward_and_fees() {
+        let spec = ChainSpec::default()
+            .with_genesis_allocations(vec![(named_address("Billy"), Amount::new(1000))])
+            .with_block_reward(Amount::new(50));
+        let mut chain = Blockchain::from_spec(&spec);
+        let before = chain.clone();
+        let payment = signed_by(
+            "Billy",
+            Transaction {
+                sender: named_address("Billy"),
+                receiver: "Timmy".to_string(),
+                timestamp: 0,
+                amount: Amount::new(100),
+                fee: Amount::new(10),
+                nonce: 0,
+                ..Transaction::default()
+            },
+        );
+        let coinbase =
+            |height, amount| Transaction::coinbase(height, "Miner", Amount::new(amount), false);
+
+        // more than the reward plus fees, in the wrong place, or at the wrong height
+        for transactions in [
+            vec![coinbase(1, 61), payment.clone()],
+            vec![payment.clone(), coinbase(1, 60)],
+            vec![coinbase(2, 60), payment.clone()],
+        ] {
+            let block = mine_block_on(&chain, transactions, 0);
+            assert_eq!(
+                chain.add_new_block(block),
+                Err(BlockchainError::InvalidCoinbase)
+            );
+        }
+
+        let block = mine_block_on(&chain, vec![coinbase(1, 60), payment], 0);
+        chain.add_new_block(block).expect("valid block");
+        assert_eq!(chain.balance_of("Miner"), Amount::new(60));
+        assert_eq!(chain.balance_of(&named_address("Billy")), Amount::new(890));
+        assert_eq!(chain.next_nonce(""), 0);
+
+        // blocks don't need anything else in them to pay out
+        let block = mine_block_on(&chain, vec![coinbase(2, 50)], 0);
+        chain.add_new_block(block).expect("valid block");
+        assert_eq!(chain.balance_of("Miner"), Amount::new(110));
+
+        chain.disconnect_tip();
+        chain.disconnect_tip();
+        assert_eq!(chain, before);
+    }
+
+    #[test]
+    pub fn chain_should_survive_a_save_and_load() {
+        let spec = ChainSpec::default()
+            .with_genesis_allocations(vec![("Billy".to_string(), Amount::new(1000))]);
+        let mut chain = Blockchain::from_spec(&spec);
+        let coinbase = Transaction::coinbase(1, "Miner", Amount::new(50), false);
+        let block = mine_block_on(&chain, vec![coinbase], 0);
+        chain.add_new_block(block).expect("valid block");
+
+        let dir = tempfile::tempdir().expect("temp dir");
+        let path = dir.path().join("chain.bin");
+        chain.save(&path).expect("saved");
+        assert_eq!(Blockchain::load(&path, &spec).expect("loaded"), chain);
+
+        // somebody else's genesis doesn't load
+        let other = ChainSpec::default();
+        assert!(Blockchain::load(&path, &other).is_err());
+    }
+
+    #[test]
+    pub fn disconnecting_a_block_should_restore_balances() {
+        let mut chain = funded_chain();
+        let before = chain.clone();
+        let new_block = mine_block_on(
+            &chain,
+            vec![signed_by(
+                "Billy",
+                Transaction {
+                    sender: named_address("Billy"),
+                    receiver: "Timmy".to_string(),
+                    timestamp: 0,
+                    amount: Amount::new(600),
+                    fee: Amount::new(10),
+                    nonce: 0,
+                    ..Transaction::default()
+                },
+            )],
+            0,
+        );
+        chain.add_new_block(new_block).expect("valid block");
+        assert_eq!(chain.balance_of(&named_address("Billy")), Amount::new(390));
+        assert_eq!(chain.balance_of("Timmy"), Amount::new(600));
+
+        chain.disconnect_tip();
+
+        assert_eq!(chain, before);
+    }
+
+    #[test]
+    pub fn should_add_valid_block() {
+        let mut chain = funded_chain();
         let previous_hash = chain.chain.first().expect("genesis block").hash();
         let mut valid_block = Block {
             index: 1,
             nonce: 245,
             previous_hash,
-            transactions: vec![Transaction {
-                sender: "me".to_string(),
-                receiver: "you".to_string(),
-                timestamp: 1719876768,
-                amount: I32F32::from_num(50),
-            }],
+            transactions: vec![signed_by(
+                "me",
+                Transaction {
+                    sender: named_address("me"),
+                    receiver: "you".to_string(),
+                    timestamp: 1719876768,
+                    amount: Amount::new(50),
+                    fee: Amount::new(0),
+                    nonce: 0,
+                    ..Transaction::default()
+                },
+            )],
             timestamp: 1719876768,
         };
 
         // "mine" for a good nonce
         while !valid_block.hash().starts_with(&chain.target_hash_prefix) {
-            valid_block.nonce = valid_block.nonce + 1;
+            valid_block.nonce += 1;
         }
 
         let res = chain.add_new_block(valid_block);
@@ -0,0 +1,91 @@
+use std::net::SocketAddr;
+
+use super::amount::{Amount, AmountError, DEFAULT_DECIMALS, MAX_DECIMALS};
+
+// 50 coins at the default decimals
+pub const DEFAULT_BLOCK_REWARD: Amount = Amount::new(50 * 10u64.pow(DEFAULT_DECIMALS));
+
+// how a chain keeps track of who owns what
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LedgerModel {
+    // a balance and a nonce per address
+    #[default]
+    Account,
+    // bitcoin style, transactions spend earlier transactions' outputs
+    Utxo,
+}
+
+// everything that makes one rustbucks network different from another
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainSpec {
+    pub name: String,
+    //this is for adjusting the difficulty
+    pub target_hash_prefix: String,
+    // nodes that are expected to be around, new nodes ask them for more addresses
+    pub seed_peers: Vec<SocketAddr>,
+    pub default_port: u16,
+    // coins that exist from the very start, paid out in the genesis block
+    pub genesis_allocations: Vec<(String, Amount)>,
+    pub ledger: LedgerModel,
+    // how many decimal places amounts are shown with, a coin is 10^decimals base units
+    pub decimals: u32,
+    // what a miner can pay themselves for each block, on top of the fees in it
+    pub block_reward: Amount,
+}
+
+impl Default for ChainSpec {
+    fn default() -> Self {
+        ChainSpec {
+            name: "rustbucks".to_string(),
+            target_hash_prefix: "00".to_string(), // pretty low difficulty
+            seed_peers: Vec::new(),
+            default_port: 7878,
+            genesis_allocations: Vec::new(),
+            ledger: LedgerModel::default(),
+            decimals: DEFAULT_DECIMALS,
+            block_reward: DEFAULT_BLOCK_REWARD,
+        }
+    }
+}
+
+impl ChainSpec {
+    // for running chains side by side on one machine, e.g. in tests. blocks
+    // don't need any proof of work so they can be made as fast as they're needed
+    pub fn regtest() -> Self {
+        ChainSpec {
+            name: "regtest".to_string(),
+            target_hash_prefix: "".to_string(),
+            default_port: 17878,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_seed_peers(mut self, seed_peers: Vec<SocketAddr>) -> Self {
+        self.seed_peers = seed_peers;
+        self
+    }
+
+    pub fn with_genesis_allocations(mut self, genesis_allocations: Vec<(String, Amount)>) -> Self {
+        self.genesis_allocations = genesis_allocations;
+        self
+    }
+
+    pub fn with_ledger(mut self, ledger: LedgerModel) -> Self {
+        self.ledger = ledger;
+        self
+    }
+
+    // more than MAX_DECIMALS and a whole coin wouldn't fit in an amount
+    pub fn with_decimals(mut self, decimals: u32) -> Result<Self, AmountError> {
+        if decimals > MAX_DECIMALS {
+            return Err(AmountError::TooManyDecimals);
+        }
+        self.decimals = decimals;
+        Ok(self)
+    }
+
+    pub fn with_block_reward(mut self, block_reward: Amount) -> Self {
+        self.block_reward = block_reward;
+        self
+    }
+}
@@ -0,0 +1,185 @@
+use tokio::sync::broadcast::{self, error::TryRecvError};
+
+use super::{block::Block, transaction::Transaction};
+use crate::net::peer::PeerId;
+
+// how many events a subscriber can fall behind by before it starts missing them
+pub const EVENT_CAPACITY: usize = 1024;
+
+// why a transaction left the mempool without being confirmed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EvictionReason {
+    // somebody paid more for the same nonce or outputs
+    Replaced,
+    // the mempool was full and it paid the least
+    MempoolFull,
+    // it sat around too long
+    Expired,
+    // a block spent the same nonce or outputs, or left the sender unable to pay
+    Conflicted,
+}
+
+// something changed in a node that other parts of the program might care about
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeEvent {
+    // a block became part of our chain, during a reorg this comes once
+    // for every block on the new branch, lowest first
+    BlockConnected(Block),
+    // a block stopped being part of our chain, highest first
+    BlockDisconnected(Block),
+    // we switched to a branch after disconnecting depth blocks
+    ChainReorganized {
+        depth: u64,
+    },
+    // a transaction made it into the mempool, including ones put back after a reorg
+    TransactionAccepted(Transaction),
+    TransactionEvicted {
+        transaction: Transaction,
+        reason: EvictionReason,
+    },
+    PeerConnected {
+        id: PeerId,
+        outbound: bool,
+    },
+    PeerDisconnected {
+        id: PeerId,
+    },
+    // banned under key, see ban::ban_key, until the given timestamp
+    PeerBanned {
+        key: String,
+        until: i64,
+    },
+}
+
+// what went wrong waiting for an event
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventError {
+    // the subscriber fell behind and this many of the oldest events were dropped,
+    // it should catch up from the node's state before carrying on
+    Lagged(u64),
+    // the node is gone, there won't be any more
+    Closed,
+}
+
+// hands every event to everybody subscribed at the time, nobody listening is fine.
+// the node never waits on a subscriber, one that can't keep up misses events
+// instead and is told how many it missed
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<NodeEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(EVENT_CAPACITY)
+    }
+}
+
+// nodes are compared by their state, not by who's listening to them
+impl PartialEq for EventBus {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        EventBus { sender }
+    }
+
+    pub fn emit(&self, event: NodeEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    // only sees events emitted from here on
+    pub fn subscribe(&self) -> EventSubscription {
+        EventSubscription {
+            receiver: self.sender.subscribe(),
+            missed: 0,
+        }
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+#[derive(Debug)]
+pub struct EventSubscription {
+    receiver: broadcast::Receiver<NodeEvent>,
+    // every event this subscriber has lost to falling behind
+    pub missed: u64,
+}
+
+impl EventSubscription {
+    pub async fn recv(&mut self) -> Result<NodeEvent, EventError> {
+        match self.receiver.recv().await {
+            Ok(event) => Ok(event),
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                self.missed += missed;
+                Err(EventError::Lagged(missed))
+            }
+            Err(broadcast::error::RecvError::Closed) => Err(EventError::Closed),
+        }
+    }
+
+    // None when there's nothing waiting
+    pub fn try_recv(&mut self) -> Result<Option<NodeEvent>, EventError> {
+        match self.receiver.try_recv() {
+            Ok(event) => Ok(Some(event)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Lagged(missed)) => {
+                self.missed += missed;
+                Err(EventError::Lagged(missed))
+            }
+            Err(TryRecvError::Closed) => Err(EventError::Closed),
+        }
+    }
+
+    // how far behind this subscriber is, counting events it's already lost
+    pub fn backlog(&self) -> usize {
+        self.receiver.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{EventBus, EventError, NodeEvent};
+
+    fn event(depth: u64) -> NodeEvent {
+        NodeEvent::ChainReorganized { depth }
+    }
+
+    #[test]
+    pub fn slow_subscribers_should_be_told_what_they_missed() {
+        let bus = EventBus::new(2);
+        let mut subscription = bus.subscribe();
+        assert_eq!(bus.subscriber_count(), 1);
+
+        for depth in 0..5 {
+            bus.emit(event(depth));
+        }
+        assert_eq!(subscription.backlog(), 5);
+        assert_eq!(subscription.try_recv(), Err(EventError::Lagged(3)));
+        assert_eq!(subscription.missed, 3);
+
+        // and then carry on with the oldest events still around
+        assert_eq!(subscription.try_recv(), Ok(Some(event(3))));
+        assert_eq!(subscription.try_recv(), Ok(Some(event(4))));
+        assert_eq!(subscription.try_recv(), Ok(None));
+    }
+
+    #[test]
+    pub fn subscribers_should_only_see_events_after_they_subscribe() {
+        let bus = EventBus::default();
+        bus.emit(event(1));
+        let mut subscription = bus.subscribe();
+        bus.emit(event(2));
+        assert_eq!(subscription.try_recv(), Ok(Some(event(2))));
+        assert_eq!(subscription.try_recv(), Ok(None));
+
+        drop(bus);
+        assert_eq!(subscription.try_recv(), Err(EventError::Closed));
+    }
+}
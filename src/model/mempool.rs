@@ -0,0 +1,888 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap, HashMap, HashSet},
+};
+
+use super::{
+    amount::Amount,
+    blockchain::{Blockchain, BlockchainError},
+    chain_spec::LedgerModel,
+    transaction::{OutPoint, Transaction},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MempoolConfig {
+    pub max_count: usize,
+    pub max_bytes: u64,
+    // transactions that have been waiting this long get dropped
+    pub max_age_secs: i64,
+    // a replacement has to pay at least this much more than what it replaces,
+    // otherwise anyone could keep the network busy relaying the same payment for free
+    pub min_replacement_fee_increment: Amount,
+    // how far past the chain's next nonce for a sender we'll hold on to a transaction
+    // while waiting for the ones in between to show up
+    pub max_nonce_gap: u64,
+}
+
+impl Default for MempoolConfig {
+    fn default() -> Self {
+        MempoolConfig {
+            max_count: 50_000,
+            max_bytes: 10_000_000,
+            max_age_secs: 14 * 24 * 60 * 60,
+            min_replacement_fee_increment: Amount::new(1000),
+            max_nonce_gap: 100,
+        }
+    }
+}
+
+// why a transaction didn't make it into the mempool
+#[derive(Debug, Clone, PartialEq)]
+pub enum MempoolError {
+    AlreadyPending,
+    AlreadyConfirmed,
+    // the sender can't cover it, counting what they're already spending in the mempool
+    InsufficientFunds,
+    // bigger than the whole mempool is allowed to be
+    TooLarge,
+    // the mempool is full of transactions paying a better fee rate
+    FeeTooLow,
+    // conflicts with a pending transaction without paying enough more to replace it
+    ReplacementFeeTooLow,
+    // the sender already used this nonce in a confirmed transaction
+    NonceTooLow,
+    // too far ahead of the sender's confirmed transactions to be worth holding on to
+    NonceTooHigh,
+    // the chain wouldn't accept it, e.g. it spends an output that doesn't exist
+    Rejected(BlockchainError),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MempoolEntry {
+    pub transaction: Transaction,
+    pub size: u64,
+    // base units per 1000 bytes
+    pub fee_rate: u64,
+    pub added_at: i64,
+    // keeps entries with the same fee rate in the order they arrived
+    sequence: u64,
+}
+
+// everything pending from one sender
+#[derive(Debug, Clone, PartialEq, Default)]
+struct Account {
+    // the nonce the chain expects from them next, as of the last time we checked
+    next_nonce: u64,
+    // hashes of their pending transactions by nonce
+    pending: BTreeMap<u64, String>,
+    // what they're spending across all of them
+    spending: Amount,
+}
+
+// transactions waiting to be mined
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Mempool {
+    pub config: MempoolConfig,
+    entries: HashMap<String, MempoolEntry>,
+    accounts: HashMap<String, Account>,
+    // which pending transaction spends each output, only used by the utxo ledger
+    spenders: HashMap<OutPoint, String>,
+    total_bytes: u64,
+    next_sequence: u64,
+}
+
+impl Mempool {
+    pub fn new(config: MempoolConfig) -> Self {
+        Mempool {
+            config,
+            entries: HashMap::new(),
+            accounts: HashMap::new(),
+            spenders: HashMap::new(),
+            total_bytes: 0,
+            next_sequence: 0,
+        }
+    }
+
+    // checks the transaction against the chain and what's already pending,
+    // returns whatever had to be evicted to make room for it, starting with
+    // the transactions it replaced if there were any
+    pub fn add(
+        &mut self,
+        transaction: Transaction,
+        blockchain: &Blockchain,
+        now: i64,
+    ) -> Result<Vec<Transaction>, MempoolError> {
+        let hash = transaction.txid();
+        if self.entries.contains_key(&hash) {
+            return Err(MempoolError::AlreadyPending);
+        }
+
+        if blockchain.is_confirmed(&transaction) {
+            return Err(MempoolError::AlreadyConfirmed);
+        }
+
+        // only miners make these, and only inside their own blocks
+        if transaction.is_coinbase() {
+            return Err(MempoolError::Rejected(BlockchainError::InvalidCoinbase));
+        }
+
+        transaction
+            .check_signatures()
+            .map_err(MempoolError::Rejected)?;
+
+        let total_cost = transaction
+            .check_amounts()
+            .map_err(MempoolError::Rejected)?;
+
+        let replaced: Vec<Transaction> = self
+            .conflicting(&transaction)
+            .into_iter()
+            .cloned()
+            .collect();
+        let mut next_nonce = 0;
+        match blockchain.ledger {
+            LedgerModel::Account => {
+                if transaction.is_utxo() {
+                    return Err(MempoolError::Rejected(BlockchainError::WrongLedgerModel));
+                }
+
+                // transactions past the next nonce wait here until the gap is filled
+                next_nonce = blockchain.next_nonce(&transaction.sender);
+                if transaction.nonce < next_nonce {
+                    return Err(MempoolError::NonceTooLow);
+                }
+                if transaction.nonce - next_nonce > self.config.max_nonce_gap {
+                    return Err(MempoolError::NonceTooHigh);
+                }
+
+                // the replaced transaction's money is free to spend again
+                let freed = Amount::checked_sum(
+                    replaced.iter().filter_map(|replaced| replaced.total_cost()),
+                )
+                .unwrap_or(Amount::MAX);
+                let available = blockchain
+                    .balance_of(&transaction.sender)
+                    .saturating_add(freed)
+                    .saturating_sub(self.spending(&transaction.sender));
+                if available < total_cost {
+                    return Err(MempoolError::InsufficientFunds);
+                }
+            }
+            // the inputs pay for it, as long as they're still unspent on chain
+            LedgerModel::Utxo => {
+                blockchain
+                    .check_utxos(std::slice::from_ref(&transaction))
+                    .map_err(MempoolError::Rejected)?;
+            }
+        }
+
+        if !replaced.is_empty() {
+            let replaced_fees = Amount::checked_sum(replaced.iter().map(|replaced| replaced.fee))
+                .unwrap_or(Amount::MAX);
+            if transaction.fee
+                < replaced_fees.saturating_add(self.config.min_replacement_fee_increment)
+            {
+                return Err(MempoolError::ReplacementFeeTooLow);
+            }
+        }
+
+        let size = transaction.size();
+        if size > self.config.max_bytes {
+            return Err(MempoolError::TooLarge);
+        }
+
+        // take the replaced ones out first so they don't count against the limits,
+        // they go back in if there's still no room
+        let replaced: Vec<MempoolEntry> = replaced
+            .iter()
+            .filter_map(|replaced| self.take(&replaced.txid()))
+            .collect();
+        let fee_rate = transaction.fee_rate();
+        let evicted = match self.make_room(&transaction, size, fee_rate) {
+            Ok(evicted) => evicted,
+            Err(e) => {
+                for replaced in replaced {
+                    self.insert(replaced, next_nonce);
+                }
+                return Err(e);
+            }
+        };
+
+        self.insert(
+            MempoolEntry {
+                transaction,
+                size,
+                fee_rate,
+                added_at: now,
+                sequence: self.next_sequence,
+            },
+            next_nonce,
+        );
+        self.next_sequence += 1;
+
+        Ok(replaced
+            .into_iter()
+            .map(|replaced| replaced.transaction)
+            .chain(evicted)
+            .collect())
+    }
+
+    // pending transactions that can't both be confirmed along with this one,
+    // the one with the same sender and nonce or the ones spending the same outputs
+    pub fn conflicting(&self, transaction: &Transaction) -> Vec<&Transaction> {
+        let mut hashes: Vec<&String> = if transaction.is_utxo() {
+            transaction
+                .inputs
+                .iter()
+                .filter_map(|input| self.spenders.get(input))
+                .collect()
+        } else {
+            self.accounts
+                .get(&transaction.sender)
+                .and_then(|account| account.pending.get(&transaction.nonce))
+                .into_iter()
+                .collect()
+        };
+        hashes.sort();
+        hashes.dedup();
+
+        hashes
+            .into_iter()
+            .filter_map(|hash| self.get(hash))
+            .collect()
+    }
+
+    // the nonce the sender's next transaction should use,
+    // counting the ones they already have lined up in here
+    pub fn next_nonce(&self, sender: &str, blockchain: &Blockchain) -> u64 {
+        let mut nonce = blockchain.next_nonce(sender);
+        if let Some(account) = self.accounts.get(sender) {
+            while account.pending.contains_key(&nonce) {
+                nonce += 1;
+            }
+        }
+        nonce
+    }
+
+    // drop whatever is pending in the same slot as a transaction that just got confirmed,
+    // whether that's the transaction itself or something that was meant to replace it
+    pub fn remove_conflicting(&mut self, transaction: &Transaction) -> Vec<Transaction> {
+        let hashes: Vec<String> = self
+            .conflicting(transaction)
+            .iter()
+            .map(|conflicting| conflicting.txid())
+            .collect();
+        hashes
+            .iter()
+            .filter_map(|hash| self.remove_by_hash(hash))
+            .collect()
+    }
+
+    pub fn remove(&mut self, transaction: &Transaction) -> Option<Transaction> {
+        self.remove_by_hash(&transaction.txid())
+    }
+
+    pub fn remove_by_hash(&mut self, hash: &str) -> Option<Transaction> {
+        self.take(hash).map(|entry| entry.transaction)
+    }
+
+    pub fn contains(&self, transaction: &Transaction) -> bool {
+        self.entries.contains_key(&transaction.txid())
+    }
+
+    pub fn contains_hash(&self, hash: &str) -> bool {
+        self.entries.contains_key(hash)
+    }
+
+    pub fn get(&self, hash: &str) -> Option<&Transaction> {
+        self.entries.get(hash).map(|entry| &entry.transaction)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    // the transactions that could go in the next block, best paying first
+    // but never ahead of an earlier nonce from the same sender,
+    // which is the order a miner wants them in.
+    // anything still waiting on a missing nonce is left out, lock times aren't looked at
+    pub fn transactions(&self) -> Vec<Transaction> {
+        self.select(|_| true)
+    }
+
+    // the same for a block at this height on top of a chain with this median time past,
+    // a transaction that's still locked is left out along with its sender's later nonces
+    pub fn block_candidates(&self, height: u64, median_time_past: i64) -> Vec<Transaction> {
+        self.select(|transaction| transaction.is_final(height, median_time_past))
+    }
+
+    fn select(&self, is_ready: impl Fn(&Transaction) -> bool) -> Vec<Transaction> {
+        let mut ready: Vec<Vec<&MempoolEntry>> = self
+            .accounts
+            .values()
+            .map(|account| {
+                (account.next_nonce..)
+                    .map_while(|nonce| account.pending.get(&nonce))
+                    .filter_map(|hash| self.entries.get(hash))
+                    .take_while(|entry| is_ready(&entry.transaction))
+                    .collect()
+            })
+            .collect();
+        // utxo transactions only ever spend confirmed outputs so they're ready once unlocked
+        ready.extend(
+            self.entries
+                .values()
+                .filter(|entry| entry.transaction.is_utxo() && is_ready(&entry.transaction))
+                .map(|entry| vec![entry]),
+        );
+        for queue in ready.iter_mut() {
+            queue.reverse();
+        }
+
+        // each sender's lowest nonce competes with everybody else's
+        let mut heads = BinaryHeap::new();
+        for (queue, entries) in ready.iter().enumerate() {
+            if let Some(entry) = entries.last() {
+                heads.push((entry.fee_rate, Reverse(entry.sequence), queue));
+            }
+        }
+
+        let mut transactions = Vec::with_capacity(self.entries.len());
+        while let Some((_, _, queue)) = heads.pop() {
+            if let Some(entry) = ready[queue].pop() {
+                transactions.push(entry.transaction.clone());
+            }
+            if let Some(entry) = ready[queue].last() {
+                heads.push((entry.fee_rate, Reverse(entry.sequence), queue));
+            }
+        }
+
+        transactions
+    }
+
+    // what the sender has committed to spending in transactions that are still pending
+    pub fn spending(&self, sender: &str) -> Amount {
+        self.accounts
+            .get(sender)
+            .map(|account| account.spending)
+            .unwrap_or_default()
+    }
+
+    // whether a pending transaction already spends the output
+    pub fn is_spent(&self, outpoint: &OutPoint) -> bool {
+        self.spenders.contains_key(outpoint)
+    }
+
+    // drop anything that has been waiting too long, returns what was dropped
+    pub fn expire(&mut self, now: i64) -> Vec<Transaction> {
+        let cutoff = now - self.config.max_age_secs;
+        let mut expired: Vec<&MempoolEntry> = self
+            .entries
+            .values()
+            .filter(|entry| entry.added_at < cutoff)
+            .collect();
+        expired.sort_by_key(|entry| entry.sequence);
+        let mut hashes = Vec::new();
+        for entry in expired {
+            for hash in self.with_descendants(entry) {
+                if !hashes.contains(&hash) {
+                    hashes.push(hash);
+                }
+            }
+        }
+
+        hashes
+            .iter()
+            .filter_map(|hash| self.remove_by_hash(hash))
+            .collect()
+    }
+
+    // after the chain changes some transactions might not be valid anymore,
+    // e.g. a sender's funds went somewhere else in the new blocks. only the senders
+    // and outputs the changed transactions touched, whether they were connected or
+    // disconnected, are checked again. signatures and amounts don't depend on the
+    // chain so they aren't. returns what didn't make it in the order it arrived
+    pub fn revalidate<'a>(
+        &mut self,
+        blockchain: &Blockchain,
+        changed: impl IntoIterator<Item = &'a Transaction>,
+    ) -> Vec<Transaction> {
+        let mut addresses: HashSet<String> = HashSet::new();
+        let mut outpoints: HashSet<OutPoint> = HashSet::new();
+        for transaction in changed {
+            addresses.insert(transaction.sender.clone());
+            addresses.extend(
+                transaction
+                    .credits()
+                    .into_iter()
+                    .map(|(receiver, _)| receiver),
+            );
+            if transaction.is_utxo() {
+                outpoints.extend(transaction.inputs.iter().cloned());
+                outpoints.extend(transaction.outpoints());
+            }
+        }
+
+        let mut invalid: Vec<&MempoolEntry> = Vec::new();
+        for address in addresses.iter() {
+            let Some(account) = self.accounts.get(address) else {
+                continue;
+            };
+
+            // the same checks add makes, in the order they first came in
+            let next_nonce = blockchain.next_nonce(address);
+            let balance = blockchain.balance_of(address);
+            let mut entries: Vec<&MempoolEntry> = account
+                .pending
+                .values()
+                .filter_map(|hash| self.entries.get(hash))
+                .collect();
+            entries.sort_by_key(|entry| entry.sequence);
+            let mut spending = Amount::ZERO;
+            for entry in entries {
+                let transaction = &entry.transaction;
+                let affordable = transaction
+                    .total_cost()
+                    .and_then(|cost| spending.checked_add(cost))
+                    .filter(|total| *total <= balance);
+                match affordable {
+                    Some(total)
+                        if transaction.nonce >= next_nonce
+                            && transaction.nonce - next_nonce <= self.config.max_nonce_gap =>
+                    {
+                        spending = total
+                    }
+                    _ => invalid.push(entry),
+                }
+            }
+        }
+        for outpoint in outpoints.iter() {
+            let Some(entry) = self
+                .spenders
+                .get(outpoint)
+                .and_then(|hash| self.entries.get(hash))
+            else {
+                continue;
+            };
+            if blockchain
+                .check_utxos(std::slice::from_ref(&entry.transaction))
+                .is_err()
+            {
+                invalid.push(entry);
+            }
+        }
+        invalid.sort_by_key(|entry| entry.sequence);
+        invalid.dedup_by_key(|entry| entry.sequence);
+        let hashes: Vec<String> = invalid
+            .into_iter()
+            .map(|entry| entry.transaction.txid())
+            .collect();
+
+        let dropped = hashes
+            .iter()
+            .filter_map(|hash| self.remove_by_hash(hash))
+            .collect();
+        for address in addresses.iter() {
+            if let Some(account) = self.accounts.get_mut(address) {
+                account.next_nonce = blockchain.next_nonce(address);
+            }
+        }
+
+        dropped
+    }
+
+    fn insert(&mut self, entry: MempoolEntry, next_nonce: u64) {
+        let transaction = &entry.transaction;
+        let hash = transaction.txid();
+        self.total_bytes += entry.size;
+        if transaction.is_utxo() {
+            for input in transaction.inputs.iter() {
+                self.spenders.insert(input.clone(), hash.clone());
+            }
+        } else {
+            let account = self.accounts.entry(transaction.sender.clone()).or_default();
+            account.next_nonce = next_nonce;
+            account.spending = account
+                .spending
+                .saturating_add(transaction.total_cost().unwrap_or(Amount::MAX));
+            account.pending.insert(transaction.nonce, hash.clone());
+        }
+        self.entries.insert(hash, entry);
+    }
+
+    fn take(&mut self, hash: &str) -> Option<MempoolEntry> {
+        let entry = self.entries.remove(hash)?;
+        self.total_bytes -= entry.size;
+
+        let transaction = &entry.transaction;
+        if transaction.is_utxo() {
+            for input in transaction.inputs.iter() {
+                self.spenders.remove(input);
+            }
+        } else if let Some(account) = self.accounts.get_mut(&transaction.sender) {
+            account.spending = account
+                .spending
+                .saturating_sub(transaction.total_cost().unwrap_or(Amount::MAX));
+            account.pending.remove(&transaction.nonce);
+            if account.pending.is_empty() {
+                self.accounts.remove(&transaction.sender);
+            }
+        }
+
+        Some(entry)
+    }
+
+    // best paying first, regardless of whether they're ready to be mined
+    fn sorted_entries(&self) -> Vec<&MempoolEntry> {
+        let mut entries: Vec<&MempoolEntry> = self.entries.values().collect();
+        entries.sort_by(|a, b| {
+            b.fee_rate
+                .cmp(&a.fee_rate)
+                .then(a.sequence.cmp(&b.sequence))
+        });
+        entries
+    }
+
+    // the entry's hash followed by its sender's later nonces, which can't be
+    // mined without it
+    fn with_descendants(&self, entry: &MempoolEntry) -> Vec<String> {
+        let transaction = &entry.transaction;
+        match self.accounts.get(&transaction.sender) {
+            Some(account) if !transaction.is_utxo() => account
+                .pending
+                .range(transaction.nonce..)
+                .map(|(_, hash)| hash.clone())
+                .collect(),
+            _ => vec![transaction.txid()],
+        }
+    }
+
+    // evict the lowest paying transactions until there's room for the newcomer,
+    // as long as they pay less than it does. anything that can't be mined without
+    // a victim goes along with it
+    fn make_room(
+        &mut self,
+        transaction: &Transaction,
+        size: u64,
+        fee_rate: u64,
+    ) -> Result<Vec<Transaction>, MempoolError> {
+        let mut victims = Vec::new();
+        let mut evicted = HashSet::new();
+        let mut count = self.entries.len();
+        let mut bytes = self.total_bytes;
+        for entry in self.sorted_entries().into_iter().rev() {
+            if count < self.config.max_count && bytes + size <= self.config.max_bytes {
+                break;
+            }
+
+            if evicted.contains(&entry.transaction.txid()) {
+                continue;
+            }
+
+            if entry.fee_rate >= fee_rate {
+                return Err(MempoolError::FeeTooLow);
+            }
+
+            // the newcomer would be stuck behind one of its sender's evicted nonces
+            let sender = &entry.transaction.sender;
+            if !transaction.is_utxo()
+                && !entry.transaction.is_utxo()
+                && *sender == transaction.sender
+                && entry.transaction.nonce < transaction.nonce
+            {
+                return Err(MempoolError::FeeTooLow);
+            }
+
+            for hash in self.with_descendants(entry) {
+                if let Some(victim) = self.entries.get(&hash) {
+                    if evicted.insert(hash.clone()) {
+                        count -= 1;
+                        bytes -= victim.size;
+                        victims.push(hash);
+                    }
+                }
+            }
+        }
+
+        if count >= self.config.max_count || bytes + size > self.config.max_bytes {
+            return Err(MempoolError::FeeTooLow);
+        }
+
+        Ok(victims
+            .iter()
+            .filter_map(|hash| self.remove_by_hash(hash))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        mine::mine_pending_transactions,
+        model::{
+            amount::Amount,
+            blockchain::Blockchain,
+            chain_spec::ChainSpec,
+            signature::test::{named_address, signed_by},
+            transaction::Transaction,
+        },
+    };
+
+    use super::{Mempool, MempoolConfig, MempoolError};
+
+    fn chain() -> Blockchain {
+        Blockchain::from_spec(&ChainSpec::default().with_genesis_allocations(vec![
+            (named_address("Billy"), Amount::new(100)),
+            (named_address("Alice"), Amount::new(100)),
+        ]))
+    }
+
+    fn transaction(sender: &str, amount: u64, fee: u64, nonce: u64) -> Transaction {
+        let transaction = Transaction {
+            sender: named_address(sender),
+            receiver: "Timmy".to_string(),
+            amount: Amount::new(amount),
+            fee: Amount::new(fee),
+            nonce,
+            ..Transaction::default()
+        };
+        signed_by(sender, transaction)
+    }
+
+    #[test]
+    pub fn mempool_should_reject_transactions_the_sender_cant_afford() {
+        let chain = chain();
+        let mut mempool = Mempool::default();
+
+        assert_eq!(
+            mempool.add(transaction("Billy", 60, 1, 0), &chain, 0),
+            Ok(vec![])
+        );
+        // the first one is still pending so there's only 39 left
+        assert_eq!(
+            mempool.add(transaction("Billy", 39, 1, 1), &chain, 0),
+            Err(MempoolError::InsufficientFunds)
+        );
+        assert_eq!(
+            mempool.add(transaction("Nobody", 1, 0, 0), &chain, 0),
+            Err(MempoolError::InsufficientFunds)
+        );
+        assert_eq!(
+            mempool.add(transaction("Billy", 60, 1, 0), &chain, 0),
+            Err(MempoolError::AlreadyPending)
+        );
+    }
+
+    #[test]
+    pub fn full_mempool_should_evict_lowest_fee_rate_first() {
+        let chain = chain();
+        let mut mempool = Mempool::new(MempoolConfig {
+            max_count: 2,
+            ..MempoolConfig::default()
+        });
+
+        mempool
+            .add(transaction("Billy", 1, 1, 0), &chain, 0)
+            .expect("room for it");
+        mempool
+            .add(transaction("Alice", 1, 5, 0), &chain, 0)
+            .expect("room for it");
+
+        assert_eq!(
+            mempool.add(transaction("Alice", 1, 1, 1), &chain, 0),
+            Err(MempoolError::FeeTooLow)
+        );
+        assert_eq!(
+            mempool.add(transaction("Alice", 1, 3, 1), &chain, 0),
+            Ok(vec![transaction("Billy", 1, 1, 0)])
+        );
+        assert_eq!(
+            mempool.transactions(),
+            vec![transaction("Alice", 1, 5, 0), transaction("Alice", 1, 3, 1)]
+        );
+    }
+
+    #[test]
+    pub fn evicting_a_transaction_should_take_its_later_nonces_with_it() {
+        let chain = chain();
+        let mut mempool = Mempool::new(MempoolConfig {
+            max_count: 3,
+            ..MempoolConfig::default()
+        });
+
+        mempool
+            .add(transaction("Billy", 1, 1, 0), &chain, 0)
+            .expect("room for it");
+        mempool
+            .add(transaction("Billy", 1, 9, 1), &chain, 0)
+            .expect("room for it");
+        mempool
+            .add(transaction("Alice", 1, 5, 0), &chain, 0)
+            .expect("room for it");
+
+        // Billy's first nonce is the cheapest, his own later ones can't get in past it
+        assert_eq!(
+            mempool.add(transaction("Billy", 1, 3, 2), &chain, 0),
+            Err(MempoolError::FeeTooLow)
+        );
+        assert_eq!(
+            mempool.add(transaction("Alice", 1, 3, 1), &chain, 0),
+            Ok(vec![
+                transaction("Billy", 1, 1, 0),
+                transaction("Billy", 1, 9, 1)
+            ])
+        );
+        assert_eq!(
+            mempool.transactions(),
+            vec![transaction("Alice", 1, 5, 0), transaction("Alice", 1, 3, 1)]
+        );
+        assert_eq!(mempool.spending(&named_address("Billy")), Amount::new(0));
+    }
+
+    #[test]
+    pub fn future_nonces_should_wait_for_the_gap_to_be_filled() {
+        let chain = chain();
+        let mut mempool = Mempool::new(MempoolConfig {
+            max_nonce_gap: 5,
+            ..MempoolConfig::default()
+        });
+
+        mempool
+            .add(transaction("Billy", 1, 0, 2), &chain, 0)
+            .expect("valid");
+        mempool
+            .add(transaction("Billy", 1, 0, 1), &chain, 0)
+            .expect("valid");
+        assert_eq!(mempool.len(), 2);
+        assert!(mempool.transactions().is_empty());
+        assert_eq!(mempool.next_nonce(&named_address("Billy"), &chain), 0);
+        assert_eq!(
+            mempool.add(transaction("Billy", 1, 0, 6), &chain, 0),
+            Err(MempoolError::NonceTooHigh)
+        );
+
+        // a lower nonce goes first even when it pays less
+        mempool
+            .add(transaction("Billy", 1, 9, 0), &chain, 0)
+            .expect("valid");
+        mempool
+            .add(transaction("Alice", 1, 5, 0), &chain, 0)
+            .expect("valid");
+        assert_eq!(
+            mempool.transactions(),
+            vec![
+                transaction("Billy", 1, 9, 0),
+                transaction("Alice", 1, 5, 0),
+                transaction("Billy", 1, 0, 1),
+                transaction("Billy", 1, 0, 2),
+            ]
+        );
+        assert_eq!(mempool.next_nonce(&named_address("Billy"), &chain), 3);
+    }
+
+    #[test]
+    pub fn higher_fee_should_replace_pending_transaction_with_same_nonce() {
+        let chain = chain();
+        let mut mempool = Mempool::new(MempoolConfig {
+            min_replacement_fee_increment: Amount::new(2),
+            ..MempoolConfig::default()
+        });
+        let stuck = transaction("Billy", 90, 1, 0);
+        mempool.add(stuck.clone(), &chain, 0).expect("valid");
+
+        // has to pay at least two more than the one it's replacing
+        let cheap = signed_by(
+            "Billy",
+            Transaction {
+                fee: Amount::new(2),
+                ..stuck.clone()
+            },
+        );
+        assert_eq!(
+            mempool.add(cheap, &chain, 0),
+            Err(MempoolError::ReplacementFeeTooLow)
+        );
+
+        // the 91 tied up in the stuck one counts towards paying for its replacement
+        let replacement = signed_by(
+            "Billy",
+            Transaction {
+                amount: Amount::new(95),
+                fee: Amount::new(5),
+                ..stuck.clone()
+            },
+        );
+        assert_eq!(
+            mempool.add(replacement.clone(), &chain, 0),
+            Ok(vec![stuck.clone()])
+        );
+        assert_eq!(mempool.transactions(), vec![replacement.clone()]);
+        assert_eq!(mempool.spending(&named_address("Billy")), Amount::new(100));
+
+        // once the original makes it into a block its replacement is dead
+        assert_eq!(mempool.remove_conflicting(&stuck), vec![replacement]);
+        assert!(mempool.is_empty());
+    }
+
+    #[test]
+    pub fn old_transactions_should_expire() {
+        let chain = chain();
+        let mut mempool = Mempool::new(MempoolConfig {
+            max_age_secs: 10,
+            ..MempoolConfig::default()
+        });
+
+        mempool
+            .add(transaction("Billy", 1, 0, 0), &chain, 0)
+            .expect("valid");
+        mempool
+            .add(transaction("Alice", 1, 0, 0), &chain, 5)
+            .expect("valid");
+
+        assert_eq!(mempool.expire(12), vec![transaction("Billy", 1, 0, 0)]);
+        assert_eq!(mempool.len(), 1);
+
+        // a later nonce can't outlive the one it's waiting on
+        mempool
+            .add(transaction("Billy", 1, 0, 0), &chain, 20)
+            .expect("valid");
+        mempool
+            .add(transaction("Billy", 1, 0, 1), &chain, 25)
+            .expect("valid");
+        assert_eq!(
+            mempool.expire(32),
+            vec![
+                transaction("Alice", 1, 0, 0),
+                transaction("Billy", 1, 0, 0),
+                transaction("Billy", 1, 0, 1),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn revalidating_should_only_drop_what_the_block_made_invalid() {
+        let mut chain = chain();
+        let mut mempool = Mempool::default();
+        let waiting = transaction("Billy", 40, 0, 1);
+        let unrelated = transaction("Alice", 60, 0, 0);
+        mempool.add(waiting.clone(), &chain, 0).expect("valid");
+        mempool.add(unrelated.clone(), &chain, 0).expect("valid");
+
+        // Billy spends most of his money on something we never saw
+        let elsewhere = transaction("Billy", 70, 0, 0);
+        let block = mine_pending_transactions(&chain, vec![elsewhere.clone()]);
+        chain.add_new_block(block).expect("valid block");
+
+        assert_eq!(mempool.revalidate(&chain, [&elsewhere]), vec![waiting]);
+        assert_eq!(mempool.transactions(), vec![unrelated]);
+        assert_eq!(mempool.spending(&named_address("Billy")), Amount::new(0));
+    }
+}
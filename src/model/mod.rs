@@ -1,4 +1,12 @@
-pub mod transaction;
-pub mod blockchain;
+pub mod address_index;
+pub mod amount;
 pub mod block;
+pub mod blockchain;
+pub mod chain_spec;
+pub mod events;
+pub mod mempool;
+pub mod multisig;
 pub mod node;
+pub mod script;
+pub mod signature;
+pub mod transaction;
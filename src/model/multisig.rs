@@ -0,0 +1,139 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{
+    blockchain::BlockchainError,
+    signature::{parse_public_key, TxSignature},
+};
+
+// addresses that need M of N keys to spend from start with this
+pub const MULTISIG_ADDRESS_PREFIX: &str = "rm";
+
+// the most keys one policy can have, every one of them goes into each spending transaction
+pub const MAX_MULTISIG_KEYS: usize = 15;
+
+const ADDRESS_HASH_BYTES: usize = 20;
+
+// threshold of the public keys have to sign before coins sent to the policy's address
+// can be spent. the address is a hash of the policy, so whoever spends from it has to
+// put the policy in the transaction for the ledger to check the signatures against
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct MultisigPolicy {
+    pub threshold: u32,
+    // hex, sorted so the same keys always make the same address
+    pub public_keys: Vec<String>,
+}
+
+impl MultisigPolicy {
+    // the keys can come in any order
+    pub fn new(threshold: u32, public_keys: &[String]) -> Result<Self, BlockchainError> {
+        let mut public_keys: Vec<String> = public_keys
+            .iter()
+            .map(|public_key| public_key.to_lowercase())
+            .collect();
+        public_keys.sort();
+        let policy = MultisigPolicy {
+            threshold,
+            public_keys,
+        };
+        policy.check()?;
+        Ok(policy)
+    }
+
+    // policies come off the wire too, so the ledger can't take their shape for granted
+    pub fn check(&self) -> Result<(), BlockchainError> {
+        let keys = self.public_keys.len();
+        if self.threshold == 0 || self.threshold as usize > keys || keys > MAX_MULTISIG_KEYS {
+            return Err(BlockchainError::InvalidMultisig);
+        }
+        if !self.public_keys.windows(2).all(|pair| pair[0] < pair[1]) {
+            return Err(BlockchainError::InvalidMultisig);
+        }
+        if !self.public_keys.iter().all(|public_key| {
+            parse_public_key(public_key).is_some() && *public_key == public_key.to_lowercase()
+        }) {
+            return Err(BlockchainError::InvalidMultisig);
+        }
+        Ok(())
+    }
+
+    pub fn address(&self) -> String {
+        let hash = Sha256::digest(bincode::serialize(self).unwrap_or_default());
+        format!(
+            "{}{}",
+            MULTISIG_ADDRESS_PREFIX,
+            hex::encode(&hash[..ADDRESS_HASH_BYTES])
+        )
+    }
+
+    pub fn contains(&self, public_key: &str) -> bool {
+        self.public_keys.iter().any(|key| key == public_key)
+    }
+
+    // the policy's keys behind the signatures, each once. only counts signatures
+    // that hold up against the message
+    pub fn signers(&self, signatures: &[TxSignature], message: &[u8]) -> Vec<String> {
+        let mut signers = HashSet::new();
+        for signature in signatures {
+            if self.contains(&signature.public_key) && signature.verify(message) {
+                signers.insert(signature.public_key.clone());
+            }
+        }
+        let mut signers: Vec<String> = signers.into_iter().collect();
+        signers.sort();
+        signers
+    }
+}
+
+pub fn is_multisig_address(address: &str) -> bool {
+    address
+        .strip_prefix(MULTISIG_ADDRESS_PREFIX)
+        .is_some_and(|hash| {
+            hash.len() == ADDRESS_HASH_BYTES * 2 && hash.chars().all(|c| c.is_ascii_hexdigit())
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use ed25519_dalek::SigningKey;
+
+    use super::{is_multisig_address, MultisigPolicy};
+    use crate::model::blockchain::BlockchainError;
+
+    fn public_key(seed: u8) -> String {
+        hex::encode(
+            SigningKey::from_bytes(&[seed; 32])
+                .verifying_key()
+                .as_bytes(),
+        )
+    }
+
+    #[test]
+    pub fn policies_should_not_depend_on_key_order() {
+        let keys = vec![public_key(1), public_key(2), public_key(3)];
+        let policy = MultisigPolicy::new(2, &keys).expect("valid policy");
+        let reversed: Vec<String> = keys.iter().rev().cloned().collect();
+        assert_eq!(MultisigPolicy::new(2, &reversed), Ok(policy.clone()));
+        assert!(is_multisig_address(&policy.address()));
+        assert_ne!(
+            MultisigPolicy::new(3, &keys)
+                .expect("valid policy")
+                .address(),
+            policy.address()
+        );
+
+        for (threshold, keys) in [
+            (0, keys.clone()),
+            (4, keys.clone()),
+            (1, vec![public_key(1), public_key(1)]),
+            (1, vec!["abc".to_string()]),
+        ] {
+            assert_eq!(
+                MultisigPolicy::new(threshold, &keys),
+                Err(BlockchainError::InvalidMultisig)
+            );
+        }
+    }
+}
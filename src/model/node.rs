@@ -1,22 +1,117 @@
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    net::SocketAddr,
+};
+
+use chrono::Utc;
+use rand::seq::SliceRandom;
+
+use crate::net::{
+    address_book::AddressBook,
+    ban::{ban_key, misbehaviour_score, BanList, BAN_THRESHOLD, DEFAULT_BAN_DURATION_SECS},
+    inventory::Inventory,
+    message::{Message, MAX_ADDR_PER_MESSAGE, OVERSIZED_MESSAGE_SCORE},
+    peer::{Peer, PeerId},
+    rate_limit::RateLimits,
+    sync::{HeaderSync, MAX_BLOCKS_IN_FLIGHT_PER_PEER, MAX_HEADERS_PER_MESSAGE},
+};
 
 use super::{
-    block::Block,
-    blockchain::{Blockchain, BlockchainError},
+    block::{Block, BlockHeader},
+    blockchain::{Blockchain, BlockchainError, ConfirmedTransaction, MAX_BLOCK_SIZE},
+    chain_spec::ChainSpec,
+    events::{EventBus, EvictionReason, NodeEvent},
+    mempool::{Mempool, MempoolError},
     transaction::Transaction,
 };
 
+// if a peer hasn't answered a GetData in this many seconds
+// we're willing to ask somebody else for the same item
+pub const REQUEST_TIMEOUT_SECS: i64 = 30;
+
+// how many peers we pass newly learned addresses along to
+pub const ADDR_RELAY_FANOUT: usize = 2;
+
+// forks and orphans we're willing to hold onto, past this the lowest ones go first
+pub const MAX_SIDE_BLOCKS: usize = 1000;
+
+// what a node knows about a transaction it's been asked about
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransactionStatus {
+    Pending(Transaction),
+    Confirmed(ConfirmedTransaction),
+}
+
 #[derive(PartialEq, Debug)]
 pub struct Node {
     pub blockchain: Blockchain,
-    pub pending_transactions: HashSet<Transaction>,
+    pub mempool: Mempool,
+    pub peers: HashMap<PeerId, Peer>,
+    // valid looking blocks that aren't part of our chain, either because
+    // they're on a shorter fork or we haven't seen their parent yet
+    pub side_blocks: HashMap<String, Block>,
+    // the side blocks building on each hash, so a branch can be followed
+    // forward without looking through every side block
+    pub side_children: HashMap<String, HashSet<String>>,
+    // items we've asked for and which peer we asked, along with when we asked
+    pub in_flight: HashMap<Inventory, (PeerId, i64)>,
+    // messages waiting to be delivered to peers by whatever transport is in use
+    pub outbox: VecDeque<(PeerId, Message)>,
+    // the headers-first download in progress, if any
+    pub header_sync: Option<HeaderSync>,
+    // every node address we know about
+    pub address_book: AddressBook,
+    // where we accept connections, shared with peers so they can tell others
+    pub listen_addr: Option<SocketAddr>,
+    // peers that misbehaved badly enough that we won't talk to them for a while
+    pub ban_list: BanList,
+    pub ban_duration_secs: i64,
+    // goes up every time a peer sends us something invalid, see ban::misbehaviour_score.
+    // kept for each peer while they're connected, the ban that comes of it is what sticks
+    pub misbehaviour: HashMap<PeerId, u32>,
+    // peers we've dropped that the transport still needs to hang up on
+    pub disconnects: Vec<PeerId>,
+    // applied to every peer we connect to from here on
+    pub rate_limits: RateLimits,
+    // tells anybody subscribed when our chain or mempool changes
+    pub events: EventBus,
+}
+
+impl Default for Node {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Node {
     pub fn new() -> Self {
+        Self::with_spec(&ChainSpec::default())
+    }
+
+    pub fn with_spec(spec: &ChainSpec) -> Self {
+        let now = Utc::now().timestamp();
+        let mut address_book = AddressBook::new();
+        for seed in spec.seed_peers.iter() {
+            address_book.add_seed(*seed, now);
+        }
+
         Node {
-            blockchain: Blockchain::new(),
-            pending_transactions: HashSet::new(),
+            blockchain: Blockchain::from_spec(spec),
+            mempool: Mempool::default(),
+            peers: HashMap::new(),
+            side_blocks: HashMap::new(),
+            side_children: HashMap::new(),
+            in_flight: HashMap::new(),
+            outbox: VecDeque::new(),
+            header_sync: None,
+            address_book,
+            listen_addr: None,
+            ban_list: BanList::new(),
+            ban_duration_secs: DEFAULT_BAN_DURATION_SECS,
+            misbehaviour: HashMap::new(),
+            disconnects: Vec::new(),
+            rate_limits: RateLimits::default(),
+            events: EventBus::default(),
         }
     }
 
@@ -26,9 +121,8 @@ impl Node {
         }
     }
 
-    // in a real implementation, we probably wouldn't be receiving
-    // entire blockchains, probably just strings of hashes
-    // or maybe the most recent x blocks
+    // a chain handed to us directly gets the same treatment as one we sync
+    // from peers, the headers are checked before any of the blocks are connected
     pub async fn receive_chain(&mut self, recieved_chain: &Blockchain) {
         // replace the current chain with the received chain
         // if the received one is valid and longer
@@ -36,72 +130,754 @@ impl Node {
             return; // reject the received chain
         }
 
+        // find the last block we have in common
+        let shared = self.blockchain.chain.len().min(recieved_chain.chain.len());
+        let fork_height = match (0..shared).rev().find(|&height| {
+            self.blockchain.chain[height].hash() == recieved_chain.chain[height].hash()
+        }) {
+            Some(height) => height,
+            None => return, // we don't even agree on genesis
+        };
+
         // ok so we find your chain intriguing,
         // let's verify that it is valid
-        if !recieved_chain.is_valid() {
+        let branch = recieved_chain.chain[fork_height + 1..].to_vec();
+        let headers: Vec<BlockHeader> = branch.iter().map(|block| block.header()).collect();
+        let fork_hash = self.blockchain.chain[fork_height].hash();
+        if self
+            .blockchain
+            .validate_headers(&fork_hash, fork_height as u64, &headers)
+            .is_err()
+        {
             return;
         }
 
-        //we are replacing the chain, we need to return
-        //any transactions that exist in the old chain but not the
-        //new one to a pending state
-        let previous_confirmed_transactions = self.blockchain.confirmed_transactions.clone();
-        let orphaned =
-            previous_confirmed_transactions.difference(&recieved_chain.confirmed_transactions);
-
-        self.blockchain = recieved_chain.clone();
+        let _ = self.switch_to_branch(fork_height as u64, branch);
+    }
 
-        // remove any pending transactions that are in the new chain
-        for block in self.blockchain.chain.iter() {
-            for transaction in block.transactions.iter() {
-                if self.pending_transactions.contains(transaction) {
-                    self.pending_transactions.remove(transaction);
+    pub async fn submit_mined_block(&mut self, new_block: Block) -> Result<(), BlockchainError> {
+        let hash = new_block.hash();
+        match self.blockchain.add_new_block(new_block.clone()) {
+            Ok(()) => {
+                for confirmed_transaction in new_block.transactions.iter() {
+                    self.remove_confirmed(confirmed_transaction);
                 }
+                // anything left that conflicts with the new block has to go
+                let invalid = self
+                    .mempool
+                    .revalidate(&self.blockchain, new_block.transactions.iter());
+                self.evicted(invalid, EvictionReason::Conflicted);
+                let expired = self.mempool.expire(Utc::now().timestamp());
+                self.evicted(expired, EvictionReason::Expired);
+
+                self.events.emit(NodeEvent::BlockConnected(new_block));
+                self.announce(Inventory::Block(hash));
+                Ok(())
             }
+            Err(e) => Err(e),
         }
+    }
+
+    // blocks relayed by peers might extend our chain, sit on a fork,
+    // or build on a parent we haven't seen yet. returns true if our chain changed
+    pub async fn receive_block(
+        &mut self,
+        from: Option<&PeerId>,
+        block: Block,
+    ) -> Result<bool, BlockchainError> {
+        let hash = block.hash();
+        if self.side_blocks.contains_key(&hash) || self.blockchain.height_of(&hash).is_some() {
+            return Ok(false);
+        }
+
+        // cheap checks before we hold onto it, we don't want to keep junk around
+        if !hash.starts_with(&self.blockchain.target_hash_prefix) {
+            return Err(BlockchainError::IncorrectProof);
+        }
+        if block.size() > MAX_BLOCK_SIZE {
+            return Err(BlockchainError::BlockTooLarge);
+        }
+
+        self.add_side_block(hash.clone(), block);
+        self.prune_side_blocks();
 
-        // put transactions orphaned by the new chain back into a pending state
-        for transaction in orphaned {
-            self.pending_transactions.insert(transaction.clone());
+        // this block might be the missing parent of blocks we already have
+        let (tip_hash, _) = self.longest_descendant(&hash);
+
+        // walk back until we reach our own chain
+        let mut branch = Vec::new();
+        let mut cursor = tip_hash;
+        let fork_height = loop {
+            if let Some(side_block) = self.side_blocks.get(&cursor) {
+                branch.push(side_block.clone());
+                cursor = side_block.previous_hash.clone();
+                continue;
+            }
+
+            match self.blockchain.height_of(&cursor) {
+                Some(height) => break height,
+                None => {
+                    // we don't have the parent, ask whoever gave us the child
+                    if let Some(peer) = from {
+                        self.request(peer, vec![Inventory::Block(cursor)]);
+                    }
+                    return Ok(false);
+                }
+            }
+        };
+        branch.reverse();
+
+        // same difficulty for every block, so the longest chain has the most work
+        if fork_height + branch.len() as u64 <= self.blockchain.tip().index {
+            return Ok(false);
         }
+
+        self.switch_to_branch(fork_height, branch)?;
+        Ok(true)
     }
 
-    pub async fn submit_mined_block(&mut self, new_block: Block) -> Result<(), BlockchainError> {
-        match self.blockchain.add_new_block(new_block.clone()) {
-            Ok(()) => {
-                for confirmed_transaction in new_block.transactions {
-                    self.pending_transactions.remove(&confirmed_transaction);
+    // connect a branch that forks off of our chain at fork_height, the
+    // transactions in it are no longer pending and any from blocks we had
+    // to disconnect become pending again
+    fn switch_to_branch(
+        &mut self,
+        fork_height: u64,
+        branch: Vec<Block>,
+    ) -> Result<(), BlockchainError> {
+        let disconnected = match self.blockchain.reorganize(fork_height, &branch) {
+            Ok(disconnected) => disconnected,
+            Err(e) => {
+                for block in branch.iter() {
+                    self.remove_side_block(&block.hash());
                 }
+                return Err(e);
+            }
+        };
 
-                Ok(())
-            },
-            Err(e) => Err(e),
+        for block in branch.iter() {
+            self.remove_side_block(&block.hash());
+            for transaction in block.transactions.iter() {
+                self.remove_confirmed(transaction);
+            }
+        }
+
+        let depth = disconnected.len() as u64;
+        // reorganize hands the old branch back lowest first
+        for block in disconnected.iter().rev() {
+            self.events
+                .emit(NodeEvent::BlockDisconnected(block.clone()));
+        }
+        for block in branch.iter() {
+            self.events.emit(NodeEvent::BlockConnected(block.clone()));
+        }
+        if depth > 0 {
+            self.events.emit(NodeEvent::ChainReorganized { depth });
+        }
+
+        // the old branch is now a fork, keep it in case it becomes the longest again.
+        // its transactions go back to pending if they're still valid on the new chain
+        let now = Utc::now().timestamp();
+        for block in disconnected.iter() {
+            for transaction in block.transactions.iter() {
+                let _ = self.add_to_mempool(transaction.clone(), now);
+            }
+        }
+        let changed = branch
+            .iter()
+            .chain(disconnected.iter())
+            .flat_map(|block| block.transactions.iter());
+        let invalid = self.mempool.revalidate(&self.blockchain, changed);
+        self.evicted(invalid, EvictionReason::Conflicted);
+        let expired = self.mempool.expire(now);
+        self.evicted(expired, EvictionReason::Expired);
+        for block in disconnected {
+            self.add_side_block(block.hash(), block);
+        }
+
+        if let Some(tip) = branch.last() {
+            self.announce(Inventory::Block(tip.hash()));
+        }
+
+        Ok(())
+    }
+
+    fn prune_side_blocks(&mut self) {
+        while self.side_blocks.len() > MAX_SIDE_BLOCKS {
+            let lowest = self
+                .side_blocks
+                .iter()
+                .min_by_key(|(_, block)| block.index)
+                .map(|(hash, _)| hash.clone());
+            match lowest {
+                Some(hash) => self.remove_side_block(&hash),
+                None => break,
+            };
         }
     }
 
-    pub async fn receive_transactions(&mut self, received_transactions: &HashSet<Transaction>) {
-        //only add them if they don't already exist in our blockchain
-        for transaction in received_transactions {
-            if !self.blockchain.confirmed_transactions.contains(transaction) {
-                self.pending_transactions.insert(transaction.clone());
+    fn add_side_block(&mut self, hash: String, block: Block) {
+        self.side_children
+            .entry(block.previous_hash.clone())
+            .or_default()
+            .insert(hash.clone());
+        self.side_blocks.insert(hash, block);
+    }
+
+    fn remove_side_block(&mut self, hash: &str) -> Option<Block> {
+        let block = self.side_blocks.remove(hash)?;
+        if let Some(children) = self.side_children.get_mut(&block.previous_hash) {
+            children.remove(hash);
+            if children.is_empty() {
+                self.side_children.remove(&block.previous_hash);
+            }
+        }
+        Some(block)
+    }
+
+    // returns the hash of the furthest side block building on top of hash
+    // and how many blocks away it is
+    fn longest_descendant(&self, hash: &str) -> (String, u64) {
+        let mut longest = (hash.to_string(), 0);
+        let mut stack = vec![(hash.to_string(), 0)];
+        while let Some((hash, depth)) = stack.pop() {
+            if let Some(children) = self.side_children.get(&hash) {
+                stack.extend(children.iter().map(|child| (child.clone(), depth + 1)));
+            }
+            if depth > longest.1 {
+                longest = (hash, depth);
             }
         }
+        longest
+    }
+
+    pub async fn receive_transactions(&mut self, received_transactions: &[Transaction]) {
+        //the mempool takes care of skipping ones we already have or can't accept
+        let now = Utc::now().timestamp();
+        for transaction in received_transactions {
+            let _ = self.add_to_mempool(transaction.clone(), now);
+        }
     }
 
     pub async fn broadcast_transactions(&self, nodes: &mut Vec<Node>) {
+        let pending_transactions = self.mempool.transactions();
         for node in nodes {
-            node.receive_transactions(&self.pending_transactions).await
+            node.receive_transactions(&pending_transactions).await
         }
     }
 
-    pub async fn submit_transaction(&mut self, transaction: Transaction) {
-        //ignore if the transaction was already confirmed
-        if !self
-            .blockchain
-            .confirmed_transactions
-            .contains(&transaction)
+    pub async fn submit_transaction(
+        &mut self,
+        transaction: Transaction,
+    ) -> Result<(), MempoolError> {
+        let now = Utc::now().timestamp();
+        let expired = self.mempool.expire(now);
+        self.evicted(expired, EvictionReason::Expired);
+
+        let hash = transaction.txid();
+        self.add_to_mempool(transaction, now)?;
+        self.announce(Inventory::Transaction(hash));
+        Ok(())
+    }
+
+    // adds to the mempool and tells subscribers what came in and what had to go
+    fn add_to_mempool(&mut self, transaction: Transaction, now: i64) -> Result<(), MempoolError> {
+        let replaced: Vec<String> = self
+            .mempool
+            .conflicting(&transaction)
+            .iter()
+            .map(|conflicting| conflicting.txid())
+            .collect();
+        let removed = self
+            .mempool
+            .add(transaction.clone(), &self.blockchain, now)?;
+
+        self.events
+            .emit(NodeEvent::TransactionAccepted(transaction));
+        for removed in removed {
+            let reason = if replaced.contains(&removed.txid()) {
+                EvictionReason::Replaced
+            } else {
+                EvictionReason::MempoolFull
+            };
+            self.evicted(vec![removed], reason);
+        }
+        Ok(())
+    }
+
+    // a transaction made it into a block, it's not pending anymore and neither
+    // is anything that was competing with it for the same nonce or outputs
+    fn remove_confirmed(&mut self, transaction: &Transaction) {
+        let txid = transaction.txid();
+        let conflicting: Vec<Transaction> = self
+            .mempool
+            .remove_conflicting(transaction)
+            .into_iter()
+            .filter(|removed| removed.txid() != txid)
+            .collect();
+        self.evicted(conflicting, EvictionReason::Conflicted);
+    }
+
+    fn evicted(&self, transactions: Vec<Transaction>, reason: EvictionReason) {
+        for transaction in transactions {
+            self.events.emit(NodeEvent::TransactionEvicted {
+                transaction,
+                reason,
+            });
+        }
+    }
+
+    pub fn get_transaction(&self, txid: &str) -> Option<TransactionStatus> {
+        if let Some(confirmed) = self.blockchain.get_transaction(txid) {
+            return Some(TransactionStatus::Confirmed(confirmed));
+        }
+
+        self.mempool
+            .get(txid)
+            .map(|transaction| TransactionStatus::Pending(transaction.clone()))
+    }
+
+    pub fn add_peer(&mut self, id: PeerId) {
+        let limits = &self.rate_limits;
+        self.peers
+            .entry(id.clone())
+            .or_insert_with(|| Peer::with_limits(id, limits));
+    }
+
+    // a transport finished connecting to a peer, introduce ourselves.
+    // if we dialed them we also want their addresses and anything they have we don't
+    pub fn peer_connected(&mut self, id: PeerId, outbound: bool) {
+        if self
+            .ban_list
+            .is_banned(&ban_key(&id), Utc::now().timestamp())
         {
-            self.pending_transactions.insert(transaction);
+            self.disconnects.push(id);
+            return;
+        }
+
+        self.add_peer(id.clone());
+        if let Some(peer) = self.peers.get_mut(&id) {
+            peer.outbound = outbound;
+        }
+        self.events.emit(NodeEvent::PeerConnected {
+            id: id.clone(),
+            outbound,
+        });
+
+        let version = Message::Version {
+            listen_addr: self.listen_addr,
+            best_height: self.blockchain.tip().index,
+        };
+        self.outbox.push_back((id.clone(), version));
+
+        if outbound {
+            self.outbox.push_back((id.clone(), Message::GetAddr));
+            let locator = self.blockchain.locator();
+            self.outbox.push_back((id, Message::GetHeaders(locator)));
+        }
+    }
+
+    pub fn remove_peer(&mut self, id: &PeerId) {
+        if self.peers.remove(id).is_some() {
+            self.events
+                .emit(NodeEvent::PeerDisconnected { id: id.clone() });
+        }
+        self.misbehaviour.remove(id);
+        // anything we were waiting on from them isn't coming
+        self.in_flight.retain(|_, (peer, _)| peer != id);
+    }
+
+    pub fn drain_outbox(&mut self) -> Vec<(PeerId, Message)> {
+        self.outbox.drain(..).collect()
+    }
+
+    pub fn drain_disconnects(&mut self) -> Vec<PeerId> {
+        self.disconnects.drain(..).collect()
+    }
+
+    pub fn misbehaviour_of(&self, id: &PeerId) -> u32 {
+        self.misbehaviour.get(id).copied().unwrap_or(0)
+    }
+
+    // a peer sent us something invalid, once they've done that
+    // enough they get dropped and banned for a while
+    pub fn misbehaving(&mut self, id: &PeerId, score: u32) {
+        if !self.peers.contains_key(id) {
+            return;
+        }
+
+        let misbehaviour = self.misbehaviour.entry(id.clone()).or_insert(0);
+        *misbehaviour = misbehaviour.saturating_add(score);
+        if *misbehaviour >= BAN_THRESHOLD {
+            let key = ban_key(id);
+            let until = Utc::now().timestamp() + self.ban_duration_secs;
+            self.ban_list.ban(&key, until);
+            self.events.emit(NodeEvent::PeerBanned {
+                key: key.clone(),
+                until,
+            });
+
+            // anybody else connected from the same place goes with them
+            let banned: Vec<PeerId> = self
+                .peers
+                .keys()
+                .filter(|peer| ban_key(peer) == key)
+                .cloned()
+                .collect();
+            for peer in banned {
+                self.remove_peer(&peer);
+                self.disconnects.push(peer);
+            }
+        }
+    }
+
+    pub async fn handle_message(&mut self, from: &PeerId, message: Message) {
+        // we only talk to peers we're connected to
+        let peer = match self.peers.get_mut(from) {
+            Some(peer) => peer,
+            None => return,
+        };
+
+        // anything over a peer's limits is dropped on the floor, honest peers
+        // can burst past them now and then so it doesn't count as misbehaviour
+        let within_limits = match &message {
+            Message::Transaction(_) => peer.transaction_limit.try_take(),
+            Message::Block(block) => {
+                let requested = self.in_flight.contains_key(&Inventory::Block(block.hash()));
+                requested || peer.block_limit.try_take()
+            }
+            _ => true,
+        } && peer.message_limit.try_take();
+        if !within_limits {
+            return;
+        }
+
+        if message.is_oversized() {
+            self.misbehaving(from, OVERSIZED_MESSAGE_SCORE);
+            return;
+        }
+
+        match message {
+            Message::Version { listen_addr, .. } => {
+                if let Some(peer) = self.peers.get_mut(from) {
+                    peer.listen_addr = listen_addr;
+                }
+                if let Some(addr) = listen_addr {
+                    self.receive_addresses(from, vec![addr]);
+                }
+            }
+            Message::GetAddr => {
+                let addresses = self.address_book.shareable(MAX_ADDR_PER_MESSAGE);
+                self.outbox
+                    .push_back((from.clone(), Message::Addr(addresses)));
+            }
+            Message::Addr(addresses) => self.receive_addresses(from, addresses),
+            Message::Inv(items) => {
+                let now = Utc::now().timestamp();
+                let mut wanted = Vec::new();
+                for item in items {
+                    self.mark_known(from, &item);
+                    if self.has_inventory(&item) || self.is_in_flight(&item, now) {
+                        continue;
+                    }
+                    wanted.push(item);
+                }
+
+                self.request(from, wanted);
+            }
+            Message::GetData(items) => {
+                let mut not_found = Vec::new();
+                for item in items {
+                    match self.find_inventory(&item) {
+                        Some(response) => {
+                            self.mark_known(from, &item);
+                            self.outbox.push_back((from.clone(), response));
+                        }
+                        None => not_found.push(item),
+                    }
+                }
+
+                if !not_found.is_empty() {
+                    self.outbox
+                        .push_back((from.clone(), Message::NotFound(not_found)));
+                }
+            }
+            Message::NotFound(items) => {
+                for item in items {
+                    self.in_flight.remove(&item);
+                }
+            }
+            Message::Block(block) => {
+                let hash = block.hash();
+                let item = Inventory::Block(hash.clone());
+                self.mark_known(from, &item);
+                self.in_flight.remove(&item);
+
+                let syncing = self
+                    .header_sync
+                    .as_ref()
+                    .is_some_and(|sync| sync.contains(&hash));
+                if !syncing {
+                    if let Err(e) = self.receive_block(Some(from), block).await {
+                        self.misbehaving(from, misbehaviour_score(&e));
+                    }
+                    return;
+                }
+
+                // we already know where this block goes, no need to chase its parent
+                if let Err(e) = self.receive_block(None, block).await {
+                    // a body that doesn't match a valid header chain, start over
+                    self.header_sync = None;
+                    self.misbehaving(from, misbehaviour_score(&e));
+                    return;
+                }
+
+                if let Some(sync) = self.header_sync.as_mut() {
+                    sync.received.insert(hash);
+                    if sync.is_complete() {
+                        self.header_sync = None;
+                    }
+                }
+                self.request_bodies();
+            }
+            Message::GetHeaders(locator) => {
+                let headers = self
+                    .blockchain
+                    .headers_after(&locator, MAX_HEADERS_PER_MESSAGE);
+                self.outbox
+                    .push_back((from.clone(), Message::Headers(headers)));
+            }
+            Message::Headers(headers) => self.receive_headers(from, headers),
+            Message::Transaction(transaction) => {
+                let item = Inventory::Transaction(transaction.txid());
+                self.mark_known(from, &item);
+                self.in_flight.remove(&item);
+                // peers can honestly race us to a conflicting spend, so no penalty
+                let _ = self.submit_transaction(transaction).await;
+            }
+        }
+    }
+
+    // remember addresses we haven't heard of and pass them along
+    // to a couple of other peers so they spread through the network
+    fn receive_addresses(&mut self, from: &PeerId, addresses: Vec<SocketAddr>) {
+        let now = Utc::now().timestamp();
+        let new: Vec<SocketAddr> = addresses
+            .into_iter()
+            .filter(|addr| Some(*addr) != self.listen_addr)
+            .filter(|addr| self.address_book.add(*addr, now))
+            .collect();
+        if new.is_empty() {
+            return;
+        }
+
+        let mut others: Vec<PeerId> = self
+            .peers
+            .keys()
+            .filter(|peer| *peer != from)
+            .cloned()
+            .collect();
+        others.sort();
+        others.shuffle(&mut rand::thread_rng());
+        for peer in others.into_iter().take(ADDR_RELAY_FANOUT) {
+            self.outbox.push_back((peer, Message::Addr(new.clone())));
+        }
+    }
+
+    // kick off a headers-first download by asking everybody
+    // for the headers that come after our tip
+    pub fn start_initial_block_download(&mut self) {
+        let locator = self.blockchain.locator();
+        let peers: Vec<PeerId> = self.peers.keys().cloned().collect();
+        for peer in peers {
+            self.outbox
+                .push_back((peer, Message::GetHeaders(locator.clone())));
+        }
+    }
+
+    // percentage of the best known header chain we have the blocks for
+    pub fn sync_progress(&self) -> f64 {
+        match &self.header_sync {
+            Some(sync) => sync.progress(),
+            None => 100.0,
+        }
+    }
+
+    fn receive_headers(&mut self, from: &PeerId, headers: Vec<BlockHeader>) {
+        let (first, last) = match (headers.first(), headers.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return, // they don't have anything we don't
+        };
+
+        // another peer with the chain we're already downloading, they can help
+        if let Some(sync) = self.header_sync.as_mut() {
+            if sync.contains(&last.hash()) {
+                sync.peers.insert(from.clone());
+                self.request_bodies();
+                return;
+            }
+        }
+
+        // the headers either continue the chain we're syncing or fork off of ours
+        let continues_sync = self
+            .header_sync
+            .as_ref()
+            .is_some_and(|sync| sync.tip_hash() == Some(&first.previous_hash));
+        let mut sync = if continues_sync {
+            self.header_sync.take().expect("checked above")
+        } else {
+            match self.blockchain.height_of(&first.previous_hash) {
+                Some(fork_height) => HeaderSync::new(fork_height),
+                None => return, // no idea where these go
+            }
+        };
+
+        if let Err(e) =
+            self.blockchain
+                .validate_headers(&first.previous_hash, sync.height(), &headers)
+        {
+            if continues_sync {
+                self.header_sync = Some(sync);
+            }
+            self.misbehaving(from, misbehaviour_score(&e));
+            return;
+        }
+
+        let full_batch = headers.len() == MAX_HEADERS_PER_MESSAGE;
+        let tip_hash = last.hash();
+        sync.extend(headers);
+        sync.peers.insert(from.clone());
+
+        // only worth downloading if it has more work than what we've got
+        let best_height = self
+            .header_sync
+            .as_ref()
+            .map_or(0, |current| current.height())
+            .max(self.blockchain.tip().index);
+        if continues_sync || sync.height() > best_height {
+            self.header_sync = Some(sync);
+        }
+
+        if full_batch {
+            self.outbox
+                .push_back((from.clone(), Message::GetHeaders(vec![tip_hash])));
+        }
+
+        self.request_bodies();
+    }
+
+    // spread the blocks we still need across every peer that has them
+    fn request_bodies(&mut self) {
+        let sync = match self.header_sync.as_ref() {
+            Some(sync) => sync,
+            None => return,
+        };
+
+        let now = Utc::now().timestamp();
+        let mut peers: Vec<PeerId> = sync
+            .peers
+            .iter()
+            .filter(|peer| self.peers.contains_key(*peer))
+            .cloned()
+            .collect();
+        peers.sort();
+
+        let mut in_flight: HashMap<PeerId, usize> = HashMap::new();
+        for (item, (peer, requested_at)) in self.in_flight.iter() {
+            if matches!(item, Inventory::Block(_)) && now - requested_at < REQUEST_TIMEOUT_SECS {
+                *in_flight.entry(peer.clone()).or_default() += 1;
+            }
+        }
+
+        let mut requests: HashMap<PeerId, Vec<Inventory>> = HashMap::new();
+        for hash in sync.missing() {
+            let item = Inventory::Block(hash.clone());
+            if self.is_in_flight(&item, now) {
+                continue;
+            }
+
+            // whoever has the least on their plate
+            let peer = peers
+                .iter()
+                .filter(|peer| {
+                    in_flight.get(*peer).copied().unwrap_or(0) < MAX_BLOCKS_IN_FLIGHT_PER_PEER
+                })
+                .min_by_key(|peer| in_flight.get(*peer).copied().unwrap_or(0));
+            let peer = match peer {
+                Some(peer) => peer.clone(),
+                None => break, // everybody is busy
+            };
+
+            *in_flight.entry(peer.clone()).or_default() += 1;
+            requests.entry(peer).or_default().push(item);
+        }
+
+        let mut requests: Vec<(PeerId, Vec<Inventory>)> = requests.into_iter().collect();
+        requests.sort_by(|a, b| a.0.cmp(&b.0));
+        for (peer, items) in requests {
+            self.request(&peer, items);
+        }
+    }
+
+    fn has_inventory(&self, item: &Inventory) -> bool {
+        match item {
+            Inventory::Block(hash) => {
+                self.side_blocks.contains_key(hash) || self.blockchain.height_of(hash).is_some()
+            }
+            Inventory::Transaction(hash) => {
+                self.mempool.contains_hash(hash)
+                    || self.blockchain.confirmed_transactions.contains_key(hash)
+            }
+        }
+    }
+
+    fn find_inventory(&self, item: &Inventory) -> Option<Message> {
+        match item {
+            Inventory::Block(hash) => self
+                .side_blocks
+                .get(hash)
+                .or_else(|| self.blockchain.get_block(hash))
+                .map(|block| Message::Block(block.clone())),
+            // only pending transactions get relayed, confirmed ones travel in blocks
+            Inventory::Transaction(hash) => self
+                .mempool
+                .get(hash)
+                .map(|transaction| Message::Transaction(transaction.clone())),
+        }
+    }
+
+    fn is_in_flight(&self, item: &Inventory, now: i64) -> bool {
+        match self.in_flight.get(item) {
+            Some((_, requested_at)) => now - requested_at < REQUEST_TIMEOUT_SECS,
+            None => false,
+        }
+    }
+
+    fn mark_known(&mut self, peer: &PeerId, item: &Inventory) {
+        if let Some(peer) = self.peers.get_mut(peer) {
+            peer.known_inventory.insert(item.clone());
+        }
+    }
+
+    fn request(&mut self, peer: &PeerId, items: Vec<Inventory>) {
+        if items.is_empty() {
+            return;
+        }
+
+        let now = Utc::now().timestamp();
+        for item in items.iter() {
+            self.in_flight.insert(item.clone(), (peer.clone(), now));
+        }
+        self.outbox
+            .push_back((peer.clone(), Message::GetData(items)));
+    }
+
+    // let every peer that doesn't already have it know about a new item
+    fn announce(&mut self, item: Inventory) {
+        for (id, peer) in self.peers.iter_mut() {
+            if peer.known_inventory.insert(item.clone()) {
+                self.outbox
+                    .push_back((id.clone(), Message::Inv(vec![item.clone()])));
+            }
         }
     }
 }
@@ -0,0 +1,788 @@
+use std::{collections::BTreeSet, error::Error, fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{
+    multisig::MAX_MULTISIG_KEYS,
+    signature::parse_public_key,
+    transaction::{LockTime, Transaction},
+};
+
+// addresses whose coins are spent by satisfying a script start with this
+pub const SCRIPT_ADDRESS_PREFIX: &str = "rs";
+
+// what running one script is allowed to cost, see Op::cost
+pub const MAX_SCRIPT_COST: u64 = 1000;
+
+// how many values the stack can hold at once, the witness included
+pub const MAX_STACK_DEPTH: usize = 100;
+
+// the biggest value that can go on the stack
+pub const MAX_VALUE_BYTES: usize = 520;
+
+const ADDRESS_HASH_BYTES: usize = 20;
+
+const SIGNATURE_COST: u64 = 50;
+
+// one thing on the stack
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum Value {
+    Bytes(Vec<u8>),
+    Number(i64),
+    Bool(bool),
+}
+
+impl Value {
+    // what If and Verify make of it, empty bytes and zero are false
+    pub fn is_true(&self) -> bool {
+        match self {
+            Value::Bytes(bytes) => !bytes.is_empty(),
+            Value::Number(number) => *number != 0,
+            Value::Bool(value) => *value,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Value::Bytes(bytes) => bytes.len(),
+            Value::Number(_) => 8,
+            Value::Bool(_) => 1,
+        }
+    }
+}
+
+// there are no jumps or loops, a script runs each op at most once
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum Op {
+    Push(Value),
+    Dup,
+    Drop,
+    Swap,
+    // pops the condition, the ops up to the matching Else or EndIf only run when it's true
+    If,
+    Else,
+    EndIf,
+    Not,
+    BoolAnd,
+    BoolOr,
+    Equal,
+    // fails the script unless the popped value is true
+    Verify,
+    // replaces bytes with their hash
+    Sha256,
+    // pops a public key, true when the transaction carries a valid signature from it
+    CheckSig,
+    // pops a count, that many public keys and how many of them have to have signed
+    CheckMultisig,
+    // pops a height, true when the transaction's lock time is a height at least that high
+    CheckLockHeight,
+    // the same for a timestamp
+    CheckLockTime,
+}
+
+impl Op {
+    // what it costs to run, signature checks are by far the most expensive thing
+    // a script can do. ops skipped over by an If still cost 1
+    fn cost(&self) -> u64 {
+        match self {
+            Op::Sha256 => 10,
+            Op::CheckSig => SIGNATURE_COST,
+            // CheckMultisig pays SIGNATURE_COST for every key on top, once it knows how many
+            _ => 1,
+        }
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        let name = match self {
+            Op::Push(_) => return None,
+            Op::Dup => "DUP",
+            Op::Drop => "DROP",
+            Op::Swap => "SWAP",
+            Op::If => "IF",
+            Op::Else => "ELSE",
+            Op::EndIf => "ENDIF",
+            Op::Not => "NOT",
+            Op::BoolAnd => "BOOLAND",
+            Op::BoolOr => "BOOLOR",
+            Op::Equal => "EQUAL",
+            Op::Verify => "VERIFY",
+            Op::Sha256 => "SHA256",
+            Op::CheckSig => "CHECKSIG",
+            Op::CheckMultisig => "CHECKMULTISIG",
+            Op::CheckLockHeight => "CHECKLOCKHEIGHT",
+            Op::CheckLockTime => "CHECKLOCKTIME",
+        };
+        Some(name)
+    }
+}
+
+const OPS: [Op; 16] = [
+    Op::Dup,
+    Op::Drop,
+    Op::Swap,
+    Op::If,
+    Op::Else,
+    Op::EndIf,
+    Op::Not,
+    Op::BoolAnd,
+    Op::BoolOr,
+    Op::Equal,
+    Op::Verify,
+    Op::Sha256,
+    Op::CheckSig,
+    Op::CheckMultisig,
+    Op::CheckLockHeight,
+    Op::CheckLockTime,
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptError {
+    // ran past MAX_SCRIPT_COST
+    CostExceeded,
+    // more than MAX_STACK_DEPTH values
+    StackTooDeep,
+    // an op needed more values than there were
+    StackUnderflow,
+    // bigger than MAX_VALUE_BYTES
+    ValueTooLarge,
+    // e.g. hashing a number or a key that isn't bytes
+    WrongType,
+    // an Else or EndIf without an If or the other way around
+    UnbalancedIf,
+    // more keys than a multisig can have, or a negative count
+    InvalidKeyCount,
+    // a multisig asking for no signatures, or more than it has keys for
+    InvalidThreshold,
+    VerifyFailed,
+    // it ran to the end without leaving true on top of the stack
+    Failed,
+    // text that isn't a script
+    Parse(String),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::CostExceeded => write!(f, "costs more than {} to run", MAX_SCRIPT_COST),
+            ScriptError::StackTooDeep => {
+                write!(f, "more than {} values on the stack", MAX_STACK_DEPTH)
+            }
+            ScriptError::StackUnderflow => write!(f, "ran out of values on the stack"),
+            ScriptError::ValueTooLarge => {
+                write!(f, "a value bigger than {} bytes", MAX_VALUE_BYTES)
+            }
+            ScriptError::WrongType => write!(f, "a value of the wrong type"),
+            ScriptError::UnbalancedIf => write!(f, "IF, ELSE and ENDIF don't match up"),
+            ScriptError::InvalidKeyCount => {
+                write!(f, "a multisig needs 0 to {} keys", MAX_MULTISIG_KEYS)
+            }
+            ScriptError::InvalidThreshold => {
+                write!(f, "a multisig needs 1 to as many signatures as it has keys")
+            }
+            ScriptError::VerifyFailed => write!(f, "VERIFY failed"),
+            ScriptError::Failed => write!(f, "the script didn't end with true"),
+            ScriptError::Parse(e) => write!(f, "not a script: {}", e),
+        }
+    }
+}
+
+impl Error for ScriptError {}
+
+// spending conditions for the coins sent to its address. the address is a hash of
+// the script, whoever spends from it puts the script in the transaction along
+// with the values it needs, see ScriptSpend
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Script {
+    pub ops: Vec<Op>,
+}
+
+// what a transaction from a script address carries
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct ScriptSpend {
+    pub script: Script,
+    // goes on the stack before the script runs, first value at the bottom
+    pub witness: Vec<Value>,
+}
+
+impl Script {
+    pub fn new(ops: Vec<Op>) -> Self {
+        Script { ops }
+    }
+
+    pub fn address(&self) -> String {
+        let hash = Sha256::digest(bincode::serialize(self).unwrap_or_default());
+        format!(
+            "{}{}",
+            SCRIPT_ADDRESS_PREFIX,
+            hex::encode(&hash[..ADDRESS_HASH_BYTES])
+        )
+    }
+
+    // hex encoded, the keys pushed right before a CheckSig or as a CheckMultisig's
+    // keys. these are the ones that could be asked to sign a spend
+    pub fn public_keys(&self) -> Vec<String> {
+        let mut public_keys: Vec<String> = Vec::new();
+        for (position, op) in self.ops.iter().enumerate() {
+            let pushed = match (op, position.checked_sub(1).map(|before| &self.ops[before])) {
+                (Op::CheckSig, Some(_)) => &self.ops[position - 1..position],
+                (Op::CheckMultisig, Some(Op::Push(Value::Number(count)))) => {
+                    let count = usize::try_from(*count).unwrap_or(usize::MAX);
+                    match (position - 1).checked_sub(count) {
+                        Some(first) => &self.ops[first..position - 1],
+                        None => continue,
+                    }
+                }
+                _ => continue,
+            };
+            for op in pushed {
+                if let Op::Push(Value::Bytes(bytes)) = op {
+                    let public_key = hex::encode(bytes);
+                    if parse_public_key(&public_key).is_some() && !public_keys.contains(&public_key)
+                    {
+                        public_keys.push(public_key);
+                    }
+                }
+            }
+        }
+        public_keys
+    }
+
+    // the fewest signatures any way through the script counts: one for a CheckSig, the
+    // threshold for a CheckMultisig and whichever branch of an If needs fewer. it's what
+    // a wallet waits for before trying a spend, running the script is what decides
+    pub fn threshold(&self) -> u32 {
+        // what each enclosing If's branches count so far, the else once it's reached
+        let mut branches: Vec<(u32, Option<u32>)> = vec![(0, None)];
+        for (position, op) in self.ops.iter().enumerate() {
+            let counted = match op {
+                Op::If => {
+                    branches.push((0, None));
+                    continue;
+                }
+                Op::Else => {
+                    if let Some(branch) = branches.last_mut() {
+                        branch.1 = Some(0);
+                    }
+                    continue;
+                }
+                Op::EndIf if branches.len() > 1 => {
+                    let (then, otherwise) = branches.pop().unwrap_or_default();
+                    then.min(otherwise.unwrap_or(0))
+                }
+                Op::CheckSig => 1,
+                // the threshold is pushed before the keys and their count
+                Op::CheckMultisig => {
+                    match position.checked_sub(1).map(|before| &self.ops[before]) {
+                        Some(Op::Push(Value::Number(count))) => usize::try_from(*count)
+                            .ok()
+                            .and_then(|count| position.checked_sub(count + 2))
+                            .and_then(|at| match &self.ops[at] {
+                                Op::Push(Value::Number(threshold)) => {
+                                    u32::try_from(*threshold).ok()
+                                }
+                                _ => None,
+                            })
+                            .unwrap_or(0),
+                        _ => 0,
+                    }
+                }
+                _ => 0,
+            };
+            if let Some((then, otherwise)) = branches.last_mut() {
+                let count = otherwise.as_mut().unwrap_or(then);
+                *count = count.saturating_add(counted);
+            }
+        }
+        branches.first().map(|(then, _)| *then).unwrap_or(0)
+    }
+
+    // runs the script on top of the witness for a transaction spending from its address.
+    // it passes when it runs to the end within the cost limit and leaves true on top
+    pub fn run(&self, witness: &[Value], transaction: &Transaction) -> Result<(), ScriptError> {
+        self.signers(witness, transaction).map(|_| ())
+    }
+
+    // runs it and says whose signatures it relied on to pass, as hex public keys
+    pub fn signers(
+        &self,
+        witness: &[Value],
+        transaction: &Transaction,
+    ) -> Result<Vec<String>, ScriptError> {
+        let mut vm = Vm {
+            stack: Vec::new(),
+            cost: 0,
+            message: transaction.signing_hash(),
+            transaction,
+            signers: Vec::new(),
+        };
+        for value in witness {
+            vm.push(value.clone())?;
+        }
+
+        // whether each enclosing If's branch is the one being run
+        let mut branches: Vec<bool> = Vec::new();
+        for op in self.ops.iter() {
+            let running = branches.iter().all(|branch| *branch);
+            vm.charge(if running { op.cost() } else { 1 })?;
+            match op {
+                Op::If => {
+                    let condition = running && vm.pop()?.is_true();
+                    branches.push(condition);
+                }
+                Op::Else => {
+                    let branch = branches.last_mut().ok_or(ScriptError::UnbalancedIf)?;
+                    *branch = !*branch;
+                }
+                Op::EndIf => {
+                    branches.pop().ok_or(ScriptError::UnbalancedIf)?;
+                }
+                _ if running => vm.step(op)?,
+                _ => {}
+            }
+        }
+        if !branches.is_empty() {
+            return Err(ScriptError::UnbalancedIf);
+        }
+
+        match vm.stack.last() {
+            Some(value) if value.is_true() => Ok(vm.signers),
+            _ => Err(ScriptError::Failed),
+        }
+    }
+}
+
+struct Vm<'a> {
+    stack: Vec<Value>,
+    cost: u64,
+    // what signatures have to be over
+    message: String,
+    transaction: &'a Transaction,
+    // keys whose signatures were checked and counted
+    signers: Vec<String>,
+}
+
+impl Vm<'_> {
+    fn charge(&mut self, cost: u64) -> Result<(), ScriptError> {
+        self.cost += cost;
+        if self.cost > MAX_SCRIPT_COST {
+            return Err(ScriptError::CostExceeded);
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, value: Value) -> Result<(), ScriptError> {
+        if value.size() > MAX_VALUE_BYTES {
+            return Err(ScriptError::ValueTooLarge);
+        }
+        if self.stack.len() >= MAX_STACK_DEPTH {
+            return Err(ScriptError::StackTooDeep);
+        }
+        self.stack.push(value);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<Value, ScriptError> {
+        self.stack.pop().ok_or(ScriptError::StackUnderflow)
+    }
+
+    fn pop_bytes(&mut self) -> Result<Vec<u8>, ScriptError> {
+        match self.pop()? {
+            Value::Bytes(bytes) => Ok(bytes),
+            _ => Err(ScriptError::WrongType),
+        }
+    }
+
+    fn pop_number(&mut self) -> Result<i64, ScriptError> {
+        match self.pop()? {
+            Value::Number(number) => Ok(number),
+            _ => Err(ScriptError::WrongType),
+        }
+    }
+
+    // whether the transaction has a signature from the key that holds up
+    fn signed_by(&self, public_key: &[u8]) -> bool {
+        let public_key = hex::encode(public_key);
+        self.transaction.signatures.iter().any(|signature| {
+            signature.public_key == public_key && signature.verify(self.message.as_bytes())
+        })
+    }
+
+    fn counted(&mut self, public_key: &[u8]) {
+        let public_key = hex::encode(public_key);
+        if !self.signers.contains(&public_key) {
+            self.signers.push(public_key);
+        }
+    }
+
+    fn step(&mut self, op: &Op) -> Result<(), ScriptError> {
+        match op {
+            Op::Push(value) => self.push(value.clone())?,
+            Op::Dup => {
+                let top = self
+                    .stack
+                    .last()
+                    .cloned()
+                    .ok_or(ScriptError::StackUnderflow)?;
+                self.push(top)?;
+            }
+            Op::Drop => {
+                self.pop()?;
+            }
+            Op::Swap => {
+                let top = self.pop()?;
+                let below = self.pop()?;
+                self.push(top)?;
+                self.push(below)?;
+            }
+            Op::Not => {
+                let value = self.pop()?;
+                self.push(Value::Bool(!value.is_true()))?;
+            }
+            Op::BoolAnd | Op::BoolOr => {
+                let a = self.pop()?.is_true();
+                let b = self.pop()?.is_true();
+                let result = if *op == Op::BoolAnd { a && b } else { a || b };
+                self.push(Value::Bool(result))?;
+            }
+            Op::Equal => {
+                let a = self.pop()?;
+                let b = self.pop()?;
+                self.push(Value::Bool(a == b))?;
+            }
+            Op::Verify => {
+                if !self.pop()?.is_true() {
+                    return Err(ScriptError::VerifyFailed);
+                }
+            }
+            Op::Sha256 => {
+                let bytes = self.pop_bytes()?;
+                self.push(Value::Bytes(Sha256::digest(bytes).to_vec()))?;
+            }
+            Op::CheckSig => {
+                let public_key = self.pop_bytes()?;
+                let signed = self.signed_by(&public_key);
+                if signed {
+                    self.counted(&public_key);
+                }
+                self.push(Value::Bool(signed))?;
+            }
+            Op::CheckMultisig => {
+                let count = self.pop_number()?;
+                if count < 0 || count as usize > MAX_MULTISIG_KEYS {
+                    return Err(ScriptError::InvalidKeyCount);
+                }
+                self.charge(SIGNATURE_COST * count as u64)?;
+                let mut public_keys = BTreeSet::new();
+                for _ in 0..count {
+                    public_keys.insert(self.pop_bytes()?);
+                }
+                let threshold = self.pop_number()?;
+                // otherwise a 0 of n passes without anybody signing and an n+1 of n never can,
+                // a key that shows up twice only counts once
+                if threshold < 1 || threshold > public_keys.len() as i64 {
+                    return Err(ScriptError::InvalidThreshold);
+                }
+                let signed: Vec<Vec<u8>> = public_keys
+                    .into_iter()
+                    .filter(|public_key| self.signed_by(public_key))
+                    .collect();
+                // only as many as it takes count, any more are along for the ride
+                for public_key in signed.iter().take(threshold as usize) {
+                    self.counted(public_key);
+                }
+                self.push(Value::Bool(signed.len() as i64 >= threshold))?;
+            }
+            Op::CheckLockHeight => {
+                let height = self.pop_number()?;
+                let locked = match self.transaction.lock_time {
+                    Some(LockTime::Height(lock)) => lock as i128 >= height as i128,
+                    _ => false,
+                };
+                self.push(Value::Bool(locked))?;
+            }
+            Op::CheckLockTime => {
+                let timestamp = self.pop_number()?;
+                let locked = match self.transaction.lock_time {
+                    Some(LockTime::Timestamp(lock)) => lock >= timestamp,
+                    _ => false,
+                };
+                self.push(Value::Bool(locked))?;
+            }
+            Op::If | Op::Else | Op::EndIf => unreachable!("handled by Script::run"),
+        }
+        Ok(())
+    }
+}
+
+pub fn is_script_address(address: &str) -> bool {
+    address
+        .strip_prefix(SCRIPT_ADDRESS_PREFIX)
+        .is_some_and(|hash| {
+            hash.len() == ADDRESS_HASH_BYTES * 2 && hash.chars().all(|c| c.is_ascii_hexdigit())
+        })
+}
+
+// scripts are written as ops and values separated by spaces, e.g.
+// "SHA256 0x9f86.. EQUAL". numbers are decimal, bytes are hex after 0x
+// and TRUE and FALSE are booleans
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bytes(bytes) => write!(f, "0x{}", hex::encode(bytes)),
+            Value::Number(number) => write!(f, "{}", number),
+            Value::Bool(true) => write!(f, "TRUE"),
+            Value::Bool(false) => write!(f, "FALSE"),
+        }
+    }
+}
+
+impl FromStr for Value {
+    type Err = ScriptError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(hex) = s.strip_prefix("0x") {
+            return hex::decode(hex)
+                .map(Value::Bytes)
+                .map_err(|e| ScriptError::Parse(format!("{}: {}", s, e)));
+        }
+        match s.to_uppercase().as_str() {
+            "TRUE" => Ok(Value::Bool(true)),
+            "FALSE" => Ok(Value::Bool(false)),
+            _ => s
+                .parse()
+                .map(Value::Number)
+                .map_err(|_| ScriptError::Parse(format!("{} isn't a value", s))),
+        }
+    }
+}
+
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let words: Vec<String> = self
+            .ops
+            .iter()
+            .map(|op| match op {
+                Op::Push(value) => value.to_string(),
+                op => op.name().unwrap_or_default().to_string(),
+            })
+            .collect();
+        write!(f, "{}", words.join(" "))
+    }
+}
+
+impl FromStr for Script {
+    type Err = ScriptError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let ops = s
+            .split_whitespace()
+            .map(|word| {
+                let upper = word.to_uppercase();
+                match OPS.iter().find(|op| op.name() == Some(upper.as_str())) {
+                    Some(op) => Ok(op.clone()),
+                    None => word.parse().map(Op::Push),
+                }
+            })
+            .collect::<Result<Vec<Op>, ScriptError>>()?;
+        Ok(Script::new(ops))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ed25519_dalek::SigningKey;
+    use sha2::{Digest, Sha256};
+
+    use super::{Op, Script, ScriptError, Value, MAX_SCRIPT_COST};
+    use crate::model::{
+        amount::Amount,
+        transaction::{LockTime, Transaction},
+    };
+
+    fn spend(lock_time: Option<LockTime>) -> Transaction {
+        Transaction {
+            sender: "".to_string(),
+            receiver: "Bobby".to_string(),
+            amount: Amount::new(10),
+            fee: Amount::new(1),
+            nonce: 0,
+            timestamp: 0,
+            lock_time,
+            ..Transaction::default()
+        }
+    }
+
+    #[test]
+    pub fn scripts_should_read_back_what_they_write() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let script: Script = format!(
+            "IF 0x{} CHECKSIG ELSE 100 CHECKLOCKHEIGHT ENDIF",
+            hex::encode(key.verifying_key().as_bytes())
+        )
+        .parse()
+        .expect("valid script");
+        assert_eq!(script.ops.len(), 7);
+        assert_eq!(script.to_string().parse::<Script>(), Ok(script.clone()));
+        assert_eq!(
+            script.public_keys(),
+            vec![hex::encode(key.verifying_key().as_bytes())]
+        );
+        assert!(matches!(
+            "SHA256 0xzz".parse::<Script>(),
+            Err(ScriptError::Parse(_))
+        ));
+    }
+
+    #[test]
+    pub fn branches_should_pick_between_a_signature_and_a_time_lock() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let script = Script::new(vec![
+            Op::If,
+            Op::Push(Value::Bytes(key.verifying_key().as_bytes().to_vec())),
+            Op::CheckSig,
+            Op::Else,
+            Op::Push(Value::Number(100)),
+            Op::CheckLockHeight,
+            Op::EndIf,
+        ]);
+
+        let mut signed = spend(None);
+        signed.sign(&key);
+        assert_eq!(script.run(&[Value::Bool(true)], &signed), Ok(()));
+        assert_eq!(
+            script.run(&[Value::Bool(true)], &spend(None)),
+            Err(ScriptError::Failed)
+        );
+        let locked = spend(Some(LockTime::Height(100)));
+        assert_eq!(script.run(&[Value::Bool(false)], &locked), Ok(()));
+        let too_early = spend(Some(LockTime::Height(99)));
+        assert_eq!(
+            script.run(&[Value::Bool(false)], &too_early),
+            Err(ScriptError::Failed)
+        );
+        assert_eq!(script.run(&[], &locked), Err(ScriptError::StackUnderflow));
+    }
+
+    #[test]
+    pub fn scripts_should_stop_at_the_cost_limit() {
+        let hash_lock = Script::new(vec![
+            Op::Sha256,
+            Op::Push(Value::Bytes(Sha256::digest(b"secret").to_vec())),
+            Op::Equal,
+        ]);
+        let preimage = Value::Bytes(b"secret".to_vec());
+        assert_eq!(
+            hash_lock.run(std::slice::from_ref(&preimage), &spend(None)),
+            Ok(())
+        );
+        assert_eq!(
+            hash_lock.run(&[Value::Number(1)], &spend(None)),
+            Err(ScriptError::WrongType)
+        );
+
+        let hashes = MAX_SCRIPT_COST as usize / 10 + 1;
+        let expensive = Script::new(vec![Op::Sha256; hashes]);
+        assert_eq!(
+            expensive.run(&[preimage], &spend(None)),
+            Err(ScriptError::CostExceeded)
+        );
+        let unbalanced = Script::new(vec![Op::Push(Value::Bool(true)), Op::If]);
+        assert_eq!(
+            unbalanced.run(&[], &spend(None)),
+            Err(ScriptError::UnbalancedIf)
+        );
+    }
+
+    #[test]
+    pub fn threshold_should_be_the_fewest_signatures_any_branch_counts() {
+        let keys: Vec<String> = (1..=3)
+            .map(|seed| {
+                hex::encode(
+                    SigningKey::from_bytes(&[seed; 32])
+                        .verifying_key()
+                        .as_bytes(),
+                )
+            })
+            .collect();
+        let threshold =
+            |script: String| script.parse::<Script>().expect("valid script").threshold();
+
+        assert_eq!(threshold(format!("0x{} CHECKSIG", keys[0])), 1);
+        assert_eq!(
+            threshold(format!(
+                "2 0x{} 0x{} 0x{} 3 CHECKMULTISIG",
+                keys[0], keys[1], keys[2]
+            )),
+            2
+        );
+        assert_eq!(
+            threshold(format!(
+                "0x{} CHECKSIG VERIFY 0x{} CHECKSIG",
+                keys[0], keys[1]
+            )),
+            2
+        );
+        assert_eq!(
+            threshold(format!(
+                "IF 0x{} CHECKSIG VERIFY 0x{} CHECKSIG ELSE 0x{} CHECKSIG ENDIF",
+                keys[0], keys[1], keys[2]
+            )),
+            1
+        );
+        assert_eq!(
+            threshold(format!(
+                "IF 0x{} CHECKSIG ELSE 100 CHECKLOCKHEIGHT ENDIF",
+                keys[0]
+            )),
+            0
+        );
+    }
+
+    #[test]
+    pub fn multisig_thresholds_should_be_between_one_and_the_key_count() {
+        let keys = [
+            SigningKey::from_bytes(&[1; 32]),
+            SigningKey::from_bytes(&[2; 32]),
+        ];
+        let multisig = |threshold| {
+            let mut ops = vec![Op::Push(Value::Number(threshold))];
+            ops.extend(
+                keys.iter()
+                    .map(|key| Op::Push(Value::Bytes(key.verifying_key().as_bytes().to_vec()))),
+            );
+            ops.extend([Op::Push(Value::Number(2)), Op::CheckMultisig]);
+            Script::new(ops)
+        };
+        let mut signed = spend(None);
+        signed.sign(&keys[0]);
+        signed.sign(&keys[1]);
+
+        // nobody signing would be enough for a 0 of 2
+        for threshold in [0, -1, 3] {
+            assert_eq!(
+                multisig(threshold).run(&[], &spend(None)),
+                Err(ScriptError::InvalidThreshold)
+            );
+            assert_eq!(
+                multisig(threshold).run(&[], &signed),
+                Err(ScriptError::InvalidThreshold)
+            );
+        }
+        assert_eq!(multisig(2).run(&[], &signed), Ok(()));
+        assert_eq!(multisig(1).run(&[], &spend(None)), Err(ScriptError::Failed));
+
+        // the same key twice is still only one to sign with
+        let repeated = Script::new(vec![
+            Op::Push(Value::Number(2)),
+            Op::Push(Value::Bytes(keys[0].verifying_key().as_bytes().to_vec())),
+            Op::Push(Value::Bytes(keys[0].verifying_key().as_bytes().to_vec())),
+            Op::Push(Value::Number(2)),
+            Op::CheckMultisig,
+        ]);
+        assert_eq!(
+            repeated.run(&[], &signed),
+            Err(ScriptError::InvalidThreshold)
+        );
+    }
+}
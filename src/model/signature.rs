@@ -0,0 +1,118 @@
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+// addresses that belong to a key start with this, everything else is a plain
+// name. those can be paid but never spend
+pub const KEY_ADDRESS_PREFIX: &str = "rb";
+
+// how many bytes of the public key's hash make up an address
+const ADDRESS_HASH_BYTES: usize = 20;
+
+// one key's approval of a transaction, both hex encoded
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct TxSignature {
+    pub public_key: String,
+    pub signature: String,
+}
+
+// the address coins sent to this key end up at
+pub fn key_address(public_key: &VerifyingKey) -> String {
+    let hash = Sha256::digest(public_key.as_bytes());
+    format!(
+        "{}{}",
+        KEY_ADDRESS_PREFIX,
+        hex::encode(&hash[..ADDRESS_HASH_BYTES])
+    )
+}
+
+// whether spending from this address needs a signature
+pub fn is_key_address(address: &str) -> bool {
+    address
+        .strip_prefix(KEY_ADDRESS_PREFIX)
+        .is_some_and(|hash| {
+            hash.len() == ADDRESS_HASH_BYTES * 2 && hash.chars().all(|c| c.is_ascii_hexdigit())
+        })
+}
+
+pub fn parse_public_key(public_key: &str) -> Option<VerifyingKey> {
+    let bytes: [u8; 32] = hex::decode(public_key).ok()?.try_into().ok()?;
+    VerifyingKey::from_bytes(&bytes).ok()
+}
+
+impl TxSignature {
+    pub fn sign(key: &SigningKey, message: &[u8]) -> Self {
+        TxSignature {
+            public_key: hex::encode(key.verifying_key().as_bytes()),
+            signature: hex::encode(key.sign(message).to_bytes()),
+        }
+    }
+
+    // false for anything that doesn't even parse
+    pub fn verify(&self, message: &[u8]) -> bool {
+        let Some(public_key) = parse_public_key(&self.public_key) else {
+            return false;
+        };
+        let Some(signature) = hex::decode(&self.signature)
+            .ok()
+            .and_then(|bytes| Signature::from_slice(&bytes).ok())
+        else {
+            return false;
+        };
+        public_key.verify_strict(message, &signature).is_ok()
+    }
+
+    // lower case hex both, the only spelling that gets into a txid
+    pub fn is_canonical(&self) -> bool {
+        let canonical = |hex: &str| hex::decode(hex).is_ok_and(|bytes| hex::encode(bytes) == hex);
+        canonical(&self.public_key) && canonical(&self.signature)
+    }
+
+    // None when the public key is garbage
+    pub fn address(&self) -> Option<String> {
+        parse_public_key(&self.public_key).map(|key| key_address(&key))
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use ed25519_dalek::SigningKey;
+    use sha2::{Digest, Sha256};
+
+    use crate::model::transaction::Transaction;
+
+    use super::{is_key_address, key_address, TxSignature};
+
+    // a key for each name the tests pass money between, so they can still say Billy
+    pub fn named_key(name: &str) -> SigningKey {
+        SigningKey::from_bytes(&Sha256::digest(name.as_bytes()).into())
+    }
+
+    pub fn named_address(name: &str) -> String {
+        key_address(&named_key(name).verifying_key())
+    }
+
+    pub fn signed_by(name: &str, mut transaction: Transaction) -> Transaction {
+        transaction.sign(&named_key(name));
+        transaction
+    }
+
+    #[test]
+    pub fn signatures_should_only_verify_the_signed_message() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let signature = TxSignature::sign(&key, b"pay Bobby");
+        assert!(signature.verify(b"pay Bobby"));
+        assert!(!signature.verify(b"pay Jill"));
+
+        let address = key_address(&key.verifying_key());
+        assert!(is_key_address(&address));
+        assert!(!is_key_address("Timmy"));
+        assert_eq!(signature.address(), Some(address));
+
+        let forged = TxSignature {
+            public_key: hex::encode(SigningKey::from_bytes(&[8; 32]).verifying_key().as_bytes()),
+            ..signature
+        };
+        assert!(!forged.verify(b"pay Bobby"));
+    }
+}
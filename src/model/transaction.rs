@@ -1,9 +1,275 @@
-use fixed::types::I32F32;
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+use sha2::Sha256;
 
-#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+use super::{
+    amount::Amount,
+    blockchain::BlockchainError,
+    multisig::{is_multisig_address, MultisigPolicy},
+    script::{is_script_address, ScriptSpend},
+    signature::{is_key_address, TxSignature},
+};
+
+// points at one output of an earlier transaction
+#[derive(Clone, Debug, Eq, PartialEq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct OutPoint {
+    pub txid: String,
+    pub index: u32,
+}
+
+// coins that belong to the receiver until a later transaction spends them
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct TxOutput {
+    pub receiver: String,
+    pub amount: Amount,
+}
+
+// the earliest block a transaction can go in
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum LockTime {
+    // the block's height has to be at least this
+    Height(u64),
+    // the median time past of the chain the block goes on has to be at least this,
+    // in seconds since the epoch
+    Timestamp(i64),
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct Transaction {
     pub sender: String,
     pub receiver: String,
-    pub amount: I32F32, //no fractions because it's easier that way
+    pub amount: Amount,
+    // paid by the sender on top of the amount
+    pub fee: Amount,
+    // the sender's sequence number, their first transaction is 0 and each one after
+    // goes up by one. a pending transaction can be replaced by another one from
+    // the same sender with the same nonce that pays more
+    pub nonce: u64,
     pub timestamp: i64,
+    // the rest is only for chains using the utxo ledger, where receiver and amount stay empty.
+    // the outputs being spent, all of them have to belong to the sender
+    pub inputs: Vec<OutPoint>,
+    // where the coins go, including any change back to the sender
+    pub outputs: Vec<TxOutput>,
+    // approvals from the keys behind the sender, see check_signatures
+    pub signatures: Vec<TxSignature>,
+    // only when the sender is a multisig address, the policy it's the hash of
+    pub multisig: Option<MultisigPolicy>,
+    // until then it waits in the mempool, None means any block will do
+    pub lock_time: Option<LockTime>,
+    // only when the sender is a script address, the script and what it needs to pass
+    pub script: Option<ScriptSpend>,
+}
+
+impl Transaction {
+    // the transaction's id, a hash of exactly the bytes that go over the wire.
+    // peers announce transactions by it and later transactions spend outputs with it
+    pub fn txid(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(bincode::serialize(self).unwrap_or_default());
+        format!("{:x}", hasher.finalize())
+    }
+
+    // what signers sign, everything except the signatures themselves
+    pub fn signing_hash(&self) -> String {
+        Transaction {
+            signatures: vec![],
+            ..self.clone()
+        }
+        .txid()
+    }
+
+    pub fn sign(&mut self, key: &SigningKey) {
+        let signature = TxSignature::sign(key, self.signing_hash().as_bytes());
+        // kept in order of public key, signing twice just replaces the first one
+        match self
+            .signatures
+            .binary_search_by(|existing| existing.public_key.cmp(&signature.public_key))
+        {
+            Ok(index) => self.signatures[index] = signature,
+            Err(index) => self.signatures.insert(index, signature),
+        }
+    }
+
+    // every signature has to hold up, a sender that's a key address has to
+    // have signed, a multisig sender needs its threshold of keys to have signed
+    // and a script sender's script has to pass. a plain name has no key behind it
+    // so nothing can spend from one, its coins only ever come in through genesis.
+    // signatures aren't covered by the signing hash, so they also have to be in
+    // the one encoding that leaves nothing for a relayer to change the txid with
+    pub fn check_signatures(&self) -> Result<(), BlockchainError> {
+        let message = self.signing_hash();
+        if !self
+            .signatures
+            .iter()
+            .all(|signature| signature.verify(message.as_bytes()))
+        {
+            return Err(BlockchainError::InvalidSignature);
+        }
+        if !self.signatures.iter().all(TxSignature::is_canonical)
+            || !self
+                .signatures
+                .windows(2)
+                .all(|pair| pair[0].public_key < pair[1].public_key)
+        {
+            return Err(BlockchainError::NonCanonicalSignatures);
+        }
+
+        if is_script_address(&self.sender) {
+            let spend = self.script.as_ref().ok_or(BlockchainError::InvalidScript)?;
+            if spend.script.address() != self.sender || self.multisig.is_some() {
+                return Err(BlockchainError::InvalidScript);
+            }
+            let signers = spend
+                .script
+                .signers(&spend.witness, self)
+                .map_err(BlockchainError::ScriptFailed)?;
+            return self.only_signed_by(signers.len());
+        }
+        if self.script.is_some() {
+            return Err(BlockchainError::InvalidScript);
+        }
+
+        if is_multisig_address(&self.sender) {
+            let policy = self
+                .multisig
+                .as_ref()
+                .ok_or(BlockchainError::InvalidMultisig)?;
+            policy.check()?;
+            if policy.address() != self.sender {
+                return Err(BlockchainError::InvalidMultisig);
+            }
+            if (policy.signers(&self.signatures, message.as_bytes()).len() as u32)
+                < policy.threshold
+            {
+                return Err(BlockchainError::MissingSignature);
+            }
+            return self.only_signed_by(policy.threshold as usize);
+        }
+        if self.multisig.is_some() {
+            return Err(BlockchainError::InvalidMultisig);
+        }
+
+        if is_key_address(&self.sender) {
+            if !self
+                .signatures
+                .iter()
+                .any(|signature| signature.address().as_deref() == Some(self.sender.as_str()))
+            {
+                return Err(BlockchainError::MissingSignature);
+            }
+            return self.only_signed_by(1);
+        }
+        Err(BlockchainError::MissingSignature)
+    }
+
+    // the signatures that were needed are all there, so any past that many are
+    // extras somebody could strip or pile on
+    fn only_signed_by(&self, needed: usize) -> Result<(), BlockchainError> {
+        if self.signatures.len() != needed {
+            return Err(BlockchainError::NonCanonicalSignatures);
+        }
+        Ok(())
+    }
+
+    // whether it can go in a block at this height on top of a chain with this median time past
+    pub fn is_final(&self, height: u64, median_time_past: i64) -> bool {
+        match self.lock_time {
+            None => true,
+            Some(LockTime::Height(lock)) => height >= lock,
+            Some(LockTime::Timestamp(lock)) => median_time_past >= lock,
+        }
+    }
+
+    // how many bytes this transaction takes up on the wire
+    pub fn size(&self) -> u64 {
+        bincode::serialized_size(self).unwrap_or(u64::MAX)
+    }
+
+    // what the sender is paying per 1000 bytes, higher pays to get in first
+    pub fn fee_rate(&self) -> u64 {
+        let rate = self.fee.base_units() as u128 * 1000 / self.size().max(1) as u128;
+        u64::try_from(rate).unwrap_or(u64::MAX)
+    }
+
+    // pays the miner of the block at height, the first transaction of a block can be
+    // one of these and it's the only way new coins come into existence after genesis
+    pub fn coinbase(height: u64, receiver: &str, amount: Amount, utxo: bool) -> Self {
+        let coinbase = Transaction {
+            sender: "".to_string(),
+            receiver: receiver.to_string(),
+            amount,
+            fee: Amount::ZERO,
+            // keeps every block's coinbase txid different
+            nonce: height,
+            timestamp: 0,
+            ..Transaction::default()
+        };
+        if !utxo {
+            return coinbase;
+        }
+
+        Transaction {
+            receiver: "".to_string(),
+            amount: Amount::ZERO,
+            outputs: vec![TxOutput {
+                receiver: receiver.to_string(),
+                amount,
+            }],
+            ..coinbase
+        }
+    }
+
+    // nobody is paying for it
+    pub fn is_coinbase(&self) -> bool {
+        self.sender.is_empty() && self.inputs.is_empty()
+    }
+
+    pub fn is_utxo(&self) -> bool {
+        !self.inputs.is_empty() || !self.outputs.is_empty()
+    }
+
+    // who gets paid what
+    pub fn credits(&self) -> Vec<(String, Amount)> {
+        if self.is_utxo() {
+            self.outputs
+                .iter()
+                .map(|output| (output.receiver.clone(), output.amount))
+                .collect()
+        } else {
+            vec![(self.receiver.clone(), self.amount)]
+        }
+    }
+
+    // what it costs the sender all told, None if it's more than an amount can hold
+    pub fn total_cost(&self) -> Option<Amount> {
+        Amount::checked_sum(
+            self.credits()
+                .into_iter()
+                .map(|(_, amount)| amount)
+                .chain([self.fee]),
+        )
+    }
+
+    // sending nothing is pointless and amounts that don't add up are never valid,
+    // returns the total cost when everything checks out
+    pub fn check_amounts(&self) -> Result<Amount, BlockchainError> {
+        if self.credits().iter().any(|(_, amount)| amount.is_zero()) {
+            return Err(BlockchainError::InvalidAmount);
+        }
+
+        self.total_cost().ok_or(BlockchainError::AmountOverflow)
+    }
+
+    // the outpoints later transactions use to spend this one's outputs
+    pub fn outpoints(&self) -> Vec<OutPoint> {
+        let txid = self.txid();
+        (0..self.outputs.len() as u32)
+            .map(|index| OutPoint {
+                txid: txid.clone(),
+                index,
+            })
+            .collect()
+    }
 }
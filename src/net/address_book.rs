@@ -0,0 +1,249 @@
+use std::{collections::HashMap, fs, io, net::SocketAddr, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+// we don't need to know about every node out there
+pub const MAX_ADDRESSES: usize = 1000;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AddressEntry {
+    pub addr: SocketAddr,
+    // when we last heard about this address from anybody
+    pub last_seen: i64,
+    pub last_attempt: Option<i64>,
+    pub last_success: Option<i64>,
+    pub successes: u32,
+    // consecutive failures since the last success
+    pub failures: u32,
+    // seeds from the chain spec are never forgotten
+    pub is_seed: bool,
+}
+
+impl AddressEntry {
+    pub fn new(addr: SocketAddr, now: i64) -> Self {
+        AddressEntry {
+            addr,
+            last_seen: now,
+            last_attempt: None,
+            last_success: None,
+            successes: 0,
+            failures: 0,
+            is_seed: false,
+        }
+    }
+
+    // nodes that have worked before float to the top,
+    // nodes that keep failing sink to the bottom
+    pub fn score(&self) -> i64 {
+        self.successes.min(10) as i64 * 2 - self.failures as i64 * 3
+    }
+
+    // back off exponentially from addresses that keep failing
+    pub fn is_ready(&self, now: i64) -> bool {
+        match self.last_attempt {
+            Some(last_attempt) if self.failures > 0 => {
+                now - last_attempt >= 2i64.pow(self.failures.min(10))
+            }
+            _ => true,
+        }
+    }
+}
+
+// every node address we've heard about and how well it has worked out for us
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AddressBook {
+    pub entries: HashMap<SocketAddr, AddressEntry>,
+}
+
+impl AddressBook {
+    pub fn new() -> Self {
+        AddressBook {
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        bincode::deserialize(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // loads the address book if there is one, starts a new one otherwise
+    pub fn load_or_default(path: &Path) -> Self {
+        Self::load(path).unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let bytes =
+            bincode::serialize(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        // write somewhere else first so a crash can't leave us with half a file
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bytes)?;
+        fs::rename(tmp, path)
+    }
+
+    pub fn add_seed(&mut self, addr: SocketAddr, now: i64) {
+        self.add(addr, now);
+        if let Some(entry) = self.entries.get_mut(&addr) {
+            entry.is_seed = true;
+        }
+    }
+
+    // returns true if we hadn't heard of this address before
+    pub fn add(&mut self, addr: SocketAddr, now: i64) -> bool {
+        if let Some(entry) = self.entries.get_mut(&addr) {
+            entry.last_seen = entry.last_seen.max(now);
+            return false;
+        }
+
+        if self.entries.len() >= MAX_ADDRESSES && !self.evict_worst() {
+            return false;
+        }
+
+        self.entries.insert(addr, AddressEntry::new(addr, now));
+        true
+    }
+
+    pub fn record_attempt(&mut self, addr: &SocketAddr, now: i64) {
+        if let Some(entry) = self.entries.get_mut(addr) {
+            entry.last_attempt = Some(now);
+        }
+    }
+
+    pub fn record_success(&mut self, addr: &SocketAddr, now: i64) {
+        if let Some(entry) = self.entries.get_mut(addr) {
+            entry.successes += 1;
+            entry.failures = 0;
+            entry.last_success = Some(now);
+            entry.last_seen = now;
+        }
+    }
+
+    pub fn record_failure(&mut self, addr: &SocketAddr, now: i64) {
+        if let Some(entry) = self.entries.get_mut(addr) {
+            entry.failures += 1;
+            entry.last_attempt = Some(now);
+        }
+    }
+
+    pub fn remove(&mut self, addr: &SocketAddr) {
+        self.entries.remove(addr);
+    }
+
+    // the best addresses to connect to right now, skipping the ones in exclude
+    pub fn candidates(&self, count: usize, exclude: &[SocketAddr], now: i64) -> Vec<SocketAddr> {
+        let mut candidates: Vec<&AddressEntry> = self
+            .entries
+            .values()
+            .filter(|entry| !exclude.contains(&entry.addr) && entry.is_ready(now))
+            .collect();
+        candidates.sort_by(|a, b| {
+            b.score()
+                .cmp(&a.score())
+                .then(b.last_seen.cmp(&a.last_seen))
+                .then(a.addr.cmp(&b.addr))
+        });
+
+        candidates
+            .into_iter()
+            .take(count)
+            .map(|entry| entry.addr)
+            .collect()
+    }
+
+    // addresses worth sharing with other peers, ones that keep failing aren't
+    pub fn shareable(&self, count: usize) -> Vec<SocketAddr> {
+        let mut entries: Vec<&AddressEntry> = self
+            .entries
+            .values()
+            .filter(|entry| entry.failures < 3)
+            .collect();
+        entries.sort_by(|a, b| b.last_seen.cmp(&a.last_seen).then(a.addr.cmp(&b.addr)));
+
+        entries
+            .into_iter()
+            .take(count)
+            .map(|entry| entry.addr)
+            .collect()
+    }
+
+    fn evict_worst(&mut self) -> bool {
+        let worst = self
+            .entries
+            .values()
+            .filter(|entry| !entry.is_seed)
+            .min_by(|a, b| {
+                a.score()
+                    .cmp(&b.score())
+                    .then(a.last_seen.cmp(&b.last_seen))
+            })
+            .map(|entry| entry.addr);
+
+        match worst {
+            Some(addr) => {
+                self.entries.remove(&addr);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+
+    use super::AddressBook;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    pub fn candidates_should_prefer_addresses_that_worked() {
+        let mut book = AddressBook::new();
+        book.add(addr(1), 0);
+        book.add(addr(2), 0);
+        book.add(addr(3), 0);
+        book.record_success(&addr(2), 1);
+        book.record_failure(&addr(3), 1);
+
+        assert_eq!(
+            book.candidates(3, &[], 100),
+            vec![addr(2), addr(1), addr(3)]
+        );
+        assert_eq!(book.candidates(1, &[addr(2)], 100), vec![addr(1)]);
+    }
+
+    #[test]
+    pub fn candidates_should_back_off_from_failing_addresses() {
+        let mut book = AddressBook::new();
+        book.add(addr(1), 0);
+        book.record_failure(&addr(1), 10);
+        book.record_failure(&addr(1), 10);
+
+        assert!(book.candidates(1, &[], 11).is_empty());
+        assert_eq!(book.candidates(1, &[], 14), vec![addr(1)]);
+    }
+
+    #[test]
+    pub fn address_book_should_survive_a_round_trip_to_disk() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let path = dir.path().join("addresses");
+
+        let mut book = AddressBook::new();
+        book.add_seed(addr(1), 0);
+        book.add(addr(2), 5);
+        book.record_success(&addr(2), 6);
+        book.save(&path).expect("save");
+
+        assert_eq!(AddressBook::load(&path).expect("load"), book);
+    }
+}
@@ -0,0 +1,155 @@
+use std::{collections::HashMap, fs, io, net::SocketAddr, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use super::peer::PeerId;
+use crate::model::blockchain::BlockchainError;
+
+// once a peer has racked up this much misbehaviour we stop talking to them
+pub const BAN_THRESHOLD: u32 = 100;
+
+pub const DEFAULT_BAN_DURATION_SECS: i64 = 24 * 60 * 60;
+
+// how bad it is for a peer to send us data that fails with this error.
+// things that can happen to honest peers racing each other cost a little,
+// things that take effort to get wrong cost a lot
+pub fn misbehaviour_score(error: &BlockchainError) -> u32 {
+    match error {
+        // nobody stumbles into a block without proof of work
+        BlockchainError::IncorrectProof => 100,
+        BlockchainError::InvalidIndex => 50,
+        BlockchainError::EmptyTransactions => 50,
+        BlockchainError::BlockTooLarge => 100,
+        // the proof of work was real but the block is no good
+        BlockchainError::InsufficientFunds => 100,
+        BlockchainError::DuplicateTransaction => 100,
+        BlockchainError::InvalidNonce => 100,
+        BlockchainError::WrongLedgerModel => 100,
+        BlockchainError::DoubleSpend => 100,
+        BlockchainError::InputNotOwned => 100,
+        BlockchainError::UnbalancedTransaction => 100,
+        BlockchainError::UnknownOutput => 100,
+        BlockchainError::InvalidAmount => 100,
+        BlockchainError::AmountOverflow => 100,
+        BlockchainError::InvalidCoinbase => 100,
+        BlockchainError::InvalidSignature => 100,
+        BlockchainError::MissingSignature => 100,
+        BlockchainError::NonCanonicalSignatures => 100,
+        BlockchainError::InvalidMultisig => 100,
+        // the block's height and the median time past say it's too early
+        BlockchainError::NonFinalTransaction => 100,
+        BlockchainError::TimestampTooEarly => 100,
+        BlockchainError::InvalidScript => 100,
+        BlockchainError::ScriptFailed(_) => 100,
+        // could just be a stale block
+        BlockchainError::PreviousHashDoesNotMatch => 10,
+        BlockchainError::UnknownTransaction => 10,
+        // their clock or ours could be off
+        BlockchainError::TimestampTooFarAhead => 10,
+    }
+}
+
+// peers are banned by the ip the transport sees them connecting from, never by
+// anything they tell us about themselves. that way coming back from another port
+// doesn't get around a ban and nobody can get somebody else banned by claiming
+// their address. everybody on loopback is on this machine and shares its ip,
+// so they're told apart by port. transports that don't deal in ip addresses
+// use the peer id as is
+pub fn ban_key(id: &PeerId) -> String {
+    match id.parse::<SocketAddr>() {
+        Ok(addr) if addr.ip().is_loopback() => addr.to_string(),
+        Ok(addr) => addr.ip().to_string(),
+        Err(_) => id.clone(),
+    }
+}
+
+// peers we refuse to talk to and until when, keyed by ban_key
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BanList {
+    pub entries: HashMap<String, i64>,
+}
+
+impl BanList {
+    pub fn new() -> Self {
+        BanList {
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        bincode::deserialize(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn load_or_default(path: &Path) -> Self {
+        Self::load(path).unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let bytes =
+            bincode::serialize(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bytes)?;
+        fs::rename(tmp, path)
+    }
+
+    pub fn ban(&mut self, key: &str, until: i64) {
+        let banned_until = self.entries.entry(key.to_string()).or_insert(until);
+        *banned_until = (*banned_until).max(until);
+    }
+
+    pub fn unban(&mut self, key: &str) {
+        self.entries.remove(key);
+    }
+
+    pub fn is_banned(&self, key: &str, now: i64) -> bool {
+        self.entries.get(key).is_some_and(|until| *until > now)
+    }
+
+    // forget bans that have run out
+    pub fn sweep(&mut self, now: i64) {
+        self.entries.retain(|_, until| *until > now);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ban_key, BanList};
+
+    #[test]
+    pub fn ban_key_should_ignore_the_port() {
+        assert_eq!(ban_key(&"10.0.0.1:7878".to_string()), "10.0.0.1");
+        assert_eq!(ban_key(&"[2001:db8::1]:7878".to_string()), "2001:db8::1");
+        assert_eq!(ban_key(&"a".to_string()), "a");
+    }
+
+    #[test]
+    pub fn ban_key_should_keep_the_port_on_loopback() {
+        assert_eq!(ban_key(&"127.0.0.1:7878".to_string()), "127.0.0.1:7878");
+        assert_eq!(ban_key(&"[::1]:7878".to_string()), "[::1]:7878");
+    }
+
+    #[test]
+    pub fn bans_should_expire() {
+        let mut bans = BanList::new();
+        bans.ban("127.0.0.1", 100);
+
+        assert!(bans.is_banned("127.0.0.1", 99));
+        assert!(!bans.is_banned("127.0.0.1", 100));
+
+        bans.sweep(100);
+        assert!(bans.entries.is_empty());
+    }
+
+    #[test]
+    pub fn ban_list_should_survive_a_round_trip_to_disk() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let path = dir.path().join("bans");
+
+        let mut bans = BanList::new();
+        bans.ban("127.0.0.1", 100);
+        bans.save(&path).expect("save");
+
+        assert_eq!(BanList::load(&path).expect("load"), bans);
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::{HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+// an announcement of something a node has, identified by its hash
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum Inventory {
+    Block(String),
+    Transaction(String),
+}
+
+// how many inventory items we remember per peer before forgetting the oldest ones
+pub const DEFAULT_KNOWN_INVENTORY_CAPACITY: usize = 50_000;
+
+// the inventory a peer is known to have, either because they announced it,
+// sent it to us, or we announced it to them. this is bounded so a chatty
+// peer can't make us remember everything forever
+#[derive(Clone, Debug, PartialEq)]
+pub struct KnownInventory {
+    items: HashSet<Inventory>,
+    order: VecDeque<Inventory>,
+    capacity: usize,
+}
+
+impl Default for KnownInventory {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_KNOWN_INVENTORY_CAPACITY)
+    }
+}
+
+impl KnownInventory {
+    pub fn with_capacity(capacity: usize) -> Self {
+        KnownInventory {
+            items: HashSet::new(),
+            order: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    // returns true if the item wasn't already known
+    pub fn insert(&mut self, item: Inventory) -> bool {
+        if !self.items.insert(item.clone()) {
+            return false;
+        }
+
+        self.order.push_back(item);
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.items.remove(&oldest);
+            }
+        }
+
+        true
+    }
+
+    pub fn contains(&self, item: &Inventory) -> bool {
+        self.items.contains(item)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Inventory, KnownInventory};
+
+    #[test]
+    pub fn known_inventory_should_forget_oldest_items_past_capacity() {
+        let mut known = KnownInventory::with_capacity(2);
+
+        assert!(known.insert(Inventory::Block("a".to_string())));
+        assert!(!known.insert(Inventory::Block("a".to_string())));
+        assert!(known.insert(Inventory::Transaction("b".to_string())));
+        assert!(known.insert(Inventory::Block("c".to_string())));
+
+        assert_eq!(known.len(), 2);
+        assert!(!known.contains(&Inventory::Block("a".to_string())));
+        assert!(known.contains(&Inventory::Block("c".to_string())));
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+
+use crate::model::node::Node;
+
+use super::peer::PeerId;
+
+// an in-process network of nodes, handy for tests and simulations.
+// messages are delivered in the order they were sent
+#[derive(Debug, Default)]
+pub struct LocalNetwork {
+    pub nodes: HashMap<PeerId, Node>,
+}
+
+impl LocalNetwork {
+    pub fn new() -> Self {
+        LocalNetwork {
+            nodes: HashMap::new(),
+        }
+    }
+
+    pub fn add_node(&mut self, id: &str, node: Node) {
+        self.nodes.insert(id.to_string(), node);
+    }
+
+    pub fn node(&self, id: &str) -> &Node {
+        self.nodes.get(id).expect("no node with that id")
+    }
+
+    pub fn node_mut(&mut self, id: &str) -> &mut Node {
+        self.nodes.get_mut(id).expect("no node with that id")
+    }
+
+    pub fn connect(&mut self, a: &str, b: &str) {
+        self.node_mut(a).add_peer(b.to_string());
+        self.node_mut(b).add_peer(a.to_string());
+    }
+
+    pub fn disconnect(&mut self, a: &str, b: &str) {
+        self.node_mut(a).remove_peer(&b.to_string());
+        self.node_mut(b).remove_peer(&a.to_string());
+    }
+
+    // keep delivering messages until nobody has anything left to say,
+    // returns how many messages were delivered
+    pub async fn run_until_idle(&mut self) -> usize {
+        let mut delivered = 0;
+        loop {
+            // hang up on whoever the nodes want gone
+            let mut disconnects = Vec::new();
+            for (id, node) in self.nodes.iter_mut() {
+                for peer in node.drain_disconnects() {
+                    disconnects.push((id.clone(), peer));
+                }
+            }
+            for (a, b) in disconnects {
+                if self.nodes.contains_key(&b) {
+                    self.disconnect(&a, &b);
+                }
+            }
+
+            let mut pending = Vec::new();
+            for (id, node) in self.nodes.iter_mut() {
+                for (to, message) in node.drain_outbox() {
+                    pending.push((id.clone(), to, message));
+                }
+            }
+
+            if pending.is_empty() {
+                return delivered;
+            }
+
+            for (from, to, message) in pending {
+                // messages for nodes that aren't part of the network go nowhere
+                if let Some(node) = self.nodes.get_mut(&to) {
+                    node.handle_message(&from, message).await;
+                    delivered += 1;
+                }
+            }
+        }
+    }
+}
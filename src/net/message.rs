@@ -0,0 +1,64 @@
+use std::net::SocketAddr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::{
+    block::{Block, BlockHeader},
+    blockchain::MAX_BLOCK_SIZE,
+    transaction::Transaction,
+};
+
+use super::{inventory::Inventory, sync::MAX_HEADERS_PER_MESSAGE};
+
+// the most addresses we'll share in one Addr message
+pub const MAX_ADDR_PER_MESSAGE: usize = 1000;
+
+// the most items allowed in an Inv, GetData or NotFound message
+pub const MAX_INV_PER_MESSAGE: usize = 10_000;
+
+// the most bytes a single encoded message may take up, enough for
+// the largest block with room to spare
+pub const MAX_MESSAGE_SIZE: usize = 2 * MAX_BLOCK_SIZE as usize;
+
+// what it costs a peer to send us a message that breaks the limits above
+pub const OVERSIZED_MESSAGE_SCORE: u32 = 20;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Message {
+    // the first thing both sides say after connecting
+    Version {
+        // where the sender accepts connections, if anywhere
+        listen_addr: Option<SocketAddr>,
+        best_height: u64,
+    },
+    // "who else do you know about?"
+    GetAddr,
+    Addr(Vec<SocketAddr>),
+    // "i have these", peers respond with GetData for anything they're missing
+    Inv(Vec<Inventory>),
+    // "send me these"
+    GetData(Vec<Inventory>),
+    // response to GetData for items we don't have (anymore)
+    NotFound(Vec<Inventory>),
+    Block(Block),
+    // "send me the headers after the first of these hashes you recognize"
+    GetHeaders(Vec<String>),
+    Headers(Vec<BlockHeader>),
+    Transaction(Transaction),
+}
+
+impl Message {
+    // true if there's more in this message than anybody should ever send
+    pub fn is_oversized(&self) -> bool {
+        match self {
+            Message::Addr(addresses) => addresses.len() > MAX_ADDR_PER_MESSAGE,
+            Message::Inv(items) | Message::GetData(items) | Message::NotFound(items) => {
+                items.len() > MAX_INV_PER_MESSAGE
+            }
+            Message::GetHeaders(locator) => locator.len() > MAX_INV_PER_MESSAGE,
+            Message::Headers(headers) => headers.len() > MAX_HEADERS_PER_MESSAGE,
+            Message::Block(block) => block.size() > MAX_BLOCK_SIZE,
+            _ => false,
+        }
+    }
+}
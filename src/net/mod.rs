@@ -0,0 +1,9 @@
+pub mod address_book;
+pub mod ban;
+pub mod inventory;
+pub mod local;
+pub mod message;
+pub mod peer;
+pub mod rate_limit;
+pub mod sync;
+pub mod tcp;
@@ -0,0 +1,43 @@
+use std::net::SocketAddr;
+
+use super::{
+    inventory::KnownInventory,
+    rate_limit::{RateLimits, TokenBucket},
+};
+
+pub type PeerId = String;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Peer {
+    pub id: PeerId,
+    // true if we dialed them, false if they dialed us
+    pub outbound: bool,
+    // where they accept connections, learned from their Version message
+    pub listen_addr: Option<SocketAddr>,
+    // everything we know this peer has, so we never announce it to them again
+    pub known_inventory: KnownInventory,
+    pub message_limit: TokenBucket,
+    pub transaction_limit: TokenBucket,
+    pub block_limit: TokenBucket,
+}
+
+impl Peer {
+    pub fn new(id: PeerId) -> Self {
+        Self::with_limits(id, &RateLimits::default())
+    }
+
+    pub fn with_limits(id: PeerId, limits: &RateLimits) -> Self {
+        Peer {
+            id,
+            outbound: false,
+            listen_addr: None,
+            known_inventory: KnownInventory::default(),
+            message_limit: TokenBucket::new(limits.messages_per_second, limits.message_burst),
+            transaction_limit: TokenBucket::new(
+                limits.transactions_per_second,
+                limits.transaction_burst,
+            ),
+            block_limit: TokenBucket::new(limits.blocks_per_second, limits.block_burst),
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use std::time::Instant;
+
+// a bucket that fills up at a steady rate and empties a token at a time,
+// lets a peer burst up to the capacity but no faster than the rate on average
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    per_second: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(per_second: f64, capacity: f64) -> Self {
+        TokenBucket {
+            capacity,
+            tokens: capacity,
+            per_second,
+            last_refill: Instant::now(),
+        }
+    }
+
+    pub fn try_take(&mut self) -> bool {
+        self.try_take_at(Instant::now())
+    }
+
+    pub fn try_take_at(&mut self, now: Instant) -> bool {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.capacity);
+        self.last_refill = now;
+
+        if self.tokens < 1.0 {
+            return false;
+        }
+
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+// how fast a single peer is allowed to send us things
+#[derive(Clone, Debug, PartialEq)]
+pub struct RateLimits {
+    pub messages_per_second: f64,
+    pub message_burst: f64,
+    pub transactions_per_second: f64,
+    pub transaction_burst: f64,
+    // only blocks we didn't ask for count against this
+    pub blocks_per_second: f64,
+    pub block_burst: f64,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        RateLimits {
+            messages_per_second: 500.0,
+            message_burst: 5000.0,
+            transactions_per_second: 100.0,
+            transaction_burst: 1000.0,
+            blocks_per_second: 2.0,
+            block_burst: 20.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::TokenBucket;
+
+    #[test]
+    pub fn token_bucket_should_refill_over_time() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(2.0, 2.0);
+
+        assert!(bucket.try_take_at(start));
+        assert!(bucket.try_take_at(start));
+        assert!(!bucket.try_take_at(start));
+
+        assert!(bucket.try_take_at(start + Duration::from_millis(500)));
+        assert!(!bucket.try_take_at(start + Duration::from_millis(500)));
+
+        // never fills past capacity
+        let later = start + Duration::from_secs(60);
+        assert!(bucket.try_take_at(later));
+        assert!(bucket.try_take_at(later));
+        assert!(!bucket.try_take_at(later));
+    }
+}
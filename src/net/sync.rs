@@ -0,0 +1,80 @@
+use std::collections::HashSet;
+
+use crate::model::block::BlockHeader;
+
+use super::peer::PeerId;
+
+// the most headers we'll send in one message, if a peer sends us
+// this many we assume they have more and ask again
+pub const MAX_HEADERS_PER_MESSAGE: usize = 2000;
+
+// how many block bodies we'll wait on from a single peer at once
+pub const MAX_BLOCKS_IN_FLIGHT_PER_PEER: usize = 16;
+
+// state for a headers-first download. we first collect and validate the
+// best header chain our peers know about, then fetch the bodies for it
+// from every peer that has it
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeaderSync {
+    // height of the last block our chain has in common with the header chain
+    pub fork_height: u64,
+    // validated headers building on top of the block at fork_height
+    pub headers: Vec<BlockHeader>,
+    // hashes of the headers above, in the same order
+    pub hashes: Vec<String>,
+    // peers that showed us this header chain, bodies get requested from them
+    pub peers: HashSet<PeerId>,
+    // hashes of the blocks whose bodies we've received
+    pub received: HashSet<String>,
+}
+
+impl HeaderSync {
+    pub fn new(fork_height: u64) -> Self {
+        HeaderSync {
+            fork_height,
+            headers: Vec::new(),
+            hashes: Vec::new(),
+            peers: HashSet::new(),
+            received: HashSet::new(),
+        }
+    }
+
+    pub fn extend(&mut self, headers: Vec<BlockHeader>) {
+        for header in headers {
+            self.hashes.push(header.hash());
+            self.headers.push(header);
+        }
+    }
+
+    // height of the last header, or the fork point if we have none
+    pub fn height(&self) -> u64 {
+        self.fork_height + self.headers.len() as u64
+    }
+
+    pub fn tip_hash(&self) -> Option<&String> {
+        self.hashes.last()
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.hashes.iter().any(|h| h == hash)
+    }
+
+    pub fn missing(&self) -> impl Iterator<Item = &String> {
+        self.hashes
+            .iter()
+            .filter(|hash| !self.received.contains(*hash))
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.received.len() >= self.hashes.len()
+    }
+
+    // percentage of block bodies downloaded so far
+    pub fn progress(&self) -> f64 {
+        if self.hashes.is_empty() {
+            return 100.0;
+        }
+
+        self.received.len() as f64 * 100.0 / self.hashes.len() as f64
+    }
+}
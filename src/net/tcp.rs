@@ -0,0 +1,410 @@
+use std::{collections::HashMap, io, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+
+use chrono::Utc;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, Mutex},
+    task::AbortHandle,
+    time,
+};
+use tracing::debug;
+
+use crate::model::node::Node;
+
+use super::{
+    address_book::AddressBook,
+    ban::{ban_key, BanList},
+    message::{Message, MAX_MESSAGE_SIZE},
+    peer::PeerId,
+};
+
+pub const DEFAULT_TARGET_OUTBOUND: usize = 8;
+
+// how often we check whether we need more outbound connections
+// and write the address book to disk
+pub const MAINTENANCE_INTERVAL: Duration = Duration::from_millis(500);
+
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// connections from other nodes we'll take before turning new ones away
+pub const DEFAULT_MAX_INBOUND: usize = 64;
+
+// messages waiting to be written to a single peer, a peer that lets this
+// fill up gets disconnected
+pub const DEFAULT_OUTBOUND_QUEUE_SIZE: usize = 1024;
+
+// messages read from a single peer waiting to be handled, once this fills up
+// we stop reading from them until the node catches up
+const INBOUND_QUEUE_SIZE: usize = 256;
+
+#[derive(Debug, Clone)]
+pub struct TcpConfig {
+    pub listen_addr: SocketAddr,
+    // how many connections we try to keep open to other nodes
+    pub target_outbound: usize,
+    pub max_inbound: usize,
+    pub outbound_queue_size: usize,
+    // where the address book is kept between runs
+    pub address_book_path: Option<PathBuf>,
+    // same for the peers we've banned
+    pub ban_list_path: Option<PathBuf>,
+}
+
+impl TcpConfig {
+    pub fn new(listen_addr: SocketAddr) -> Self {
+        TcpConfig {
+            listen_addr,
+            target_outbound: DEFAULT_TARGET_OUTBOUND,
+            max_inbound: DEFAULT_MAX_INBOUND,
+            outbound_queue_size: DEFAULT_OUTBOUND_QUEUE_SIZE,
+            address_book_path: None,
+            ban_list_path: None,
+        }
+    }
+}
+
+struct Connection {
+    sender: mpsc::Sender<Message>,
+    outbound: bool,
+    // the tasks reading and handling this peer's messages
+    readers: Vec<AbortHandle>,
+}
+
+// runs a node on top of real tcp connections. every message is a big endian
+// u32 length followed by the bincode encoded message
+pub struct TcpNetwork {
+    pub node: Arc<Mutex<Node>>,
+    pub local_addr: SocketAddr,
+    config: TcpConfig,
+    connections: Mutex<HashMap<PeerId, Connection>>,
+    tasks: std::sync::Mutex<Vec<AbortHandle>>,
+}
+
+impl TcpNetwork {
+    pub async fn start(mut node: Node, config: TcpConfig) -> io::Result<Arc<Self>> {
+        let listener = TcpListener::bind(config.listen_addr).await?;
+        let local_addr = listener.local_addr()?;
+        node.listen_addr = Some(local_addr);
+
+        // anything we learned on previous runs
+        if let Some(path) = config.address_book_path.as_ref() {
+            let stored = AddressBook::load_or_default(path);
+            for (addr, entry) in stored.entries {
+                node.address_book.entries.entry(addr).or_insert(entry);
+            }
+        }
+        if let Some(path) = config.ban_list_path.as_ref() {
+            let stored = BanList::load_or_default(path);
+            for (key, until) in stored.entries {
+                node.ban_list.ban(&key, until);
+            }
+        }
+
+        let network = Arc::new(TcpNetwork {
+            node: Arc::new(Mutex::new(node)),
+            local_addr,
+            config,
+            connections: Mutex::new(HashMap::new()),
+            tasks: std::sync::Mutex::new(Vec::new()),
+        });
+
+        let accepting = network.clone();
+        network.spawn(async move { accepting.accept_connections(listener).await });
+        let maintaining = network.clone();
+        network.spawn(async move { maintaining.maintain_connections().await });
+
+        Ok(network)
+    }
+
+    // stop listening and drop every connection
+    pub fn shutdown(&self) {
+        let tasks = std::mem::take(&mut *self.tasks.lock().expect("poisoned lock"));
+        for task in tasks {
+            task.abort();
+        }
+    }
+
+    pub async fn connect(self: &Arc<Self>, addr: SocketAddr) -> io::Result<()> {
+        let now = Utc::now().timestamp();
+        {
+            let mut node = self.node.lock().await;
+            node.address_book.add(addr, now);
+            node.address_book.record_attempt(&addr, now);
+        }
+
+        let stream = match time::timeout(CONNECT_TIMEOUT, TcpStream::connect(addr)).await {
+            Ok(Ok(stream)) => stream,
+            Ok(Err(e)) => {
+                self.node
+                    .lock()
+                    .await
+                    .address_book
+                    .record_failure(&addr, now);
+                return Err(e);
+            }
+            Err(_) => {
+                self.node
+                    .lock()
+                    .await
+                    .address_book
+                    .record_failure(&addr, now);
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+        };
+
+        self.node
+            .lock()
+            .await
+            .address_book
+            .record_success(&addr, now);
+        self.attach(stream, addr.to_string(), true).await;
+        Ok(())
+    }
+
+    // hand everything the node wants to say to the connections it's meant for
+    // and hang up on anybody it doesn't want to talk to anymore
+    pub async fn flush(&self) {
+        // the node lock is held the whole time so messages go out in order
+        let mut node = self.node.lock().await;
+        let mut connections = self.connections.lock().await;
+        let mut stalled = Vec::new();
+        for (peer, message) in node.drain_outbox() {
+            if let Some(connection) = connections.get(&peer) {
+                // a peer that can't keep up would silently miss things like the
+                // blocks it asked for, better to hang up so it goes elsewhere
+                if let Err(mpsc::error::TrySendError::Full(_)) = connection.sender.try_send(message)
+                {
+                    if !stalled.contains(&peer) {
+                        debug!("dropping peer {}: send queue is full", peer);
+                        stalled.push(peer);
+                    }
+                }
+            }
+        }
+        for peer in stalled {
+            node.remove_peer(&peer);
+            node.disconnects.push(peer);
+        }
+
+        for peer in node.drain_disconnects() {
+            // dropping the sender lets the writer finish what's queued and close up
+            if let Some(connection) = connections.remove(&peer) {
+                for reader in connection.readers {
+                    reader.abort();
+                }
+            }
+        }
+    }
+
+    pub async fn peer_ids(&self) -> Vec<PeerId> {
+        self.connections.lock().await.keys().cloned().collect()
+    }
+
+    pub async fn outbound_count(&self) -> usize {
+        self.connections
+            .lock()
+            .await
+            .values()
+            .filter(|connection| connection.outbound)
+            .count()
+    }
+
+    pub async fn inbound_count(&self) -> usize {
+        self.connections
+            .lock()
+            .await
+            .values()
+            .filter(|connection| !connection.outbound)
+            .count()
+    }
+
+    // tasks are kept track of so shutdown can stop them
+    fn spawn<F>(&self, future: F) -> AbortHandle
+    where
+        F: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let handle = tokio::spawn(future);
+        let mut tasks = self.tasks.lock().expect("poisoned lock");
+        tasks.retain(|task| !task.is_finished());
+        tasks.push(handle.abort_handle());
+        handle.abort_handle()
+    }
+
+    async fn accept_connections(self: Arc<Self>, listener: TcpListener) {
+        loop {
+            match listener.accept().await {
+                Ok((stream, remote)) => {
+                    // dropping the stream hangs up on them
+                    if self.inbound_count().await >= self.config.max_inbound {
+                        debug!("turning away {}: too many inbound connections", remote);
+                        continue;
+                    }
+                    self.attach(stream, remote.to_string(), false).await
+                }
+                Err(e) => debug!("failed to accept connection: {}", e),
+            }
+        }
+    }
+
+    async fn maintain_connections(self: Arc<Self>) {
+        let mut interval = time::interval(MAINTENANCE_INTERVAL);
+        let mut last_saved = None;
+        let mut last_saved_bans = None;
+        loop {
+            interval.tick().await;
+
+            let outbound = self.outbound_count().await;
+            if outbound < self.config.target_outbound {
+                for addr in self
+                    .connection_candidates(self.config.target_outbound - outbound)
+                    .await
+                {
+                    if let Err(e) = self.connect(addr).await {
+                        debug!("failed to connect to {}: {}", addr, e);
+                    }
+                }
+            }
+
+            if let Some(path) = self.config.address_book_path.as_ref() {
+                let book = self.node.lock().await.address_book.clone();
+                if last_saved.as_ref() != Some(&book) {
+                    match book.save(path) {
+                        Ok(()) => last_saved = Some(book),
+                        Err(e) => debug!("failed to save address book: {}", e),
+                    }
+                }
+            }
+
+            if let Some(path) = self.config.ban_list_path.as_ref() {
+                let bans = {
+                    let mut node = self.node.lock().await;
+                    node.ban_list.sweep(Utc::now().timestamp());
+                    node.ban_list.clone()
+                };
+                if last_saved_bans.as_ref() != Some(&bans) {
+                    match bans.save(path) {
+                        Ok(()) => last_saved_bans = Some(bans),
+                        Err(e) => debug!("failed to save ban list: {}", e),
+                    }
+                }
+            }
+        }
+    }
+
+    // addresses we aren't already talking to, best first
+    async fn connection_candidates(&self, count: usize) -> Vec<SocketAddr> {
+        let connected: Vec<PeerId> = self.peer_ids().await;
+        let node = self.node.lock().await;
+        let mut exclude = vec![self.local_addr];
+        for peer in connected.iter() {
+            if let Ok(addr) = peer.parse() {
+                exclude.push(addr);
+            }
+            if let Some(addr) = node.peers.get(peer).and_then(|peer| peer.listen_addr) {
+                exclude.push(addr);
+            }
+        }
+
+        let now = Utc::now().timestamp();
+        node.address_book
+            .candidates(count + exclude.len(), &exclude, now)
+            .into_iter()
+            .filter(|addr| !node.ban_list.is_banned(&ban_key(&addr.to_string()), now))
+            .take(count)
+            .collect()
+    }
+
+    async fn attach(self: &Arc<Self>, stream: TcpStream, id: PeerId, outbound: bool) {
+        let (mut reader, mut writer) = stream.into_split();
+        let (sender, mut receiver) = mpsc::channel(self.config.outbound_queue_size);
+        self.connections.lock().await.insert(
+            id.clone(),
+            Connection {
+                sender,
+                outbound,
+                readers: Vec::new(),
+            },
+        );
+
+        self.spawn(async move {
+            while let Some(message) = receiver.recv().await {
+                if write_message(&mut writer, &message).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        self.node.lock().await.peer_connected(id.clone(), outbound);
+        self.flush().await;
+
+        // reading and handling happen separately so a peer flooding us
+        // only ever has a bounded number of messages waiting on the node
+        let (inbound, mut inbox) = mpsc::channel(INBOUND_QUEUE_SIZE);
+        let reader_id = id.clone();
+        let reader = self.spawn(async move {
+            loop {
+                match read_message(&mut reader).await {
+                    Ok(message) => {
+                        if inbound.send(message).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        debug!("dropping peer {}: {}", reader_id, e);
+                        break;
+                    }
+                }
+            }
+        });
+
+        let network = self.clone();
+        let handler_id = id.clone();
+        let handler = self.spawn(async move {
+            let id = handler_id;
+            while let Some(message) = inbox.recv().await {
+                network.node.lock().await.handle_message(&id, message).await;
+                network.flush().await;
+            }
+
+            network.connections.lock().await.remove(&id);
+            network.node.lock().await.remove_peer(&id);
+        });
+
+        if let Some(connection) = self.connections.lock().await.get_mut(&id) {
+            connection.readers = vec![reader, handler];
+        }
+    }
+}
+
+pub async fn write_message<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    message: &Message,
+) -> io::Result<()> {
+    let bytes =
+        bincode::serialize(message).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if bytes.len() > MAX_MESSAGE_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message too large",
+        ));
+    }
+    writer.write_u32(bytes.len() as u32).await?;
+    writer.write_all(&bytes).await?;
+    writer.flush().await
+}
+
+pub async fn read_message<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Message> {
+    let length = reader.read_u32().await? as usize;
+    // don't even think about allocating for something this big
+    if length > MAX_MESSAGE_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message too large",
+        ));
+    }
+
+    let mut bytes = vec![0; length];
+    reader.read_exact(&mut bytes).await?;
+    bincode::deserialize(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
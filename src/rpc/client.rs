@@ -0,0 +1,77 @@
+use std::{error::Error, fmt, io, net::SocketAddr};
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tokio::{io::BufReader, net::TcpStream};
+
+use super::{
+    http::{read_response, write_request},
+    message::{Request, Response, RpcError},
+};
+
+#[derive(Debug)]
+pub enum ClientError {
+    Io(io::Error),
+    // the server answered with something other than 200
+    Http(u16),
+    // the server understood us and said no
+    Rpc(RpcError),
+    // the response wasn't what we expected
+    Decode(String),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Io(e) => write!(f, "couldn't reach the node: {}", e),
+            ClientError::Http(status) => write!(f, "the node answered with http {}", status),
+            ClientError::Rpc(e) => write!(f, "{} ({})", e.message, e.code),
+            ClientError::Decode(e) => write!(f, "couldn't make sense of the response: {}", e),
+        }
+    }
+}
+
+impl Error for ClientError {}
+
+impl From<io::Error> for ClientError {
+    fn from(e: io::Error) -> Self {
+        ClientError::Io(e)
+    }
+}
+
+// talks to an RpcServer, a fresh connection for every call
+#[derive(Debug, Clone)]
+pub struct RpcClient {
+    pub addr: SocketAddr,
+}
+
+impl RpcClient {
+    pub fn new(addr: SocketAddr) -> Self {
+        RpcClient { addr }
+    }
+
+    pub async fn call<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<T, ClientError> {
+        let request = Request::new(method, params, Value::from(1));
+        let body = serde_json::to_vec(&request).map_err(|e| ClientError::Decode(e.to_string()))?;
+
+        let stream = TcpStream::connect(self.addr).await?;
+        let (reader, mut writer) = stream.into_split();
+        write_request(&mut writer, &self.addr.to_string(), "/", &body).await?;
+        let response = read_response(&mut BufReader::new(reader)).await?;
+        if response.status != 200 {
+            return Err(ClientError::Http(response.status));
+        }
+
+        let response: Response = serde_json::from_slice(&response.body)
+            .map_err(|e| ClientError::Decode(e.to_string()))?;
+        if let Some(error) = response.error {
+            return Err(ClientError::Rpc(error));
+        }
+        serde_json::from_value(response.result.unwrap_or(Value::Null))
+            .map_err(|e| ClientError::Decode(e.to_string()))
+    }
+}
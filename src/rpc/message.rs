@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::model::{amount::Amount, block::Block, transaction::Transaction};
+
+pub const JSONRPC_VERSION: &str = "2.0";
+
+// error codes from the json-rpc 2.0 spec
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+// ours, the spec leaves -32000 to -32099 for servers to use
+pub const NOT_FOUND: i64 = -32001;
+pub const TRANSACTION_REJECTED: i64 = -32002;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    pub jsonrpc: String,
+    pub method: String,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub params: Value,
+    // requests without an id are notifications and don't get a response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<Value>,
+}
+
+impl Request {
+    pub fn new(method: &str, params: Value, id: Value) -> Self {
+        Request {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            method: method.to_string(),
+            params,
+            id: Some(id),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+    pub jsonrpc: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+    // null when the request was too broken to tell what its id was
+    pub id: Value,
+}
+
+impl Response {
+    pub fn new(id: Value, outcome: Result<Value, RpcError>) -> Self {
+        let (result, error) = match outcome {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        Response {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            result,
+            error,
+            id,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+// what the methods hand back, shared with the client so both sides agree
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BlockResult {
+    pub hash: String,
+    // 1 for the tip, 0 for a block that's no longer on the best chain
+    pub confirmations: u64,
+    pub block: Block,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TipResult {
+    pub height: u64,
+    pub hash: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PendingTransaction {
+    pub txid: String,
+    pub transaction: Transaction,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BalanceResult {
+    pub address: String,
+    pub balance: Amount,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PeerInfo {
+    pub id: String,
+    pub outbound: bool,
+    pub listen_addr: Option<String>,
+    pub misbehaviour: u32,
+}
+
+// sent down a websocket whenever something a client subscribed to happens
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Notification {
+    pub jsonrpc: String,
+    pub method: String,
+    pub params: NotificationParams,
+}
+
+impl Notification {
+    pub fn new(subscription: u64, result: Value) -> Self {
+        Notification {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            method: "subscription".to_string(),
+            params: NotificationParams {
+                subscription,
+                result,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NotificationParams {
+    pub subscription: u64,
+    pub result: Value,
+}
+
+// what a websocket client can subscribe to, e.g. {"topic": "transaction", "txid": "..."}
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "topic", rename_all = "snake_case")]
+pub enum Topic {
+    // every block that becomes our tip, as a TipResult
+    NewTips,
+    // every time we switch branches, as a ReorgResult
+    Reorgs,
+    // every transaction accepted into the mempool, as a PendingTransaction
+    Mempool,
+    // how many confirmations a transaction has whenever that changes, as a ConfirmationResult
+    Transaction { txid: String },
+    // blocks connecting or disconnecting transactions that touch an address, as AddressActivity
+    Address { address: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReorgResult {
+    pub depth: u64,
+    pub tip: TipResult,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConfirmationResult {
+    pub txid: String,
+    // 0 once a block holding it gets disconnected
+    pub confirmations: u64,
+    pub block_hash: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AddressActivity {
+    pub address: String,
+    pub txid: String,
+    pub block_hash: String,
+    pub height: u64,
+    // false when the block holding it was disconnected
+    pub connected: bool,
+}
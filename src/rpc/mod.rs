@@ -0,0 +1,5 @@
+pub mod client;
+pub mod http;
+pub mod message;
+pub mod server;
+pub mod subscriptions;
@@ -0,0 +1,446 @@
+use std::{io, net::SocketAddr, sync::Arc, time::Duration};
+
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::Value;
+use tokio::{
+    io::BufReader,
+    net::{TcpListener, TcpStream},
+    task::{self, AbortHandle},
+    time,
+};
+use tracing::debug;
+
+use crate::{
+    mine::{fill_block, proof_of_work},
+    model::{
+        block::Block,
+        events::{EventError, EventSubscription, NodeEvent},
+        node::Node,
+        transaction::Transaction,
+    },
+    net::tcp::TcpNetwork,
+};
+
+use super::{
+    http::{read_request, write_response, HttpResponse},
+    message::{
+        BalanceResult, BlockResult, PeerInfo, PendingTransaction, Request, Response, RpcError,
+        TipResult, INVALID_PARAMS, INVALID_REQUEST, JSONRPC_VERSION, METHOD_NOT_FOUND, NOT_FOUND,
+        PARSE_ERROR, TRANSACTION_REJECTED,
+    },
+};
+
+// how long the miner waits before looking at the mempool again when it's empty
+// and nothing has told it about a new transaction
+pub const MINING_INTERVAL: Duration = Duration::from_millis(100);
+
+// how often a miner with a reward address mines a block even when there's nothing to put in it
+pub const EMPTY_BLOCK_INTERVAL: Duration = Duration::from_secs(1);
+
+// how long a client gets to send its whole request
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Deserialize)]
+struct HeightParams {
+    height: u64,
+}
+
+#[derive(Deserialize)]
+struct HashParams {
+    hash: String,
+}
+
+#[derive(Deserialize)]
+struct AddressParams {
+    address: String,
+}
+
+#[derive(Deserialize)]
+struct MiningParams {
+    #[serde(default)]
+    address: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TransactionParams {
+    transaction: Transaction,
+}
+
+// json-rpc 2.0 over http for controlling a node running on a TcpNetwork
+pub struct RpcServer {
+    pub network: Arc<TcpNetwork>,
+    pub local_addr: SocketAddr,
+    mining: std::sync::Mutex<Option<AbortHandle>>,
+    tasks: std::sync::Mutex<Vec<AbortHandle>>,
+}
+
+impl RpcServer {
+    pub async fn start(network: Arc<TcpNetwork>, listen_addr: SocketAddr) -> io::Result<Arc<Self>> {
+        let listener = TcpListener::bind(listen_addr).await?;
+        let local_addr = listener.local_addr()?;
+
+        let server = Arc::new(RpcServer {
+            network,
+            local_addr,
+            mining: std::sync::Mutex::new(None),
+            tasks: std::sync::Mutex::new(Vec::new()),
+        });
+
+        let accepting = server.clone();
+        let handle = tokio::spawn(async move { accepting.accept_connections(listener).await });
+        server.track(handle.abort_handle());
+        Ok(server)
+    }
+
+    // stop listening and mining, the network is left running
+    pub fn shutdown(&self) {
+        self.stop_mining();
+        let tasks = std::mem::take(&mut *self.tasks.lock().expect("poisoned lock"));
+        for task in tasks {
+            task.abort();
+        }
+    }
+
+    pub fn is_mining(&self) -> bool {
+        self.mining
+            .lock()
+            .expect("poisoned lock")
+            .as_ref()
+            .is_some_and(|miner| !miner.is_finished())
+    }
+
+    // returns false if we were already mining. without a reward address the
+    // fees are left unclaimed and there's no point mining empty blocks
+    pub fn start_mining(&self, reward_address: Option<String>) -> bool {
+        let mut mining = self.mining.lock().expect("poisoned lock");
+        if mining.as_ref().is_some_and(|miner| !miner.is_finished()) {
+            return false;
+        }
+
+        let network = self.network.clone();
+        *mining = Some(tokio::spawn(mine_blocks(network, reward_address)).abort_handle());
+        true
+    }
+
+    // returns false if we weren't mining
+    pub fn stop_mining(&self) -> bool {
+        match self.mining.lock().expect("poisoned lock").take() {
+            Some(miner) => {
+                miner.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // a json-rpc body in, the body to send back out. None when
+    // everything in it was a notification so there's nothing to say
+    pub async fn handle_body(&self, body: &[u8]) -> Option<Value> {
+        let value: Value = match serde_json::from_slice(body) {
+            Ok(value) => value,
+            Err(e) => return Some(error_response(Value::Null, PARSE_ERROR, e.to_string())),
+        };
+
+        match value {
+            Value::Array(batch) => {
+                if batch.is_empty() {
+                    return Some(error_response(Value::Null, INVALID_REQUEST, "empty batch"));
+                }
+
+                let mut responses = Vec::new();
+                for item in batch {
+                    if let Some(response) = self.handle_value(item).await {
+                        responses.push(response);
+                    }
+                }
+                (!responses.is_empty()).then_some(Value::Array(responses))
+            }
+            value => self.handle_value(value).await,
+        }
+    }
+
+    async fn handle_value(&self, value: Value) -> Option<Value> {
+        let request: Request = match serde_json::from_value(value) {
+            Ok(request) => request,
+            Err(e) => return Some(error_response(Value::Null, INVALID_REQUEST, e.to_string())),
+        };
+        if request.jsonrpc != JSONRPC_VERSION {
+            let id = request.id.unwrap_or(Value::Null);
+            return Some(error_response(
+                id,
+                INVALID_REQUEST,
+                "jsonrpc must be \"2.0\"",
+            ));
+        }
+
+        let outcome = self.call(&request.method, request.params).await;
+        let id = request.id?;
+        Some(to_value(&Response::new(id, outcome)))
+    }
+
+    pub async fn call(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "get_block_by_height" => {
+                let params: HeightParams = parse_params(params)?;
+                let node = self.network.node.lock().await;
+                let block = node.blockchain.chain.get(params.height as usize);
+                Ok(to_value(&block_result(&node, block)?))
+            }
+            "get_block_by_hash" => {
+                let params: HashParams = parse_params(params)?;
+                let node = self.network.node.lock().await;
+                let block = node
+                    .blockchain
+                    .get_block(&params.hash)
+                    .or_else(|| node.side_blocks.get(&params.hash));
+                Ok(to_value(&block_result(&node, block)?))
+            }
+            "get_best_tip" => {
+                let node = self.network.node.lock().await;
+                let tip = node.blockchain.tip();
+                Ok(to_value(&TipResult {
+                    height: tip.index,
+                    hash: tip.hash(),
+                }))
+            }
+            "submit_transaction" => {
+                let params: TransactionParams = parse_params(params)?;
+                let txid = params.transaction.txid();
+                let result = self
+                    .network
+                    .node
+                    .lock()
+                    .await
+                    .submit_transaction(params.transaction)
+                    .await;
+                match result {
+                    Ok(()) => {
+                        self.network.flush().await;
+                        Ok(Value::String(txid))
+                    }
+                    Err(e) => Err(RpcError::new(TRANSACTION_REJECTED, format!("{:?}", e))),
+                }
+            }
+            "get_mempool" => {
+                let node = self.network.node.lock().await;
+                let pending: Vec<PendingTransaction> = node
+                    .mempool
+                    .transactions()
+                    .into_iter()
+                    .map(|transaction| PendingTransaction {
+                        txid: transaction.txid(),
+                        transaction,
+                    })
+                    .collect();
+                Ok(to_value(&pending))
+            }
+            "get_next_nonce" => {
+                let params: AddressParams = parse_params(params)?;
+                let node = self.network.node.lock().await;
+                let nonce = node.mempool.next_nonce(&params.address, &node.blockchain);
+                Ok(Value::from(nonce))
+            }
+            "get_balance" => {
+                let params: AddressParams = parse_params(params)?;
+                let node = self.network.node.lock().await;
+                Ok(to_value(&BalanceResult {
+                    balance: node.blockchain.balance_of(&params.address),
+                    address: params.address,
+                }))
+            }
+            "get_peer_info" => {
+                let node = self.network.node.lock().await;
+                let mut peers: Vec<PeerInfo> = node
+                    .peers
+                    .values()
+                    .map(|peer| PeerInfo {
+                        id: peer.id.clone(),
+                        outbound: peer.outbound,
+                        listen_addr: peer.listen_addr.map(|addr| addr.to_string()),
+                        misbehaviour: node.misbehaviour_of(&peer.id),
+                    })
+                    .collect();
+                peers.sort_by(|a, b| a.id.cmp(&b.id));
+                Ok(to_value(&peers))
+            }
+            "start_mining" => {
+                let params: MiningParams = match params {
+                    Value::Null => MiningParams { address: None },
+                    params => parse_params(params)?,
+                };
+                Ok(Value::Bool(self.start_mining(params.address)))
+            }
+            "stop_mining" => Ok(Value::Bool(self.stop_mining())),
+            "is_mining" => Ok(Value::Bool(self.is_mining())),
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("no method called {}", method),
+            )),
+        }
+    }
+
+    fn track(&self, task: AbortHandle) {
+        let mut tasks = self.tasks.lock().expect("poisoned lock");
+        tasks.retain(|task| !task.is_finished());
+        tasks.push(task);
+    }
+
+    async fn accept_connections(self: Arc<Self>, listener: TcpListener) {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let server = self.clone();
+                    let handle = tokio::spawn(async move {
+                        if let Err(e) = server.serve(stream).await {
+                            debug!("rpc connection failed: {}", e);
+                        }
+                    });
+                    self.track(handle.abort_handle());
+                }
+                Err(e) => debug!("failed to accept rpc connection: {}", e),
+            }
+        }
+    }
+
+    async fn serve(&self, stream: TcpStream) -> io::Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        let request = match time::timeout(REQUEST_TIMEOUT, read_request(&mut reader)).await {
+            Ok(Ok(request)) => request,
+            Ok(Err(e)) => {
+                let response = HttpResponse {
+                    status: 400,
+                    body: Vec::new(),
+                };
+                write_response(&mut writer, &response).await?;
+                return Err(e);
+            }
+            Err(_) => return Err(io::ErrorKind::TimedOut.into()),
+        };
+
+        let response = if request.method != "POST" {
+            HttpResponse {
+                status: 405,
+                body: Vec::new(),
+            }
+        } else {
+            match self.handle_body(&request.body).await {
+                Some(body) => HttpResponse {
+                    status: 200,
+                    body: serde_json::to_vec(&body).map_err(io::Error::other)?,
+                },
+                None => HttpResponse {
+                    status: 204,
+                    body: Vec::new(),
+                },
+            }
+        };
+        write_response(&mut writer, &response).await
+    }
+}
+
+// keeps mining blocks out of whatever is in the mempool until it's aborted
+// with a reward address every block pays it, and blocks with nothing but
+// the reward in them get mined every EMPTY_BLOCK_INTERVAL
+async fn mine_blocks(network: Arc<TcpNetwork>, reward_address: Option<String>) {
+    let mut events = network.node.lock().await.events.subscribe();
+    let wait = match reward_address {
+        Some(_) => EMPTY_BLOCK_INTERVAL,
+        None => MINING_INTERVAL,
+    };
+    loop {
+        let idle = block_candidates(&*network.node.lock().await).is_empty();
+        if idle {
+            let woken = wait_for_transactions(&mut events, wait).await;
+            if woken || reward_address.is_none() {
+                continue;
+            }
+        }
+
+        let (mut block, target_hash_prefix) = {
+            let node = network.node.lock().await;
+            let candidates = block_candidates(&node);
+            (
+                fill_block(&node.blockchain, candidates, reward_address.as_deref()),
+                node.blockchain.target_hash_prefix.clone(),
+            )
+        };
+
+        // the node stays free to handle peers while we grind
+        let solved = task::spawn_blocking(move || {
+            proof_of_work(&mut block, &target_hash_prefix);
+            block
+        })
+        .await;
+        let block = match solved {
+            Ok(block) => block,
+            Err(e) => {
+                debug!("mining failed: {}", e);
+                continue;
+            }
+        };
+
+        // somebody else may have beaten us to it, then we just start over
+        let result = network.node.lock().await.submit_mined_block(block).await;
+        match result {
+            Ok(()) => network.flush().await,
+            Err(e) => {
+                debug!("mined block was rejected: {:?}", e);
+                time::sleep(MINING_INTERVAL).await;
+            }
+        }
+    }
+}
+
+// what could go in a block on the tip right now, transactions that are
+// still locked wait for a later one
+fn block_candidates(node: &Node) -> Vec<Transaction> {
+    node.mempool.block_candidates(
+        node.blockchain.tip().index + 1,
+        node.blockchain.median_time_past(),
+    )
+}
+
+// true once a transaction shows up, false if wait passes first.
+// falling behind on events counts too, the mempool gets checked either way
+async fn wait_for_transactions(events: &mut EventSubscription, wait: Duration) -> bool {
+    time::timeout(wait, async {
+        loop {
+            match events.recv().await {
+                Ok(NodeEvent::TransactionAccepted(_)) | Err(EventError::Lagged(_)) => return,
+                Ok(_) => continue,
+                // the node went away, the timeout will take care of it
+                Err(EventError::Closed) => std::future::pending::<()>().await,
+            }
+        }
+    })
+    .await
+    .is_ok()
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn block_result(node: &Node, block: Option<&Block>) -> Result<BlockResult, RpcError> {
+    let block = block.ok_or_else(|| RpcError::new(NOT_FOUND, "block not found"))?;
+    let hash = block.hash();
+    let confirmations = match node.blockchain.height_of(&hash) {
+        Some(height) => node.blockchain.tip().index - height + 1,
+        None => 0,
+    };
+    Ok(BlockResult {
+        hash,
+        confirmations,
+        block: block.clone(),
+    })
+}
+
+fn to_value<T: serde::Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+fn error_response(id: Value, code: i64, message: impl Into<String>) -> Value {
+    to_value(&Response::new(id, Err(RpcError::new(code, message))))
+}
@@ -0,0 +1,355 @@
+use std::{collections::HashMap, io, net::SocketAddr, sync::Arc};
+
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    task::AbortHandle,
+};
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+use tracing::debug;
+
+use crate::{
+    model::{
+        address_index::touched_addresses,
+        block::Block,
+        events::{EventError, NodeEvent},
+        node::Node,
+    },
+    net::tcp::TcpNetwork,
+};
+
+use super::message::{
+    AddressActivity, ConfirmationResult, Notification, PendingTransaction, ReorgResult, Request,
+    Response, RpcError, TipResult, Topic, INVALID_PARAMS, INVALID_REQUEST, JSONRPC_VERSION,
+    METHOD_NOT_FOUND, PARSE_ERROR,
+};
+
+// the most subscriptions one websocket can have open at once
+pub const MAX_SUBSCRIPTIONS_PER_CONNECTION: usize = 100;
+
+#[derive(Deserialize)]
+struct UnsubscribeParams {
+    subscription: u64,
+}
+
+struct Subscription {
+    topic: Topic,
+    // the last confirmation count we told them about, for transaction topics
+    confirmations: Option<ConfirmationResult>,
+}
+
+// websocket clients subscribe here instead of polling the rpc server. requests are
+// json-rpc "subscribe" with a Topic as params, which answers with a subscription id,
+// and "unsubscribe" with that id. events come back as "subscription" notifications
+pub struct SubscriptionServer {
+    pub network: Arc<TcpNetwork>,
+    pub local_addr: SocketAddr,
+    tasks: std::sync::Mutex<Vec<AbortHandle>>,
+}
+
+impl SubscriptionServer {
+    pub async fn start(network: Arc<TcpNetwork>, listen_addr: SocketAddr) -> io::Result<Arc<Self>> {
+        let listener = TcpListener::bind(listen_addr).await?;
+        let local_addr = listener.local_addr()?;
+
+        let server = Arc::new(SubscriptionServer {
+            network,
+            local_addr,
+            tasks: std::sync::Mutex::new(Vec::new()),
+        });
+
+        let accepting = server.clone();
+        let handle = tokio::spawn(async move { accepting.accept_connections(listener).await });
+        server.track(handle.abort_handle());
+        Ok(server)
+    }
+
+    // hang up on every client and stop listening
+    pub fn shutdown(&self) {
+        let tasks = std::mem::take(&mut *self.tasks.lock().expect("poisoned lock"));
+        for task in tasks {
+            task.abort();
+        }
+    }
+
+    fn track(&self, task: AbortHandle) {
+        let mut tasks = self.tasks.lock().expect("poisoned lock");
+        tasks.retain(|task| !task.is_finished());
+        tasks.push(task);
+    }
+
+    async fn accept_connections(self: Arc<Self>, listener: TcpListener) {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let server = self.clone();
+                    let handle = tokio::spawn(async move {
+                        if let Err(e) = server.serve(stream).await {
+                            debug!("websocket connection failed: {}", e);
+                        }
+                    });
+                    self.track(handle.abort_handle());
+                }
+                Err(e) => debug!("failed to accept websocket connection: {}", e),
+            }
+        }
+    }
+
+    async fn serve(&self, stream: TcpStream) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+        let socket = accept_async(stream).await?;
+        let (mut sink, mut incoming) = socket.split();
+        // subscribed before the first request so nothing slips through in between
+        let mut events = self.network.node.lock().await.events.subscribe();
+        let mut subscriptions: HashMap<u64, Subscription> = HashMap::new();
+        let mut next_id = 1;
+
+        loop {
+            let outgoing = tokio::select! {
+                message = incoming.next() => match message {
+                    Some(Ok(Message::Text(text))) => {
+                        self.handle_request(text.as_str(), &mut subscriptions, &mut next_id).await
+                    }
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => return Err(e),
+                },
+                event = events.recv() => match event {
+                    Ok(event) => self.notifications(&event, &mut subscriptions).await,
+                    Err(EventError::Lagged(missed)) => {
+                        debug!("websocket client missed {} events", missed);
+                        self.catch_up(&mut subscriptions).await
+                    }
+                    Err(EventError::Closed) => break,
+                },
+            };
+
+            for message in outgoing {
+                sink.send(message).await?;
+            }
+        }
+        Ok(())
+    }
+
+    // the response to a request, followed by anything the client should know right away
+    async fn handle_request(
+        &self,
+        text: &str,
+        subscriptions: &mut HashMap<u64, Subscription>,
+        next_id: &mut u64,
+    ) -> Vec<Message> {
+        let request: Request = match serde_json::from_str::<Value>(text) {
+            Ok(value) => match serde_json::from_value(value) {
+                Ok(request) => request,
+                Err(e) => return vec![error(Value::Null, INVALID_REQUEST, e.to_string())],
+            },
+            Err(e) => return vec![error(Value::Null, PARSE_ERROR, e.to_string())],
+        };
+        let id = request.id.clone().unwrap_or(Value::Null);
+        if request.jsonrpc != JSONRPC_VERSION {
+            return vec![error(id, INVALID_REQUEST, "jsonrpc must be \"2.0\"")];
+        }
+
+        match request.method.as_str() {
+            "subscribe" => {
+                let topic: Topic = match serde_json::from_value(request.params) {
+                    Ok(topic) => topic,
+                    Err(e) => return vec![error(id, INVALID_PARAMS, e.to_string())],
+                };
+                if subscriptions.len() >= MAX_SUBSCRIPTIONS_PER_CONNECTION {
+                    return vec![error(id, INVALID_REQUEST, "too many subscriptions")];
+                }
+
+                let subscription = *next_id;
+                *next_id += 1;
+                let mut messages = vec![response(id, Ok(Value::from(subscription)))];
+
+                // where a transaction stands now, later notifications are changes to this
+                let mut confirmations = None;
+                if let Topic::Transaction { txid } = &topic {
+                    let status = confirmation(&*self.network.node.lock().await, txid);
+                    messages.push(notification(subscription, &status));
+                    confirmations = Some(status);
+                }
+                subscriptions.insert(
+                    subscription,
+                    Subscription {
+                        topic,
+                        confirmations,
+                    },
+                );
+                messages
+            }
+            "unsubscribe" => match serde_json::from_value::<UnsubscribeParams>(request.params) {
+                Ok(params) => {
+                    let removed = subscriptions.remove(&params.subscription).is_some();
+                    vec![response(id, Ok(Value::Bool(removed)))]
+                }
+                Err(e) => vec![error(id, INVALID_PARAMS, e.to_string())],
+            },
+            method => vec![error(
+                id,
+                METHOD_NOT_FOUND,
+                format!("no method called {}", method),
+            )],
+        }
+    }
+
+    // we fell behind on events, tell subscribers where things stand now for anything
+    // that can be read off the node. address activity in the missed blocks is lost
+    async fn catch_up(&self, subscriptions: &mut HashMap<u64, Subscription>) -> Vec<Message> {
+        let mut ids: Vec<u64> = subscriptions.keys().copied().collect();
+        ids.sort();
+
+        let node = self.network.node.lock().await;
+        let mut messages = Vec::new();
+        for id in ids {
+            let subscription = match subscriptions.get_mut(&id) {
+                Some(subscription) => subscription,
+                None => continue,
+            };
+
+            match &subscription.topic {
+                Topic::NewTips => {
+                    let tip = node.blockchain.tip();
+                    let tip = TipResult {
+                        height: tip.index,
+                        hash: tip.hash(),
+                    };
+                    messages.push(notification(id, &tip));
+                }
+                Topic::Transaction { txid } => {
+                    let status = confirmation(&node, txid);
+                    if subscription.confirmations.as_ref() != Some(&status) {
+                        messages.push(notification(id, &status));
+                        subscription.confirmations = Some(status);
+                    }
+                }
+                _ => {}
+            }
+        }
+        messages
+    }
+
+    async fn notifications(
+        &self,
+        event: &NodeEvent,
+        subscriptions: &mut HashMap<u64, Subscription>,
+    ) -> Vec<Message> {
+        let mut ids: Vec<u64> = subscriptions.keys().copied().collect();
+        ids.sort();
+
+        let mut messages = Vec::new();
+        for id in ids {
+            let subscription = match subscriptions.get_mut(&id) {
+                Some(subscription) => subscription,
+                None => continue,
+            };
+
+            match (&subscription.topic, event) {
+                (Topic::NewTips, NodeEvent::BlockConnected(block)) => {
+                    let tip = TipResult {
+                        height: block.index,
+                        hash: block.hash(),
+                    };
+                    messages.push(notification(id, &tip));
+                }
+                (Topic::Reorgs, NodeEvent::ChainReorganized { depth }) => {
+                    let node = self.network.node.lock().await;
+                    let tip = node.blockchain.tip();
+                    let reorg = ReorgResult {
+                        depth: *depth,
+                        tip: TipResult {
+                            height: tip.index,
+                            hash: tip.hash(),
+                        },
+                    };
+                    messages.push(notification(id, &reorg));
+                }
+                (Topic::Mempool, NodeEvent::TransactionAccepted(transaction)) => {
+                    let pending = PendingTransaction {
+                        txid: transaction.txid(),
+                        transaction: transaction.clone(),
+                    };
+                    messages.push(notification(id, &pending));
+                }
+                (
+                    Topic::Transaction { txid },
+                    NodeEvent::BlockConnected(_) | NodeEvent::BlockDisconnected(_),
+                ) => {
+                    // every block changes the count, but by the time we see the event the
+                    // chain may have moved on again, so we only say something if it's different
+                    let status = confirmation(&*self.network.node.lock().await, txid);
+                    if subscription.confirmations.as_ref() != Some(&status) {
+                        messages.push(notification(id, &status));
+                        subscription.confirmations = Some(status);
+                    }
+                }
+                (Topic::Address { address }, NodeEvent::BlockConnected(block)) => {
+                    messages.extend(address_activity(id, address, block, true));
+                }
+                (Topic::Address { address }, NodeEvent::BlockDisconnected(block)) => {
+                    messages.extend(address_activity(id, address, block, false));
+                }
+                _ => {}
+            }
+        }
+        messages
+    }
+}
+
+fn confirmation(node: &Node, txid: &str) -> ConfirmationResult {
+    match node.blockchain.get_transaction(txid) {
+        Some(confirmed) => ConfirmationResult {
+            txid: txid.to_string(),
+            confirmations: confirmed.confirmations,
+            block_hash: Some(confirmed.block_hash),
+        },
+        None => ConfirmationResult {
+            txid: txid.to_string(),
+            confirmations: 0,
+            block_hash: None,
+        },
+    }
+}
+
+fn address_activity(id: u64, address: &str, block: &Block, connected: bool) -> Vec<Message> {
+    let block_hash = block.hash();
+    block
+        .transactions
+        .iter()
+        .filter(|transaction| {
+            touched_addresses(transaction)
+                .iter()
+                .any(|(touched, _, _)| touched == address)
+        })
+        .map(|transaction| {
+            let activity = AddressActivity {
+                address: address.to_string(),
+                txid: transaction.txid(),
+                block_hash: block_hash.clone(),
+                height: block.index,
+                connected,
+            };
+            notification(id, &activity)
+        })
+        .collect()
+}
+
+fn text<T: Serialize>(value: &T) -> Message {
+    Message::text(serde_json::to_string(value).unwrap_or_default())
+}
+
+fn response(id: Value, outcome: Result<Value, RpcError>) -> Message {
+    text(&Response::new(id, outcome))
+}
+
+fn error(id: Value, code: i64, message: impl Into<String>) -> Message {
+    response(id, Err(RpcError::new(code, message)))
+}
+
+fn notification<T: Serialize>(subscription: u64, result: &T) -> Message {
+    let result = serde_json::to_value(result).unwrap_or(Value::Null);
+    text(&Notification::new(subscription, result))
+}
@@ -0,0 +1,321 @@
+use std::{error::Error, fmt};
+
+use chrono::Utc;
+use ed25519_dalek::SigningKey;
+
+use super::keystore::{Keystore, WalletError};
+use crate::model::{
+    amount::Amount,
+    blockchain::Blockchain,
+    chain_spec::LedgerModel,
+    mempool::Mempool,
+    multisig::MultisigPolicy,
+    script::ScriptSpend,
+    signature::TxSignature,
+    transaction::{LockTime, OutPoint, Transaction, TxOutput},
+};
+
+// base units per 1000 bytes, the same unit Transaction::fee_rate uses
+pub const DEFAULT_FEE_RATE: u64 = 1000;
+
+#[derive(Debug)]
+pub enum BuildError {
+    NoRecipients,
+    // account chains pay a single receiver per transaction
+    TooManyRecipients,
+    // paying somebody nothing
+    InvalidAmount,
+    AmountOverflow,
+    // what the payment and its fee come to against what the sender has to spend
+    InsufficientFunds { needed: Amount, available: Amount },
+    Wallet(WalletError),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::NoRecipients => write!(f, "nobody to pay"),
+            BuildError::TooManyRecipients => {
+                write!(f, "this chain only pays one receiver per transaction")
+            }
+            BuildError::InvalidAmount => write!(f, "can't pay nothing"),
+            BuildError::AmountOverflow => {
+                write!(f, "the amounts add up to more than there could ever be")
+            }
+            BuildError::InsufficientFunds { needed, available } => write!(
+                f,
+                "insufficient funds, {} base units needed but only {} available",
+                needed.base_units(),
+                available.base_units()
+            ),
+            BuildError::Wallet(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for BuildError {}
+
+impl From<WalletError> for BuildError {
+    fn from(e: WalletError) -> Self {
+        BuildError::Wallet(e)
+    }
+}
+
+// puts together a payment from sender, picking what funds it and what it
+// pays in fees. nothing pending in the mempool gets spent twice
+pub struct TransactionBuilder<'a> {
+    blockchain: &'a Blockchain,
+    mempool: Option<&'a Mempool>,
+    sender: String,
+    recipients: Vec<(String, Amount)>,
+    fee_rate: u64,
+    change_address: Option<String>,
+    timestamp: i64,
+    multisig: Option<MultisigPolicy>,
+    lock_time: Option<LockTime>,
+    script: Option<ScriptSpend>,
+}
+
+impl<'a> TransactionBuilder<'a> {
+    pub fn new(blockchain: &'a Blockchain, sender: &str) -> Self {
+        TransactionBuilder {
+            blockchain,
+            mempool: None,
+            sender: sender.to_string(),
+            recipients: Vec::new(),
+            fee_rate: DEFAULT_FEE_RATE,
+            change_address: None,
+            timestamp: Utc::now().timestamp(),
+            multisig: None,
+            lock_time: None,
+            script: None,
+        }
+    }
+
+    // pays from the policy's address, leaving the transaction for its keys to sign
+    pub fn multisig(blockchain: &'a Blockchain, policy: &MultisigPolicy) -> Self {
+        TransactionBuilder {
+            multisig: Some(policy.clone()),
+            ..Self::new(blockchain, &policy.address())
+        }
+    }
+
+    // pays from the script's address, the witness is whatever the script needs on
+    // the stack besides signatures. any keys it checks still have to sign
+    pub fn script(blockchain: &'a Blockchain, spend: &ScriptSpend) -> Self {
+        TransactionBuilder {
+            script: Some(spend.clone()),
+            ..Self::new(blockchain, &spend.script.address())
+        }
+    }
+
+    // leaves alone whatever the sender already has pending
+    pub fn with_mempool(mut self, mempool: &'a Mempool) -> Self {
+        self.mempool = Some(mempool);
+        self
+    }
+
+    pub fn pay(mut self, receiver: &str, amount: Amount) -> Self {
+        self.recipients.push((receiver.to_string(), amount));
+        self
+    }
+
+    pub fn with_fee_rate(mut self, fee_rate: u64) -> Self {
+        self.fee_rate = fee_rate;
+        self
+    }
+
+    // utxo change goes back to the sender unless told otherwise
+    pub fn with_change_address(mut self, change_address: &str) -> Self {
+        self.change_address = Some(change_address.to_string());
+        self
+    }
+
+    pub fn with_timestamp(mut self, timestamp: i64) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    // can't be mined before then, it waits in the mempool until it can
+    pub fn with_lock_time(mut self, lock_time: LockTime) -> Self {
+        self.lock_time = Some(lock_time);
+        self
+    }
+
+    // ready for the keystore's account to sign
+    pub fn build(&self) -> Result<Transaction, BuildError> {
+        if self.recipients.is_empty() {
+            return Err(BuildError::NoRecipients);
+        }
+        if self.recipients.iter().any(|(_, amount)| amount.is_zero()) {
+            return Err(BuildError::InvalidAmount);
+        }
+        let sending = Amount::checked_sum(self.recipients.iter().map(|(_, amount)| *amount))
+            .ok_or(BuildError::AmountOverflow)?;
+
+        match self.blockchain.ledger {
+            LedgerModel::Account => self.build_account(sending),
+            LedgerModel::Utxo => self.build_utxo(sending),
+        }
+    }
+
+    pub fn build_signed(&self, key: &SigningKey) -> Result<Transaction, BuildError> {
+        let mut transaction = self.build()?;
+        transaction.sign(key);
+        Ok(transaction)
+    }
+
+    // account is the keystore account's name or address
+    pub fn build_with_keystore(
+        &self,
+        keystore: &Keystore,
+        account: &str,
+    ) -> Result<Transaction, BuildError> {
+        let mut transaction = self.build()?;
+        keystore.sign(account, &mut transaction)?;
+        Ok(transaction)
+    }
+
+    fn build_account(&self, sending: Amount) -> Result<Transaction, BuildError> {
+        let [(receiver, amount)] = self.recipients.as_slice() else {
+            return Err(BuildError::TooManyRecipients);
+        };
+
+        let (nonce, pending) = match self.mempool {
+            Some(mempool) => (
+                mempool.next_nonce(&self.sender, self.blockchain),
+                mempool.spending(&self.sender),
+            ),
+            None => (self.blockchain.next_nonce(&self.sender), Amount::ZERO),
+        };
+        let mut transaction = Transaction {
+            sender: self.sender.clone(),
+            receiver: receiver.clone(),
+            amount: *amount,
+            fee: Amount::ZERO,
+            nonce,
+            timestamp: self.timestamp,
+            multisig: self.multisig.clone(),
+            lock_time: self.lock_time,
+            script: self.script.clone(),
+            ..Transaction::default()
+        };
+        transaction.fee = self.fee_for(&transaction);
+
+        let needed = sending
+            .checked_add(transaction.fee)
+            .ok_or(BuildError::AmountOverflow)?;
+        let available = self
+            .blockchain
+            .balance_of(&self.sender)
+            .saturating_sub(pending);
+        if available < needed {
+            return Err(BuildError::InsufficientFunds { needed, available });
+        }
+        Ok(transaction)
+    }
+
+    // largest outputs first, until they cover the payment and the fee for
+    // however many inputs that took. whatever is left over comes back as change
+    fn build_utxo(&self, sending: Amount) -> Result<Transaction, BuildError> {
+        let mut candidates: Vec<(OutPoint, TxOutput)> = self
+            .blockchain
+            .unspent_outputs(&self.sender)
+            .into_iter()
+            .filter(|(outpoint, _)| {
+                !self
+                    .mempool
+                    .is_some_and(|mempool| mempool.is_spent(outpoint))
+            })
+            .collect();
+        candidates.sort_by(|(a_outpoint, a), (b_outpoint, b)| {
+            b.amount.cmp(&a.amount).then(a_outpoint.cmp(b_outpoint))
+        });
+
+        let payments: Vec<TxOutput> = self
+            .recipients
+            .iter()
+            .map(|(receiver, amount)| TxOutput {
+                receiver: receiver.clone(),
+                amount: *amount,
+            })
+            .collect();
+        let change_output = TxOutput {
+            receiver: self
+                .change_address
+                .clone()
+                .unwrap_or_else(|| self.sender.clone()),
+            amount: Amount::ZERO,
+        };
+        let mut transaction = Transaction {
+            sender: self.sender.clone(),
+            receiver: "".to_string(),
+            amount: Amount::ZERO,
+            fee: Amount::ZERO,
+            nonce: 0,
+            timestamp: self.timestamp,
+            outputs: payments.clone(),
+            multisig: self.multisig.clone(),
+            lock_time: self.lock_time,
+            script: self.script.clone(),
+            ..Transaction::default()
+        };
+
+        let mut available = Amount::ZERO;
+        let mut needed = sending
+            .checked_add(self.fee_for(&transaction))
+            .ok_or(BuildError::AmountOverflow)?;
+        for (outpoint, output) in candidates {
+            transaction.inputs.push(outpoint);
+            available = available
+                .checked_add(output.amount)
+                .ok_or(BuildError::AmountOverflow)?;
+
+            // with change if there's any worth having
+            transaction.outputs = payments.clone();
+            transaction.outputs.push(change_output.clone());
+            let fee = self.fee_for(&transaction);
+            let change = available
+                .checked_sub(sending)
+                .and_then(|left| left.checked_sub(fee));
+            if let Some(change) = change.filter(|change| !change.is_zero()) {
+                transaction.outputs.last_mut().expect("just pushed").amount = change;
+                transaction.fee = fee;
+                return Ok(transaction);
+            }
+
+            // otherwise the leftovers all go to the miner
+            transaction.outputs = payments.clone();
+            let fee = self.fee_for(&transaction);
+            needed = sending.checked_add(fee).ok_or(BuildError::AmountOverflow)?;
+            if available >= needed {
+                transaction.fee = available.saturating_sub(sending);
+                return Ok(transaction);
+            }
+        }
+
+        Err(BuildError::InsufficientFunds { needed, available })
+    }
+
+    // the fee rate applied to the size the transaction will be once the sender signs it,
+    // once enough of a multisig sender's keys have or once every key a script checks has
+    fn fee_for(&self, transaction: &Transaction) -> Amount {
+        let signers = match (&self.multisig, &self.script) {
+            (Some(policy), _) => policy.threshold as usize,
+            (None, Some(spend)) => spend.script.public_keys().len(),
+            (None, None) => 1,
+        };
+        let mut signed = transaction.clone();
+        signed.signatures = vec![placeholder_signature(); signers];
+        let fee = (signed.size() as u128 * self.fee_rate as u128).div_ceil(1000);
+        Amount::new(u64::try_from(fee).unwrap_or(u64::MAX))
+    }
+}
+
+// the same size as a real signature
+fn placeholder_signature() -> TxSignature {
+    TxSignature {
+        public_key: "0".repeat(64),
+        signature: "0".repeat(128),
+    }
+}
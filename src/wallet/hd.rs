@@ -0,0 +1,244 @@
+use std::{collections::HashSet, fmt};
+
+use bip39::Mnemonic;
+use ed25519_dalek::SigningKey;
+use hmac::{Hmac, Mac};
+use rand::{rngs::OsRng, RngCore};
+use sha2::Sha512;
+
+use super::keystore::WalletError;
+use crate::model::{
+    address_index::{touched_addresses, HistoryEntry},
+    amount::Amount,
+    blockchain::Blockchain,
+    signature::key_address,
+};
+
+// how many unused addresses in a row a scan looks past before deciding
+// there are no more, same as most bitcoin wallets
+pub const DEFAULT_GAP_LIMIT: u32 = 20;
+
+// keys live at m/44'/COIN_TYPE'/0'/0'/index', every level hardened since
+// ed25519 can't do anything else
+pub const COIN_TYPE: u32 = 7878;
+
+const HARDENED: u32 = 1 << 31;
+
+// where slip-0010 starts an ed25519 tree
+const MASTER_KEY: &[u8] = b"ed25519 seed";
+
+// a tree of keys grown from a mnemonic, writing down the words is the whole backup
+#[derive(Clone)]
+pub struct HdWallet {
+    mnemonic: Mnemonic,
+    passphrase: String,
+    seed: [u8; 64],
+}
+
+// keeps the words out of logs
+impl fmt::Debug for HdWallet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HdWallet")
+            .field("words", &self.mnemonic.word_count())
+            .finish()
+    }
+}
+
+// one of the wallet's addresses the chain knows about
+#[derive(Debug, Clone, PartialEq)]
+pub struct DerivedAddress {
+    pub index: u32,
+    pub address: String,
+    pub balance: Amount,
+    // oldest first
+    pub history: Vec<HistoryEntry>,
+}
+
+// what a scan of the chain turned up
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scan {
+    pub used: Vec<DerivedAddress>,
+    // the first index after the last used one, where new addresses should come from
+    pub next_index: u32,
+    pub balance: Amount,
+}
+
+impl HdWallet {
+    // a fresh mnemonic, words has to be 12, 15, 18, 21 or 24
+    pub fn generate(words: usize, passphrase: &str) -> Result<Self, WalletError> {
+        if !(12..=24).contains(&words) || !words.is_multiple_of(3) {
+            return Err(WalletError::InvalidMnemonic(format!(
+                "can't have {} words",
+                words
+            )));
+        }
+        let mut entropy = vec![0u8; words / 3 * 4];
+        OsRng.fill_bytes(&mut entropy);
+        let mnemonic = Mnemonic::from_entropy(&entropy)
+            .map_err(|e| WalletError::InvalidMnemonic(e.to_string()))?;
+        Ok(Self::from(mnemonic, passphrase))
+    }
+
+    // the passphrase is optional extra protection, a different one gives a different tree
+    pub fn from_mnemonic(phrase: &str, passphrase: &str) -> Result<Self, WalletError> {
+        let mnemonic =
+            Mnemonic::parse(phrase).map_err(|e| WalletError::InvalidMnemonic(e.to_string()))?;
+        Ok(Self::from(mnemonic, passphrase))
+    }
+
+    fn from(mnemonic: Mnemonic, passphrase: &str) -> Self {
+        HdWallet {
+            seed: mnemonic.to_seed(passphrase),
+            mnemonic,
+            passphrase: passphrase.to_string(),
+        }
+    }
+
+    pub fn phrase(&self) -> String {
+        self.mnemonic.to_string()
+    }
+
+    pub fn passphrase(&self) -> &str {
+        &self.passphrase
+    }
+
+    pub fn derive_path(&self, path: &[u32]) -> SigningKey {
+        SigningKey::from_bytes(&derive(&self.seed, path))
+    }
+
+    pub fn key(&self, index: u32) -> SigningKey {
+        self.derive_path(&[44, COIN_TYPE, 0, 0, index])
+    }
+
+    pub fn address(&self, index: u32) -> String {
+        key_address(&self.key(index).verifying_key())
+    }
+
+    // walks the addresses in order until gap_limit in a row have never been used
+    pub fn scan(&self, blockchain: &Blockchain, gap_limit: u32) -> Scan {
+        let touched: HashSet<String> = blockchain
+            .chain
+            .iter()
+            .flat_map(|block| block.transactions.iter())
+            .flat_map(|transaction| touched_addresses(transaction).into_iter())
+            .map(|(address, _, _)| address)
+            .collect();
+
+        let mut used = Vec::new();
+        let mut next_index = 0;
+        let mut index = 0;
+        while index < next_index + gap_limit {
+            let address = self.address(index);
+            if touched.contains(&address) {
+                used.push(DerivedAddress {
+                    index,
+                    balance: blockchain.balance_of(&address),
+                    history: history(blockchain, &address),
+                    address,
+                });
+                next_index = index + 1;
+            }
+            index += 1;
+        }
+
+        Scan {
+            balance: used.iter().fold(Amount::ZERO, |total, used| {
+                total.saturating_add(used.balance)
+            }),
+            used,
+            next_index,
+        }
+    }
+}
+
+// slip-0010 derivation down a path of indexes, all of them hardened
+fn derive(seed: &[u8], path: &[u32]) -> [u8; 32] {
+    let (mut key, mut chain_code) = split(hmac(MASTER_KEY, &[seed]));
+    for index in path {
+        let index = (index | HARDENED).to_be_bytes();
+        (key, chain_code) = split(hmac(&chain_code, &[&[0], &key, &index]));
+    }
+    key
+}
+
+fn hmac(key: &[u8], data: &[&[u8]]) -> [u8; 64] {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("hmac takes any key size");
+    for data in data {
+        mac.update(data);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+fn split(bytes: [u8; 64]) -> ([u8; 32], [u8; 32]) {
+    let mut key = [0u8; 32];
+    let mut chain_code = [0u8; 32];
+    key.copy_from_slice(&bytes[..32]);
+    chain_code.copy_from_slice(&bytes[32..]);
+    (key, chain_code)
+}
+
+// straight from the chain, whether or not it keeps an address index
+fn history(blockchain: &Blockchain, address: &str) -> Vec<HistoryEntry> {
+    let mut history = Vec::new();
+    for block in blockchain.chain.iter() {
+        for transaction in block.transactions.iter() {
+            for (touched, sent, received) in touched_addresses(transaction) {
+                if touched == address {
+                    history.push(HistoryEntry {
+                        txid: transaction.txid(),
+                        height: block.index,
+                        sent,
+                        received,
+                    });
+                }
+            }
+        }
+    }
+    history
+}
+
+#[cfg(test)]
+mod test {
+    use super::{derive, HdWallet};
+
+    // the first english vector from bip39
+    #[test]
+    pub fn seeds_should_match_bip39() {
+        let wallet = HdWallet::from_mnemonic(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            "TREZOR",
+        )
+        .expect("valid mnemonic");
+        assert_eq!(
+            hex::encode(wallet.seed),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+        assert!(HdWallet::from_mnemonic("abandon abandon", "").is_err());
+    }
+
+    // slip-0010 test vector 1 for ed25519
+    #[test]
+    pub fn derivation_should_match_slip_0010() {
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").expect("hex");
+        for (path, key) in [
+            (
+                vec![],
+                "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7",
+            ),
+            (
+                vec![0],
+                "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3",
+            ),
+            (
+                vec![0, 1],
+                "b1d0bad404bf35da785a64ca1ac54b2617211d2777696fbffaf208f746ae84f2",
+            ),
+            (
+                vec![0, 1, 2, 2, 1000000000],
+                "8f94d394a8e8fd6b1bc2f3f49f5c47e385281d5c17e65324b0f62483e37e8793",
+            ),
+        ] {
+            assert_eq!(hex::encode(derive(&seed, &path)), key);
+        }
+    }
+}
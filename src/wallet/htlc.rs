@@ -0,0 +1,159 @@
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::builder::{BuildError, TransactionBuilder};
+use crate::model::{
+    amount::Amount,
+    blockchain::Blockchain,
+    script::{Op, Script, ScriptSpend, Value},
+    transaction::{LockTime, Transaction},
+};
+
+// what a hash time-locked contract's secret is hashed with
+pub fn secret_hash(secret: &[u8]) -> Vec<u8> {
+    Sha256::digest(secret).to_vec()
+}
+
+// hash time-locked contract, coins the receiver can take by revealing the secret
+// behind the hash or that go back to the sender once the chain reaches refund_height.
+// the coins are sent to the address of its script
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Htlc {
+    // sha256 of the secret
+    pub hash: Vec<u8>,
+    // hex public keys
+    pub sender: String,
+    pub receiver: String,
+    pub refund_height: u64,
+}
+
+impl Htlc {
+    pub fn new(
+        hash: &[u8],
+        sender: &VerifyingKey,
+        receiver: &VerifyingKey,
+        refund_height: u64,
+    ) -> Self {
+        Htlc {
+            hash: hash.to_vec(),
+            sender: hex::encode(sender.as_bytes()),
+            receiver: hex::encode(receiver.as_bytes()),
+            refund_height,
+        }
+    }
+
+    // the witness picks the branch, [secret, TRUE] to claim and [FALSE] to refund
+    pub fn script(&self) -> Script {
+        let public_key = |key: &str| Value::Bytes(hex::decode(key).unwrap_or_default());
+        Script::new(vec![
+            Op::If,
+            Op::Sha256,
+            Op::Push(Value::Bytes(self.hash.clone())),
+            Op::Equal,
+            Op::Verify,
+            Op::Push(public_key(&self.receiver)),
+            Op::CheckSig,
+            Op::Else,
+            Op::Push(Value::Number(
+                i64::try_from(self.refund_height).unwrap_or(i64::MAX),
+            )),
+            Op::CheckLockHeight,
+            Op::Verify,
+            Op::Push(public_key(&self.sender)),
+            Op::CheckSig,
+            Op::EndIf,
+        ])
+    }
+
+    pub fn address(&self) -> String {
+        self.script().address()
+    }
+
+    // what's locked up in it as of the chain's tip
+    pub fn balance(&self, blockchain: &Blockchain) -> Amount {
+        blockchain.balance_of(&self.address())
+    }
+
+    // the receiver takes everything locked up, paying it on to the given address.
+    // the secret ends up on chain for the sender to see
+    pub fn claim(
+        &self,
+        blockchain: &Blockchain,
+        secret: &[u8],
+        to: &str,
+        fee_rate: u64,
+        key: &SigningKey,
+    ) -> Result<Transaction, BuildError> {
+        let witness = vec![Value::Bytes(secret.to_vec()), Value::Bool(true)];
+        self.sweep(blockchain, witness, None, to, fee_rate, key)
+    }
+
+    // the sender takes everything back, it can't be mined before refund_height
+    pub fn refund(
+        &self,
+        blockchain: &Blockchain,
+        to: &str,
+        fee_rate: u64,
+        key: &SigningKey,
+    ) -> Result<Transaction, BuildError> {
+        let lock_time = Some(LockTime::Height(self.refund_height));
+        self.sweep(
+            blockchain,
+            vec![Value::Bool(false)],
+            lock_time,
+            to,
+            fee_rate,
+            key,
+        )
+    }
+
+    // the secret behind the hash if the transaction is a claim of this contract
+    pub fn revealed_secret(&self, transaction: &Transaction) -> Option<Vec<u8>> {
+        let spend = transaction.script.as_ref()?;
+        if transaction.sender != self.address() || spend.script != self.script() {
+            return None;
+        }
+        match spend.witness.first() {
+            Some(Value::Bytes(secret)) if secret_hash(secret) == self.hash => Some(secret.clone()),
+            _ => None,
+        }
+    }
+
+    fn sweep(
+        &self,
+        blockchain: &Blockchain,
+        witness: Vec<Value>,
+        lock_time: Option<LockTime>,
+        to: &str,
+        fee_rate: u64,
+        key: &SigningKey,
+    ) -> Result<Transaction, BuildError> {
+        let spend = ScriptSpend {
+            script: self.script(),
+            witness,
+        };
+        let builder = |amount: Amount| {
+            let builder = TransactionBuilder::script(blockchain, &spend)
+                .pay(to, amount)
+                .with_fee_rate(fee_rate);
+            match lock_time {
+                Some(lock_time) => builder.with_lock_time(lock_time),
+                None => builder,
+            }
+        };
+
+        // amounts are always the same size so the fee for a token amount
+        // is the fee for the whole lot, give or take the change output
+        let locked = self.balance(blockchain);
+        let fee = builder(Amount::new(1)).build()?.fee;
+        let amount = locked
+            .checked_sub(fee)
+            .filter(|amount| !amount.is_zero())
+            .ok_or(BuildError::InsufficientFunds {
+                needed: fee,
+                available: locked,
+            })?;
+        builder(amount).build_signed(key)
+    }
+}
@@ -0,0 +1,566 @@
+use std::{
+    error::Error,
+    fmt,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use chacha20poly1305::{
+    aead::{Aead, Payload},
+    KeyInit, XChaCha20Poly1305, XNonce,
+};
+use ed25519_dalek::SigningKey;
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use super::hd::{HdWallet, Scan};
+use crate::model::{signature::key_address, transaction::Transaction};
+
+// bumped whenever the file layout changes
+pub const KEYSTORE_VERSION: u32 = 1;
+
+// scrypt's cost, 2^15 rounds takes a fraction of a second and 32MB
+pub const DEFAULT_LOG_N: u8 = 15;
+
+// the most we'll run scrypt with. the parameters come from the file before
+// anything's authenticated, so without a cap a doctored one could ask for
+// terabytes. 2^20 rounds with r = 8 is 1GB
+pub const MAX_LOG_N: u8 = 20;
+pub const MAX_R: u32 = 8;
+pub const MAX_P: u32 = 4;
+
+const SALT_BYTES: usize = 16;
+const NONCE_BYTES: usize = 24;
+
+#[derive(Debug)]
+pub enum WalletError {
+    Io(io::Error),
+    // the file isn't a keystore we can read
+    Corrupt(String),
+    // or the file was tampered with, there's no telling the two apart
+    WrongPassword,
+    // create refuses to overwrite an existing keystore
+    AlreadyExists,
+    DuplicateName(String),
+    // the key is already in here under another name
+    DuplicateKey(String),
+    UnknownAccount(String),
+    InvalidKey,
+    // asked to sign a transaction some other address is sending
+    WrongSender,
+    InvalidMnemonic(String),
+    // a keystore only ever holds one, replacing it would orphan the keys it made
+    MnemonicAlreadySet,
+    // partly signed copies that turned out not to be copies of the same transaction
+    MismatchedTransactions,
+    // scrypt parameters past MAX_LOG_N, MAX_R or MAX_P
+    KdfTooCostly,
+}
+
+impl fmt::Display for WalletError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WalletError::Io(e) => write!(f, "{}", e),
+            WalletError::Corrupt(e) => write!(f, "the keystore can't be read: {}", e),
+            WalletError::WrongPassword => write!(f, "wrong password"),
+            WalletError::AlreadyExists => write!(f, "there's already a keystore there"),
+            WalletError::DuplicateName(name) => {
+                write!(f, "there's already an account called {}", name)
+            }
+            WalletError::DuplicateKey(name) => write!(f, "that key is already there as {}", name),
+            WalletError::UnknownAccount(name) => write!(f, "there's no account {}", name),
+            WalletError::InvalidKey => write!(f, "not a hex encoded 32 byte secret key"),
+            WalletError::WrongSender => write!(f, "that account isn't the sender"),
+            WalletError::InvalidMnemonic(e) => write!(f, "bad mnemonic: {}", e),
+            WalletError::MnemonicAlreadySet => write!(f, "the keystore already has a mnemonic"),
+            WalletError::MismatchedTransactions => {
+                write!(f, "those aren't all signatures for the same transaction")
+            }
+            WalletError::KdfTooCostly => {
+                write!(f, "the key derivation asks for too much memory or time")
+            }
+        }
+    }
+}
+
+impl Error for WalletError {}
+
+impl From<io::Error> for WalletError {
+    fn from(e: io::Error) -> Self {
+        WalletError::Io(e)
+    }
+}
+
+// how the encryption key is stretched out of the password
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KdfParams {
+    // hex
+    pub salt: String,
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KdfParams {
+    // a fresh salt at the default cost
+    pub fn new() -> Self {
+        let mut salt = [0u8; SALT_BYTES];
+        OsRng.fill_bytes(&mut salt);
+        KdfParams {
+            salt: hex::encode(salt),
+            log_n: DEFAULT_LOG_N,
+            r: 8,
+            p: 1,
+        }
+    }
+
+    // lower is faster and weaker, tests use tiny values
+    pub fn with_log_n(mut self, log_n: u8) -> Self {
+        self.log_n = log_n;
+        self
+    }
+
+    fn derive_key(&self, password: &str) -> Result<[u8; 32], WalletError> {
+        if self.log_n > MAX_LOG_N || self.r > MAX_R || self.p > MAX_P {
+            return Err(WalletError::KdfTooCostly);
+        }
+        let salt = hex::decode(&self.salt).map_err(|e| WalletError::Corrupt(e.to_string()))?;
+        let params = scrypt::Params::new(self.log_n, self.r, self.p, 32)
+            .map_err(|e| WalletError::Corrupt(e.to_string()))?;
+        let mut key = [0u8; 32];
+        scrypt::scrypt(password.as_bytes(), &salt, &params, &mut key)
+            .map_err(|e| WalletError::Corrupt(e.to_string()))?;
+        Ok(key)
+    }
+}
+
+// what anybody can see about an account without the password
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AccountInfo {
+    pub name: String,
+    pub address: String,
+    // hex
+    pub public_key: String,
+}
+
+// the file on disk. the secret keys are encrypted together, in the same order
+// as accounts, and the accounts are authenticated along with them so nobody
+// can swap in an address of their own
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct KeystoreFile {
+    version: u32,
+    kdf: KdfParams,
+    accounts: Vec<AccountInfo>,
+    // hex
+    nonce: String,
+    ciphertext: String,
+    // the mnemonic, when there is one, encrypted under the same key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hd: Option<Sealed>,
+}
+
+// hex encoded nonce and ciphertext
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Sealed {
+    nonce: String,
+    ciphertext: String,
+}
+
+// what's needed to carry on deriving keys from a mnemonic
+#[derive(Clone, Serialize, Deserialize)]
+struct HdState {
+    phrase: String,
+    passphrase: String,
+    // the next key new_account hands out
+    next_index: u32,
+}
+
+pub struct Account {
+    pub name: String,
+    key: SigningKey,
+}
+
+// keeps secrets out of logs
+impl fmt::Debug for Account {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Account")
+            .field("name", &self.name)
+            .field("address", &self.address())
+            .finish()
+    }
+}
+
+impl Account {
+    pub fn address(&self) -> String {
+        key_address(&self.key.verifying_key())
+    }
+
+    pub fn public_key(&self) -> String {
+        hex::encode(self.key.verifying_key().as_bytes())
+    }
+
+    pub fn info(&self) -> AccountInfo {
+        AccountInfo {
+            name: self.name.clone(),
+            address: self.address(),
+            public_key: self.public_key(),
+        }
+    }
+
+    pub fn signing_key(&self) -> &SigningKey {
+        &self.key
+    }
+}
+
+// named keys kept in a password encrypted file. every change is written
+// straight back out
+pub struct Keystore {
+    path: PathBuf,
+    kdf: KdfParams,
+    key: [u8; 32],
+    accounts: Vec<Account>,
+    hd: Option<HdState>,
+}
+
+impl Keystore {
+    pub fn create(path: &Path, password: &str, kdf: KdfParams) -> Result<Self, WalletError> {
+        // claims the path, so two of these racing can't both think they made it
+        create_private(path).map_err(|e| match e.kind() {
+            io::ErrorKind::AlreadyExists => WalletError::AlreadyExists,
+            _ => WalletError::Io(e),
+        })?;
+
+        let created = kdf.derive_key(password).and_then(|key| {
+            let keystore = Keystore {
+                path: path.to_path_buf(),
+                key,
+                kdf,
+                accounts: Vec::new(),
+                hd: None,
+            };
+            keystore.save()?;
+            Ok(keystore)
+        });
+        if created.is_err() {
+            let _ = fs::remove_file(path);
+        }
+        created
+    }
+
+    pub fn open(path: &Path, password: &str) -> Result<Self, WalletError> {
+        let file = read_file(path)?;
+        let key = file.kdf.derive_key(password)?;
+        let aad =
+            serde_json::to_vec(&file.accounts).map_err(|e| WalletError::Corrupt(e.to_string()))?;
+        let secrets = open_sealed(
+            &key,
+            &Sealed {
+                nonce: file.nonce,
+                ciphertext: file.ciphertext,
+            },
+            &aad,
+        )?;
+        let secrets: Vec<[u8; 32]> =
+            bincode::deserialize(&secrets).map_err(|e| WalletError::Corrupt(e.to_string()))?;
+        if secrets.len() != file.accounts.len() {
+            return Err(WalletError::Corrupt(
+                "accounts and keys don't line up".to_string(),
+            ));
+        }
+        let hd = match file.hd {
+            Some(sealed) => Some(
+                bincode::deserialize(&open_sealed(&key, &sealed, HD_AAD)?)
+                    .map_err(|e| WalletError::Corrupt(e.to_string()))?,
+            ),
+            None => None,
+        };
+
+        let accounts = file
+            .accounts
+            .into_iter()
+            .zip(secrets)
+            .map(|(info, secret)| Account {
+                name: info.name,
+                key: SigningKey::from_bytes(&secret),
+            })
+            .collect();
+        Ok(Keystore {
+            path: path.to_path_buf(),
+            kdf: file.kdf,
+            key,
+            accounts,
+            hd,
+        })
+    }
+
+    // opens the keystore, making an empty one if there isn't one yet
+    pub fn open_or_create(
+        path: &Path,
+        password: &str,
+        kdf: KdfParams,
+    ) -> Result<Self, WalletError> {
+        match Self::create(path, password, kdf) {
+            Err(WalletError::AlreadyExists) => Self::open(path, password),
+            result => result,
+        }
+    }
+
+    // the accounts in a keystore without unlocking it
+    pub fn list(path: &Path) -> Result<Vec<AccountInfo>, WalletError> {
+        Ok(read_file(path)?.accounts)
+    }
+
+    pub fn save(&self) -> Result<(), WalletError> {
+        let accounts: Vec<AccountInfo> = self.accounts.iter().map(Account::info).collect();
+        let secrets: Vec<[u8; 32]> = self
+            .accounts
+            .iter()
+            .map(|account| account.key.to_bytes())
+            .collect();
+        let secrets =
+            bincode::serialize(&secrets).map_err(|e| WalletError::Corrupt(e.to_string()))?;
+        let aad = serde_json::to_vec(&accounts).map_err(|e| WalletError::Corrupt(e.to_string()))?;
+        let sealed = seal(&self.key, &secrets, &aad)?;
+        let hd = match self.hd.as_ref() {
+            Some(hd) => {
+                let hd = bincode::serialize(hd).map_err(|e| WalletError::Corrupt(e.to_string()))?;
+                Some(seal(&self.key, &hd, HD_AAD)?)
+            }
+            None => None,
+        };
+
+        let file = KeystoreFile {
+            version: KEYSTORE_VERSION,
+            kdf: self.kdf.clone(),
+            accounts,
+            nonce: sealed.nonce,
+            ciphertext: sealed.ciphertext,
+            hd,
+        };
+        let json =
+            serde_json::to_vec_pretty(&file).map_err(|e| WalletError::Corrupt(e.to_string()))?;
+        let tmp = self.path.with_extension("tmp");
+        // whatever a crash left behind, so the new one gets our permissions
+        match fs::remove_file(&tmp) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        create_private(&tmp)?.write_all(&json)?;
+        fs::rename(tmp, &self.path)?;
+        Ok(())
+    }
+
+    // re-encrypts everything under a new password and salt
+    pub fn change_password(&mut self, password: &str) -> Result<(), WalletError> {
+        let kdf = KdfParams::new().with_log_n(self.kdf.log_n);
+        self.key = kdf.derive_key(password)?;
+        self.kdf = kdf;
+        self.save()
+    }
+
+    pub fn accounts(&self) -> &[Account] {
+        &self.accounts
+    }
+
+    // by name or address
+    pub fn account(&self, name: &str) -> Option<&Account> {
+        self.accounts
+            .iter()
+            .find(|account| account.name == name || account.address() == name)
+    }
+
+    // the next key from the mnemonic if there is one, otherwise a random one
+    pub fn new_account(&mut self, name: &str) -> Result<&Account, WalletError> {
+        let Some(wallet) = self.hd_wallet()? else {
+            return self.add(name, SigningKey::generate(&mut OsRng));
+        };
+        let hd = self.hd.as_mut().expect("there's a wallet");
+        let key = wallet.key(hd.next_index);
+        hd.next_index += 1;
+        self.add(name, key)
+    }
+
+    pub fn hd_wallet(&self) -> Result<Option<HdWallet>, WalletError> {
+        self.hd
+            .as_ref()
+            .map(|hd| HdWallet::from_mnemonic(&hd.phrase, &hd.passphrase))
+            .transpose()
+    }
+
+    // new accounts come from the mnemonic from here on
+    pub fn set_mnemonic(&mut self, wallet: &HdWallet) -> Result<(), WalletError> {
+        if self.hd.is_some() {
+            return Err(WalletError::MnemonicAlreadySet);
+        }
+        self.hd = Some(HdState {
+            phrase: wallet.phrase(),
+            passphrase: wallet.passphrase().to_string(),
+            next_index: 0,
+        });
+        self.save()
+    }
+
+    // takes on the mnemonic along with every key a scan found used, as hd-<index>.
+    // keys that are already here are left alone
+    pub fn restore(&mut self, wallet: &HdWallet, scan: &Scan) -> Result<(), WalletError> {
+        self.set_mnemonic(wallet)?;
+        for used in scan.used.iter() {
+            let key = wallet.key(used.index);
+            let known = self
+                .accounts
+                .iter()
+                .any(|account| account.key.verifying_key() == key.verifying_key());
+            if !known {
+                self.add(&format!("hd-{}", used.index), key)?;
+            }
+        }
+        self.hd.as_mut().expect("just set").next_index = scan.next_index;
+        self.save()
+    }
+
+    pub fn import_key(&mut self, name: &str, secret_key: &str) -> Result<&Account, WalletError> {
+        let secret: [u8; 32] = hex::decode(secret_key.trim())
+            .ok()
+            .and_then(|secret| secret.try_into().ok())
+            .ok_or(WalletError::InvalidKey)?;
+        self.add(name, SigningKey::from_bytes(&secret))
+    }
+
+    // the hex secret key, anybody holding it can spend the account's coins
+    pub fn export_key(&self, name: &str) -> Result<String, WalletError> {
+        let account = self
+            .account(name)
+            .ok_or_else(|| WalletError::UnknownAccount(name.to_string()))?;
+        Ok(hex::encode(account.key.to_bytes()))
+    }
+
+    pub fn remove_account(&mut self, name: &str) -> Result<(), WalletError> {
+        let before = self.accounts.len();
+        self.accounts
+            .retain(|account| account.name != name && account.address() != name);
+        if self.accounts.len() == before {
+            return Err(WalletError::UnknownAccount(name.to_string()));
+        }
+        self.save()
+    }
+
+    // adds the account's signature, the transaction has to be sent from it or from a
+    // multisig or script address the account's key is part of. signing again changes nothing
+    pub fn sign(&self, name: &str, transaction: &mut Transaction) -> Result<(), WalletError> {
+        let account = self
+            .account(name)
+            .ok_or_else(|| WalletError::UnknownAccount(name.to_string()))?;
+        let public_key = account.public_key();
+        let co_signer = transaction.multisig.as_ref().is_some_and(|policy| {
+            policy.address() == transaction.sender && policy.contains(&public_key)
+        }) || transaction.script.as_ref().is_some_and(|spend| {
+            spend.script.address() == transaction.sender
+                && spend.script.public_keys().contains(&public_key)
+        });
+        if transaction.sender != account.address() && !co_signer {
+            return Err(WalletError::WrongSender);
+        }
+        if transaction
+            .signatures
+            .iter()
+            .any(|signature| signature.public_key == public_key)
+        {
+            return Ok(());
+        }
+        transaction.sign(&account.key);
+        Ok(())
+    }
+
+    fn add(&mut self, name: &str, key: SigningKey) -> Result<&Account, WalletError> {
+        if self.accounts.iter().any(|account| account.name == name) {
+            return Err(WalletError::DuplicateName(name.to_string()));
+        }
+        if let Some(existing) = self
+            .accounts
+            .iter()
+            .find(|account| account.key.verifying_key() == key.verifying_key())
+        {
+            return Err(WalletError::DuplicateKey(existing.name.clone()));
+        }
+
+        self.accounts.push(Account {
+            name: name.to_string(),
+            key,
+        });
+        self.save()?;
+        Ok(self.accounts.last().expect("just pushed"))
+    }
+}
+
+// what the mnemonic is authenticated with, so it can't be swapped with the keys
+const HD_AAD: &[u8] = b"hd";
+
+// a new file only its owner can read
+fn create_private(path: &Path) -> io::Result<fs::File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+fn seal(key: &[u8; 32], plaintext: &[u8], aad: &[u8]) -> Result<Sealed, WalletError> {
+    // never reuse a nonce with the same key
+    let mut nonce = [0u8; NONCE_BYTES];
+    OsRng.fill_bytes(&mut nonce);
+    let ciphertext = XChaCha20Poly1305::new(key.into())
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .map_err(|e| WalletError::Corrupt(e.to_string()))?;
+    Ok(Sealed {
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(ciphertext),
+    })
+}
+
+// a wrong password and a tampered file look exactly the same from here
+fn open_sealed(key: &[u8; 32], sealed: &Sealed, aad: &[u8]) -> Result<Vec<u8>, WalletError> {
+    let nonce: [u8; NONCE_BYTES] = hex::decode(&sealed.nonce)
+        .ok()
+        .and_then(|nonce| nonce.try_into().ok())
+        .ok_or_else(|| WalletError::Corrupt("bad nonce".to_string()))?;
+    let ciphertext =
+        hex::decode(&sealed.ciphertext).map_err(|e| WalletError::Corrupt(e.to_string()))?;
+    XChaCha20Poly1305::new(key.into())
+        .decrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: &ciphertext,
+                aad,
+            },
+        )
+        .map_err(|_| WalletError::WrongPassword)
+}
+
+fn read_file(path: &Path) -> Result<KeystoreFile, WalletError> {
+    let bytes = fs::read(path)?;
+    let file: KeystoreFile =
+        serde_json::from_slice(&bytes).map_err(|e| WalletError::Corrupt(e.to_string()))?;
+    if file.version != KEYSTORE_VERSION {
+        return Err(WalletError::Corrupt(format!(
+            "unknown version {}",
+            file.version
+        )));
+    }
+    Ok(file)
+}
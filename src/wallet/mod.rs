@@ -0,0 +1,8 @@
+pub mod builder;
+pub mod hd;
+pub mod htlc;
+pub mod keystore;
+pub mod multisig;
+pub mod psbt;
+pub mod swap;
+pub mod watch;
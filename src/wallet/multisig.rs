@@ -0,0 +1,69 @@
+use serde::Serialize;
+
+use super::{keystore::WalletError, psbt::PartiallySignedTransaction};
+use crate::model::transaction::Transaction;
+
+// how far a multisig transaction has got on its way round the signers
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SigningStatus {
+    pub address: String,
+    pub threshold: u32,
+    // hex public keys, sorted
+    pub signed: Vec<String>,
+    pub unsigned: Vec<String>,
+}
+
+impl SigningStatus {
+    // None for transactions that aren't from a multisig address
+    pub fn of(transaction: &Transaction) -> Option<Self> {
+        let policy = transaction.multisig.as_ref()?;
+        let signed = policy.signers(
+            &transaction.signatures,
+            transaction.signing_hash().as_bytes(),
+        );
+        Some(SigningStatus {
+            address: policy.address(),
+            threshold: policy.threshold,
+            unsigned: policy
+                .public_keys
+                .iter()
+                .filter(|public_key| !signed.contains(public_key))
+                .cloned()
+                .collect(),
+            signed,
+        })
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.signed.len() as u32 >= self.threshold
+    }
+
+    // how many more signatures it needs
+    pub fn missing(&self) -> u32 {
+        self.threshold.saturating_sub(self.signed.len() as u32)
+    }
+}
+
+// each signer signs their own copy of the same unsigned transaction, this puts their
+// signatures back together the way a partially signed transaction would. any copy
+// with a signature that doesn't hold up is refused
+pub fn combine(first: Transaction, others: &[Transaction]) -> Result<Transaction, WalletError> {
+    let container = |transaction: Transaction| {
+        PartiallySignedTransaction::new(transaction, None)
+            .map_err(|_| WalletError::MismatchedTransactions)
+    };
+    let mut combined = container(first)?;
+    for other in others {
+        combined
+            .combine(&container(other.clone())?)
+            .map_err(|_| WalletError::MismatchedTransactions)?;
+    }
+
+    let mut transaction = combined.transaction;
+    transaction.signatures = combined.signatures;
+    // past the threshold they're extras the chain won't take
+    if transaction.multisig.is_some() {
+        transaction.signatures.truncate(combined.threshold as usize);
+    }
+    Ok(transaction)
+}
@@ -0,0 +1,298 @@
+use std::{collections::BTreeMap, error::Error, fmt};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
+
+use super::multisig::SigningStatus;
+use crate::model::{
+    blockchain::BlockchainError,
+    script::Script,
+    signature::{is_key_address, key_address, parse_public_key, TxSignature},
+    transaction::Transaction,
+};
+
+// every encoded container starts with this, then the version
+pub const PSBT_MAGIC: &[u8] = b"rbpsbt";
+
+// bumped whenever the layout changes
+pub const PSBT_VERSION: u8 = 1;
+
+#[derive(Debug)]
+pub enum PsbtError {
+    // not base64, not a container or cut short
+    Encoding(String),
+    UnknownVersion(u8),
+    // the required signers aren't the ones the sender needs
+    WrongSigners,
+    // the key isn't one of the required signers
+    NotASigner(String),
+    // a signature from this key doesn't hold up against the transaction
+    InvalidSignature(String),
+    // the transaction inside isn't meant to carry signatures of its own
+    SignedTransaction,
+    // combining containers for different transactions or signers
+    Mismatched,
+    Incomplete { missing: u32 },
+    // the finished transaction still isn't one the ledger takes
+    Rejected(BlockchainError),
+}
+
+impl fmt::Display for PsbtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PsbtError::Encoding(e) => write!(f, "not a partially signed transaction: {}", e),
+            PsbtError::UnknownVersion(version) => write!(f, "unknown version {}", version),
+            PsbtError::WrongSigners => write!(f, "those aren't the keys the sender needs"),
+            PsbtError::NotASigner(key) => write!(f, "{} isn't one of the signers", key),
+            PsbtError::InvalidSignature(key) => write!(f, "the signature from {} is invalid", key),
+            PsbtError::SignedTransaction => {
+                write!(f, "signatures belong in the container, not the transaction")
+            }
+            PsbtError::Mismatched => write!(f, "those aren't for the same transaction"),
+            PsbtError::Incomplete { missing } => {
+                write!(f, "it still needs {} more signatures", missing)
+            }
+            PsbtError::Rejected(e) => write!(f, "the transaction isn't valid: {:?}", e),
+        }
+    }
+}
+
+impl Error for PsbtError {}
+
+// a transaction on its way round the people who have to sign it, possibly on
+// machines that never see a node. each signer adds to their own copy and the
+// copies get combined, once there are enough signatures it's finalized into a
+// transaction that can be submitted
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PartiallySignedTransaction {
+    // without any signatures, they're collected alongside it
+    pub transaction: Transaction,
+    // hex public keys that can sign, sorted
+    pub required_signers: Vec<String>,
+    // how many of them have to
+    pub threshold: u32,
+    pub signatures: Vec<TxSignature>,
+    // whatever the signers want to tell each other, it isn't part of the transaction
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl PartiallySignedTransaction {
+    // the signers come from the transaction's multisig policy or the keys in its script,
+    // a sender that's a key address needs its public_key passed in since the address is
+    // only its hash. signatures already on the transaction move into the container
+    pub fn new(mut transaction: Transaction, public_key: Option<&str>) -> Result<Self, PsbtError> {
+        let (required_signers, threshold) =
+            match (&transaction.multisig, &transaction.script, public_key) {
+                (Some(policy), _, _) => (policy.public_keys.clone(), policy.threshold),
+                (None, Some(spend), None) => script_signers(&spend.script),
+                (None, _, Some(public_key)) => (vec![public_key.to_lowercase()], 1),
+                (None, None, None) => return Err(PsbtError::WrongSigners),
+            };
+
+        let psbt = PartiallySignedTransaction {
+            signatures: std::mem::take(&mut transaction.signatures),
+            transaction,
+            required_signers,
+            threshold,
+            metadata: BTreeMap::new(),
+        };
+        psbt.validate()?;
+        Ok(psbt)
+    }
+
+    pub fn with_metadata(mut self, key: &str, value: &str) -> Self {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = PSBT_MAGIC.to_vec();
+        bytes.push(PSBT_VERSION);
+        bytes.extend(bincode::serialize(self).unwrap_or_default());
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, PsbtError> {
+        let rest = bytes
+            .strip_prefix(PSBT_MAGIC)
+            .ok_or_else(|| PsbtError::Encoding("wrong magic bytes".to_string()))?;
+        let (version, rest) = rest
+            .split_first()
+            .ok_or_else(|| PsbtError::Encoding("no version".to_string()))?;
+        if *version != PSBT_VERSION {
+            return Err(PsbtError::UnknownVersion(*version));
+        }
+        bincode::deserialize(rest).map_err(|e| PsbtError::Encoding(e.to_string()))
+    }
+
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(self.encode())
+    }
+
+    pub fn from_base64(encoded: &str) -> Result<Self, PsbtError> {
+        let bytes = STANDARD
+            .decode(encoded.trim())
+            .map_err(|e| PsbtError::Encoding(e.to_string()))?;
+        Self::decode(&bytes)
+    }
+
+    // the key has to be one of the required signers, signing twice changes nothing
+    pub fn sign(&mut self, key: &SigningKey) -> Result<(), PsbtError> {
+        let public_key = hex::encode(key.verifying_key().as_bytes());
+        if !self.required_signers.contains(&public_key) {
+            return Err(PsbtError::NotASigner(public_key));
+        }
+        if self
+            .signatures
+            .iter()
+            .any(|signature| signature.public_key == public_key)
+        {
+            return Ok(());
+        }
+        self.signatures.push(TxSignature::sign(
+            key,
+            self.transaction.signing_hash().as_bytes(),
+        ));
+        self.signatures
+            .sort_by(|a, b| a.public_key.cmp(&b.public_key));
+        Ok(())
+    }
+
+    // takes in the signatures from another copy of the same container. metadata
+    // that's only on the other copy is added, where both have a key ours is kept
+    pub fn combine(&mut self, other: &PartiallySignedTransaction) -> Result<(), PsbtError> {
+        if other.transaction != self.transaction
+            || other.required_signers != self.required_signers
+            || other.threshold != self.threshold
+        {
+            return Err(PsbtError::Mismatched);
+        }
+        other.validate()?;
+
+        for signature in other.signatures.iter() {
+            if !self.signatures.contains(signature) {
+                self.signatures.push(signature.clone());
+            }
+        }
+        self.signatures
+            .sort_by(|a, b| a.public_key.cmp(&b.public_key));
+        self.signatures
+            .dedup_by(|a, b| a.public_key == b.public_key);
+        for (key, value) in other.metadata.iter() {
+            self.metadata
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+        Ok(())
+    }
+
+    // the signers have to be the ones the sender needs and every signature has to
+    // be from one of them and hold up. says nothing about whether there are enough
+    pub fn validate(&self) -> Result<(), PsbtError> {
+        if !self.transaction.signatures.is_empty() {
+            return Err(PsbtError::SignedTransaction);
+        }
+        if !self.signers_match_sender() {
+            return Err(PsbtError::WrongSigners);
+        }
+
+        let message = self.transaction.signing_hash();
+        for signature in self.signatures.iter() {
+            if !self.required_signers.contains(&signature.public_key) {
+                return Err(PsbtError::NotASigner(signature.public_key.clone()));
+            }
+            if !signature.verify(message.as_bytes()) {
+                return Err(PsbtError::InvalidSignature(signature.public_key.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn status(&self) -> SigningStatus {
+        let signed: Vec<String> = self
+            .required_signers
+            .iter()
+            .filter(|public_key| {
+                self.signatures
+                    .iter()
+                    .any(|signature| signature.public_key == **public_key)
+            })
+            .cloned()
+            .collect();
+        SigningStatus {
+            address: self.transaction.sender.clone(),
+            threshold: self.threshold,
+            unsigned: self
+                .required_signers
+                .iter()
+                .filter(|public_key| !signed.contains(public_key))
+                .cloned()
+                .collect(),
+            signed,
+        }
+    }
+
+    // the transaction with its signatures, ready to submit
+    pub fn finalize(&self) -> Result<Transaction, PsbtError> {
+        self.validate()?;
+        let status = self.status();
+        if !status.is_complete() {
+            return Err(PsbtError::Incomplete {
+                missing: status.missing(),
+            });
+        }
+
+        // only as many as it takes, the chain won't take extras
+        let mut transaction = self.transaction.clone();
+        transaction.signatures = self.signatures.clone();
+        match &transaction.script {
+            // whichever ones the script ended up counting
+            Some(spend) => {
+                let signers = spend
+                    .script
+                    .signers(&spend.witness, &transaction)
+                    .map_err(|e| PsbtError::Rejected(BlockchainError::ScriptFailed(e)))?;
+                transaction
+                    .signatures
+                    .retain(|signature| signers.contains(&signature.public_key));
+            }
+            None => transaction.signatures.truncate(self.threshold as usize),
+        }
+        transaction
+            .check_signatures()
+            .map_err(PsbtError::Rejected)?;
+        Ok(transaction)
+    }
+
+    fn signers_match_sender(&self) -> bool {
+        match (&self.transaction.multisig, &self.transaction.script) {
+            (Some(policy), _) => {
+                policy.address() == self.transaction.sender
+                    && policy.public_keys == self.required_signers
+                    && policy.threshold == self.threshold
+            }
+            (None, Some(spend)) => {
+                spend.script.address() == self.transaction.sender
+                    && !self.required_signers.is_empty()
+                    && script_signers(&spend.script)
+                        == (self.required_signers.clone(), self.threshold)
+            }
+            (None, None) if is_key_address(&self.transaction.sender) => {
+                self.threshold == 1
+                    && matches!(self.required_signers.as_slice(), [public_key]
+                        if parse_public_key(public_key)
+                            .is_some_and(|key| key_address(&key) == self.transaction.sender))
+            }
+            // nothing can sign for a plain name
+            (None, None) => false,
+        }
+    }
+}
+
+// the keys in a script sorted, and how many of them the script needs at the least
+fn script_signers(script: &Script) -> (Vec<String>, u32) {
+    let mut public_keys = script.public_keys();
+    public_keys.sort();
+    (public_keys, script.threshold())
+}
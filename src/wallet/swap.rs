@@ -0,0 +1,412 @@
+use std::{error::Error, fmt};
+
+use ed25519_dalek::SigningKey;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    builder::{BuildError, TransactionBuilder, DEFAULT_FEE_RATE},
+    htlc::{secret_hash, Htlc},
+};
+use crate::model::{
+    amount::Amount,
+    blockchain::Blockchain,
+    signature::{key_address, parse_public_key},
+    transaction::Transaction,
+};
+
+// the participant won't lock anything unless both contracts have at least this many
+// blocks to go before they can be refunded, otherwise there's no time to claim
+pub const REFUND_MARGIN: u64 = 2;
+
+#[derive(Debug)]
+pub enum SwapError {
+    // the secret doesn't hash to the one in the terms
+    WrongSecret,
+    // the key isn't the one the terms name for this side of the swap
+    WrongKey,
+    // the initiator's refund doesn't come far enough after the participant's
+    UnsafeTerms,
+    Build(BuildError),
+}
+
+impl fmt::Display for SwapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SwapError::WrongSecret => write!(f, "that's not the secret behind the hash"),
+            SwapError::WrongKey => write!(f, "that key isn't part of the swap"),
+            SwapError::UnsafeTerms => write!(
+                f,
+                "the initiator's refund has to come more than {} of its blocks after the participant's",
+                REFUND_MARGIN
+            ),
+            SwapError::Build(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for SwapError {}
+
+impl From<BuildError> for SwapError {
+    fn from(e: BuildError) -> Self {
+        SwapError::Build(e)
+    }
+}
+
+// what both sides agree on before anything gets locked up. the initiator picks the
+// secret and locks their coins on their chain first, the participant locks theirs
+// on the other chain once they've seen that. the initiator's refund has to come well
+// after the participant's, so the participant still has time to claim once the
+// initiator's claim gives the secret away
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SwapTerms {
+    // sha256 of the initiator's secret
+    pub hash: Vec<u8>,
+    // hex public keys
+    pub initiator: String,
+    pub participant: String,
+    // what each of them locks up, on their own chain
+    pub initiator_amount: Amount,
+    pub participant_amount: Amount,
+    // heights on each one's own chain
+    pub initiator_refund_height: u64,
+    pub participant_refund_height: u64,
+    // where each chain was at when the terms were agreed and how many seconds it
+    // takes to add a block, so the refunds on the two can be compared in time
+    pub initiator_start_height: u64,
+    pub participant_start_height: u64,
+    pub initiator_block_secs: u64,
+    pub participant_block_secs: u64,
+}
+
+impl SwapTerms {
+    // the participant's refund and then REFUND_MARGIN of the initiator's blocks have
+    // to fit in before the initiator's refund, otherwise the participant could see
+    // the secret too late to claim with it
+    pub fn check(&self) -> Result<(), SwapError> {
+        let secs_until = |refund_height: u64, start_height: u64, block_secs: u64| {
+            refund_height
+                .checked_sub(start_height)
+                .filter(|blocks| *blocks > 0 && block_secs > 0)
+                .and_then(|blocks| blocks.checked_mul(block_secs))
+        };
+        let initiator = secs_until(
+            self.initiator_refund_height,
+            self.initiator_start_height,
+            self.initiator_block_secs,
+        );
+        let needed = secs_until(
+            self.participant_refund_height,
+            self.participant_start_height,
+            self.participant_block_secs,
+        )
+        .and_then(|secs| secs.checked_add(REFUND_MARGIN.checked_mul(self.initiator_block_secs)?));
+        match (initiator, needed) {
+            (Some(initiator), Some(needed)) if initiator > needed => Ok(()),
+            _ => Err(SwapError::UnsafeTerms),
+        }
+    }
+
+    // on the initiator's chain, paying the participant
+    pub fn initiator_htlc(&self) -> Htlc {
+        Htlc {
+            hash: self.hash.clone(),
+            sender: self.initiator.clone(),
+            receiver: self.participant.clone(),
+            refund_height: self.initiator_refund_height,
+        }
+    }
+
+    // on the participant's chain, paying the initiator
+    pub fn participant_htlc(&self) -> Htlc {
+        Htlc {
+            hash: self.hash.clone(),
+            sender: self.participant.clone(),
+            receiver: self.initiator.clone(),
+            refund_height: self.participant_refund_height,
+        }
+    }
+}
+
+// which of the two chains
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SwapChain {
+    Initiator,
+    Participant,
+}
+
+// a transaction the coordinator wants on one of the chains
+#[derive(Clone, Debug, PartialEq)]
+pub struct SwapSubmission {
+    pub chain: SwapChain,
+    pub transaction: Transaction,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SwapState {
+    // nothing of ours is locked up yet
+    Waiting,
+    // our coins are in our contract
+    Locked,
+    // we've claimed the other side's coins, waiting for it to confirm
+    Claimed,
+    Completed,
+    // the swap fell through and we're taking our coins back, waiting for it to confirm
+    Refunding,
+    Refunded,
+}
+
+// one side's part of a swap, it keeps no connections of its own. each poll looks at
+// both chains as they are now and says what to submit where, whoever drives it
+// passes the transactions on to nodes of the right chains
+pub struct SwapCoordinator {
+    terms: SwapTerms,
+    key: SigningKey,
+    initiator: bool,
+    // the participant only learns it from the initiator's claim
+    secret: Option<Vec<u8>>,
+    fee_rate: u64,
+    state: SwapState,
+    // our latest claim or refund, until it confirms
+    pending: Option<Transaction>,
+    // the tip of the chain pending went to when we last asked for it
+    submitted_at: u64,
+}
+
+impl SwapCoordinator {
+    pub fn initiator(terms: SwapTerms, secret: &[u8], key: SigningKey) -> Result<Self, SwapError> {
+        if secret_hash(secret) != terms.hash {
+            return Err(SwapError::WrongSecret);
+        }
+        Self::new(terms, key, true, Some(secret.to_vec()))
+    }
+
+    pub fn participant(terms: SwapTerms, key: SigningKey) -> Result<Self, SwapError> {
+        Self::new(terms, key, false, None)
+    }
+
+    fn new(
+        terms: SwapTerms,
+        key: SigningKey,
+        initiator: bool,
+        secret: Option<Vec<u8>>,
+    ) -> Result<Self, SwapError> {
+        let expected = if initiator {
+            &terms.initiator
+        } else {
+            &terms.participant
+        };
+        if parse_public_key(expected) != Some(key.verifying_key()) {
+            return Err(SwapError::WrongKey);
+        }
+        terms.check()?;
+        Ok(SwapCoordinator {
+            terms,
+            key,
+            initiator,
+            secret,
+            fee_rate: DEFAULT_FEE_RATE,
+            state: SwapState::Waiting,
+            pending: None,
+            submitted_at: 0,
+        })
+    }
+
+    pub fn with_fee_rate(mut self, fee_rate: u64) -> Self {
+        self.fee_rate = fee_rate;
+        self
+    }
+
+    pub fn state(&self) -> SwapState {
+        self.state
+    }
+
+    pub fn secret(&self) -> Option<&[u8]> {
+        self.secret.as_deref()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self.state, SwapState::Completed | SwapState::Refunded)
+    }
+
+    // works out what to do next from where both chains are at. calling it again
+    // before anything has changed asks for nothing new
+    pub fn poll(
+        &mut self,
+        initiator_chain: &Blockchain,
+        participant_chain: &Blockchain,
+    ) -> Result<Vec<SwapSubmission>, SwapError> {
+        // we lock on our own chain and claim on theirs
+        let (ours, theirs) = self.sides();
+        let chain = |side: &Side| match side.chain {
+            SwapChain::Initiator => initiator_chain,
+            SwapChain::Participant => participant_chain,
+        };
+        let (our_chain, their_chain) = (chain(&ours), chain(&theirs));
+        let address = key_address(&self.key.verifying_key());
+        let submit = |side: &Side, transaction: &Transaction| {
+            vec![SwapSubmission {
+                chain: side.chain,
+                transaction: transaction.clone(),
+            }]
+        };
+
+        match self.state {
+            SwapState::Waiting => {
+                // the participant waits for the initiator's coins, with time to spare
+                let ready = self.initiator
+                    || (theirs.is_funded(their_chain)
+                        && their_chain.tip().index + REFUND_MARGIN < theirs.htlc.refund_height
+                        && our_chain.tip().index + REFUND_MARGIN < ours.htlc.refund_height);
+                if !ready {
+                    return Ok(Vec::new());
+                }
+                let funding = TransactionBuilder::new(our_chain, &address)
+                    .pay(&ours.htlc.address(), ours.amount)
+                    .with_fee_rate(self.fee_rate)
+                    .build_signed(&self.key)?;
+                self.state = SwapState::Locked;
+                Ok(submit(&ours, &funding))
+            }
+            SwapState::Locked => {
+                if self.secret.is_none() {
+                    self.secret = find_secret(&ours.htlc, our_chain);
+                }
+                // a claim that could lose the race against their refund would
+                // give the secret away for nothing
+                let claimable = theirs.is_funded(their_chain)
+                    && their_chain.tip().index + 1 < theirs.htlc.refund_height;
+                match &self.secret {
+                    Some(secret) if claimable => {
+                        let claim = theirs.htlc.claim(
+                            their_chain,
+                            secret,
+                            &address,
+                            self.fee_rate,
+                            &self.key,
+                        )?;
+                        self.state = SwapState::Claimed;
+                        Ok(self.pend(&theirs, their_chain, claim))
+                    }
+                    // nobody claimed in time, the refund can go in the next block
+                    _ if ours.is_refundable(our_chain) => {
+                        let refund =
+                            ours.htlc
+                                .refund(our_chain, &address, self.fee_rate, &self.key)?;
+                        self.state = SwapState::Refunding;
+                        Ok(self.pend(&ours, our_chain, refund))
+                    }
+                    _ => Ok(Vec::new()),
+                }
+            }
+            SwapState::Claimed => {
+                let Some(claim) = self.pending.clone() else {
+                    return Ok(Vec::new());
+                };
+                if their_chain.is_confirmed(&claim) {
+                    self.state = SwapState::Completed;
+                    self.pending = None;
+                    return Ok(Vec::new());
+                }
+                // the claim still hasn't made it by the time ours can be refunded,
+                // better to take ours back than hope it gets in before their refund
+                if ours.is_refundable(our_chain) {
+                    let refund = ours
+                        .htlc
+                        .refund(our_chain, &address, self.fee_rate, &self.key)?;
+                    self.state = SwapState::Refunding;
+                    return Ok(self.pend(&ours, our_chain, refund));
+                }
+                Ok(self.resubmit(&theirs, their_chain))
+            }
+            SwapState::Refunding => {
+                if self
+                    .pending
+                    .as_ref()
+                    .is_some_and(|refund| our_chain.is_confirmed(refund))
+                {
+                    self.state = SwapState::Refunded;
+                    self.pending = None;
+                    return Ok(Vec::new());
+                }
+                Ok(self.resubmit(&ours, our_chain))
+            }
+            SwapState::Completed | SwapState::Refunded => Ok(Vec::new()),
+        }
+    }
+
+    // our new claim or refund, to watch until it confirms
+    fn pend(
+        &mut self,
+        side: &Side,
+        blockchain: &Blockchain,
+        transaction: Transaction,
+    ) -> Vec<SwapSubmission> {
+        self.submitted_at = blockchain.tip().index;
+        self.pending = Some(transaction.clone());
+        vec![SwapSubmission {
+            chain: side.chain,
+            transaction,
+        }]
+    }
+
+    // asks for the pending claim or refund again once a block has gone by without it,
+    // it could have been dropped on the way. not once the contract it spends is empty
+    // though, whatever emptied it got there first
+    fn resubmit(&mut self, side: &Side, blockchain: &Blockchain) -> Vec<SwapSubmission> {
+        let Some(transaction) = self.pending.clone() else {
+            return Vec::new();
+        };
+        if blockchain.tip().index <= self.submitted_at || side.htlc.balance(blockchain).is_zero() {
+            return Vec::new();
+        }
+        self.pend(side, blockchain, transaction)
+    }
+
+    // ours first, then theirs
+    fn sides(&self) -> (Side, Side) {
+        let initiator = Side {
+            chain: SwapChain::Initiator,
+            htlc: self.terms.initiator_htlc(),
+            amount: self.terms.initiator_amount,
+        };
+        let participant = Side {
+            chain: SwapChain::Participant,
+            htlc: self.terms.participant_htlc(),
+            amount: self.terms.participant_amount,
+        };
+        match self.initiator {
+            true => (initiator, participant),
+            false => (participant, initiator),
+        }
+    }
+}
+
+// one party's contract and what they promised to lock up in it
+struct Side {
+    chain: SwapChain,
+    htlc: Htlc,
+    amount: Amount,
+}
+
+impl Side {
+    fn is_funded(&self, blockchain: &Blockchain) -> bool {
+        self.htlc.balance(blockchain) >= self.amount
+    }
+
+    // the refund can go in the next block and there's still something to refund
+    fn is_refundable(&self, blockchain: &Blockchain) -> bool {
+        blockchain.tip().index + 1 >= self.htlc.refund_height
+            && !self.htlc.balance(blockchain).is_zero()
+    }
+}
+
+// the other side's claim of our contract gives the secret away
+fn find_secret(htlc: &Htlc, blockchain: &Blockchain) -> Option<Vec<u8>> {
+    let address = htlc.address();
+    blockchain
+        .chain
+        .iter()
+        .flat_map(|block| block.transactions.iter())
+        .filter(|transaction| transaction.sender == address)
+        .find_map(|transaction| htlc.revealed_secret(transaction))
+}
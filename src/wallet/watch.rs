@@ -0,0 +1,424 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::keystore::WalletError;
+use crate::model::{
+    amount::Amount,
+    block::Block,
+    blockchain::Blockchain,
+    events::NodeEvent,
+    signature::{key_address, parse_public_key},
+    transaction::Transaction,
+};
+
+// bumped whenever the file layout changes
+pub const WATCH_LIST_VERSION: u32 = 1;
+
+// how many confirmations a payment needs before it's considered settled
+pub const DEFAULT_SETTLED_DEPTH: u64 = 6;
+
+// an address somebody else holds the keys to
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WatchedAddress {
+    pub label: String,
+    pub address: String,
+    // hex, when it was imported as a public key
+    pub public_key: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct WatchListFile {
+    version: u32,
+    addresses: Vec<WatchedAddress>,
+}
+
+// the addresses a watch-only wallet keeps an eye on. there's nothing secret in
+// here so unlike the keystore it's plain json
+#[derive(Debug)]
+pub struct WatchList {
+    path: PathBuf,
+    addresses: Vec<WatchedAddress>,
+}
+
+impl WatchList {
+    // an empty list when there's nothing at path yet
+    pub fn open(path: &Path) -> Result<Self, WalletError> {
+        if !path.exists() {
+            return Ok(WatchList {
+                path: path.to_path_buf(),
+                addresses: Vec::new(),
+            });
+        }
+
+        let bytes = fs::read(path)?;
+        let file: WatchListFile =
+            serde_json::from_slice(&bytes).map_err(|e| WalletError::Corrupt(e.to_string()))?;
+        if file.version != WATCH_LIST_VERSION {
+            return Err(WalletError::Corrupt(format!(
+                "unknown version {}",
+                file.version
+            )));
+        }
+        Ok(WatchList {
+            path: path.to_path_buf(),
+            addresses: file.addresses,
+        })
+    }
+
+    pub fn save(&self) -> Result<(), WalletError> {
+        let file = WatchListFile {
+            version: WATCH_LIST_VERSION,
+            addresses: self.addresses.clone(),
+        };
+        let json =
+            serde_json::to_vec_pretty(&file).map_err(|e| WalletError::Corrupt(e.to_string()))?;
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, json)?;
+        fs::rename(tmp, &self.path)?;
+        Ok(())
+    }
+
+    pub fn addresses(&self) -> &[WatchedAddress] {
+        &self.addresses
+    }
+
+    // by label or address
+    pub fn get(&self, label: &str) -> Option<&WatchedAddress> {
+        self.addresses
+            .iter()
+            .find(|watched| watched.label == label || watched.address == label)
+    }
+
+    pub fn watch_address(
+        &mut self,
+        label: &str,
+        address: &str,
+    ) -> Result<&WatchedAddress, WalletError> {
+        self.add(WatchedAddress {
+            label: label.to_string(),
+            address: address.to_string(),
+            public_key: None,
+        })
+    }
+
+    // watches the address the key signs for
+    pub fn watch_public_key(
+        &mut self,
+        label: &str,
+        public_key: &str,
+    ) -> Result<&WatchedAddress, WalletError> {
+        let key = parse_public_key(public_key).ok_or(WalletError::InvalidKey)?;
+        self.add(WatchedAddress {
+            label: label.to_string(),
+            address: key_address(&key),
+            public_key: Some(hex::encode(key.as_bytes())),
+        })
+    }
+
+    pub fn remove(&mut self, label: &str) -> Result<WatchedAddress, WalletError> {
+        let index = self
+            .addresses
+            .iter()
+            .position(|watched| watched.label == label || watched.address == label)
+            .ok_or_else(|| WalletError::UnknownAccount(label.to_string()))?;
+        let removed = self.addresses.remove(index);
+        self.save()?;
+        Ok(removed)
+    }
+
+    fn add(&mut self, watched: WatchedAddress) -> Result<&WatchedAddress, WalletError> {
+        if self
+            .addresses
+            .iter()
+            .any(|existing| existing.label == watched.label)
+        {
+            return Err(WalletError::DuplicateName(watched.label));
+        }
+        if let Some(existing) = self
+            .addresses
+            .iter()
+            .find(|existing| existing.address == watched.address)
+        {
+            return Err(WalletError::DuplicateKey(existing.label.clone()));
+        }
+
+        self.addresses.push(watched);
+        self.save()?;
+        Ok(self.addresses.last().expect("just pushed"))
+    }
+}
+
+// coins coming in to a watched address, change the address paid itself doesn't count
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Payment {
+    pub txid: String,
+    pub label: String,
+    pub address: String,
+    pub amount: Amount,
+    // None while it's only in the mempool
+    pub height: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum WatchEvent {
+    // seen in the mempool, it could still go away
+    PaymentPending(Payment),
+    // made it into a block
+    PaymentConfirmed(Payment),
+    // reached the monitor's depth, it won't be reported again
+    PaymentSettled {
+        payment: Payment,
+        confirmations: u64,
+    },
+    // its block got disconnected before it settled, it's back to waiting
+    PaymentReverted(Payment),
+    // left the mempool without being confirmed
+    PaymentDropped(Payment),
+    BalanceChanged {
+        label: String,
+        address: String,
+        balance: Amount,
+    },
+}
+
+// follows a node's events for the watched addresses, keeping their balances and
+// telling whoever runs it about payments as they come in and get buried.
+// it doesn't subscribe to anything itself, feed it with handle
+#[derive(Debug)]
+pub struct WatchMonitor {
+    // address to label
+    labels: HashMap<String, String>,
+    depth: u64,
+    tip: u64,
+    balances: HashMap<String, Amount>,
+    // everything that hasn't settled yet, oldest first
+    payments: Vec<Payment>,
+}
+
+impl WatchMonitor {
+    // a depth of 1 settles payments as soon as they're confirmed
+    pub fn new(addresses: &[WatchedAddress], blockchain: &Blockchain, depth: u64) -> Self {
+        let mut monitor = WatchMonitor {
+            labels: addresses
+                .iter()
+                .map(|watched| (watched.address.clone(), watched.label.clone()))
+                .collect(),
+            depth: depth.max(1),
+            tip: 0,
+            balances: HashMap::new(),
+            payments: Vec::new(),
+        };
+        monitor.catch_up(blockchain);
+        monitor
+    }
+
+    pub fn depth(&self) -> u64 {
+        self.depth
+    }
+
+    pub fn tip(&self) -> u64 {
+        self.tip
+    }
+
+    pub fn balance(&self, address: &str) -> Amount {
+        self.balances.get(address).copied().unwrap_or_default()
+    }
+
+    pub fn total_balance(&self) -> Amount {
+        self.balances.values().fold(Amount::ZERO, |total, balance| {
+            total.saturating_add(*balance)
+        })
+    }
+
+    // pending and confirmed payments that haven't settled yet
+    pub fn unsettled(&self) -> &[Payment] {
+        &self.payments
+    }
+
+    // starts over from the chain, for when events were missed. payments in the
+    // blocks that haven't reached the depth are picked up again without being reported,
+    // anything only in the mempool is forgotten
+    pub fn catch_up(&mut self, blockchain: &Blockchain) {
+        self.tip = blockchain.tip().index;
+        self.balances = self
+            .labels
+            .keys()
+            .map(|address| (address.clone(), blockchain.balance_of(address)))
+            .filter(|(_, balance)| !balance.is_zero())
+            .collect();
+
+        let first_unsettled = (self.tip + 2).saturating_sub(self.depth);
+        self.payments = blockchain
+            .chain
+            .iter()
+            .skip(first_unsettled as usize)
+            .flat_map(|block| {
+                block
+                    .transactions
+                    .iter()
+                    .flat_map(|transaction| self.incoming(transaction, Some(block.index)))
+            })
+            .collect();
+    }
+
+    pub fn handle(&mut self, event: &NodeEvent) -> Vec<WatchEvent> {
+        match event {
+            NodeEvent::TransactionAccepted(transaction) => self.transaction_accepted(transaction),
+            NodeEvent::TransactionEvicted { transaction, .. } => {
+                self.transaction_evicted(transaction)
+            }
+            NodeEvent::BlockConnected(block) => self.block_connected(block),
+            NodeEvent::BlockDisconnected(block) => self.block_disconnected(block),
+            _ => Vec::new(),
+        }
+    }
+
+    fn transaction_accepted(&mut self, transaction: &Transaction) -> Vec<WatchEvent> {
+        let mut events = Vec::new();
+        for payment in self.incoming(transaction, None) {
+            if !self.is_tracked(&payment) {
+                self.payments.push(payment.clone());
+                events.push(WatchEvent::PaymentPending(payment));
+            }
+        }
+        events
+    }
+
+    fn transaction_evicted(&mut self, transaction: &Transaction) -> Vec<WatchEvent> {
+        let txid = transaction.txid();
+        let (dropped, kept) = std::mem::take(&mut self.payments)
+            .into_iter()
+            .partition(|payment| payment.txid == txid && payment.height.is_none());
+        self.payments = kept;
+        dropped
+            .into_iter()
+            .map(WatchEvent::PaymentDropped)
+            .collect()
+    }
+
+    fn block_connected(&mut self, block: &Block) -> Vec<WatchEvent> {
+        self.tip = block.index;
+        let mut events = self.apply(block, false);
+
+        for transaction in block.transactions.iter() {
+            for payment in self.incoming(transaction, Some(block.index)) {
+                match self.payments.iter_mut().find(|tracked| {
+                    tracked.txid == payment.txid && tracked.address == payment.address
+                }) {
+                    Some(tracked) => tracked.height = Some(block.index),
+                    None => self.payments.push(payment.clone()),
+                }
+                events.push(WatchEvent::PaymentConfirmed(payment));
+            }
+        }
+
+        let tip = self.tip;
+        let depth = self.depth;
+        let (settled, unsettled) =
+            std::mem::take(&mut self.payments)
+                .into_iter()
+                .partition(|payment| {
+                    payment
+                        .height
+                        .is_some_and(|height| tip + 1 - height >= depth)
+                });
+        self.payments = unsettled;
+        events.extend(
+            settled
+                .into_iter()
+                .map(|payment: Payment| WatchEvent::PaymentSettled {
+                    confirmations: tip + 1
+                        - payment.height.expect("only confirmed payments settle"),
+                    payment,
+                }),
+        );
+        events
+    }
+
+    fn block_disconnected(&mut self, block: &Block) -> Vec<WatchEvent> {
+        self.tip = block.index.saturating_sub(1);
+        let mut events = self.apply(block, true);
+
+        for payment in self.payments.iter_mut() {
+            if payment.height == Some(block.index) {
+                payment.height = None;
+                events.push(WatchEvent::PaymentReverted(payment.clone()));
+            }
+        }
+        events
+    }
+
+    // moves the watched balances by what the block did to them
+    fn apply(&mut self, block: &Block, undo: bool) -> Vec<WatchEvent> {
+        let mut changes: HashMap<&str, i128> = HashMap::new();
+        for transaction in block.transactions.iter() {
+            if self.labels.contains_key(&transaction.sender) {
+                let cost = transaction.total_cost().unwrap_or_default().base_units() as i128;
+                *changes.entry(transaction.sender.as_str()).or_insert(0) -= cost;
+            }
+            for (receiver, amount) in transaction.credits() {
+                if let Some((address, _)) = self.labels.get_key_value(&receiver) {
+                    *changes.entry(address.as_str()).or_insert(0) += amount.base_units() as i128;
+                }
+            }
+        }
+
+        let mut events = Vec::new();
+        let mut changes: Vec<(String, i128)> = changes
+            .into_iter()
+            .filter(|(_, change)| *change != 0)
+            .map(|(address, change)| (address.to_string(), change))
+            .collect();
+        changes.sort();
+        for (address, change) in changes {
+            let change = if undo { -change } else { change };
+            let balance = self.balance(&address).base_units() as i128 + change;
+            let balance = Amount::new(u64::try_from(balance.max(0)).unwrap_or(u64::MAX));
+            self.balances.insert(address.clone(), balance);
+            events.push(WatchEvent::BalanceChanged {
+                label: self.labels[&address].clone(),
+                address,
+                balance,
+            });
+        }
+        events
+    }
+
+    // what the transaction pays each watched address, one payment per address
+    fn incoming(&self, transaction: &Transaction, height: Option<u64>) -> Vec<Payment> {
+        let mut payments: Vec<Payment> = Vec::new();
+        for (receiver, amount) in transaction.credits() {
+            if receiver == transaction.sender {
+                continue;
+            }
+            let Some(label) = self.labels.get(&receiver) else {
+                continue;
+            };
+            match payments
+                .iter_mut()
+                .find(|payment| payment.address == receiver)
+            {
+                Some(payment) => payment.amount = payment.amount.saturating_add(amount),
+                None => payments.push(Payment {
+                    txid: transaction.txid(),
+                    label: label.clone(),
+                    address: receiver,
+                    amount,
+                    height,
+                }),
+            }
+        }
+        payments
+    }
+
+    fn is_tracked(&self, payment: &Payment) -> bool {
+        self.payments
+            .iter()
+            .any(|tracked| tracked.txid == payment.txid && tracked.address == payment.address)
+    }
+}
@@ -0,0 +1,309 @@
+use ed25519_dalek::SigningKey;
+use rustbucks::{
+    mine::{fill_block, proof_of_work},
+    model::{
+        amount::Amount,
+        chain_spec::{ChainSpec, LedgerModel},
+        mempool::MempoolError,
+        node::Node,
+        signature::key_address,
+    },
+    wallet::{
+        htlc::secret_hash,
+        swap::{SwapChain, SwapCoordinator, SwapError, SwapState, SwapSubmission, SwapTerms},
+    },
+};
+
+const SECRET: &[u8] = b"correct horse battery staple";
+
+fn alice() -> SigningKey {
+    SigningKey::from_bytes(&[1; 32])
+}
+
+fn bob() -> SigningKey {
+    SigningKey::from_bytes(&[2; 32])
+}
+
+fn address(key: &SigningKey) -> String {
+    key_address(&key.verifying_key())
+}
+
+// two separate regtest networks, alice has coins on one and bob on the other
+struct Chains {
+    initiator: Node,
+    participant: Node,
+}
+
+impl Chains {
+    fn new() -> Self {
+        let initiator = ChainSpec::regtest()
+            .with_genesis_allocations(vec![(address(&alice()), Amount::new(1_000_000))]);
+        let participant = ChainSpec::regtest()
+            .with_ledger(LedgerModel::Utxo)
+            .with_genesis_allocations(vec![(address(&bob()), Amount::new(1_000_000))]);
+        Chains {
+            initiator: Node::with_spec(&initiator),
+            participant: Node::with_spec(&participant),
+        }
+    }
+
+    async fn poll(&mut self, swap: &mut SwapCoordinator) {
+        let submissions = self.requests(swap);
+        self.submit(submissions).await;
+    }
+
+    // what the swap asks for, without passing it on
+    fn requests(&self, swap: &mut SwapCoordinator) -> Vec<SwapSubmission> {
+        swap.poll(&self.initiator.blockchain, &self.participant.blockchain)
+            .expect("swap should go on")
+    }
+
+    async fn submit(&mut self, submissions: Vec<SwapSubmission>) {
+        for SwapSubmission { chain, transaction } in submissions {
+            let node = match chain {
+                SwapChain::Initiator => &mut self.initiator,
+                SwapChain::Participant => &mut self.participant,
+            };
+            // asking again for something the node still has is fine
+            match node.submit_transaction(transaction).await {
+                Ok(()) | Err(MempoolError::AlreadyPending) => {}
+                Err(e) => panic!("invalid transaction: {:?}", e),
+            }
+        }
+    }
+
+    // a block on each chain with whatever is ready to go in
+    async fn mine(&mut self) {
+        for node in [&mut self.initiator, &mut self.participant] {
+            let height = node.blockchain.tip().index + 1;
+            let candidates = node
+                .mempool
+                .block_candidates(height, node.blockchain.median_time_past());
+            let mut block = fill_block(&node.blockchain, candidates, Some("miner"));
+            proof_of_work(&mut block, &node.blockchain.target_hash_prefix);
+            node.submit_mined_block(block).await.expect("valid block");
+        }
+    }
+}
+
+fn terms() -> SwapTerms {
+    SwapTerms {
+        hash: secret_hash(SECRET),
+        initiator: hex::encode(alice().verifying_key().as_bytes()),
+        participant: hex::encode(bob().verifying_key().as_bytes()),
+        initiator_amount: Amount::new(300_000),
+        participant_amount: Amount::new(200_000),
+        initiator_refund_height: 10,
+        participant_refund_height: 6,
+        initiator_start_height: 0,
+        participant_start_height: 0,
+        initiator_block_secs: 600,
+        participant_block_secs: 600,
+    }
+}
+
+#[test]
+pub fn swap_terms_should_leave_the_participant_time_to_claim() {
+    assert!(terms().check().is_ok());
+    let unsafe_terms = [
+        // only just REFUND_MARGIN blocks after
+        SwapTerms {
+            initiator_refund_height: 8,
+            ..terms()
+        },
+        // the same heights, but the participant's chain takes twice as long to get there
+        SwapTerms {
+            participant_block_secs: 1200,
+            ..terms()
+        },
+        // the initiator's chain was already most of the way there
+        SwapTerms {
+            initiator_start_height: 5,
+            ..terms()
+        },
+        SwapTerms {
+            participant_refund_height: 0,
+            ..terms()
+        },
+        SwapTerms {
+            initiator_block_secs: 0,
+            ..terms()
+        },
+    ];
+    for terms in unsafe_terms {
+        assert!(matches!(
+            SwapCoordinator::initiator(terms.clone(), SECRET, alice()),
+            Err(SwapError::UnsafeTerms)
+        ));
+        assert!(matches!(
+            SwapCoordinator::participant(terms, bob()),
+            Err(SwapError::UnsafeTerms)
+        ));
+    }
+}
+
+#[tokio::test]
+pub async fn swap_should_trade_coins_across_two_chains() {
+    assert!(matches!(
+        SwapCoordinator::initiator(terms(), b"guess", alice()),
+        Err(SwapError::WrongSecret)
+    ));
+    assert!(matches!(
+        SwapCoordinator::participant(terms(), alice()),
+        Err(SwapError::WrongKey)
+    ));
+
+    let mut chains = Chains::new();
+    let mut initiator = SwapCoordinator::initiator(terms(), SECRET, alice()).expect("valid terms");
+    let mut participant = SwapCoordinator::participant(terms(), bob()).expect("valid terms");
+    for _ in 0..10 {
+        chains.poll(&mut initiator).await;
+        chains.poll(&mut participant).await;
+        chains.mine().await;
+        if initiator.is_finished() && participant.is_finished() {
+            break;
+        }
+    }
+
+    assert_eq!(initiator.state(), SwapState::Completed);
+    assert_eq!(participant.state(), SwapState::Completed);
+    // bob only ever saw the secret on chain
+    assert_eq!(participant.secret(), Some(SECRET));
+    let (a, b) = (&chains.initiator.blockchain, &chains.participant.blockchain);
+    assert!(terms().initiator_htlc().balance(a).is_zero());
+    assert!(terms().participant_htlc().balance(b).is_zero());
+
+    // everybody ends up with the other's coins, less what the contracts paid in fees
+    let bobs = a.balance_of(&address(&bob()));
+    assert!(bobs < Amount::new(300_000) && bobs > Amount::new(299_000));
+    let alices = b.balance_of(&address(&alice()));
+    assert!(alices < Amount::new(200_000) && alices > Amount::new(199_000));
+    assert!(a.balance_of(&address(&alice())) < Amount::new(700_000));
+    assert!(b.balance_of(&address(&bob())) < Amount::new(800_000));
+}
+
+#[tokio::test]
+pub async fn swap_should_refund_both_sides_when_the_secret_never_comes_out() {
+    let mut chains = Chains::new();
+    let mut initiator = SwapCoordinator::initiator(terms(), SECRET, alice()).expect("valid terms");
+    let mut participant = SwapCoordinator::participant(terms(), bob()).expect("valid terms");
+
+    // alice locks up her coins and goes quiet once bob has locked his
+    while participant.state() == SwapState::Waiting {
+        chains.poll(&mut initiator).await;
+        chains.poll(&mut participant).await;
+        chains.mine().await;
+    }
+    assert_eq!(initiator.state(), SwapState::Locked);
+
+    for _ in 0..15 {
+        chains.poll(&mut participant).await;
+        if participant.state() == SwapState::Refunded {
+            // alice turns up too late, bob's contract is empty so she can't claim
+            chains.poll(&mut initiator).await;
+        }
+        chains.mine().await;
+        if initiator.is_finished() && participant.is_finished() {
+            break;
+        }
+    }
+
+    assert_eq!(participant.state(), SwapState::Refunded);
+    assert_eq!(initiator.state(), SwapState::Refunded);
+    assert_eq!(participant.secret(), None);
+    let (a, b) = (&chains.initiator.blockchain, &chains.participant.blockchain);
+    assert!(a.tip().index >= terms().initiator_refund_height);
+    // all they're out is the fees
+    assert!(a.balance_of(&address(&alice())) > Amount::new(998_000));
+    assert!(b.balance_of(&address(&bob())) > Amount::new(998_000));
+    assert!(a.balance_of(&address(&bob())).is_zero());
+    assert!(b.balance_of(&address(&alice())).is_zero());
+}
+
+#[tokio::test]
+pub async fn swap_should_ask_for_a_claim_again_if_it_goes_missing() {
+    let mut chains = Chains::new();
+    let mut initiator = SwapCoordinator::initiator(terms(), SECRET, alice()).expect("valid terms");
+    let mut participant = SwapCoordinator::participant(terms(), bob()).expect("valid terms");
+
+    // alice's claim never makes it to a node
+    let lost = loop {
+        let submissions = chains.requests(&mut initiator);
+        if initiator.state() == SwapState::Claimed {
+            break submissions;
+        }
+        chains.submit(submissions).await;
+        chains.poll(&mut participant).await;
+        chains.mine().await;
+    };
+    assert_eq!(lost.len(), 1);
+    assert!(chains.requests(&mut initiator).is_empty());
+
+    // a block without it is when to ask again
+    chains.mine().await;
+    assert_eq!(chains.requests(&mut initiator), lost);
+    assert!(chains.requests(&mut initiator).is_empty());
+    chains.submit(lost).await;
+
+    for _ in 0..5 {
+        chains.mine().await;
+        chains.poll(&mut initiator).await;
+        chains.poll(&mut participant).await;
+        if initiator.is_finished() && participant.is_finished() {
+            break;
+        }
+    }
+    assert_eq!(initiator.state(), SwapState::Completed);
+    assert_eq!(participant.state(), SwapState::Completed);
+}
+
+#[tokio::test]
+pub async fn swap_should_refund_when_a_claim_never_confirms() {
+    let mut chains = Chains::new();
+    let mut initiator = SwapCoordinator::initiator(terms(), SECRET, alice()).expect("valid terms");
+    let mut participant = SwapCoordinator::participant(terms(), bob()).expect("valid terms");
+    let bobs_contract = terms().participant_htlc();
+
+    // alice's claims keep getting dropped, so bob never sees the secret
+    let mut claims = 0;
+    let mut states = vec![initiator.state()];
+    for _ in 0..20 {
+        let submissions = chains.requests(&mut initiator);
+        let (lost, kept): (Vec<SwapSubmission>, Vec<SwapSubmission>) = submissions
+            .into_iter()
+            .partition(|submission| submission.chain == SwapChain::Participant);
+        // once bob's refund has emptied his contract there's nothing left to claim
+        if bobs_contract
+            .balance(&chains.participant.blockchain)
+            .is_zero()
+        {
+            assert!(lost.is_empty());
+        }
+        claims += lost.len();
+        chains.submit(kept).await;
+        chains.poll(&mut participant).await;
+        chains.mine().await;
+        if states.last() != Some(&initiator.state()) {
+            states.push(initiator.state());
+        }
+        if initiator.is_finished() && participant.is_finished() {
+            break;
+        }
+    }
+
+    assert!(claims > 1);
+    assert_eq!(
+        states,
+        vec![
+            SwapState::Waiting,
+            SwapState::Locked,
+            SwapState::Claimed,
+            SwapState::Refunding,
+            SwapState::Refunded,
+        ]
+    );
+    assert_eq!(participant.state(), SwapState::Refunded);
+    let (a, b) = (&chains.initiator.blockchain, &chains.participant.blockchain);
+    assert!(a.balance_of(&address(&alice())) > Amount::new(998_000));
+    assert!(b.balance_of(&address(&bob())) > Amount::new(998_000));
+}
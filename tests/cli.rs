@@ -0,0 +1,420 @@
+use std::{net::SocketAddr, path::Path, time::Duration};
+
+use rustbucks::{
+    model::{chain_spec::ChainSpec, node::Node},
+    net::tcp::{TcpConfig, TcpNetwork},
+    rpc::server::RpcServer,
+};
+use serde_json::Value;
+use tokio::process::Command;
+
+fn localhost() -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], 0))
+}
+
+// runs the binary and hands back what it printed, failing the test if it failed
+async fn rustbucks(data_dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_rustbucks"))
+        .arg("--data-dir")
+        .arg(data_dir)
+        .args(args)
+        .output()
+        .await
+        .expect("binary should run");
+    assert!(
+        output.status.success(),
+        "{:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).expect("utf8 output")
+}
+
+async fn rustbucks_json(data_dir: &Path, args: &[&str]) -> Value {
+    let mut args = args.to_vec();
+    args.push("--json");
+    serde_json::from_str(&rustbucks(data_dir, &args).await).expect("valid json")
+}
+
+#[tokio::test]
+pub async fn cli_should_work_on_the_data_directory() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let dir = dir.path();
+
+    let account = rustbucks_json(
+        dir,
+        &[
+            "wallet",
+            "new",
+            "--name",
+            "savings",
+            "--password",
+            "hunter2",
+        ],
+    )
+    .await;
+    let address = account["address"].as_str().expect("address");
+    let listed = rustbucks_json(dir, &["wallet", "list"]).await;
+    assert_eq!(listed[0]["name"], "savings");
+
+    let mined = rustbucks_json(dir, &["mine", "--address", address, "--blocks", "2"]).await;
+    assert_eq!(mined[1]["height"], 2);
+    let balances = rustbucks_json(dir, &["wallet", "balance"]).await;
+    assert_eq!(balances[0]["address"], address);
+    assert_eq!(balances[0]["formatted"], "100.00000000");
+
+    // watching the same address by its public key, without the keystore
+    let public_key = account["public_key"].as_str().expect("public key");
+    let watched = rustbucks_json(
+        dir,
+        &[
+            "wallet",
+            "watch",
+            "add",
+            "--label",
+            "cold",
+            "--public-key",
+            public_key,
+        ],
+    )
+    .await;
+    assert_eq!(watched["address"], address);
+    rustbucks(
+        dir,
+        &[
+            "wallet",
+            "watch",
+            "add",
+            "--label",
+            "bob",
+            "--address",
+            "bob",
+        ],
+    )
+    .await;
+    let watching = rustbucks_json(dir, &["wallet", "watch", "list"]).await;
+    assert_eq!(watching[0]["label"], "cold");
+    assert_eq!(watching[0]["formatted"], "100.00000000");
+    assert_eq!(watching[1]["balance"], 0);
+
+    let block = rustbucks_json(dir, &["chain", "show", "--height", "1"]).await;
+    assert_eq!(block["hash"], mined[0]["hash"]);
+    assert_eq!(block["confirmations"], 2);
+    let validated = rustbucks_json(dir, &["chain", "validate"]).await;
+    assert_eq!(validated["blocks"], 3);
+
+    let export = dir.join("export.json");
+    rustbucks(
+        dir,
+        &[
+            "chain",
+            "export",
+            "--output",
+            export.to_str().expect("path"),
+        ],
+    )
+    .await;
+    let blocks: Vec<Value> =
+        serde_json::from_slice(&std::fs::read(export).expect("exported")).expect("json");
+    assert_eq!(blocks.len(), 3);
+
+    // a signed transaction can be built without a node and decoded again
+    let sent = rustbucks_json(
+        dir,
+        &[
+            "wallet",
+            "send",
+            "--from",
+            "savings",
+            "--to",
+            "bob",
+            "--amount",
+            "1.5",
+            "--dry-run",
+            "--lock-height",
+            "10",
+            "--password",
+            "hunter2",
+        ],
+    )
+    .await;
+    let encoded = sent["encoded"].as_str().expect("encoded transaction");
+    let decoded = rustbucks_json(dir, &["tx", "decode", encoded]).await;
+    assert_eq!(decoded["txid"], sent["txid"]);
+    assert_eq!(decoded["transaction"]["sender"], address);
+    assert_eq!(decoded["transaction"]["amount"], 150_000_000);
+    assert_eq!(
+        decoded["transaction"]["signatures"]
+            .as_array()
+            .map(Vec::len),
+        Some(1)
+    );
+    assert_eq!(decoded["transaction"]["lock_time"]["Height"], 10);
+
+    let exported = rustbucks_json(
+        dir,
+        &["wallet", "export", "savings", "--password", "hunter2"],
+    )
+    .await;
+    assert_eq!(exported["address"], address);
+
+    // a 2 of 2 address, each signer signs their own copy and the copies get combined
+    let second = rustbucks_json(
+        dir,
+        &[
+            "wallet",
+            "new",
+            "--name",
+            "spending",
+            "--password",
+            "hunter2",
+        ],
+    )
+    .await;
+    let keys = [
+        "--threshold",
+        "2",
+        "--public-key",
+        public_key,
+        "--public-key",
+        second["public_key"].as_str().expect("public key"),
+    ];
+    let shared = rustbucks_json(
+        dir,
+        &[&["wallet", "multisig", "address"][..], &keys].concat(),
+    )
+    .await;
+    let shared = shared["address"].as_str().expect("address");
+    rustbucks(dir, &["mine", "--address", shared]).await;
+    let unsigned = rustbucks_json(
+        dir,
+        &[
+            &[
+                "wallet", "multisig", "spend", "--to", "bob", "--amount", "10",
+            ][..],
+            &keys,
+        ]
+        .concat(),
+    )
+    .await;
+    assert_eq!(
+        unsigned["signing"]["signed"].as_array().map(Vec::len),
+        Some(0)
+    );
+    let unsigned = unsigned["encoded"].as_str().expect("encoded");
+
+    let mut copies = Vec::new();
+    for account in ["savings", "spending"] {
+        let signed = rustbucks_json(
+            dir,
+            &[
+                "tx",
+                "sign",
+                unsigned,
+                "--account",
+                account,
+                "--password",
+                "hunter2",
+            ],
+        )
+        .await;
+        copies.push(signed["encoded"].as_str().expect("encoded").to_string());
+    }
+    let combined = rustbucks_json(dir, &["tx", "combine", &copies[0], &copies[1]]).await;
+    assert_eq!(
+        combined["signing"]["unsigned"].as_array().map(Vec::len),
+        Some(0)
+    );
+    let decoded = rustbucks_json(
+        dir,
+        &[
+            "tx",
+            "decode",
+            combined["encoded"].as_str().expect("encoded"),
+        ],
+    )
+    .await;
+    assert_eq!(decoded["transaction"]["sender"], shared);
+    assert_eq!(
+        decoded["transaction"]["signatures"]
+            .as_array()
+            .map(Vec::len),
+        Some(2)
+    );
+
+    // coins only the savings key can spend, and only past height 2
+    let script = format!("2 CHECKLOCKHEIGHT VERIFY 0x{} CHECKSIG", public_key);
+    let locked = rustbucks_json(dir, &["wallet", "script", "address", &script]).await;
+    assert_eq!(locked["script"], script);
+    let locked = locked["address"].as_str().expect("address");
+    rustbucks(dir, &["mine", "--address", locked]).await;
+    let spend = rustbucks_json(
+        dir,
+        &[
+            "wallet",
+            "script",
+            "spend",
+            "--script",
+            &script,
+            "--to",
+            "bob",
+            "--amount",
+            "10",
+            "--lock-height",
+            "2",
+        ],
+    )
+    .await;
+    let signed = rustbucks_json(
+        dir,
+        &[
+            "tx",
+            "sign",
+            spend["encoded"].as_str().expect("encoded"),
+            "--account",
+            "savings",
+            "--password",
+            "hunter2",
+        ],
+    )
+    .await;
+    let decoded = rustbucks_json(
+        dir,
+        &["tx", "decode", signed["encoded"].as_str().expect("encoded")],
+    )
+    .await;
+    assert_eq!(decoded["transaction"]["sender"], locked);
+    assert_eq!(
+        decoded["transaction"]["signatures"]
+            .as_array()
+            .map(Vec::len),
+        Some(1)
+    );
+
+    // the same again in a container, one copy travelling as a binary file
+    let psbt = rustbucks_json(dir, &["psbt", "create", unsigned, "--meta", "memo=payroll"]).await;
+    let psbt = psbt["base64"].as_str().expect("base64");
+    let file = dir.join("savings.psbt");
+    let file = file.to_str().expect("path");
+    rustbucks(
+        dir,
+        &[
+            "psbt",
+            "sign",
+            psbt,
+            "--account",
+            "savings",
+            "--password",
+            "hunter2",
+            "-o",
+            file,
+        ],
+    )
+    .await;
+    let spending = rustbucks_json(
+        dir,
+        &[
+            "psbt",
+            "sign",
+            psbt,
+            "--account",
+            "spending",
+            "--password",
+            "hunter2",
+        ],
+    )
+    .await;
+    assert_eq!(
+        spending["status"]["signed"].as_array().map(Vec::len),
+        Some(1)
+    );
+    let combined = rustbucks_json(
+        dir,
+        &[
+            "psbt",
+            "combine",
+            file,
+            spending["base64"].as_str().expect("base64"),
+        ],
+    )
+    .await;
+    let combined = combined["base64"].as_str().expect("base64");
+    let inspected = rustbucks_json(dir, &["psbt", "inspect", combined]).await;
+    assert_eq!(inspected["psbt"]["metadata"]["memo"], "payroll");
+    assert_eq!(
+        inspected["status"]["unsigned"].as_array().map(Vec::len),
+        Some(0)
+    );
+    let finalized = rustbucks_json(dir, &["psbt", "finalize", combined]).await;
+    let decoded = rustbucks_json(
+        dir,
+        &[
+            "tx",
+            "decode",
+            finalized["encoded"].as_str().expect("encoded"),
+        ],
+    )
+    .await;
+    assert_eq!(
+        decoded["transaction"]["signatures"]
+            .as_array()
+            .map(Vec::len),
+        Some(2)
+    );
+}
+
+#[tokio::test]
+pub async fn cli_should_talk_to_a_node() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let dir = dir.path();
+    let network = TcpNetwork::start(
+        Node::with_spec(&ChainSpec::default()),
+        TcpConfig::new(localhost()),
+    )
+    .await
+    .expect("network should start");
+    let server = RpcServer::start(network.clone(), localhost())
+        .await
+        .expect("rpc server should start");
+    let rpc = server.local_addr.to_string();
+
+    let account = rustbucks_json(dir, &["wallet", "new", "--password", "hunter2"]).await;
+    let address = account["address"].as_str().expect("address");
+    rustbucks(dir, &["--rpc", &rpc, "mine", "--address", address]).await;
+    let mut balance = Value::Null;
+    for _ in 0..100 {
+        balance = rustbucks_json(dir, &["--rpc", &rpc, "wallet", "balance", address]).await;
+        if balance[0]["balance"].as_u64() > Some(0) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    rustbucks(dir, &["--rpc", &rpc, "mine", "--stop"]).await;
+    assert!(balance[0]["balance"].as_u64() > Some(0));
+
+    let sent = rustbucks_json(
+        dir,
+        &[
+            "--rpc",
+            &rpc,
+            "wallet",
+            "send",
+            "--from",
+            address,
+            "--to",
+            "bob",
+            "--amount",
+            "1",
+            "--password",
+            "hunter2",
+        ],
+    )
+    .await;
+    let pending = network.node.lock().await.mempool.transactions();
+    assert_eq!(pending[0].txid(), sent["txid"].as_str().expect("txid"));
+
+    let validated = rustbucks_json(dir, &["--rpc", &rpc, "chain", "validate"]).await;
+    assert_eq!(validated["valid"], true);
+    server.shutdown();
+    network.shutdown();
+}
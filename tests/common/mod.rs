@@ -0,0 +1,48 @@
+// fixtures the integration tests share, not every test uses all of them
+#![allow(dead_code)]
+
+use ed25519_dalek::SigningKey;
+use rustbucks::model::{
+    amount::Amount, chain_spec::ChainSpec, signature::key_address, transaction::Transaction,
+};
+use sha2::{Digest, Sha256};
+
+// a key for each name the tests pass money around with, so they can still say Timmy
+pub fn key(name: &str) -> SigningKey {
+    SigningKey::from_bytes(&Sha256::digest(name.as_bytes()).into())
+}
+
+pub fn address(name: &str) -> String {
+    key_address(&key(name).verifying_key())
+}
+
+// signs again after a test has changed something
+pub fn signed(name: &str, mut transaction: Transaction) -> Transaction {
+    transaction.sign(&key(name));
+    transaction
+}
+
+// everybody named starts out with the same amount at their key's address
+pub fn spec(names: &[&str], amount: u64) -> ChainSpec {
+    let allocations = names
+        .iter()
+        .map(|name| (address(name), Amount::new(amount)))
+        .collect();
+    ChainSpec::default().with_genesis_allocations(allocations)
+}
+
+// a signed payment on the account ledger from the sender's key. the receiver is
+// taken as it is, anything else a test needs can go on top with .. and be signed again
+pub fn payment(sender: &str, receiver: &str, amount: u64, fee: u64, nonce: u64) -> Transaction {
+    signed(
+        sender,
+        Transaction {
+            sender: address(sender),
+            receiver: receiver.to_string(),
+            amount: Amount::new(amount),
+            fee: Amount::new(fee),
+            nonce,
+            ..Transaction::default()
+        },
+    )
+}
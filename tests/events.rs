@@ -0,0 +1,118 @@
+mod common;
+
+use rustbucks::{
+    mine::mine_pending_transactions,
+    model::{
+        events::{EventBus, EventError, EventSubscription, EvictionReason, NodeEvent},
+        node::Node,
+        transaction::Transaction,
+    },
+    net::ban::BAN_THRESHOLD,
+};
+
+fn node() -> Node {
+    Node::with_spec(&common::spec(&["Timmy"], 100_000))
+}
+
+fn transaction(nonce: u64, fee: u64) -> Transaction {
+    common::payment("Timmy", "Bobby", 100, fee, nonce)
+}
+
+fn drain(subscription: &mut EventSubscription) -> Vec<NodeEvent> {
+    let mut events = Vec::new();
+    while let Some(event) = subscription.try_recv().expect("no lag") {
+        events.push(event);
+    }
+    events
+}
+
+#[tokio::test]
+pub async fn node_should_emit_mempool_and_chain_events() {
+    let mut node = node();
+    let mut events = node.events.subscribe();
+
+    let original = transaction(0, 1);
+    let replacement = transaction(0, 2000);
+    node.submit_transaction(original.clone())
+        .await
+        .expect("valid transaction");
+    node.submit_transaction(replacement.clone())
+        .await
+        .expect("valid replacement");
+
+    let block = mine_pending_transactions(&node.blockchain, vec![replacement.clone()]);
+    node.submit_mined_block(block.clone())
+        .await
+        .expect("valid block");
+
+    assert_eq!(
+        drain(&mut events),
+        vec![
+            NodeEvent::TransactionAccepted(original.clone()),
+            NodeEvent::TransactionAccepted(replacement),
+            NodeEvent::TransactionEvicted {
+                transaction: original,
+                reason: EvictionReason::Replaced,
+            },
+            NodeEvent::BlockConnected(block),
+        ]
+    );
+}
+
+#[tokio::test]
+pub async fn node_should_emit_peer_events() {
+    let mut node = node();
+    let mut events = node.events.subscribe();
+
+    node.peer_connected("a".to_string(), true);
+    node.peer_connected("b".to_string(), false);
+    node.remove_peer(&"a".to_string());
+    node.misbehaving(&"b".to_string(), BAN_THRESHOLD);
+
+    let events = drain(&mut events);
+    assert_eq!(
+        events[..3],
+        [
+            NodeEvent::PeerConnected {
+                id: "a".to_string(),
+                outbound: true,
+            },
+            NodeEvent::PeerConnected {
+                id: "b".to_string(),
+                outbound: false,
+            },
+            NodeEvent::PeerDisconnected {
+                id: "a".to_string()
+            },
+        ]
+    );
+    assert!(matches!(&events[3], NodeEvent::PeerBanned { key, .. } if key == "b"));
+    assert_eq!(
+        events[4],
+        NodeEvent::PeerDisconnected {
+            id: "b".to_string()
+        }
+    );
+    assert_eq!(events.len(), 5);
+}
+
+#[tokio::test]
+pub async fn slow_subscribers_should_not_hold_up_the_node() {
+    let mut node = node();
+    node.events = EventBus::new(2);
+    let mut events = node.events.subscribe();
+
+    for nonce in 0..5 {
+        node.submit_transaction(transaction(nonce, 1))
+            .await
+            .expect("valid transaction");
+    }
+    assert_eq!(node.mempool.len(), 5);
+
+    assert_eq!(events.recv().await, Err(EventError::Lagged(3)));
+    assert_eq!(
+        events.recv().await,
+        Ok(NodeEvent::TransactionAccepted(transaction(3, 1)))
+    );
+    assert_eq!(events.missed, 3);
+}
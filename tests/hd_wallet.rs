@@ -0,0 +1,120 @@
+use rustbucks::{
+    mine::{fill_block, proof_of_work},
+    model::{amount::Amount, blockchain::Blockchain, chain_spec::ChainSpec},
+    wallet::{
+        hd::{HdWallet, DEFAULT_GAP_LIMIT},
+        keystore::{KdfParams, Keystore, WalletError},
+    },
+};
+
+const PHRASE: &str = "legal winner thank year wave sausage worth useful legal winner thank yellow";
+
+fn kdf() -> KdfParams {
+    KdfParams::new().with_log_n(4)
+}
+
+// a coinbase only block paying address
+fn pay(blockchain: &mut Blockchain, address: &str) {
+    let mut block = fill_block(blockchain, Vec::new(), Some(address));
+    proof_of_work(&mut block, &blockchain.target_hash_prefix);
+    blockchain.add_new_block(block).expect("valid block");
+}
+
+#[test]
+pub fn the_same_mnemonic_should_give_the_same_keys() {
+    let first = HdWallet::from_mnemonic(PHRASE, "").expect("valid mnemonic");
+    let second = HdWallet::from_mnemonic(PHRASE, "").expect("valid mnemonic");
+    assert_eq!(first.address(0), second.address(0));
+    assert_ne!(first.address(0), first.address(1));
+
+    // a passphrase grows a whole different tree
+    let protected = HdWallet::from_mnemonic(PHRASE, "hunter2").expect("valid mnemonic");
+    assert_ne!(first.address(0), protected.address(0));
+
+    let generated = HdWallet::generate(24, "").expect("generated");
+    assert_eq!(generated.phrase().split(' ').count(), 24);
+    assert!(matches!(
+        HdWallet::generate(13, ""),
+        Err(WalletError::InvalidMnemonic(_))
+    ));
+}
+
+#[test]
+pub fn scanning_should_stop_at_the_gap_limit() {
+    let wallet = HdWallet::from_mnemonic(PHRASE, "").expect("valid mnemonic");
+    let spec = ChainSpec::default().with_block_reward(Amount::new(50));
+    let mut blockchain = Blockchain::from_spec(&spec);
+    pay(&mut blockchain, &wallet.address(0));
+    pay(&mut blockchain, &wallet.address(2));
+    pay(&mut blockchain, &wallet.address(2));
+    // too far past the last used one to be found
+    pay(&mut blockchain, &wallet.address(3 + DEFAULT_GAP_LIMIT));
+
+    let scan = wallet.scan(&blockchain, DEFAULT_GAP_LIMIT);
+    assert_eq!(
+        scan.used
+            .iter()
+            .map(|used| used.index)
+            .collect::<Vec<u32>>(),
+        vec![0, 2]
+    );
+    assert_eq!(scan.next_index, 3);
+    assert_eq!(scan.balance, Amount::new(150));
+    assert_eq!(scan.used[1].balance, Amount::new(100));
+    assert_eq!(
+        scan.used[1]
+            .history
+            .iter()
+            .map(|entry| entry.height)
+            .collect::<Vec<u64>>(),
+        vec![2, 3]
+    );
+
+    // a bigger gap finds it
+    let scan = wallet.scan(&blockchain, DEFAULT_GAP_LIMIT + 1);
+    assert_eq!(scan.used.len(), 3);
+}
+
+#[test]
+pub fn restoring_should_bring_back_the_keys_and_carry_on_from_them() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let wallet = HdWallet::from_mnemonic(PHRASE, "").expect("valid mnemonic");
+
+    // the original keystore hands out keys from the mnemonic
+    let mut original =
+        Keystore::create(&dir.path().join("original.json"), "a", kdf()).expect("created");
+    original.set_mnemonic(&wallet).expect("set");
+    let first = original
+        .new_account("first")
+        .expect("new account")
+        .address();
+    let second = original
+        .new_account("second")
+        .expect("new account")
+        .address();
+    assert_eq!(first, wallet.address(0));
+    assert_eq!(second, wallet.address(1));
+    assert!(matches!(
+        original.set_mnemonic(&wallet),
+        Err(WalletError::MnemonicAlreadySet)
+    ));
+
+    let mut blockchain = Blockchain::from_spec(&ChainSpec::default());
+    pay(&mut blockchain, &second);
+
+    // everything else was lost, only the words are left
+    let scan = wallet.scan(&blockchain, DEFAULT_GAP_LIMIT);
+    let path = dir.path().join("restored.json");
+    let mut restored = Keystore::create(&path, "b", kdf()).expect("created");
+    restored.restore(&wallet, &scan).expect("restored");
+    assert_eq!(restored.account("hd-1").expect("account").address(), second);
+    assert_eq!(
+        restored.export_key("hd-1").expect("exported"),
+        original.export_key("second").expect("exported")
+    );
+
+    // and the mnemonic survives a reopen, new accounts pick up after the used ones
+    let mut reopened = Keystore::open(&path, "b").expect("opened");
+    let next = reopened.new_account("next").expect("new account").address();
+    assert_eq!(next, wallet.address(2));
+}
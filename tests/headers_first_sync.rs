@@ -0,0 +1,123 @@
+mod common;
+
+use rustbucks::{
+    mine::mine_pending_transactions,
+    model::{block::merkle_root, chain_spec::ChainSpec, node::Node, transaction::Transaction},
+    net::{local::LocalNetwork, message::Message, sync::MAX_BLOCKS_IN_FLIGHT_PER_PEER},
+};
+
+fn spec() -> ChainSpec {
+    common::spec(&["Timmy"], 1_000_000)
+}
+
+async fn node_with_blocks(count: i64) -> Node {
+    let mut node = Node::with_spec(&spec());
+    for i in 0..count {
+        let transaction = common::payment("Timmy", "Bobby", 1, 0, i as u64);
+        let new_block = mine_pending_transactions(&node.blockchain, vec![transaction]);
+        node.submit_mined_block(new_block)
+            .await
+            .expect("valid block");
+    }
+
+    node
+}
+
+// a and b have the same 40 block chain, c is brand new
+async fn network_with_new_node() -> LocalNetwork {
+    let a = node_with_blocks(40).await;
+    let mut b = Node::with_spec(&spec());
+    b.receive_chain(&a.blockchain).await;
+
+    let mut network = LocalNetwork::new();
+    network.add_node("a", a);
+    network.add_node("b", b);
+    network.add_node("c", Node::with_spec(&spec()));
+    network.connect("a", "c");
+    network.connect("b", "c");
+    network
+}
+
+#[tokio::test]
+pub async fn new_node_should_download_the_best_chain() {
+    let mut network = network_with_new_node().await;
+
+    network.node_mut("c").start_initial_block_download();
+    network.run_until_idle().await;
+
+    let c = network.node("c");
+    assert_eq!(c.blockchain, network.node("a").blockchain);
+    assert_eq!(c.sync_progress(), 100.0);
+    assert!(c.header_sync.is_none());
+}
+
+#[tokio::test]
+pub async fn new_node_should_fetch_bodies_from_multiple_peers() {
+    let mut network = network_with_new_node().await;
+    let headers = network.node("a").blockchain.headers_after(&[], 2000);
+
+    let c = network.node_mut("c");
+    c.handle_message(&"a".to_string(), Message::Headers(headers.clone()))
+        .await;
+    c.handle_message(&"b".to_string(), Message::Headers(headers))
+        .await;
+
+    let requests = c.drain_outbox();
+    let requested_from = |peer: &str| -> usize {
+        requests
+            .iter()
+            .filter(|(to, _)| to == peer)
+            .map(|(_, message)| match message {
+                Message::GetData(items) => items.len(),
+                _ => 0,
+            })
+            .sum()
+    };
+
+    assert_eq!(requested_from("a"), MAX_BLOCKS_IN_FLIGHT_PER_PEER);
+    assert_eq!(requested_from("b"), MAX_BLOCKS_IN_FLIGHT_PER_PEER);
+    assert_eq!(c.sync_progress(), 0.0);
+}
+
+#[tokio::test]
+pub async fn sync_progress_should_reflect_downloaded_bodies() {
+    let mut network = network_with_new_node().await;
+    let a_blocks = network.node("a").blockchain.chain.clone();
+    let headers = network.node("a").blockchain.headers_after(&[], 2000);
+
+    let c = network.node_mut("c");
+    c.handle_message(&"a".to_string(), Message::Headers(headers))
+        .await;
+    for block in a_blocks[1..=10].iter() {
+        c.handle_message(&"a".to_string(), Message::Block(block.clone()))
+            .await;
+    }
+
+    // bodies that extend our tip get connected as soon as they arrive
+    assert_eq!(c.sync_progress(), 25.0);
+    assert_eq!(c.blockchain.tip().index, 10);
+}
+
+#[tokio::test]
+pub async fn new_node_should_ignore_headers_without_proof_of_work() {
+    let mut network = network_with_new_node().await;
+    let mut headers = network.node("a").blockchain.headers_after(&[], 2000);
+    headers[5].nonce += 1;
+
+    let c = network.node_mut("c");
+    c.handle_message(&"a".to_string(), Message::Headers(headers))
+        .await;
+
+    assert!(c.header_sync.is_none());
+    assert!(c.drain_outbox().is_empty());
+}
+
+#[test]
+pub fn repeating_the_last_transaction_should_change_the_block_hash() {
+    let transaction = |nonce| common::payment("Timmy", "Bobby", 1, 0, nonce);
+    let transactions: Vec<Transaction> = (0..3).map(transaction).collect();
+    let mut repeated = transactions.clone();
+    repeated.push(transaction(2));
+
+    assert_ne!(merkle_root(&transactions), merkle_root(&repeated));
+}
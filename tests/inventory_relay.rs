@@ -0,0 +1,174 @@
+mod common;
+
+use rustbucks::{
+    mine::mine_pending_transactions,
+    model::{amount::Amount, chain_spec::ChainSpec, node::Node, transaction::Transaction},
+    net::{inventory::Inventory, local::LocalNetwork, message::Message},
+};
+
+// everybody in these tests starts out with plenty to spend
+fn spec() -> ChainSpec {
+    common::spec(&["Timmy", "Spock"], 1_000_000)
+}
+
+fn transaction(nonce: u64, sender: &str, receiver: &str) -> Transaction {
+    common::payment(sender, receiver, 100, 0, nonce)
+}
+
+// a - b - c, nobody is connected to everybody
+fn line_network() -> LocalNetwork {
+    let mut network = LocalNetwork::new();
+    network.add_node("a", Node::with_spec(&spec()));
+    network.add_node("b", Node::with_spec(&spec()));
+    network.add_node("c", Node::with_spec(&spec()));
+    network.connect("a", "b");
+    network.connect("b", "c");
+    network
+}
+
+#[tokio::test]
+pub async fn transactions_and_blocks_should_relay_across_the_network() {
+    let mut network = line_network();
+    let new_transaction = transaction(0, "Timmy", "Bobby");
+
+    network
+        .node_mut("a")
+        .submit_transaction(new_transaction.clone())
+        .await
+        .expect("valid transaction");
+    network.run_until_idle().await;
+
+    assert!(network.node("c").mempool.contains(&new_transaction));
+
+    let new_block = {
+        let a = network.node("a");
+        mine_pending_transactions(&a.blockchain, a.mempool.transactions())
+    };
+    let res = network
+        .node_mut("a")
+        .submit_mined_block(new_block.clone())
+        .await;
+    assert_eq!(Ok(()), res);
+    network.run_until_idle().await;
+
+    for id in ["a", "b", "c"] {
+        let node = network.node(id);
+        assert_eq!(node.blockchain.tip(), &new_block);
+        assert!(node.mempool.is_empty());
+    }
+}
+
+#[tokio::test]
+pub async fn replacements_should_relay_across_the_network() {
+    let mut network = line_network();
+    let stuck = transaction(0, "Timmy", "Bobby");
+    let replacement = common::signed(
+        "Timmy",
+        Transaction {
+            fee: Amount::new(1000),
+            ..stuck.clone()
+        },
+    );
+
+    network
+        .node_mut("a")
+        .submit_transaction(stuck.clone())
+        .await
+        .expect("valid transaction");
+    network.run_until_idle().await;
+    network
+        .node_mut("c")
+        .submit_transaction(replacement.clone())
+        .await
+        .expect("pays enough to replace it");
+    network.run_until_idle().await;
+
+    for id in ["a", "b", "c"] {
+        let node = network.node(id);
+        assert_eq!(node.mempool.transactions(), vec![replacement.clone()]);
+    }
+}
+
+#[tokio::test]
+pub async fn node_should_only_request_an_item_once() {
+    let mut node = Node::with_spec(&spec());
+    node.add_peer("a".to_string());
+    node.add_peer("b".to_string());
+
+    let item = Inventory::Transaction(transaction(0, "Timmy", "Bobby").txid());
+    node.handle_message(&"a".to_string(), Message::Inv(vec![item.clone()]))
+        .await;
+    node.handle_message(&"b".to_string(), Message::Inv(vec![item.clone()]))
+        .await;
+
+    assert_eq!(
+        node.drain_outbox(),
+        vec![("a".to_string(), Message::GetData(vec![item.clone()]))]
+    );
+
+    // both peers already know about it, so there is nobody to tell
+    node.handle_message(
+        &"a".to_string(),
+        Message::Transaction(transaction(0, "Timmy", "Bobby")),
+    )
+    .await;
+    assert!(node.drain_outbox().is_empty());
+}
+
+#[tokio::test]
+pub async fn node_should_not_announce_items_back_to_peers_that_know_them() {
+    let mut network = line_network();
+    network
+        .node_mut("b")
+        .submit_transaction(transaction(0, "Timmy", "Bobby"))
+        .await
+        .expect("valid transaction");
+
+    // b announces to a and c, each asks for it once and gets it once.
+    // neither of them has anyone new to tell
+    assert_eq!(network.run_until_idle().await, 6);
+}
+
+#[tokio::test]
+pub async fn node_should_reorganize_onto_a_longer_relayed_fork() {
+    let mut network = line_network();
+    network.disconnect("b", "c");
+
+    let a_transaction = transaction(0, "Timmy", "Bobby");
+    let new_block =
+        mine_pending_transactions(&network.node("a").blockchain, vec![a_transaction.clone()]);
+    network
+        .node_mut("a")
+        .submit_mined_block(new_block)
+        .await
+        .expect("valid block");
+    network.run_until_idle().await;
+
+    // c builds a longer chain while it can't hear from anybody
+    for i in 0..2 {
+        let c = network.node_mut("c");
+        let new_block =
+            mine_pending_transactions(&c.blockchain, vec![transaction(i, "Spock", "Kirk")]);
+        c.submit_mined_block(new_block).await.expect("valid block");
+    }
+
+    // once they're connected again c tells b about its tip
+    network.connect("b", "c");
+    let c_tip = network.node("c").blockchain.tip().clone();
+    network
+        .node_mut("b")
+        .handle_message(
+            &"c".to_string(),
+            Message::Inv(vec![Inventory::Block(c_tip.hash())]),
+        )
+        .await;
+    network.run_until_idle().await;
+
+    for id in ["a", "b", "c"] {
+        assert_eq!(network.node(id).blockchain.tip(), &c_tip);
+    }
+
+    // the transaction from the abandoned fork needs to be mined again
+    assert!(network.node("a").mempool.contains(&a_transaction));
+    assert!(network.node("b").mempool.contains(&a_transaction));
+}
@@ -1,2 +1,3 @@
+mod common;
 mod one_node;
 mod two_node;
@@ -0,0 +1,116 @@
+mod common;
+
+use rustbucks::{
+    mine::mine_pending_transactions,
+    model::{
+        amount::Amount,
+        blockchain::ConfirmedTransaction,
+        chain_spec::ChainSpec,
+        mempool::MempoolError,
+        node::{Node, TransactionStatus},
+        transaction::Transaction,
+    },
+};
+
+fn spec() -> ChainSpec {
+    common::spec(&["Timmy"], 100)
+}
+
+fn transaction(nonce: u64, amount: u64) -> Transaction {
+    common::payment("Timmy", "Bobby", amount, 1, nonce)
+}
+
+#[tokio::test]
+pub async fn node_should_explain_why_it_rejected_a_transaction() {
+    let mut node = Node::with_spec(&spec());
+
+    assert_eq!(node.submit_transaction(transaction(0, 50)).await, Ok(()));
+    assert_eq!(
+        node.submit_transaction(transaction(0, 50)).await,
+        Err(MempoolError::AlreadyPending)
+    );
+    // 51 of the 100 are already spoken for
+    assert_eq!(
+        node.submit_transaction(transaction(1, 49)).await,
+        Err(MempoolError::InsufficientFunds)
+    );
+
+    let new_block = mine_pending_transactions(&node.blockchain, node.mempool.transactions());
+    node.submit_mined_block(new_block)
+        .await
+        .expect("valid block");
+
+    assert_eq!(
+        node.submit_transaction(transaction(0, 50)).await,
+        Err(MempoolError::AlreadyConfirmed)
+    );
+    assert_eq!(
+        node.blockchain.balance_of(&common::address("Timmy")),
+        Amount::new(49)
+    );
+    assert_eq!(node.blockchain.balance_of("Bobby"), Amount::new(50));
+}
+
+#[tokio::test]
+pub async fn pending_transactions_should_be_dropped_once_a_block_spends_the_funds() {
+    let mut node = Node::with_spec(&spec());
+    node.submit_transaction(transaction(0, 90))
+        .await
+        .expect("valid transaction");
+
+    // somebody else's block spends the money first
+    let spent_elsewhere = common::signed(
+        "Timmy",
+        Transaction {
+            receiver: "Sally".to_string(),
+            ..transaction(0, 90)
+        },
+    );
+    let new_block = mine_pending_transactions(&node.blockchain, vec![spent_elsewhere]);
+    node.submit_mined_block(new_block)
+        .await
+        .expect("valid block");
+
+    assert!(node.mempool.is_empty());
+}
+
+#[tokio::test]
+pub async fn node_should_look_up_transactions_by_txid() {
+    let mut node = Node::with_spec(&spec());
+    let payment = transaction(0, 10);
+    let txid = payment.txid();
+    assert_eq!(node.get_transaction(&txid), None);
+
+    node.submit_transaction(payment.clone())
+        .await
+        .expect("valid transaction");
+    assert_eq!(
+        node.get_transaction(&txid),
+        Some(TransactionStatus::Pending(payment.clone()))
+    );
+
+    let new_block = mine_pending_transactions(&node.blockchain, node.mempool.transactions());
+    let block_hash = new_block.hash();
+    node.submit_mined_block(new_block)
+        .await
+        .expect("valid block");
+    let next_block = mine_pending_transactions(&node.blockchain, vec![transaction(1, 10)]);
+    node.submit_mined_block(next_block)
+        .await
+        .expect("valid block");
+
+    assert_eq!(
+        node.get_transaction(&txid),
+        Some(TransactionStatus::Confirmed(ConfirmedTransaction {
+            transaction: payment,
+            block_hash,
+            height: 1,
+            confirmations: 2,
+        }))
+    );
+
+    // the index forgets about blocks once they're disconnected
+    node.blockchain.disconnect_tip();
+    node.blockchain.disconnect_tip();
+    assert_eq!(node.get_transaction(&txid), None);
+}
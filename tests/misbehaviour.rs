@@ -0,0 +1,214 @@
+mod common;
+
+use std::{net::SocketAddr, time::Duration};
+
+use rustbucks::{
+    mine::mine_pending_transactions,
+    model::{chain_spec::ChainSpec, node::Node, transaction::Transaction},
+    net::{
+        ban::{ban_key, BAN_THRESHOLD},
+        local::LocalNetwork,
+        message::Message,
+        tcp::{read_message, write_message, TcpConfig, TcpNetwork},
+    },
+};
+use tokio::{net::TcpStream, time::timeout};
+
+fn spec() -> ChainSpec {
+    common::spec(&["Timmy"], 1_000_000)
+}
+
+fn transaction(nonce: u64) -> Transaction {
+    common::payment("Timmy", "Bobby", 1, 0, nonce)
+}
+
+fn network() -> LocalNetwork {
+    let mut network = LocalNetwork::new();
+    network.add_node("honest", Node::with_spec(&spec()));
+    network.add_node("evil", Node::with_spec(&spec()));
+    network.connect("honest", "evil");
+    network
+}
+
+#[tokio::test]
+pub async fn peer_sending_block_without_proof_should_be_banned() {
+    let mut network = network();
+    let mut bad_block =
+        mine_pending_transactions(&network.node("evil").blockchain, vec![transaction(0)]);
+    while bad_block
+        .hash()
+        .starts_with(&network.node("evil").blockchain.target_hash_prefix)
+    {
+        bad_block.nonce += 1;
+    }
+
+    network
+        .node_mut("evil")
+        .outbox
+        .push_back(("honest".to_string(), Message::Block(bad_block)));
+    network.run_until_idle().await;
+
+    let honest = network.node("honest");
+    assert!(!honest.peers.contains_key("evil"));
+    assert!(!network.node("evil").peers.contains_key("honest"));
+    assert!(honest
+        .ban_list
+        .is_banned("evil", chrono::Utc::now().timestamp()));
+}
+
+#[tokio::test]
+pub async fn banned_peer_should_not_be_able_to_reconnect() {
+    let mut network = network();
+    network
+        .node_mut("honest")
+        .misbehaving(&"evil".to_string(), BAN_THRESHOLD);
+    network.run_until_idle().await;
+
+    let honest = network.node_mut("honest");
+    honest.peer_connected("evil".to_string(), false);
+
+    assert!(!honest.peers.contains_key("evil"));
+    assert_eq!(honest.drain_disconnects(), vec!["evil".to_string()]);
+}
+
+#[tokio::test]
+pub async fn minor_misbehaviour_should_add_up() {
+    let mut network = network();
+    let evil = network.node("evil");
+    let new_block = mine_pending_transactions(&evil.blockchain, vec![transaction(0)]);
+    // a header that builds on genesis but claims to be much further along
+    let mut header = new_block.header();
+    header.index = 5;
+
+    let honest = network.node_mut("honest");
+    honest
+        .handle_message(&"evil".to_string(), Message::Headers(vec![header.clone()]))
+        .await;
+    assert_eq!(honest.misbehaviour_of(&"evil".to_string()), 50);
+    assert!(!honest
+        .ban_list
+        .is_banned("evil", chrono::Utc::now().timestamp()));
+
+    honest
+        .handle_message(&"evil".to_string(), Message::Headers(vec![header]))
+        .await;
+    assert!(!honest.peers.contains_key("evil"));
+    assert!(honest
+        .ban_list
+        .is_banned("evil", chrono::Utc::now().timestamp()));
+}
+
+#[tokio::test]
+pub async fn banned_peer_should_not_get_back_in_from_another_port() {
+    let mut honest = Node::with_spec(&spec());
+    honest.peer_connected("10.0.0.2:50000".to_string(), false);
+    honest.misbehaving(&"10.0.0.2:50000".to_string(), BAN_THRESHOLD);
+    assert_eq!(
+        honest.drain_disconnects(),
+        vec!["10.0.0.2:50000".to_string()]
+    );
+
+    honest.peer_connected("10.0.0.2:50001".to_string(), false);
+    assert!(honest.peers.is_empty());
+    assert_eq!(
+        honest.drain_disconnects(),
+        vec!["10.0.0.2:50001".to_string()]
+    );
+}
+
+#[tokio::test]
+pub async fn misbehaviour_should_be_scored_for_each_peer() {
+    let mut honest = Node::with_spec(&spec());
+    let (first, second) = ("10.0.0.2:50000".to_string(), "10.0.0.2:50001".to_string());
+    honest.peer_connected(first.clone(), false);
+    honest.peer_connected(second.clone(), false);
+    honest.misbehaving(&first, BAN_THRESHOLD / 2);
+    honest.misbehaving(&second, BAN_THRESHOLD / 2);
+    assert_eq!(honest.misbehaviour_of(&first), BAN_THRESHOLD / 2);
+    assert!(!honest
+        .ban_list
+        .is_banned("10.0.0.2", chrono::Utc::now().timestamp()));
+
+    // the ban is for where they connect from so it takes both of them
+    honest.misbehaving(&first, BAN_THRESHOLD / 2);
+    assert!(honest
+        .ban_list
+        .is_banned("10.0.0.2", chrono::Utc::now().timestamp()));
+    assert!(honest.peers.is_empty());
+}
+
+#[tokio::test]
+pub async fn peer_claiming_somebody_elses_address_should_not_get_them_banned() {
+    let mut honest = Node::with_spec(&spec());
+    let innocent: SocketAddr = "10.0.0.3:7878".parse().expect("valid address");
+    honest.peer_connected("10.0.0.2:50000".to_string(), false);
+    honest
+        .handle_message(
+            &"10.0.0.2:50000".to_string(),
+            Message::Version {
+                listen_addr: Some(innocent),
+                best_height: 0,
+            },
+        )
+        .await;
+    honest.misbehaving(&"10.0.0.2:50000".to_string(), BAN_THRESHOLD);
+
+    let now = chrono::Utc::now().timestamp();
+    assert!(honest.ban_list.is_banned("10.0.0.2", now));
+    assert!(!honest.ban_list.is_banned(&innocent.ip().to_string(), now));
+    honest.peer_connected(innocent.to_string(), true);
+    assert!(honest.peers.contains_key(&innocent.to_string()));
+}
+
+#[tokio::test]
+pub async fn only_the_misbehaving_one_of_two_local_peers_should_be_banned() {
+    let mut config = TcpConfig::new(SocketAddr::from(([127, 0, 0, 1], 0)));
+    config.target_outbound = 0;
+    let network = TcpNetwork::start(Node::with_spec(&spec()), config)
+        .await
+        .expect("node should start");
+    let mut honest = TcpStream::connect(network.local_addr)
+        .await
+        .expect("connect");
+    read_message(&mut honest).await.expect("version");
+    let mut evil = TcpStream::connect(network.local_addr)
+        .await
+        .expect("connect");
+    read_message(&mut evil).await.expect("version");
+    let honest_id = honest.local_addr().expect("local addr").to_string();
+    let evil_id = evil.local_addr().expect("local addr").to_string();
+
+    let blockchain = network.node.lock().await.blockchain.clone();
+    let mut bad_block = mine_pending_transactions(&blockchain, vec![transaction(0)]);
+    while bad_block.hash().starts_with(&blockchain.target_hash_prefix) {
+        bad_block.nonce += 1;
+    }
+    write_message(&mut evil, &Message::Block(bad_block))
+        .await
+        .expect("write");
+
+    // they get hung up on, whatever else was already on its way
+    let hung_up = timeout(Duration::from_secs(5), async {
+        while read_message(&mut evil).await.is_ok() {}
+    })
+    .await;
+    assert!(hung_up.is_ok());
+
+    let now = chrono::Utc::now().timestamp();
+    {
+        let node = network.node.lock().await;
+        assert!(node.ban_list.is_banned(&ban_key(&evil_id), now));
+        assert!(!node.ban_list.is_banned(&ban_key(&honest_id), now));
+        assert!(node.peers.contains_key(&honest_id));
+    }
+    assert_eq!(network.peer_ids().await, vec![honest_id]);
+
+    // anybody else on this machine still gets in
+    let mut newcomer = TcpStream::connect(network.local_addr)
+        .await
+        .expect("connect");
+    let greeting = timeout(Duration::from_secs(5), read_message(&mut newcomer)).await;
+    assert!(matches!(greeting, Ok(Ok(Message::Version { .. }))));
+
+    network.shutdown();
+}
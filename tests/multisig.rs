@@ -0,0 +1,209 @@
+use std::slice;
+
+use rustbucks::{
+    mine::{fill_block, proof_of_work},
+    model::{
+        amount::Amount, blockchain::BlockchainError, chain_spec::ChainSpec, mempool::MempoolError,
+        multisig::MultisigPolicy, node::Node, transaction::Transaction,
+    },
+    wallet::{
+        builder::TransactionBuilder,
+        keystore::{KdfParams, Keystore, WalletError},
+        multisig::{combine, SigningStatus},
+    },
+};
+
+fn kdf() -> KdfParams {
+    KdfParams::new().with_log_n(4)
+}
+
+// one keystore per signer, the way they'd each have their own
+fn signers(dir: &std::path::Path) -> Vec<Keystore> {
+    ["alice", "bob", "carol"]
+        .iter()
+        .map(|name| {
+            let mut keystore = Keystore::create(&dir.join(format!("{}.json", name)), name, kdf())
+                .expect("created");
+            keystore.new_account(name).expect("new account");
+            keystore
+        })
+        .collect()
+}
+
+fn public_keys(signers: &[Keystore]) -> Vec<String> {
+    signers
+        .iter()
+        .map(|keystore| keystore.accounts()[0].public_key())
+        .collect()
+}
+
+fn signed_by(keystore: &Keystore, transaction: &Transaction) -> Transaction {
+    let mut copy = transaction.clone();
+    let name = keystore.accounts()[0].name.clone();
+    keystore.sign(&name, &mut copy).expect("signed");
+    copy
+}
+
+#[tokio::test]
+pub async fn multisig_spends_should_need_the_threshold_of_signatures() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let signers = signers(dir.path());
+    let policy = MultisigPolicy::new(2, &public_keys(&signers)).expect("valid policy");
+    let spec = ChainSpec::default()
+        .with_genesis_allocations(vec![(policy.address(), Amount::new(1_000_000))]);
+    let mut node = Node::with_spec(&spec);
+
+    let unsigned = TransactionBuilder::multisig(&node.blockchain, &policy)
+        .pay("Bobby", Amount::new(400_000))
+        .build()
+        .expect("built");
+    assert_eq!(unsigned.sender, policy.address());
+
+    // alice and carol sign their own copies
+    let alice = signed_by(&signers[0], &unsigned);
+    let carol = signed_by(&signers[2], &unsigned);
+    assert_eq!(
+        node.submit_transaction(alice.clone()).await,
+        Err(MempoolError::Rejected(BlockchainError::MissingSignature))
+    );
+    // the same key twice is still only one signer
+    assert_eq!(
+        node.submit_transaction(combine(alice.clone(), slice::from_ref(&alice)).expect("combined"))
+            .await,
+        Err(MempoolError::Rejected(BlockchainError::MissingSignature))
+    );
+
+    let status = SigningStatus::of(&alice).expect("multisig");
+    assert_eq!((status.signed.len(), status.missing()), (1, 1));
+    let combined = combine(alice, slice::from_ref(&carol)).expect("combined");
+    let status = SigningStatus::of(&combined).expect("multisig");
+    assert!(status.is_complete());
+    assert_eq!(status.unsigned, vec![signers[1].accounts()[0].public_key()]);
+    // it doesn't matter who puts them together
+    assert_eq!(
+        combine(carol, slice::from_ref(&combined))
+            .expect("combined")
+            .txid(),
+        combined.txid()
+    );
+
+    node.submit_transaction(combined.clone())
+        .await
+        .expect("valid transaction");
+    let mut block = fill_block(&node.blockchain, vec![combined], None);
+    proof_of_work(&mut block, &node.blockchain.target_hash_prefix);
+    node.submit_mined_block(block).await.expect("valid block");
+    assert_eq!(node.blockchain.balance_of("Bobby"), Amount::new(400_000));
+}
+
+#[tokio::test]
+pub async fn multisig_policies_should_match_the_sender() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let signers = signers(dir.path());
+    let keys = public_keys(&signers);
+    let policy = MultisigPolicy::new(2, &keys).expect("valid policy");
+    let spec = ChainSpec::default()
+        .with_genesis_allocations(vec![(policy.address(), Amount::new(1_000_000))]);
+    let mut node = Node::with_spec(&spec);
+    let unsigned = TransactionBuilder::multisig(&node.blockchain, &policy)
+        .pay("Bobby", Amount::new(400_000))
+        .build()
+        .expect("built");
+
+    // a 1 of 3 policy hashes to a different address, so it can't stand in for the real one
+    let mut weaker = unsigned.clone();
+    weaker.multisig = Some(MultisigPolicy::new(1, &keys).expect("valid policy"));
+    // the keystore wouldn't sign that, but somebody with the key could
+    weaker.sign(signers[0].accounts()[0].signing_key());
+    assert_eq!(
+        node.submit_transaction(weaker).await,
+        Err(MempoolError::Rejected(BlockchainError::InvalidMultisig))
+    );
+
+    let mut missing = unsigned.clone();
+    missing.multisig = None;
+    assert_eq!(
+        node.submit_transaction(missing).await,
+        Err(MempoolError::Rejected(BlockchainError::InvalidMultisig))
+    );
+
+    // keys outside the policy can't sign for it
+    let mut outsider =
+        Keystore::create(&dir.path().join("outsider.json"), "x", kdf()).expect("created");
+    outsider.new_account("mallory").expect("new account");
+    let mut copy = unsigned.clone();
+    assert!(matches!(
+        outsider.sign("mallory", &mut copy),
+        Err(WalletError::WrongSender)
+    ));
+
+    let other = TransactionBuilder::multisig(&node.blockchain, &policy)
+        .pay("Billy", Amount::new(1))
+        .build()
+        .expect("built");
+    assert!(matches!(
+        combine(
+            signed_by(&signers[0], &unsigned),
+            &[signed_by(&signers[1], &other)]
+        ),
+        Err(WalletError::MismatchedTransactions)
+    ));
+}
+
+#[tokio::test]
+pub async fn reencoded_signatures_should_not_get_a_new_txid_through() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let signers = signers(dir.path());
+    let policy = MultisigPolicy::new(2, &public_keys(&signers)).expect("valid policy");
+    let spec = ChainSpec::default()
+        .with_genesis_allocations(vec![(policy.address(), Amount::new(1_000_000))]);
+    let mut node = Node::with_spec(&spec);
+    let unsigned = TransactionBuilder::multisig(&node.blockchain, &policy)
+        .pay("Bobby", Amount::new(400_000))
+        .build()
+        .expect("built");
+
+    // every signer chipping in still only leaves the threshold's worth
+    let everybody: Vec<Transaction> = signers
+        .iter()
+        .map(|signer| signed_by(signer, &unsigned))
+        .collect();
+    let combined = combine(everybody[0].clone(), &everybody[1..]).expect("combined");
+    assert_eq!(combined.signatures.len(), 2);
+
+    // each of these still carries signatures that hold up, under a different txid
+    let mut malleated = Vec::new();
+    let mut extra = combined.clone();
+    extra.signatures = everybody
+        .iter()
+        .flat_map(|copy| copy.signatures.clone())
+        .collect();
+    extra
+        .signatures
+        .sort_by(|a, b| a.public_key.cmp(&b.public_key));
+    malleated.push(extra);
+    let mut repeated = combined.clone();
+    repeated
+        .signatures
+        .insert(1, combined.signatures[0].clone());
+    malleated.push(repeated);
+    let mut shouted = combined.clone();
+    shouted.signatures[0].signature = shouted.signatures[0].signature.to_uppercase();
+    malleated.push(shouted);
+    let mut reordered = combined.clone();
+    reordered.signatures.reverse();
+    malleated.push(reordered);
+
+    for transaction in malleated {
+        assert_ne!(transaction.txid(), combined.txid());
+        assert_eq!(
+            node.submit_transaction(transaction).await,
+            Err(MempoolError::Rejected(
+                BlockchainError::NonCanonicalSignatures
+            ))
+        );
+    }
+    node.submit_transaction(combined)
+        .await
+        .expect("valid transaction");
+}
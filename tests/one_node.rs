@@ -1,29 +1,22 @@
-use fixed::types::I32F32;
-use rustbucks::{mine::mine_pending_transactions, model::{node::Node, transaction::Transaction}};
+use crate::common;
+
+use rustbucks::{
+    mine::mine_pending_transactions,
+    model::{chain_spec::ChainSpec, node::Node},
+};
+
+fn spec() -> ChainSpec {
+    common::spec(&["Timmy", "Alice", "Jill"], 1_000_000)
+}
 
 #[tokio::test]
 pub async fn one_node_should_accept_one_block() {
-    let mut node = Node::new();
+    let mut node = Node::with_spec(&spec());
 
     let new_transactions = vec![
-        Transaction {
-            timestamp: 0,
-            sender: "Timmy".to_string(),
-            receiver: "Bobby".to_string(),
-            amount: I32F32::from_num(100),
-        },
-        Transaction {
-            timestamp: 1,
-            sender: "Alice".to_string(),
-            receiver: "Charlie".to_string(),
-            amount: I32F32::from_num(100),
-        },
-        Transaction {
-            timestamp: 2,
-            sender: "Jill".to_string(),
-            receiver: "Jane".to_string(),
-            amount: I32F32::from_num(20),
-        },
+        common::payment("Timmy", "Bobby", 100, 0, 0),
+        common::payment("Alice", "Charlie", 100, 0, 0),
+        common::payment("Jill", "Jane", 20, 0, 0),
     ];
 
     // no need to submit to the node pending transactions for this #[cfg(test)]
@@ -33,3 +26,27 @@ pub async fn one_node_should_accept_one_block() {
 
     assert_eq!(Ok(()), res);
 }
+
+#[tokio::test]
+pub async fn blocks_arriving_before_their_parents_should_connect_once_they_show_up() {
+    let mut node = Node::with_spec(&spec());
+    let mut fork = node.blockchain.clone();
+    let mut blocks = Vec::new();
+    for nonce in 0..3 {
+        let block =
+            mine_pending_transactions(&fork, vec![common::payment("Timmy", "Bobby", 1, 0, nonce)]);
+        fork.add_new_block(block.clone()).expect("valid block");
+        blocks.push(block);
+    }
+
+    // the last two wait around for the first
+    for block in blocks.iter().skip(1).rev() {
+        assert_eq!(node.receive_block(None, block.clone()).await, Ok(false));
+    }
+    assert_eq!(node.side_blocks.len(), 2);
+
+    assert_eq!(node.receive_block(None, blocks[0].clone()).await, Ok(true));
+    assert_eq!(node.blockchain, fork);
+    assert!(node.side_blocks.is_empty());
+    assert!(node.side_children.is_empty());
+}
@@ -0,0 +1,98 @@
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use rustbucks::{
+    model::{chain_spec::ChainSpec, node::Node},
+    net::{
+        address_book::AddressBook,
+        tcp::{TcpConfig, TcpNetwork},
+    },
+};
+use tokio::time::{sleep, Instant};
+
+fn localhost() -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], 0))
+}
+
+async fn connected_to(network: &Arc<TcpNetwork>, addr: SocketAddr) -> bool {
+    let node = network.node.lock().await;
+    node.peers
+        .values()
+        .any(|peer| peer.listen_addr == Some(addr))
+}
+
+async fn wait_for<F, Fut>(mut condition: F)
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = bool>,
+{
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !condition().await {
+        assert!(Instant::now() < deadline, "timed out waiting for condition");
+        sleep(Duration::from_millis(50)).await;
+    }
+}
+
+#[tokio::test]
+pub async fn nodes_should_find_each_other_through_a_seed() {
+    let seed = TcpNetwork::start(Node::new(), TcpConfig::new(localhost()))
+        .await
+        .expect("seed should start");
+    let spec = ChainSpec::default().with_seed_peers(vec![seed.local_addr]);
+
+    let dir = tempfile::tempdir().expect("temp dir");
+    let address_book_path = dir.path().join("addresses");
+
+    let mut config = TcpConfig::new(localhost());
+    config.target_outbound = 2;
+    let b = TcpNetwork::start(Node::with_spec(&spec), config.clone())
+        .await
+        .expect("b should start");
+
+    config.address_book_path = Some(address_book_path.clone());
+    let c = TcpNetwork::start(Node::with_spec(&spec), config)
+        .await
+        .expect("c should start");
+
+    // b and c only know about the seed, they learn about each other through it
+    wait_for(|| connected_to(&c, b.local_addr)).await;
+    wait_for(|| connected_to(&b, c.local_addr)).await;
+
+    // and c remembers b for next time
+    wait_for(|| async {
+        AddressBook::load_or_default(&address_book_path)
+            .entries
+            .contains_key(&b.local_addr)
+    })
+    .await;
+
+    for network in [seed, b, c] {
+        network.shutdown();
+    }
+}
+
+#[tokio::test]
+pub async fn failed_connections_should_count_against_an_address() {
+    // grab a port that nobody is listening on
+    let unused = std::net::TcpListener::bind(localhost())
+        .expect("bind")
+        .local_addr()
+        .expect("local addr");
+
+    let a = TcpNetwork::start(Node::new(), TcpConfig::new(localhost()))
+        .await
+        .expect("a should start");
+
+    assert!(a.connect(unused).await.is_err());
+
+    let node = a.node.lock().await;
+    let entry = node
+        .address_book
+        .entries
+        .get(&unused)
+        .expect("address should be remembered");
+    assert_eq!(entry.failures, 1);
+    assert_eq!(entry.successes, 0);
+    drop(node);
+
+    a.shutdown();
+}
@@ -0,0 +1,232 @@
+use ed25519_dalek::SigningKey;
+use rustbucks::{
+    model::{
+        amount::Amount,
+        chain_spec::ChainSpec,
+        multisig::MultisigPolicy,
+        node::Node,
+        script::{Script, ScriptSpend},
+        signature::key_address,
+        transaction::Transaction,
+    },
+    wallet::{
+        builder::TransactionBuilder,
+        psbt::{PartiallySignedTransaction, PsbtError, PSBT_MAGIC, PSBT_VERSION},
+    },
+};
+
+fn keys() -> Vec<SigningKey> {
+    (1..=3)
+        .map(|seed| SigningKey::from_bytes(&[seed; 32]))
+        .collect()
+}
+
+fn public_key(key: &SigningKey) -> String {
+    hex::encode(key.verifying_key().as_bytes())
+}
+
+// a 2 of 3 address with coins in it, and a payment out of it for the keys to sign
+fn setup() -> (Node, Transaction) {
+    let keys: Vec<String> = keys().iter().map(public_key).collect();
+    let policy = MultisigPolicy::new(2, &keys).expect("valid policy");
+    let spec = ChainSpec::default()
+        .with_genesis_allocations(vec![(policy.address(), Amount::new(1_000_000))]);
+    let node = Node::with_spec(&spec);
+    let unsigned = TransactionBuilder::multisig(&node.blockchain, &policy)
+        .pay("Bobby", Amount::new(400_000))
+        .build()
+        .expect("built");
+    (node, unsigned)
+}
+
+#[tokio::test]
+pub async fn signers_should_pass_it_around_and_finalize_it() {
+    let (mut node, unsigned) = setup();
+    let keys = keys();
+    let psbt = PartiallySignedTransaction::new(unsigned.clone(), None)
+        .expect("created")
+        .with_metadata("memo", "rent for march");
+    assert_eq!(psbt.threshold, 2);
+    assert_eq!(psbt.required_signers.len(), 3);
+
+    // it survives the trip both ways
+    assert_eq!(
+        PartiallySignedTransaction::decode(&psbt.encode()).expect("decoded"),
+        psbt
+    );
+    assert_eq!(
+        PartiallySignedTransaction::from_base64(&psbt.to_base64()).expect("decoded"),
+        psbt
+    );
+
+    // each signer gets a copy, signs it and sends it back
+    let mut first = PartiallySignedTransaction::from_base64(&psbt.to_base64()).expect("decoded");
+    first.sign(&keys[0]).expect("signed");
+    let mut third = PartiallySignedTransaction::decode(&psbt.encode()).expect("decoded");
+    third.sign(&keys[2]).expect("signed");
+    third
+        .metadata
+        .insert("memo".to_string(), "something else".to_string());
+    third
+        .metadata
+        .insert("signed by".to_string(), "carol".to_string());
+    assert!(matches!(
+        first.finalize(),
+        Err(PsbtError::Incomplete { missing: 1 })
+    ));
+
+    let mut combined = first.clone();
+    combined.combine(&third).expect("combined");
+    combined.validate().expect("valid");
+    assert!(combined.status().is_complete());
+    assert_eq!(combined.metadata["memo"], "rent for march");
+    assert_eq!(combined.metadata["signed by"], "carol");
+
+    let transaction = combined.finalize().expect("finalized");
+    assert_eq!(transaction.signatures.len(), 2);
+    assert_eq!(transaction.signing_hash(), unsigned.signing_hash());
+    node.submit_transaction(transaction)
+        .await
+        .expect("valid transaction");
+}
+
+#[test]
+pub fn bad_containers_should_be_refused() {
+    let (_, unsigned) = setup();
+    let psbt = PartiallySignedTransaction::new(unsigned.clone(), None).expect("created");
+
+    let mut outsider = psbt.clone();
+    let mallory = SigningKey::from_bytes(&[9; 32]);
+    assert!(matches!(
+        outsider.sign(&mallory),
+        Err(PsbtError::NotASigner(key)) if key == public_key(&mallory)
+    ));
+
+    // a signature for some other transaction
+    let mut forged = psbt.clone();
+    forged.sign(&keys()[0]).expect("signed");
+    forged.signatures[0].signature = {
+        let mut other = unsigned.clone();
+        other.fee = Amount::new(1);
+        other.sign(&keys()[0]);
+        other.signatures[0].signature.clone()
+    };
+    assert!(matches!(
+        forged.validate(),
+        Err(PsbtError::InvalidSignature(_))
+    ));
+    let mut combined = psbt.clone();
+    assert!(matches!(
+        combined.combine(&forged),
+        Err(PsbtError::InvalidSignature(_))
+    ));
+
+    let mut other = unsigned.clone();
+    other.fee = Amount::new(1);
+    let other = PartiallySignedTransaction::new(other, None).expect("created");
+    assert!(matches!(
+        combined.combine(&other),
+        Err(PsbtError::Mismatched)
+    ));
+
+    // a signer list that lets fewer keys through than the sender needs
+    let mut weaker = psbt.clone();
+    weaker.threshold = 1;
+    assert!(matches!(weaker.validate(), Err(PsbtError::WrongSigners)));
+
+    let mut encoded = psbt.encode();
+    encoded[PSBT_MAGIC.len()] = PSBT_VERSION + 1;
+    assert!(matches!(
+        PartiallySignedTransaction::decode(&encoded),
+        Err(PsbtError::UnknownVersion(_))
+    ));
+    assert!(matches!(
+        PartiallySignedTransaction::decode(&psbt.encode()[1..]),
+        Err(PsbtError::Encoding(_))
+    ));
+    assert!(matches!(
+        PartiallySignedTransaction::from_base64("not base64!"),
+        Err(PsbtError::Encoding(_))
+    ));
+}
+
+#[test]
+pub fn key_addresses_should_need_their_public_key() {
+    let key = SigningKey::from_bytes(&[4; 32]);
+    let transaction = Transaction {
+        sender: key_address(&key.verifying_key()),
+        receiver: "Bobby".to_string(),
+        amount: Amount::new(10),
+        fee: Amount::new(1),
+        nonce: 0,
+        timestamp: 0,
+        ..Transaction::default()
+    };
+
+    assert!(matches!(
+        PartiallySignedTransaction::new(transaction.clone(), None),
+        Err(PsbtError::WrongSigners)
+    ));
+    assert!(matches!(
+        PartiallySignedTransaction::new(transaction.clone(), Some(&public_key(&keys()[0]))),
+        Err(PsbtError::WrongSigners)
+    ));
+
+    let mut psbt =
+        PartiallySignedTransaction::new(transaction, Some(&public_key(&key))).expect("created");
+    psbt.sign(&key).expect("signed");
+    let signed = psbt.finalize().expect("finalized");
+    signed.check_signatures().expect("valid signatures");
+}
+
+#[tokio::test]
+pub async fn script_senders_should_need_the_keys_in_their_script() {
+    let keys = keys();
+    let escrow: Script = format!(
+        "2 0x{} 0x{} 0x{} 3 CHECKMULTISIG",
+        public_key(&keys[0]),
+        public_key(&keys[1]),
+        public_key(&keys[2])
+    )
+    .parse()
+    .expect("valid script");
+    let spec = ChainSpec::default()
+        .with_genesis_allocations(vec![(escrow.address(), Amount::new(1_000_000))]);
+    let mut node = Node::with_spec(&spec);
+    let spend = ScriptSpend {
+        script: escrow,
+        witness: vec![],
+    };
+    let unsigned = TransactionBuilder::script(&node.blockchain, &spend)
+        .pay("Bobby", Amount::new(400_000))
+        .build()
+        .expect("built");
+
+    let mut psbt = PartiallySignedTransaction::new(unsigned.clone(), None).expect("created");
+    let mut expected: Vec<String> = keys.iter().map(public_key).collect();
+    expected.sort();
+    assert_eq!(psbt.required_signers, expected);
+    assert_eq!(psbt.threshold, 2);
+
+    psbt.sign(&keys[2]).expect("signed");
+    assert!(matches!(
+        psbt.finalize(),
+        Err(PsbtError::Incomplete { missing: 1 })
+    ));
+    psbt.sign(&keys[0]).expect("signed");
+    let signed = psbt.finalize().expect("finalized");
+    node.submit_transaction(signed)
+        .await
+        .expect("valid transaction");
+
+    // a plain name has nobody to sign for it
+    let named = Transaction {
+        sender: "Timmy".to_string(),
+        script: None,
+        ..unsigned
+    };
+    assert!(matches!(
+        PartiallySignedTransaction::new(named, None),
+        Err(PsbtError::WrongSigners)
+    ));
+}
@@ -0,0 +1,133 @@
+mod common;
+
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use rustbucks::{
+    model::{chain_spec::ChainSpec, node::Node, transaction::Transaction},
+    net::{
+        inventory::Inventory,
+        message::{Message, MAX_INV_PER_MESSAGE, MAX_MESSAGE_SIZE, OVERSIZED_MESSAGE_SCORE},
+        rate_limit::RateLimits,
+        tcp::{read_message, TcpConfig, TcpNetwork},
+    },
+};
+use tokio::{io::AsyncWriteExt, net::TcpStream, time::timeout};
+
+fn spec() -> ChainSpec {
+    common::spec(&["Timmy"], 1_000_000)
+}
+
+fn transaction(nonce: u64) -> Transaction {
+    common::payment("Timmy", "Bobby", 1, 0, nonce)
+}
+
+#[tokio::test]
+pub async fn flooding_peer_should_be_cut_off_without_affecting_others() {
+    let mut node = Node::with_spec(&spec());
+    node.rate_limits = RateLimits {
+        transactions_per_second: 0.001,
+        transaction_burst: 10.0,
+        ..RateLimits::default()
+    };
+    node.add_peer("flooder".to_string());
+    node.add_peer("honest".to_string());
+
+    for i in 0..100 {
+        node.handle_message(&"flooder".to_string(), Message::Transaction(transaction(i)))
+            .await;
+    }
+    assert_eq!(node.mempool.len(), 10);
+
+    node.handle_message(&"honest".to_string(), Message::Transaction(transaction(10)))
+        .await;
+    assert!(node.mempool.contains(&transaction(10)));
+
+    // going over the limit isn't misbehaviour on its own
+    assert!(node.peers.contains_key("flooder"));
+}
+
+#[tokio::test]
+pub async fn oversized_messages_should_count_as_misbehaviour() {
+    let mut node = Node::with_spec(&spec());
+    node.add_peer("a".to_string());
+
+    let items = (0..=MAX_INV_PER_MESSAGE)
+        .map(|i| Inventory::Transaction(i.to_string()))
+        .collect();
+    node.handle_message(&"a".to_string(), Message::Inv(items))
+        .await;
+
+    assert_eq!(
+        node.misbehaviour_of(&"a".to_string()),
+        OVERSIZED_MESSAGE_SCORE
+    );
+    assert!(node.drain_outbox().is_empty());
+}
+
+#[tokio::test]
+pub async fn read_message_should_refuse_oversized_frames() {
+    let (mut client, mut server) = tokio::io::duplex(64);
+    client
+        .write_u32(MAX_MESSAGE_SIZE as u32 + 1)
+        .await
+        .expect("write length");
+
+    let res = read_message(&mut server).await;
+
+    assert_eq!(
+        res.expect_err("should refuse").kind(),
+        std::io::ErrorKind::InvalidData
+    );
+}
+
+async fn listening_node(config: impl FnOnce(&mut TcpConfig)) -> Arc<TcpNetwork> {
+    let mut tcp_config = TcpConfig::new(SocketAddr::from(([127, 0, 0, 1], 0)));
+    tcp_config.target_outbound = 0;
+    config(&mut tcp_config);
+    TcpNetwork::start(Node::with_spec(&spec()), tcp_config)
+        .await
+        .expect("node should start")
+}
+
+#[tokio::test]
+pub async fn inbound_connections_past_the_cap_should_be_turned_away() {
+    let network = listening_node(|config| config.max_inbound = 1).await;
+
+    // the first one gets a version, the second gets hung up on
+    let mut first = TcpStream::connect(network.local_addr)
+        .await
+        .expect("connect");
+    let greeting = timeout(Duration::from_secs(5), read_message(&mut first)).await;
+    assert!(matches!(greeting, Ok(Ok(Message::Version { .. }))));
+    let mut second = TcpStream::connect(network.local_addr)
+        .await
+        .expect("connect");
+    let greeting = timeout(Duration::from_secs(5), read_message(&mut second)).await;
+    assert!(matches!(greeting, Ok(Err(_))));
+    assert_eq!(network.inbound_count().await, 1);
+
+    network.shutdown();
+}
+
+#[tokio::test]
+pub async fn peer_that_falls_behind_should_be_disconnected() {
+    let network = listening_node(|config| config.outbound_queue_size = 1).await;
+    let mut client = TcpStream::connect(network.local_addr)
+        .await
+        .expect("connect");
+    read_message(&mut client).await.expect("version");
+    let id = client.local_addr().expect("local addr").to_string();
+
+    // nothing gets a chance to write these out before the queue overflows
+    {
+        let mut node = network.node.lock().await;
+        for _ in 0..10 {
+            node.outbox.push_back((id.clone(), Message::GetAddr));
+        }
+    }
+    network.flush().await;
+
+    assert!(network.peer_ids().await.is_empty());
+    assert!(!network.node.lock().await.peers.contains_key(&id));
+    network.shutdown();
+}
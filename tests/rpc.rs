@@ -0,0 +1,194 @@
+mod common;
+
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use rustbucks::{
+    model::{amount::Amount, node::Node, transaction::Transaction},
+    net::tcp::{TcpConfig, TcpNetwork},
+    rpc::{
+        client::{ClientError, RpcClient},
+        message::{
+            BalanceResult, BlockResult, PeerInfo, PendingTransaction, TipResult, METHOD_NOT_FOUND,
+            NOT_FOUND, TRANSACTION_REJECTED,
+        },
+        server::RpcServer,
+    },
+};
+use serde_json::{json, Value};
+use tokio::time::{sleep, Instant};
+
+fn localhost() -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], 0))
+}
+
+async fn start() -> (Arc<TcpNetwork>, Arc<RpcServer>, RpcClient) {
+    let spec = common::spec(&["Timmy"], 1000);
+    let network = TcpNetwork::start(Node::with_spec(&spec), TcpConfig::new(localhost()))
+        .await
+        .expect("network should start");
+    let server = RpcServer::start(network.clone(), localhost())
+        .await
+        .expect("rpc server should start");
+    let client = RpcClient::new(server.local_addr);
+    (network, server, client)
+}
+
+fn transaction(nonce: u64) -> Transaction {
+    common::payment("Timmy", "Bobby", 100, 1, nonce)
+}
+
+fn rpc_error_code(result: Result<Value, ClientError>) -> i64 {
+    match result {
+        Err(ClientError::Rpc(error)) => error.code,
+        other => panic!("expected an rpc error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+pub async fn rpc_should_serve_blocks_and_balances() {
+    let (_network, _server, client) = start().await;
+
+    let tip: TipResult = client.call("get_best_tip", json!(null)).await.expect("tip");
+    assert_eq!(tip.height, 0);
+
+    let by_height: BlockResult = client
+        .call("get_block_by_height", json!({ "height": 0 }))
+        .await
+        .expect("genesis by height");
+    assert_eq!(by_height.hash, tip.hash);
+    assert_eq!(by_height.confirmations, 1);
+
+    // positional params work too
+    let by_hash: BlockResult = client
+        .call("get_block_by_hash", json!([tip.hash]))
+        .await
+        .expect("genesis by hash");
+    assert_eq!(by_hash, by_height);
+
+    let balance: BalanceResult = client
+        .call(
+            "get_balance",
+            json!({ "address": common::address("Timmy") }),
+        )
+        .await
+        .expect("balance");
+    assert_eq!(balance.balance, Amount::new(1000));
+
+    let missing = client
+        .call("get_block_by_height", json!({ "height": 7 }))
+        .await;
+    assert_eq!(rpc_error_code(missing), NOT_FOUND);
+    let unknown = client.call("get_everything", json!(null)).await;
+    assert_eq!(rpc_error_code(unknown), METHOD_NOT_FOUND);
+
+    let peers: Vec<PeerInfo> = client
+        .call("get_peer_info", json!(null))
+        .await
+        .expect("peers");
+    assert!(peers.is_empty());
+}
+
+#[tokio::test]
+pub async fn rpc_should_accept_transactions_and_mine_them() {
+    let (network, _server, client) = start().await;
+
+    let txid: String = client
+        .call(
+            "submit_transaction",
+            json!({ "transaction": transaction(0) }),
+        )
+        .await
+        .expect("valid transaction");
+    assert_eq!(txid, transaction(0).txid());
+
+    let rejected = client
+        .call(
+            "submit_transaction",
+            json!({ "transaction": transaction(0) }),
+        )
+        .await;
+    assert_eq!(rpc_error_code(rejected), TRANSACTION_REJECTED);
+
+    let mempool: Vec<PendingTransaction> = client
+        .call("get_mempool", json!(null))
+        .await
+        .expect("mempool");
+    assert_eq!(mempool.len(), 1);
+    assert_eq!(mempool[0].txid, txid);
+
+    let started: bool = client
+        .call("start_mining", json!(null))
+        .await
+        .expect("start");
+    assert!(started);
+    let again: bool = client
+        .call("start_mining", json!(null))
+        .await
+        .expect("start");
+    assert!(!again);
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let tip: TipResult = client.call("get_best_tip", json!(null)).await.expect("tip");
+        if tip.height == 1 {
+            break;
+        }
+        assert!(Instant::now() < deadline, "timed out waiting for a block");
+        sleep(Duration::from_millis(50)).await;
+    }
+
+    let stopped: bool = client.call("stop_mining", json!(null)).await.expect("stop");
+    assert!(stopped);
+
+    let balance: BalanceResult = client
+        .call("get_balance", json!(["Bobby"]))
+        .await
+        .expect("balance");
+    assert_eq!(balance.balance, Amount::new(100));
+    assert!(network.node.lock().await.mempool.is_empty());
+}
+
+#[tokio::test]
+pub async fn rpc_should_follow_the_json_rpc_spec() {
+    let (_network, server, _client) = start().await;
+
+    let parse_error = server.handle_body(b"{ not json").await.expect("a response");
+    assert_eq!(parse_error["error"]["code"], json!(-32700));
+    assert_eq!(parse_error["id"], Value::Null);
+
+    let wrong_version = server
+        .handle_body(br#"{"jsonrpc": "1.0", "method": "get_best_tip", "id": 3}"#)
+        .await
+        .expect("a response");
+    assert_eq!(wrong_version["error"]["code"], json!(-32600));
+    assert_eq!(wrong_version["id"], json!(3));
+
+    let bad_params = server
+        .handle_body(
+            br#"{"jsonrpc": "2.0", "method": "get_balance", "params": {"who": 1}, "id": "a"}"#,
+        )
+        .await
+        .expect("a response");
+    assert_eq!(bad_params["error"]["code"], json!(-32602));
+
+    // notifications don't get an answer, batches get one per request
+    let notification = server
+        .handle_body(br#"{"jsonrpc": "2.0", "method": "get_best_tip"}"#)
+        .await;
+    assert_eq!(notification, None);
+
+    let batch = server
+        .handle_body(
+            br#"[
+                {"jsonrpc": "2.0", "method": "get_best_tip", "id": 1},
+                {"jsonrpc": "2.0", "method": "get_best_tip"},
+                {"jsonrpc": "2.0", "method": "is_mining", "id": 2}
+            ]"#,
+        )
+        .await
+        .expect("a response");
+    let responses = batch.as_array().expect("an array");
+    assert_eq!(responses.len(), 2);
+    assert_eq!(responses[0]["result"]["height"], json!(0));
+    assert_eq!(responses[1]["result"], json!(false));
+}
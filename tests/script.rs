@@ -0,0 +1,172 @@
+use ed25519_dalek::SigningKey;
+use rustbucks::{
+    mine::{fill_block, proof_of_work},
+    model::{
+        amount::Amount,
+        blockchain::BlockchainError,
+        chain_spec::{ChainSpec, LedgerModel},
+        mempool::MempoolError,
+        node::Node,
+        script::{Op, Script, ScriptError, ScriptSpend, Value},
+        transaction::LockTime,
+    },
+    wallet::builder::TransactionBuilder,
+};
+use sha2::{Digest, Sha256};
+
+fn key(seed: u8) -> SigningKey {
+    SigningKey::from_bytes(&[seed; 32])
+}
+
+fn public_key(key: &SigningKey) -> Value {
+    Value::Bytes(key.verifying_key().as_bytes().to_vec())
+}
+
+async fn mine(node: &mut Node) {
+    let height = node.blockchain.tip().index + 1;
+    let candidates = node
+        .mempool
+        .block_candidates(height, node.blockchain.median_time_past());
+    let mut block = fill_block(&node.blockchain, candidates, Some("miner"));
+    proof_of_work(&mut block, &node.blockchain.target_hash_prefix);
+    node.submit_mined_block(block).await.expect("valid block");
+}
+
+#[tokio::test]
+pub async fn escrow_should_need_two_of_buyer_seller_and_arbiter() {
+    let (buyer, seller, arbiter) = (key(1), key(2), key(3));
+    let escrow = Script::new(vec![
+        Op::Push(Value::Number(2)),
+        Op::Push(public_key(&buyer)),
+        Op::Push(public_key(&seller)),
+        Op::Push(public_key(&arbiter)),
+        Op::Push(Value::Number(3)),
+        Op::CheckMultisig,
+    ]);
+    let spec = ChainSpec::default()
+        .with_genesis_allocations(vec![(escrow.address(), Amount::new(100_000))]);
+    let mut node = Node::with_spec(&spec);
+
+    let spend = ScriptSpend {
+        script: escrow.clone(),
+        witness: vec![],
+    };
+    let unsigned = TransactionBuilder::script(&node.blockchain, &spend)
+        .pay("seller", Amount::new(50_000))
+        .build()
+        .expect("built");
+    assert_eq!(unsigned.sender, escrow.address());
+
+    let mut buyer_only = unsigned.clone();
+    buyer_only.sign(&buyer);
+    assert_eq!(
+        node.submit_transaction(buyer_only.clone()).await,
+        Err(MempoolError::Rejected(BlockchainError::ScriptFailed(
+            ScriptError::Failed
+        )))
+    );
+
+    // somebody else's script can't spend the escrow's coins
+    let mut forged = unsigned.clone();
+    forged.script = Some(ScriptSpend {
+        script: Script::new(vec![Op::Push(Value::Bool(true))]),
+        witness: vec![],
+    });
+    forged.sign(&buyer);
+    assert_eq!(
+        forged.check_signatures(),
+        Err(BlockchainError::InvalidScript)
+    );
+
+    let mut settled = buyer_only;
+    settled.sign(&arbiter);
+    node.submit_transaction(settled.clone())
+        .await
+        .expect("valid transaction");
+    mine(&mut node).await;
+    assert!(node.blockchain.is_confirmed(&settled));
+    assert_eq!(node.blockchain.balance_of("seller"), Amount::new(50_000));
+}
+
+#[tokio::test]
+pub async fn hash_locked_coins_should_go_back_after_a_time_lock() {
+    let (alice, bob) = (key(1), key(2));
+    let secret = b"open sesame".to_vec();
+    let refund_height = 3;
+    // bob with the secret, or alice once the chain is tall enough
+    let swap = Script::new(vec![
+        Op::If,
+        Op::Sha256,
+        Op::Push(Value::Bytes(Sha256::digest(&secret).to_vec())),
+        Op::Equal,
+        Op::Verify,
+        Op::Push(public_key(&bob)),
+        Op::CheckSig,
+        Op::Else,
+        Op::Push(Value::Number(refund_height)),
+        Op::CheckLockHeight,
+        Op::Verify,
+        Op::Push(public_key(&alice)),
+        Op::CheckSig,
+        Op::EndIf,
+    ]);
+    let spec = ChainSpec::default()
+        .with_ledger(LedgerModel::Utxo)
+        .with_genesis_allocations(vec![(swap.address(), Amount::new(100_000))]);
+    let mut node = Node::with_spec(&spec);
+
+    let spending = |witness: Vec<Value>| ScriptSpend {
+        script: swap.clone(),
+        witness,
+    };
+    let mut wrong_secret = TransactionBuilder::script(
+        &node.blockchain,
+        &spending(vec![Value::Bytes(b"guess".to_vec()), Value::Bool(true)]),
+    )
+    .pay("bob", Amount::new(90_000))
+    .build()
+    .expect("built");
+    wrong_secret.sign(&bob);
+    assert_eq!(
+        node.submit_transaction(wrong_secret).await,
+        Err(MempoolError::Rejected(BlockchainError::ScriptFailed(
+            ScriptError::VerifyFailed
+        )))
+    );
+
+    // the refund only passes with a lock time that keeps it out of blocks until then
+    let refund = TransactionBuilder::script(&node.blockchain, &spending(vec![Value::Bool(false)]))
+        .pay("alice", Amount::new(90_000));
+    let mut unlocked = refund.build().expect("built");
+    unlocked.sign(&alice);
+    let locked = refund
+        .with_lock_time(LockTime::Height(refund_height as u64))
+        .build_signed(&alice)
+        .expect("built");
+    assert!(matches!(
+        node.submit_transaction(unlocked).await,
+        Err(MempoolError::Rejected(BlockchainError::ScriptFailed(_)))
+    ));
+    node.submit_transaction(locked.clone())
+        .await
+        .expect("valid transaction");
+    mine(&mut node).await;
+    assert!(!node.blockchain.is_confirmed(&locked));
+
+    // bob shows up with the secret in time, paying enough to replace the refund
+    let claim = TransactionBuilder::script(
+        &node.blockchain,
+        &spending(vec![Value::Bytes(secret), Value::Bool(true)]),
+    )
+    .pay("bob", Amount::new(90_000))
+    .with_fee_rate(10_000)
+    .build_signed(&bob)
+    .expect("built");
+    node.submit_transaction(claim.clone())
+        .await
+        .expect("valid transaction");
+    assert!(!node.mempool.contains(&locked));
+    mine(&mut node).await;
+    assert!(node.blockchain.is_confirmed(&claim));
+    assert_eq!(node.blockchain.balance_of("bob"), Amount::new(90_000));
+}
@@ -0,0 +1,161 @@
+mod common;
+
+use std::{net::SocketAddr, time::Duration};
+
+use futures::{SinkExt, StreamExt};
+use rustbucks::{
+    mine::mine_pending_transactions,
+    model::{node::Node, transaction::Transaction},
+    net::tcp::{TcpConfig, TcpNetwork},
+    rpc::subscriptions::SubscriptionServer,
+};
+use serde_json::{json, Value};
+use tokio::{net::TcpStream, time::timeout};
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+fn localhost() -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], 0))
+}
+
+fn transaction(receiver: &str, nonce: u64) -> Transaction {
+    common::payment("Timmy", receiver, 10, 1, nonce)
+}
+
+async fn next_json(socket: &mut Socket) -> Value {
+    let message = timeout(Duration::from_secs(10), socket.next())
+        .await
+        .expect("timed out waiting for a message")
+        .expect("socket closed")
+        .expect("socket failed");
+    match message {
+        Message::Text(text) => serde_json::from_str(text.as_str()).expect("valid json"),
+        other => panic!("expected text, got {:?}", other),
+    }
+}
+
+async fn subscribe(socket: &mut Socket, topic: Value) -> u64 {
+    let request = json!({ "jsonrpc": "2.0", "method": "subscribe", "params": topic, "id": 0 });
+    socket
+        .send(Message::text(request.to_string()))
+        .await
+        .expect("send");
+    next_json(socket).await["result"]
+        .as_u64()
+        .expect("a subscription id")
+}
+
+// the subscription a notification is for and what it says
+async fn next_notification(socket: &mut Socket) -> (u64, Value) {
+    let notification = next_json(socket).await;
+    assert_eq!(notification["method"], json!("subscription"));
+    let subscription = notification["params"]["subscription"]
+        .as_u64()
+        .expect("a subscription id");
+    (subscription, notification["params"]["result"].clone())
+}
+
+#[tokio::test]
+pub async fn subscribers_should_hear_about_blocks_transactions_and_reorgs() {
+    let spec = common::spec(&["Timmy"], 1000);
+    let network = TcpNetwork::start(Node::with_spec(&spec), TcpConfig::new(localhost()))
+        .await
+        .expect("network should start");
+    let server = SubscriptionServer::start(network.clone(), localhost())
+        .await
+        .expect("subscription server should start");
+    let (mut socket, _) = connect_async(format!("ws://{}", server.local_addr))
+        .await
+        .expect("should connect");
+
+    let paid = transaction("Bobby", 0);
+    let tips = subscribe(&mut socket, json!({ "topic": "new_tips" })).await;
+    let mempool = subscribe(&mut socket, json!({ "topic": "mempool" })).await;
+    let confirmations = subscribe(
+        &mut socket,
+        json!({ "topic": "transaction", "txid": paid.txid() }),
+    )
+    .await;
+    // where it stands right away
+    let (id, status) = next_notification(&mut socket).await;
+    assert_eq!(id, confirmations);
+    assert_eq!(status["confirmations"], json!(0));
+    let bobby = subscribe(
+        &mut socket,
+        json!({ "topic": "address", "address": "Bobby" }),
+    )
+    .await;
+    let reorgs = subscribe(&mut socket, json!({ "topic": "reorgs" })).await;
+
+    // into the mempool
+    let fork = network.node.lock().await.blockchain.clone();
+    network
+        .node
+        .lock()
+        .await
+        .submit_transaction(paid.clone())
+        .await
+        .expect("valid transaction");
+    let (id, pending) = next_notification(&mut socket).await;
+    assert_eq!(id, mempool);
+    assert_eq!(pending["txid"], json!(paid.txid()));
+
+    // and into a block
+    {
+        let mut node = network.node.lock().await;
+        let block = mine_pending_transactions(&node.blockchain, vec![paid.clone()]);
+        node.submit_mined_block(block).await.expect("valid block");
+    }
+    let (id, tip) = next_notification(&mut socket).await;
+    assert_eq!((id, tip["height"].clone()), (tips, json!(1)));
+    let (id, status) = next_notification(&mut socket).await;
+    assert_eq!(
+        (id, status["confirmations"].clone()),
+        (confirmations, json!(1))
+    );
+    let (id, activity) = next_notification(&mut socket).await;
+    assert_eq!(id, bobby);
+    assert_eq!(activity["txid"], json!(paid.txid()));
+    assert_eq!(activity["connected"], json!(true));
+
+    // a longer branch that spends Timmy's nonce on somebody else knocks it back out
+    let mut fork = fork;
+    for nonce in 0..2 {
+        let block = mine_pending_transactions(&fork, vec![transaction("Jill", nonce)]);
+        fork.add_new_block(block.clone()).expect("valid block");
+        network
+            .node
+            .lock()
+            .await
+            .receive_block(None, block)
+            .await
+            .expect("valid block");
+    }
+
+    // the old block goes first
+    let (id, status) = next_notification(&mut socket).await;
+    assert_eq!(
+        (id, status["confirmations"].clone()),
+        (confirmations, json!(0))
+    );
+    let (id, activity) = next_notification(&mut socket).await;
+    assert_eq!((id, activity["connected"].clone()), (bobby, json!(false)));
+    // then the new ones
+    for height in 1..=2 {
+        let (id, tip) = next_notification(&mut socket).await;
+        assert_eq!((id, tip["height"].clone()), (tips, json!(height)));
+    }
+    let (id, reorg) = next_notification(&mut socket).await;
+    assert_eq!(id, reorgs);
+    assert_eq!(reorg["depth"], json!(1));
+    assert_eq!(reorg["tip"]["height"], json!(2));
+
+    // unsubscribing stops the notifications
+    let request = json!({ "jsonrpc": "2.0", "method": "unsubscribe", "params": [tips], "id": 9 });
+    socket
+        .send(Message::text(request.to_string()))
+        .await
+        .expect("send");
+    assert_eq!(next_json(&mut socket).await["result"], json!(true));
+}
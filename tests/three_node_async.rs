@@ -1,10 +1,12 @@
+mod common;
+
 use std::{
+    collections::HashMap,
     sync::{Arc, RwLock},
     thread::{self, sleep, JoinHandle},
 };
 
 use anyhow::anyhow;
-use fixed::types::I32F32;
 use futures::executor::block_on;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
@@ -26,6 +28,8 @@ pub async fn three_node_async_convergence() {
     ];
 
     let mut rng = thread_rng();
+    // each sender's transactions have to count up from zero
+    let mut nonces: HashMap<&str, u64> = HashMap::new();
 
     let transactions: Vec<Transaction> = (0..1000)
         .map(|i| {
@@ -41,12 +45,16 @@ pub async fn three_node_async_convergence() {
                     .ok_or(anyhow!("participant choice failure"))?;
             }
 
-            Ok(Transaction {
-                timestamp: i,
-                sender: sender.to_string(),
-                receiver: receiver.to_string(),
-                amount: I32F32::from_num(100),
-            })
+            let nonce = nonces.entry(sender).or_insert(0);
+            *nonce += 1;
+
+            Ok(common::signed(
+                sender,
+                Transaction {
+                    timestamp: i,
+                    ..common::payment(sender, receiver, 100, 0, *nonce - 1)
+                },
+            ))
         })
         .collect::<Result<Vec<Transaction>, anyhow::Error>>()
         .expect("issue creating transactions");
@@ -55,9 +63,19 @@ pub async fn three_node_async_convergence() {
     // the basic idea here is that transactions are submitted to random nodes
     // and miners submit their blocks to random nodes
     // and in the end all nodes should have the same set of confirmed transactions
-    let a = Arc::new(RwLock::new(Node::new()));
-    let b = Arc::new(RwLock::new(Node::new()));
-    let c = Arc::new(RwLock::new(Node::new()));
+    // everybody starts out with enough to cover every transaction they could be picked for
+    let spec = common::spec(&participant_names, 1_000_000);
+
+    // transactions get submitted a lot faster than they're mined here,
+    // so a node can end up holding a sender's nonces far ahead of its chain
+    let node = || {
+        let mut node = Node::with_spec(&spec);
+        node.mempool.config.max_nonce_gap = transactions.len() as u64;
+        node
+    };
+    let a = Arc::new(RwLock::new(node()));
+    let b = Arc::new(RwLock::new(node()));
+    let c = Arc::new(RwLock::new(node()));
 
     let nodes = vec![a.clone(), b.clone(), c.clone()];
 
@@ -77,7 +95,7 @@ pub async fn three_node_async_convergence() {
             sleep(Duration::from_millis(2));
             println!("submitting transaction");
             let mut lock = node.write().expect("issue getting write lock");
-            block_on(lock.submit_transaction(transaction.clone()));
+            block_on(lock.submit_transaction(transaction.clone())).expect("valid transaction");
         }
     });
 
@@ -92,13 +110,10 @@ pub async fn three_node_async_convergence() {
             let new_block = {
                 let lock = node.read().expect("issue getting read lock");
                 // don't try to mine an empty block
-                if lock.pending_transactions.is_empty() {
+                if lock.mempool.is_empty() {
                     continue;
                 }
-                mine_pending_transactions(
-                    &lock.blockchain,
-                    lock.pending_transactions.clone().into_iter().collect(),
-                )
+                mine_pending_transactions(&lock.blockchain, lock.mempool.transactions())
             };
             println!("miner mining block");
             let mut lock = node.write().expect("issue getting write lock");
@@ -113,7 +128,7 @@ pub async fn three_node_async_convergence() {
             let other_nodes: Vec<Arc<RwLock<Node>>> = nodes_cloned_3
                 .iter()
                 .filter_map(|other_node| {
-                    if !Arc::ptr_eq(&other_node, &node) {
+                    if !Arc::ptr_eq(other_node, &node) {
                         Some(other_node.clone())
                     } else {
                         None
@@ -145,7 +160,7 @@ pub async fn three_node_async_convergence() {
                         println!("node receiving transactions");
                         let pending_transactions = {
                             let node_read_lock = other_node.read().expect("should get read lock");
-                            node_read_lock.pending_transactions.clone()
+                            node_read_lock.mempool.transactions()
                         };
                         let mut node_write_lock = node.write().expect("couldn't get read lock");
                         block_on(node_write_lock.receive_transactions(&pending_transactions))
@@ -173,20 +188,15 @@ pub async fn three_node_async_convergence() {
     let c = c.read().expect("read lock failure");
 
     assert_eq!(
-        transactions.len() + 1,
+        transactions.len() + a.blockchain.chain[0].transactions.len(),
         a.blockchain.confirmed_transactions.len(),
     );
-    assert_eq!(
-        a.blockchain,
-        b.blockchain
-    );
-    assert_eq!(
-        a.blockchain,
-        c.blockchain
-    );
+    assert_eq!(a.blockchain, b.blockchain);
+    assert_eq!(a.blockchain, c.blockchain);
 }
 
 // didn't wind up using this, leaving the code anyway
+#[allow(dead_code)]
 fn extract_node(node: Arc<RwLock<Node>>) -> Node {
     Arc::try_unwrap(node)
         .expect("noah not here")
@@ -0,0 +1,164 @@
+mod common;
+
+use chrono::Utc;
+use rustbucks::{
+    mine::{fill_block, proof_of_work},
+    model::{
+        amount::Amount,
+        block::Block,
+        blockchain::{BlockchainError, MAX_FUTURE_DRIFT_SECS},
+        chain_spec::{ChainSpec, LedgerModel},
+        node::Node,
+        transaction::{LockTime, Transaction},
+    },
+    wallet::builder::TransactionBuilder,
+};
+
+fn payment(sender: &str, nonce: u64, lock_time: Option<LockTime>) -> Transaction {
+    common::signed(
+        sender,
+        Transaction {
+            lock_time,
+            ..common::payment(sender, "Bobby", 1_000, 1, nonce)
+        },
+    )
+}
+
+// the next block with the given transactions after the coinbase, at the given time
+fn block(node: &Node, transactions: Vec<Transaction>, timestamp: i64) -> Block {
+    let mut block = fill_block(&node.blockchain, transactions, Some("miner"));
+    block.timestamp = timestamp;
+    proof_of_work(&mut block, &node.blockchain.target_hash_prefix);
+    block
+}
+
+#[tokio::test]
+pub async fn height_locked_transactions_should_wait_in_the_mempool() {
+    let spec = common::spec(&["Timmy", "Alice"], 100_000);
+    let mut node = Node::with_spec(&spec);
+    let now = Utc::now().timestamp();
+
+    let locked = payment("Timmy", 0, Some(LockTime::Height(3)));
+    let after_locked = payment("Timmy", 1, None);
+    let unlocked = payment("Alice", 0, None);
+    for transaction in [locked.clone(), after_locked.clone(), unlocked.clone()] {
+        node.submit_transaction(transaction)
+            .await
+            .expect("valid transaction");
+    }
+    assert_eq!(node.mempool.len(), 3);
+
+    // Timmy's later nonce can't go ahead of the locked one
+    let median_time_past = node.blockchain.median_time_past();
+    assert_eq!(
+        node.mempool.block_candidates(1, median_time_past),
+        vec![unlocked.clone()]
+    );
+    let early = block(&node, vec![locked.clone()], now);
+    assert_eq!(
+        node.blockchain.add_new_block(early),
+        Err(BlockchainError::NonFinalTransaction)
+    );
+
+    let first = block(
+        &node,
+        node.mempool.block_candidates(1, median_time_past),
+        now,
+    );
+    node.submit_mined_block(first).await.expect("valid block");
+    assert!(node
+        .mempool
+        .block_candidates(2, node.blockchain.median_time_past())
+        .is_empty());
+    let second = block(&node, Vec::new(), now + 1);
+    node.submit_mined_block(second).await.expect("valid block");
+
+    let median_time_past = node.blockchain.median_time_past();
+    assert_eq!(
+        node.mempool.block_candidates(3, median_time_past),
+        vec![locked.clone(), after_locked.clone()]
+    );
+    let third = block(
+        &node,
+        node.mempool.block_candidates(3, median_time_past),
+        now + 2,
+    );
+    node.submit_mined_block(third).await.expect("valid block");
+    assert!(node.blockchain.is_confirmed(&locked));
+    assert!(node.blockchain.is_confirmed(&after_locked));
+    assert!(node.mempool.is_empty());
+}
+
+#[tokio::test]
+pub async fn timestamp_locked_transactions_should_wait_for_the_median_time_past() {
+    let spec = ChainSpec::default()
+        .with_ledger(LedgerModel::Utxo)
+        .with_genesis_allocations(vec![(common::address("Timmy"), Amount::new(100_000))]);
+    let mut node = Node::with_spec(&spec);
+    let now = Utc::now().timestamp();
+    let unlocks_at = now - 60 * 60;
+
+    let locked = TransactionBuilder::new(&node.blockchain, &common::address("Timmy"))
+        .pay("Bobby", Amount::new(10_000))
+        .with_lock_time(LockTime::Timestamp(unlocks_at))
+        .build()
+        .expect("built");
+    let locked = common::signed("Timmy", locked);
+    node.submit_transaction(locked.clone())
+        .await
+        .expect("valid transaction");
+    assert!(node
+        .mempool
+        .block_candidates(1, node.blockchain.median_time_past())
+        .is_empty());
+
+    // the block's own timestamp is past the lock but the chain's median isn't
+    let early = block(&node, vec![locked.clone()], now);
+    assert_eq!(
+        node.blockchain.add_new_block(early),
+        Err(BlockchainError::NonFinalTransaction)
+    );
+
+    let catching_up = block(&node, Vec::new(), unlocks_at);
+    node.submit_mined_block(catching_up)
+        .await
+        .expect("valid block");
+    assert_eq!(node.blockchain.median_time_past(), unlocks_at);
+    assert_eq!(
+        node.mempool
+            .block_candidates(2, node.blockchain.median_time_past()),
+        vec![locked.clone()]
+    );
+    let on_time = block(&node, vec![locked.clone()], now);
+    node.submit_mined_block(on_time).await.expect("valid block");
+    assert!(node.blockchain.is_confirmed(&locked));
+}
+
+#[tokio::test]
+pub async fn blocks_should_be_timestamped_after_the_median_time_past_and_not_far_ahead() {
+    let spec = common::spec(&["Timmy"], 100_000);
+    let mut node = Node::with_spec(&spec);
+    let now = Utc::now().timestamp();
+
+    let far_ahead = block(&node, Vec::new(), now + MAX_FUTURE_DRIFT_SECS + 60 * 60);
+    assert_eq!(
+        node.blockchain.add_new_block(far_ahead),
+        Err(BlockchainError::TimestampTooFarAhead)
+    );
+
+    let first = block(&node, Vec::new(), now);
+    node.submit_mined_block(first).await.expect("valid block");
+    assert_eq!(node.blockchain.median_time_past(), now);
+
+    // going back to or behind the median doesn't work
+    for timestamp in [now, now - 1] {
+        let stale = block(&node, Vec::new(), timestamp);
+        assert_eq!(
+            node.blockchain.add_new_block(stale),
+            Err(BlockchainError::TimestampTooEarly)
+        );
+    }
+
+    let second = block(&node, Vec::new(), now + 1);
+    node.submit_mined_block(second).await.expect("valid block");
+}
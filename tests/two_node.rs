@@ -1,76 +1,67 @@
-use fixed::types::I32F32;
-use rustbucks::{mine::mine_pending_transactions, model::{node::Node, transaction::Transaction}};
+use crate::common;
+
+use rustbucks::{
+    mine::mine_pending_transactions,
+    model::{chain_spec::ChainSpec, node::Node},
+};
+
+fn spec() -> ChainSpec {
+    common::spec(&["Timmy", "Alice", "Jill", "Spock", "Picard"], 1_000_000)
+}
 
 #[tokio::test]
 pub async fn two_nodes_with_distinct_blockchains_should_converge() {
-    let mut a = Node::new();
+    let mut a = Node::with_spec(&spec());
 
     let a_transactions = vec![
-        Transaction {
-            timestamp: 0,
-            sender: "Timmy".to_string(),
-            receiver: "Bobby".to_string(),
-            amount: I32F32::from_num(100),
-        },
-        Transaction {
-            timestamp: 1,
-            sender: "Alice".to_string(),
-            receiver: "Charlie".to_string(),
-            amount: I32F32::from_num(100),
-        },
+        common::payment("Timmy", "Bobby", 100, 0, 0),
+        common::payment("Alice", "Charlie", 100, 0, 0),
     ];
 
-    let a_transactions_2 = vec![
-        Transaction {
-            timestamp: 2,
-            sender: "Jill".to_string(),
-            receiver: "Jane".to_string(),
-            amount: I32F32::from_num(20),
-        },
-    ];
+    let a_transactions_2 = vec![common::payment("Jill", "Jane", 20, 0, 0)];
 
-    a.submit_transaction(a_transactions[0].clone()).await;
-    a.submit_transaction(a_transactions[1].clone()).await;
-    a.submit_transaction(a_transactions_2[0].clone()).await;
+    a.submit_transaction(a_transactions[0].clone())
+        .await
+        .expect("valid transaction");
+    a.submit_transaction(a_transactions[1].clone())
+        .await
+        .expect("valid transaction");
+    a.submit_transaction(a_transactions_2[0].clone())
+        .await
+        .expect("valid transaction");
 
     //submit the first block
     let new_block = mine_pending_transactions(&a.blockchain, a_transactions.clone());
     let block_submission_res = a.submit_mined_block(new_block).await;
     assert_eq!(Ok(()), block_submission_res);
     assert_eq!(a.blockchain.chain[1].transactions, a_transactions);
-    assert_eq!(1, a.pending_transactions.len());
+    assert_eq!(1, a.mempool.len());
 
     //submit the second block (just one transaction)
     let new_block = mine_pending_transactions(&a.blockchain, a_transactions_2.clone());
     let block_submission_res = a.submit_mined_block(new_block).await;
     assert_eq!(Ok(()), block_submission_res);
     assert_eq!(a.blockchain.chain[1].transactions, a_transactions);
-    assert!(a.pending_transactions.is_empty());
+    assert!(a.mempool.is_empty());
 
-    let mut b = Node::new();
+    let mut b = Node::with_spec(&spec());
     let b_transactions = vec![
-        Transaction {
-            timestamp: 3,
-            sender: "Spock".to_string(),
-            receiver: "Kirk".to_string(),
-            amount: I32F32::from_num(100),
-        },
-        Transaction {
-            timestamp: 1,
-            sender: "Picard".to_string(),
-            receiver: "Janeway".to_string(),
-            amount: I32F32::from_num(100),
-        },
+        common::payment("Spock", "Kirk", 100, 0, 0),
+        common::payment("Picard", "Janeway", 100, 0, 0),
     ];
 
-    b.submit_transaction(b_transactions[0].clone()).await;
-    b.submit_transaction(b_transactions[1].clone()).await;
+    b.submit_transaction(b_transactions[0].clone())
+        .await
+        .expect("valid transaction");
+    b.submit_transaction(b_transactions[1].clone())
+        .await
+        .expect("valid transaction");
 
     let new_block = mine_pending_transactions(&b.blockchain, b_transactions.clone());
     let block_submission_res = b.submit_mined_block(new_block).await;
 
     assert_eq!(Ok(()), block_submission_res);
-    assert!(b.pending_transactions.is_empty());
+    assert!(b.mempool.is_empty());
     assert_eq!(b.blockchain.chain[1].transactions, b_transactions);
 
     // when b shows a its blockchain, its blockchain should not be replaced
@@ -84,8 +75,8 @@ pub async fn two_nodes_with_distinct_blockchains_should_converge() {
     assert_eq!(b.blockchain.chain[2].transactions, a_transactions_2);
 
     // transactions that we previously confirmed in b's blockchain should be pending again
-    assert!(b.pending_transactions.contains(&b_transactions[0]));
-    assert!(b.pending_transactions.contains(&b_transactions[1]));
+    assert!(b.mempool.contains(&b_transactions[0]));
+    assert!(b.mempool.contains(&b_transactions[1]));
 
     //mine the b transactions again
     let new_block = mine_pending_transactions(&b.blockchain, b_transactions.clone());
@@ -93,7 +84,7 @@ pub async fn two_nodes_with_distinct_blockchains_should_converge() {
 
     //make sure the pending transactions are now empty
     assert_eq!(Ok(()), block_submission_res);
-    assert!(b.pending_transactions.is_empty());
+    assert!(b.mempool.is_empty());
 
     //make sure all of the transactions are now in b's blockchain
     assert_eq!(b.blockchain.chain[1].transactions, a_transactions);
@@ -0,0 +1,128 @@
+use ed25519_dalek::SigningKey;
+use rustbucks::{
+    mine::{fill_block, proof_of_work},
+    model::{
+        amount::Amount,
+        blockchain::Blockchain,
+        chain_spec::{ChainSpec, LedgerModel},
+        node::Node,
+        signature::key_address,
+        transaction::Transaction,
+    },
+    wallet::builder::{BuildError, TransactionBuilder},
+};
+
+fn key() -> SigningKey {
+    SigningKey::from_bytes(&[7; 32])
+}
+
+fn confirm(blockchain: &mut Blockchain, transaction: Transaction) {
+    let mut block = fill_block(blockchain, vec![transaction], None);
+    proof_of_work(&mut block, &blockchain.target_hash_prefix);
+    blockchain.add_new_block(block).expect("valid block");
+}
+
+#[tokio::test]
+pub async fn account_payments_should_count_what_is_pending() {
+    let address = key_address(&key().verifying_key());
+    let spec = ChainSpec::default()
+        .with_genesis_allocations(vec![(address.clone(), Amount::new(100_000))]);
+    let mut node = Node::with_spec(&spec);
+
+    let first = TransactionBuilder::new(&node.blockchain, &address)
+        .pay("Bobby", Amount::new(60_000))
+        .build_signed(&key())
+        .expect("built");
+    assert_eq!(first.nonce, 0);
+    assert!(first.fee_rate() >= 1000);
+    node.submit_transaction(first.clone())
+        .await
+        .expect("valid transaction");
+
+    // the first one is still waiting, so there isn't enough left for the same again
+    let second = TransactionBuilder::new(&node.blockchain, &address)
+        .with_mempool(&node.mempool)
+        .pay("Bobby", Amount::new(60_000));
+    match second.build() {
+        Err(BuildError::InsufficientFunds { needed, available }) => {
+            assert_eq!(available, Amount::new(40_000).saturating_sub(first.fee));
+            assert!(needed > Amount::new(60_000));
+        }
+        other => panic!("expected insufficient funds, got {:?}", other),
+    }
+
+    let smaller = TransactionBuilder::new(&node.blockchain, &address)
+        .with_mempool(&node.mempool)
+        .pay("Bobby", Amount::new(10_000))
+        .build_signed(&key())
+        .expect("built");
+    assert_eq!(smaller.nonce, 1);
+    node.submit_transaction(smaller)
+        .await
+        .expect("valid transaction");
+
+    let builder = TransactionBuilder::new(&node.blockchain, &address);
+    assert!(matches!(builder.build(), Err(BuildError::NoRecipients)));
+    let builder = builder
+        .pay("Bobby", Amount::new(1))
+        .pay("Billy", Amount::new(1));
+    assert!(matches!(
+        builder.build(),
+        Err(BuildError::TooManyRecipients)
+    ));
+}
+
+#[tokio::test]
+pub async fn utxo_payments_should_pick_the_biggest_outputs_and_make_change() {
+    let address = key_address(&key().verifying_key());
+    let spec = ChainSpec::default()
+        .with_ledger(LedgerModel::Utxo)
+        .with_genesis_allocations(vec![
+            (address.clone(), Amount::new(100_000)),
+            (address.clone(), Amount::new(500_000)),
+            (address.clone(), Amount::new(20_000)),
+        ]);
+    let mut node = Node::with_spec(&spec);
+
+    let payment = TransactionBuilder::new(&node.blockchain, &address)
+        .pay("Bobby", Amount::new(300_000))
+        .with_fee_rate(2000)
+        .build_signed(&key())
+        .expect("built");
+    assert_eq!(payment.inputs.len(), 1);
+    assert_eq!(payment.outputs[0].amount, Amount::new(300_000));
+    assert_eq!(payment.outputs[1].receiver, address);
+    assert_eq!(
+        payment.outputs[1].amount,
+        Amount::new(200_000).saturating_sub(payment.fee)
+    );
+    assert!(payment.fee.base_units() >= payment.size() * 2);
+    node.submit_transaction(payment.clone())
+        .await
+        .expect("valid transaction");
+
+    // the biggest output is already spoken for
+    let builder = TransactionBuilder::new(&node.blockchain, &address)
+        .with_mempool(&node.mempool)
+        .pay("Billy", Amount::new(110_000))
+        .with_change_address("change");
+    let spent = payment.inputs[0].clone();
+    let next = builder.build_signed(&key()).expect("built");
+    assert_eq!(next.inputs.len(), 2);
+    assert!(!next.inputs.contains(&spent));
+    assert_eq!(next.outputs[1].receiver, "change");
+    assert!(matches!(
+        TransactionBuilder::new(&node.blockchain, &address)
+            .with_mempool(&node.mempool)
+            .pay("Billy", Amount::new(120_000))
+            .build(),
+        Err(BuildError::InsufficientFunds { available, .. }) if available == Amount::new(120_000)
+    ));
+
+    // and the ledger takes what got built
+    let mut blockchain = node.blockchain.clone();
+    confirm(&mut blockchain, payment);
+    confirm(&mut blockchain, next);
+    assert_eq!(blockchain.balance_of("Bobby"), Amount::new(300_000));
+    assert_eq!(blockchain.balance_of("Billy"), Amount::new(110_000));
+}
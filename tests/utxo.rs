@@ -0,0 +1,115 @@
+mod common;
+
+use rustbucks::{
+    mine::mine_pending_transactions,
+    model::{
+        amount::Amount,
+        blockchain::{Blockchain, BlockchainError},
+        chain_spec::{ChainSpec, LedgerModel},
+        mempool::MempoolError,
+        node::Node,
+        transaction::{Transaction, TxOutput},
+    },
+    net::local::LocalNetwork,
+};
+
+fn spec() -> ChainSpec {
+    ChainSpec::default()
+        .with_ledger(LedgerModel::Utxo)
+        .with_genesis_allocations(vec![(common::address("Timmy"), Amount::new(100_000))])
+}
+
+// pays amount to Bobby out of everything Timmy has, the rest comes back as change
+fn payment(blockchain: &Blockchain, amount: u64, fee: u64) -> Transaction {
+    let timmy = common::address("Timmy");
+    let unspent = blockchain.unspent_outputs(&timmy);
+    let total = Amount::checked_sum(unspent.iter().map(|(_, output)| output.amount))
+        .expect("nobody has that much");
+    let transaction = Transaction {
+        sender: timmy.clone(),
+        receiver: "".to_string(),
+        amount: Amount::new(0),
+        fee: Amount::new(fee),
+        nonce: 0,
+        timestamp: 0,
+        inputs: unspent.into_iter().map(|(outpoint, _)| outpoint).collect(),
+        outputs: vec![
+            TxOutput {
+                receiver: "Bobby".to_string(),
+                amount: Amount::new(amount),
+            },
+            TxOutput {
+                receiver: timmy,
+                amount: total
+                    .checked_sub(Amount::new(amount + fee))
+                    .expect("Timmy can afford it"),
+            },
+        ],
+        ..Transaction::default()
+    };
+    common::signed("Timmy", transaction)
+}
+
+#[tokio::test]
+pub async fn utxo_transactions_should_relay_and_confirm() {
+    let mut network = LocalNetwork::new();
+    network.add_node("a", Node::with_spec(&spec()));
+    network.add_node("b", Node::with_spec(&spec()));
+    network.connect("a", "b");
+
+    let first = payment(&network.node("a").blockchain, 10, 1);
+    network
+        .node_mut("a")
+        .submit_transaction(first.clone())
+        .await
+        .expect("valid transaction");
+    network.run_until_idle().await;
+
+    // spending the same outputs again has to pay more to take its place
+    let cheap_double_spend = payment(&network.node("b").blockchain, 50, 1);
+    assert_eq!(
+        network
+            .node_mut("b")
+            .submit_transaction(cheap_double_spend)
+            .await,
+        Err(MempoolError::ReplacementFeeTooLow)
+    );
+    let replacement = payment(&network.node("b").blockchain, 50, 2000);
+    network
+        .node_mut("b")
+        .submit_transaction(replacement.clone())
+        .await
+        .expect("pays enough to replace it");
+    network.run_until_idle().await;
+    assert_eq!(
+        network.node("a").mempool.transactions(),
+        vec![replacement.clone()]
+    );
+
+    let new_block = {
+        let b = network.node("b");
+        mine_pending_transactions(&b.blockchain, b.mempool.transactions())
+    };
+    network
+        .node_mut("b")
+        .submit_mined_block(new_block)
+        .await
+        .expect("valid block");
+    network.run_until_idle().await;
+
+    for id in ["a", "b"] {
+        let node = network.node(id);
+        assert!(node.mempool.is_empty());
+        assert_eq!(node.blockchain.balance_of("Bobby"), Amount::new(50));
+        assert_eq!(
+            node.blockchain.balance_of(&common::address("Timmy")),
+            Amount::new(97_950)
+        );
+    }
+
+    // the original spends outputs that are gone now
+    assert_eq!(
+        network.node_mut("a").submit_transaction(first).await,
+        Err(MempoolError::Rejected(BlockchainError::UnknownOutput))
+    );
+}
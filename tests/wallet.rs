@@ -0,0 +1,183 @@
+use std::fs;
+
+use rustbucks::{
+    model::{
+        amount::Amount, blockchain::BlockchainError, chain_spec::ChainSpec, mempool::MempoolError,
+        node::Node, transaction::Transaction,
+    },
+    wallet::keystore::{KdfParams, Keystore, WalletError, MAX_LOG_N},
+};
+
+// cheap enough for tests
+fn kdf() -> KdfParams {
+    KdfParams::new().with_log_n(4)
+}
+
+// unsigned, whoever it's from
+fn payment(sender: &str, amount: u64) -> Transaction {
+    Transaction {
+        sender: sender.to_string(),
+        receiver: "Bobby".to_string(),
+        amount: Amount::new(amount),
+        fee: Amount::new(1),
+        ..Transaction::default()
+    }
+}
+
+#[test]
+pub fn keystore_should_only_open_with_the_right_password() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let path = dir.path().join("keystore.json");
+    let mut keystore = Keystore::create(&path, "hunter2", kdf()).expect("created");
+    let address = keystore
+        .new_account("savings")
+        .expect("new account")
+        .address();
+    keystore.new_account("spending").expect("new account");
+    assert!(matches!(
+        keystore.new_account("savings"),
+        Err(WalletError::DuplicateName(_))
+    ));
+    assert!(matches!(
+        Keystore::create(&path, "hunter2", kdf()),
+        Err(WalletError::AlreadyExists)
+    ));
+
+    // the addresses can be listed without the password but nothing else
+    let listed = Keystore::list(&path).expect("listed");
+    assert_eq!(listed[0].address, address);
+    assert!(!fs::read_to_string(&path)
+        .expect("file")
+        .contains(&keystore.export_key("savings").expect("exported")));
+    assert!(matches!(
+        Keystore::open(&path, "hunter3"),
+        Err(WalletError::WrongPassword)
+    ));
+
+    let reopened = Keystore::open(&path, "hunter2").expect("opened");
+    assert_eq!(reopened.accounts().len(), 2);
+    assert_eq!(
+        reopened.account("savings").expect("account").address(),
+        address
+    );
+    assert_eq!(reopened.account(&address).expect("account").name, "savings");
+
+    // swapping somebody else's address in gets noticed
+    let tampered = fs::read_to_string(&path)
+        .expect("file")
+        .replace(&address, &listed[1].address);
+    fs::write(&path, tampered).expect("written");
+    assert!(matches!(
+        Keystore::open(&path, "hunter2"),
+        Err(WalletError::WrongPassword)
+    ));
+}
+
+#[test]
+pub fn keystores_should_refuse_to_run_a_costly_kdf() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let path = dir.path().join("keystore.json");
+    assert!(matches!(
+        Keystore::create(&path, "hunter2", KdfParams::new().with_log_n(MAX_LOG_N + 1)),
+        Err(WalletError::KdfTooCostly)
+    ));
+    assert!(!path.exists());
+
+    // only the owner can read it
+    Keystore::create(&path, "hunter2", kdf()).expect("created");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&path).expect("metadata").permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    // the parameters are read before anything's authenticated
+    let doctored = fs::read_to_string(&path)
+        .expect("file")
+        .replace("\"log_n\": 4", "\"log_n\": 60");
+    fs::write(&path, doctored).expect("written");
+    assert!(matches!(
+        Keystore::open(&path, "hunter2"),
+        Err(WalletError::KdfTooCostly)
+    ));
+    assert!(matches!(
+        Keystore::open_or_create(&path, "hunter2", kdf()),
+        Err(WalletError::KdfTooCostly)
+    ));
+}
+
+#[test]
+pub fn keys_should_move_between_keystores() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let mut first = Keystore::create(&dir.path().join("first.json"), "a", kdf()).expect("created");
+    let mut second =
+        Keystore::create(&dir.path().join("second.json"), "b", kdf()).expect("created");
+
+    let address = first.new_account("cold").expect("new account").address();
+    let secret = first.export_key("cold").expect("exported");
+    let imported = second.import_key("warm", &secret).expect("imported");
+    assert_eq!(imported.address(), address);
+    assert!(matches!(
+        second.import_key("again", &secret),
+        Err(WalletError::DuplicateKey(name)) if name == "warm"
+    ));
+    assert!(matches!(
+        second.import_key("garbage", "abc"),
+        Err(WalletError::InvalidKey)
+    ));
+
+    // and still sign the same after a new password
+    second.change_password("c").expect("changed");
+    let reopened = Keystore::open(&dir.path().join("second.json"), "c").expect("opened");
+    assert_eq!(reopened.export_key(&address).expect("exported"), secret);
+}
+
+#[tokio::test]
+pub async fn nodes_should_only_take_signed_transactions_from_key_addresses() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let mut keystore =
+        Keystore::create(&dir.path().join("keystore.json"), "hunter2", kdf()).expect("created");
+    let address = keystore
+        .new_account("savings")
+        .expect("new account")
+        .address();
+    let spec = ChainSpec::default().with_genesis_allocations(vec![
+        (address.clone(), Amount::new(1000)),
+        ("Timmy".to_string(), Amount::new(1000)),
+    ]);
+    let mut node = Node::with_spec(&spec);
+
+    // a plain name has no key to sign with, so what genesis gave it stays put
+    assert_eq!(
+        node.submit_transaction(payment("Timmy", 100)).await,
+        Err(MempoolError::Rejected(BlockchainError::MissingSignature))
+    );
+
+    let unsigned = payment(&address, 100);
+    assert_eq!(
+        node.submit_transaction(unsigned.clone()).await,
+        Err(MempoolError::Rejected(BlockchainError::MissingSignature))
+    );
+
+    // signatures don't carry over to a different transaction
+    let mut signed = unsigned.clone();
+    keystore.sign("savings", &mut signed).expect("signed");
+    let mut tampered = signed.clone();
+    tampered.amount = Amount::new(900);
+    assert_eq!(
+        node.submit_transaction(tampered).await,
+        Err(MempoolError::Rejected(BlockchainError::InvalidSignature))
+    );
+
+    // only the sender can sign
+    assert!(matches!(
+        keystore.sign("savings", &mut payment("Timmy", 100)),
+        Err(WalletError::WrongSender)
+    ));
+
+    node.submit_transaction(signed.clone())
+        .await
+        .expect("signed transaction");
+    assert_eq!(node.mempool.transactions(), vec![signed]);
+}
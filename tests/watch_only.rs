@@ -0,0 +1,145 @@
+mod common;
+
+use ed25519_dalek::SigningKey;
+use rustbucks::{
+    mine::{fill_block, proof_of_work},
+    model::{
+        amount::Amount,
+        events::{EventSubscription, NodeEvent},
+        node::Node,
+        transaction::Transaction,
+    },
+    wallet::{
+        keystore::WalletError,
+        watch::{WatchEvent, WatchList, WatchMonitor},
+    },
+};
+
+fn node() -> Node {
+    Node::with_spec(&common::spec(&["Timmy"], 100_000))
+}
+
+fn payment(receiver: &str, nonce: u64, amount: u64) -> Transaction {
+    common::payment("Timmy", receiver, amount, 1, nonce)
+}
+
+fn drain(subscription: &mut EventSubscription) -> Vec<NodeEvent> {
+    let mut events = Vec::new();
+    while let Some(event) = subscription.try_recv().expect("no lag") {
+        events.push(event);
+    }
+    events
+}
+
+// mines whatever is in the mempool into the next block
+async fn mine(node: &mut Node) {
+    let mut block = fill_block(&node.blockchain, node.mempool.transactions(), Some("miner"));
+    proof_of_work(&mut block, &node.blockchain.target_hash_prefix);
+    node.submit_mined_block(block).await.expect("valid block");
+}
+
+#[test]
+pub fn watch_list_should_keep_addresses_and_public_keys() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let path = dir.path().join("watching.json");
+    let key = SigningKey::from_bytes(&[3; 32]).verifying_key();
+
+    let mut watch_list = WatchList::open(&path).expect("opened");
+    watch_list
+        .watch_address("bobby", "Bobby")
+        .expect("watching");
+    let watched = watch_list
+        .watch_public_key("cold", &hex::encode(key.as_bytes()))
+        .expect("watching")
+        .clone();
+    assert!(watched.address.starts_with("rb"));
+    assert!(matches!(
+        watch_list.watch_address("bobby", "Billy"),
+        Err(WalletError::DuplicateName(_))
+    ));
+    assert!(matches!(
+        watch_list.watch_address("again", &watched.address),
+        Err(WalletError::DuplicateKey(label)) if label == "cold"
+    ));
+    assert!(matches!(
+        watch_list.watch_public_key("garbage", "abc"),
+        Err(WalletError::InvalidKey)
+    ));
+
+    let mut reopened = WatchList::open(&path).expect("opened");
+    assert_eq!(reopened.get(&watched.address), Some(&watched));
+    reopened.remove("bobby").expect("removed");
+    assert_eq!(
+        WatchList::open(&path).expect("opened").addresses(),
+        &[watched]
+    );
+}
+
+#[tokio::test]
+pub async fn monitor_should_follow_payments_until_they_settle() {
+    let dir = tempfile::tempdir().expect("temp dir");
+    let mut watch_list = WatchList::open(&dir.path().join("watching.json")).expect("opened");
+    watch_list
+        .watch_address("bobby", "Bobby")
+        .expect("watching");
+
+    let mut node = node();
+    let mut events = node.events.subscribe();
+    let mut monitor = WatchMonitor::new(watch_list.addresses(), &node.blockchain, 2);
+
+    let first = payment("Bobby", 0, 500);
+    node.submit_transaction(first.clone())
+        .await
+        .expect("valid transaction");
+    // nobody's watching Billy
+    node.submit_transaction(payment("Billy", 1, 10))
+        .await
+        .expect("valid transaction");
+    let handled: Vec<WatchEvent> = drain(&mut events)
+        .iter()
+        .flat_map(|event| monitor.handle(event))
+        .collect();
+    assert_eq!(handled.len(), 1);
+    assert!(matches!(&handled[0], WatchEvent::PaymentPending(payment)
+        if payment.txid == first.txid() && payment.amount == Amount::new(500) && payment.height.is_none()));
+
+    mine(&mut node).await;
+    let handled: Vec<WatchEvent> = drain(&mut events)
+        .iter()
+        .flat_map(|event| monitor.handle(event))
+        .collect();
+    assert!(matches!(&handled[..], [
+        WatchEvent::BalanceChanged { balance, .. },
+        WatchEvent::PaymentConfirmed(payment),
+    ] if *balance == Amount::new(500) && payment.height == Some(1)));
+    assert_eq!(
+        monitor.balance("Bobby"),
+        node.blockchain.balance_of("Bobby")
+    );
+
+    // a reorg takes it back out again
+    let block = node.blockchain.tip().clone();
+    let handled = monitor.handle(&NodeEvent::BlockDisconnected(block.clone()));
+    assert!(matches!(&handled[..], [
+        WatchEvent::BalanceChanged { balance, .. },
+        WatchEvent::PaymentReverted(payment),
+    ] if balance.is_zero() && payment.height.is_none()));
+    assert_eq!(monitor.unsettled().len(), 1);
+    monitor.handle(&NodeEvent::BlockConnected(block));
+
+    // until it's buried deep enough
+    mine(&mut node).await;
+    let handled: Vec<WatchEvent> = drain(&mut events)
+        .iter()
+        .flat_map(|event| monitor.handle(event))
+        .collect();
+    assert!(matches!(&handled[..], [
+        WatchEvent::PaymentSettled { payment, confirmations: 2 },
+    ] if payment.txid == first.txid()));
+    assert!(monitor.unsettled().is_empty());
+
+    // picking up from the chain doesn't bring back what already settled
+    let caught_up = WatchMonitor::new(watch_list.addresses(), &node.blockchain, 2);
+    assert!(caught_up.unsettled().is_empty());
+    assert_eq!(caught_up.total_balance(), Amount::new(500));
+}